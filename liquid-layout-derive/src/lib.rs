@@ -0,0 +1,110 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Type};
+
+/// Derives `ReflectMeasures` from the `Measure` fields of a struct.
+///
+/// Fields marked `#[reflect(nested)]` contribute their own measures, prefixed with the field
+/// name. Fields marked `#[reflect(skip)]` are left out.
+#[proc_macro_derive(ReflectMeasures, attributes(reflect))]
+pub fn derive_reflect_measures(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match expand(input) {
+    Ok(x) => x.into(),
+    Err(e) => e.to_compile_error().into(),
+  }
+}
+
+enum FieldKind {
+  Measure,
+  Nested,
+  Skip,
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+  let name = &input.ident;
+  let lifetime = match input.generics.lifetimes().next() {
+    Some(x) => x.lifetime.clone(),
+    None => {
+      return Err(Error::new(
+        input.generics.span(),
+        "ReflectMeasures requires a lifetime parameter for the measures",
+      ))
+    }
+  };
+  let fields = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => &fields.named,
+      _ => {
+        return Err(Error::new(
+          name.span(),
+          "ReflectMeasures requires named fields",
+        ))
+      }
+    },
+    _ => {
+      return Err(Error::new(
+        name.span(),
+        "ReflectMeasures can only be derived for structs",
+      ))
+    }
+  };
+
+  let mut entries = vec![];
+  for field in fields {
+    let ident = field.ident.as_ref().unwrap();
+    let label = ident.to_string();
+    let entry = match field_kind(field)? {
+      FieldKind::Skip => continue,
+      FieldKind::Measure => quote! {
+        measures.push((::std::string::String::from(#label), self.#ident));
+      },
+      FieldKind::Nested => quote! {
+        for (name, measure) in ::liquid_layout::layout::reflect::ReflectMeasures::named_measures(&self.#ident) {
+          measures.push((::std::format!("{}.{}", #label, name), measure));
+        }
+      },
+    };
+    entries.push(entry);
+  }
+
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::liquid_layout::layout::reflect::ReflectMeasures<#lifetime> for #name #ty_generics #where_clause {
+      fn named_measures(&self) -> ::std::vec::Vec<(::std::string::String, ::liquid_layout::layout::measure::Measure<#lifetime>)> {
+        let mut measures = ::std::vec::Vec::new();
+        #(#entries)*
+        measures
+      }
+    }
+  })
+}
+
+fn field_kind(field: &syn::Field) -> Result<FieldKind, Error> {
+  for attr in &field.attrs {
+    if !attr.path.is_ident("reflect") {
+      continue;
+    }
+    let option: syn::Ident = attr.parse_args()?;
+    return match option.to_string().as_str() {
+      "nested" => Ok(FieldKind::Nested),
+      "skip" => Ok(FieldKind::Skip),
+      _ => Err(Error::new(option.span(), "expected `nested` or `skip`")),
+    };
+  }
+
+  let is_measure = match &field.ty {
+    Type::Path(path) => path
+      .path
+      .segments
+      .last()
+      .map(|x| x.ident == "Measure")
+      .unwrap_or(false),
+    _ => false,
+  };
+  Ok(if is_measure {
+    FieldKind::Measure
+  } else {
+    FieldKind::Skip
+  })
+}
@@ -0,0 +1,155 @@
+//! Support for interactive layout editors.
+
+use std::{cell::Cell, rc::Rc};
+
+use crate::{
+  layout::{builder::LayoutBuilder, context::LayoutContext, error::LayoutError, measure::Measure},
+  widgets::{Rectangle, RectangleMetrics},
+};
+
+/// Weight of a guide exactly at its target. Guides further away get proportionally less.
+const GUIDE_WEIGHT: f64 = 1000.0;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GuideAxis {
+  /// A vertical line, aligning horizontal positions.
+  Vertical,
+
+  /// A horizontal line, aligning vertical positions.
+  Horizontal,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GuideAnchor {
+  Start,
+  Center,
+  End,
+}
+
+/// An alignment between an anchor of the dragged rectangle and an anchor of a target.
+#[derive(Copy, Clone, Debug)]
+pub struct Guide {
+  pub axis: GuideAxis,
+  pub position: f64,
+  pub target: usize,
+  pub dragged_anchor: GuideAnchor,
+  pub target_anchor: GuideAnchor,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapResult {
+  pub rect: RectangleMetrics,
+
+  /// Guides the rectangle snapped to, for rendering guide lines.
+  pub guides: Vec<Guide>,
+}
+
+const ANCHORS: [GuideAnchor; 3] = [GuideAnchor::Start, GuideAnchor::Center, GuideAnchor::End];
+
+fn anchor_position(start: f64, end: f64, anchor: GuideAnchor) -> f64 {
+  match anchor {
+    GuideAnchor::Start => start,
+    GuideAnchor::Center => (start + end) / 2.0,
+    GuideAnchor::End => end,
+  }
+}
+
+fn anchor_measure<'a>(start: Measure<'a>, end: Measure<'a>, anchor: GuideAnchor) -> Measure<'a> {
+  match anchor {
+    GuideAnchor::Start => start,
+    GuideAnchor::Center => (start + end) / 2.0,
+    GuideAnchor::End => end,
+  }
+}
+
+/// Snaps `dragged` to edges and centers of `targets` that are within `threshold` of it.
+///
+/// The rectangle keeps its size and stays where it was dropped along any axis without a guide.
+pub fn snap(
+  dragged: RectangleMetrics,
+  targets: &[RectangleMetrics],
+  threshold: f64,
+) -> Result<SnapResult, LayoutError> {
+  let mut candidates = vec![];
+  for (i, target) in targets.iter().enumerate() {
+    for &axis in &[GuideAxis::Vertical, GuideAxis::Horizontal] {
+      let (dragged_range, target_range) = match axis {
+        GuideAxis::Vertical => ((dragged.left, dragged.right), (target.left, target.right)),
+        GuideAxis::Horizontal => ((dragged.top, dragged.bottom), (target.top, target.bottom)),
+      };
+      for &dragged_anchor in &ANCHORS {
+        for &target_anchor in &ANCHORS {
+          let from = anchor_position(dragged_range.0, dragged_range.1, dragged_anchor);
+          let position = anchor_position(target_range.0, target_range.1, target_anchor);
+          let distance = (from - position).abs();
+          if distance <= threshold {
+            let guide = Guide {
+              axis,
+              position,
+              target: i,
+              dragged_anchor,
+              target_anchor,
+            };
+            candidates.push((guide, distance));
+          }
+        }
+      }
+    }
+  }
+
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let snapped = Rc::new(Cell::new(None));
+  let snapped_out = snapped.clone();
+  let rect = Rectangle::with_width_and_height(
+    &ctx,
+    dragged.width,
+    dragged.height,
+    Box::new(move |metrics| {
+      snapped_out.set(Some(metrics));
+      Ok(())
+    }),
+  );
+  builder.push_constraint(
+    rect
+      .left
+      .prop_eq(Measure::new_const(&ctx, dragged.left)?)
+      .with_weight(1),
+  );
+  builder.push_constraint(
+    rect
+      .top
+      .prop_eq(Measure::new_const(&ctx, dragged.top)?)
+      .with_weight(1),
+  );
+  for (guide, distance) in &candidates {
+    let (start, end) = match guide.axis {
+      GuideAxis::Vertical => (rect.left, rect.right),
+      GuideAxis::Horizontal => (rect.top, rect.bottom),
+    };
+    let weight = GUIDE_WEIGHT * (1.0 - distance / (threshold + 1.0));
+    builder.push_constraint(
+      anchor_measure(start, end, guide.dragged_anchor)
+        .prop_eq(Measure::new_const(&ctx, guide.position)?)
+        .with_weight(weight as u32 + 1),
+    );
+  }
+  builder.push_widget(rect);
+  builder.build()?;
+
+  let rect = snapped
+    .get()
+    .expect("build succeeded but the dragged rectangle was not painted");
+  let guides = candidates
+    .into_iter()
+    .map(|(guide, _)| guide)
+    .filter(|guide| {
+      let (start, end) = match guide.axis {
+        GuideAxis::Vertical => (rect.left, rect.right),
+        GuideAxis::Horizontal => (rect.top, rect.bottom),
+      };
+      (anchor_position(start, end, guide.dragged_anchor) - guide.position).abs() < 0.01
+    })
+    .collect();
+  Ok(SnapResult { rect, guides })
+}
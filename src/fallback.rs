@@ -0,0 +1,77 @@
+//! Fixed-width fallbacks of flexible layouts, such as email-safe variants.
+
+use std::collections::HashSet;
+
+use crate::layout::{
+  analysis::is_tabular,
+  builder::LayoutBuilder,
+  error::LayoutError,
+  measure::Measure,
+  prop::Prop,
+  solution::{free_measures, Solution},
+};
+
+/// Width of the single column most email clients render reliably.
+pub const EMAIL_WIDTH: f64 = 600.0;
+
+/// A solved layout and its fixed-width variant.
+pub struct Fallback<'a> {
+  pub flexible: Solution,
+  pub fixed: Solution,
+
+  /// Constraints of the layout that a table cannot express, which the fixed variant leaves out.
+  pub dropped: Vec<Prop<'a>>,
+}
+
+/// A fixed-width variant of `flexible`, a solution of the layout of `builder`, with `width`, the
+/// measure of the layout width, held at `fixed_width`.
+///
+/// Only the constraints that nested tables can express are kept: conjunctions of linear
+/// comparisons without choices. Conditional constraints, disjunctions, selections and nonlinear
+/// terms are dropped. The objectives of the layout are kept, and free measures that no kept
+/// constraint depends on keep their values in `flexible`.
+///
+/// The builder is left as it was. Paint either solution with `Solution::paint`.
+pub fn fallback<'a>(
+  builder: &LayoutBuilder<'a>,
+  flexible: &Solution,
+  width: Measure<'a>,
+  fixed_width: f64,
+) -> Result<Fallback<'a>, LayoutError> {
+  let ctx = builder.ctx();
+  let (kept, dropped): (Vec<_>, Vec<_>) = builder
+    .all_constraints()
+    .into_iter()
+    .partition(|&c| is_tabular(c));
+  let reached = free_measures(vec![width], kept.iter().copied())
+    .into_iter()
+    .map(|m| m.addr())
+    .collect::<HashSet<_>>();
+
+  let mut reduced = LayoutBuilder::new(ctx).with_solver_config(builder.solver_config());
+  for c in kept {
+    reduced.push_constraint(c);
+  }
+  for m in builder.free_measures() {
+    if !reached.contains(&m.addr()) {
+      let value = flexible
+        .value_of(m)
+        .ok_or_else(|| LayoutError::ModelExtraction {
+          measure: m.to_string(),
+        })?;
+      reduced.push_hard_constraint(m.prop_eq(Measure::new_const(ctx, value)?));
+    }
+  }
+  reduced.push_hard_constraint(width.prop_eq(Measure::new_const(ctx, fixed_width)?));
+  for o in builder.all_objectives() {
+    reduced.push_objective(o);
+  }
+
+  let mut fixed = reduced.solve()?;
+  fixed.set_hit_regions(builder.hit_regions(&fixed)?);
+  Ok(Fallback {
+    flexible: flexible.clone(),
+    fixed,
+    dropped,
+  })
+}
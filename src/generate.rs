@@ -0,0 +1,42 @@
+//! Random layouts within the freedom a constraint system leaves, for fuzzing renderers and for
+//! generative design.
+
+use std::ops::Range;
+
+use rand::Rng;
+
+use crate::layout::{
+  builder::LayoutBuilder, error::LayoutError, measure::Measure, objective::Objective,
+  session::SolverSession, solution::Solution,
+};
+
+/// Draws `count` solutions of the layout of `builder`.
+///
+/// Each solution pulls every free measure towards its own random target in `range`, after the
+/// objectives of the layout. Solutions that differ in every free measure are likely, but not
+/// guaranteed. The builder is left as it was.
+pub fn sample<'a, R: Rng>(
+  builder: &mut LayoutBuilder<'a>,
+  session: &mut SolverSession,
+  range: Range<f64>,
+  count: usize,
+  rng: &mut R,
+) -> Result<Vec<Solution>, LayoutError> {
+  let ctx = builder.ctx();
+  let free = builder.free_measures();
+  let mut solutions = Vec::with_capacity(count);
+  for _ in 0..count {
+    let mut distance = Measure::zero(ctx);
+    for &m in &free {
+      let target = Measure::new_const(ctx, rng.gen_range(range.start, range.end))?;
+      distance = distance + (m - target).abs();
+    }
+
+    builder.checkpoint();
+    builder.push_objective(Objective::Minimize(distance));
+    let report = builder.check_with(session);
+    builder.rollback();
+    solutions.push(report?.solution);
+  }
+  Ok(solutions)
+}
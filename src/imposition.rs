@@ -0,0 +1,235 @@
+//! Imposition of pages onto print sheets.
+
+use std::{cell::RefCell, rc::Rc};
+
+use thiserror::Error;
+
+use crate::{
+  layout::{builder::LayoutBuilder, context::LayoutContext, error::LayoutError, measure::Measure},
+  widgets::{Rectangle, RectangleMetrics},
+};
+
+#[derive(Error, Debug)]
+pub enum ImpositionError {
+  #[error("booklets are imposed two pages per side")]
+  BookletGrid,
+  #[error("sheets need at least one column and one row of pages")]
+  EmptyGrid,
+  #[error(transparent)]
+  Layout(#[from] LayoutError),
+}
+
+/// The sheet and the grid of pages printed on each of its sides.
+#[derive(Copy, Clone, Debug)]
+pub struct SheetSpec {
+  pub width: f64,
+  pub height: f64,
+  pub columns: usize,
+  pub rows: usize,
+  pub gutter: f64,
+
+  /// Space kept free along the sheet edges for crop marks.
+  pub crop_margin: f64,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PageOrder {
+  /// Pages fill the slots of one side after another, row by row.
+  Sequential,
+
+  /// Pages are ordered so that folding the stacked sheets in half yields a booklet.
+  Booklet,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SheetSide {
+  Front,
+  Back,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PagePlacement {
+  pub page: usize,
+
+  /// Placement relative to the top left corner of the sheet.
+  pub rect: RectangleMetrics,
+}
+
+#[derive(Clone, Debug)]
+pub struct SheetPlacement {
+  pub sheet: usize,
+  pub side: SheetSide,
+  pub pages: Vec<PagePlacement>,
+}
+
+/// A sheet side and the page in each of its slots.
+type SideSlots = (usize, SheetSide, Vec<Option<usize>>);
+
+/// Pages of possibly different sizes to impose on sheets.
+///
+/// Every side of every sheet shares the same grid, so that all sheets can be cut alike. Pages
+/// are centered in their cells, except in booklets where they meet at the spine.
+pub struct Imposition {
+  pub sheet: SheetSpec,
+  pub order: PageOrder,
+  pages: Vec<(f64, f64)>,
+}
+
+impl Imposition {
+  pub fn new(sheet: SheetSpec, order: PageOrder) -> Self {
+    Self {
+      sheet,
+      order,
+      pages: vec![],
+    }
+  }
+
+  pub fn push_page(&mut self, width: f64, height: f64) {
+    self.pages.push((width, height));
+  }
+
+  /// Assigns pages to the slots of each sheet side, in row-major order. `None` is a blank slot.
+  fn sides(&self) -> Result<Vec<SideSlots>, ImpositionError> {
+    if self.sheet.columns == 0 || self.sheet.rows == 0 {
+      return Err(ImpositionError::EmptyGrid);
+    }
+    let n = self.pages.len();
+    match self.order {
+      PageOrder::Sequential => {
+        let per_side = self.sheet.columns * self.sheet.rows;
+        Ok(
+          (0..n)
+            .step_by(per_side)
+            .enumerate()
+            .map(|(sheet, first)| {
+              let slots = (first..first + per_side)
+                .map(|page| if page < n { Some(page) } else { None })
+                .collect();
+              (sheet, SheetSide::Front, slots)
+            })
+            .collect(),
+        )
+      }
+      PageOrder::Booklet => {
+        if self.sheet.columns != 2 || self.sheet.rows != 1 {
+          return Err(ImpositionError::BookletGrid);
+        }
+        let padded = n.div_ceil(4) * 4;
+        let page = |i: usize| if i < n { Some(i) } else { None };
+        Ok(
+          (0..padded / 4)
+            .flat_map(|sheet| {
+              vec![
+                (
+                  sheet,
+                  SheetSide::Front,
+                  vec![page(padded - 1 - 2 * sheet), page(2 * sheet)],
+                ),
+                (
+                  sheet,
+                  SheetSide::Back,
+                  vec![page(2 * sheet + 1), page(padded - 2 - 2 * sheet)],
+                ),
+              ]
+            })
+            .collect(),
+        )
+      }
+    }
+  }
+
+  pub fn solve(&self) -> Result<Vec<SheetPlacement>, ImpositionError> {
+    let sides = self.sides()?;
+    let spec = &self.sheet;
+
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+
+    let columns = grid_axis(&mut builder, spec, spec.columns, spec.width);
+    let rows = grid_axis(&mut builder, spec, spec.rows, spec.height);
+
+    let placed = Rc::new(RefCell::new(vec![None; self.pages.len()]));
+    for (_, _, slots) in &sides {
+      for (slot, page) in slots.iter().enumerate() {
+        let page = match page {
+          Some(x) => *x,
+          None => continue,
+        };
+        let (width, height) = self.pages[page];
+        let (col_start, col_end) = columns[slot % spec.columns];
+        let (row_start, row_end) = rows[slot / spec.columns];
+
+        let placed = placed.clone();
+        let rect = Rectangle::with_width_and_height(
+          &ctx,
+          width,
+          height,
+          Box::new(move |metrics| {
+            placed.borrow_mut()[page] = Some(metrics);
+            Ok(())
+          }),
+        );
+        builder.push_hard_constraint(rect.left.prop_ge(col_start));
+        builder.push_hard_constraint(rect.right.prop_le(col_end));
+        builder.push_hard_constraint(rect.top.prop_ge(row_start));
+        builder.push_hard_constraint(rect.bottom.prop_le(row_end));
+        builder.push_constraint((rect.top - row_start).prop_eq(row_end - rect.bottom));
+        match self.order {
+          PageOrder::Booklet if slot == 0 => {
+            builder.push_hard_constraint(rect.right.prop_eq(col_end))
+          }
+          PageOrder::Booklet => builder.push_hard_constraint(rect.left.prop_eq(col_start)),
+          PageOrder::Sequential => {
+            builder.push_constraint((rect.left - col_start).prop_eq(col_end - rect.right))
+          }
+        };
+        builder.push_widget(rect);
+      }
+    }
+    builder.build()?;
+
+    let placed = placed.borrow();
+    Ok(
+      sides
+        .into_iter()
+        .map(|(sheet, side, slots)| SheetPlacement {
+          sheet,
+          side,
+          pages: slots
+            .into_iter()
+            .flatten()
+            .map(|page| PagePlacement {
+              page,
+              rect: placed[page].expect("page was not painted"),
+            })
+            .collect(),
+        })
+        .collect(),
+    )
+  }
+}
+
+/// Cell edges along one axis of the grid shared by every sheet side.
+fn grid_axis<'a>(
+  builder: &mut LayoutBuilder<'a>,
+  spec: &SheetSpec,
+  count: usize,
+  extent: f64,
+) -> Vec<(Measure<'a>, Measure<'a>)> {
+  let ctx = builder.ctx();
+  let mut edges = vec![];
+  let mut cursor = Measure::new_const(ctx, spec.crop_margin).unwrap();
+  for i in 0..count {
+    let size = Measure::new_unbound(ctx);
+    builder.push_hard_constraint(size.prop_ge(Measure::zero(ctx)));
+    if i != 0 {
+      cursor = cursor + spec.gutter;
+    }
+    edges.push((cursor, cursor + size));
+    cursor = cursor + size;
+  }
+  builder.push_hard_constraint(
+    (cursor + spec.crop_margin).prop_le(Measure::new_const(ctx, extent).unwrap()),
+  );
+  edges
+}
@@ -0,0 +1,220 @@
+//! Analyses of the expressions of a layout before solving.
+
+use std::{collections::HashMap, fmt};
+
+use super::{
+  measure::{Measure, MeasureVariant},
+  objective::Objective,
+  prop::{Prop, PropVariant},
+};
+
+/// A product or quotient of measures that are not constant. Z3 may give up on layouts with one,
+/// and the backend without Z3 cannot solve them.
+#[derive(Clone, Debug)]
+pub struct Nonlinearity<'a> {
+  pub term: Measure<'a>,
+
+  /// Constraints and objectives that depend on `term`, rendered with `Display`, so with their
+  /// labels.
+  pub users: Vec<String>,
+
+  /// A linear reformulation of `term`, where one is known.
+  pub suggestion: Option<String>,
+}
+
+impl<'a> fmt::Display for Nonlinearity<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} is nonlinear", self.term)?;
+    if !self.users.is_empty() {
+      write!(f, " in {}", self.users.join("; "))?;
+    }
+    if let Some(suggestion) = &self.suggestion {
+      write!(f, ". {}", suggestion)?;
+    }
+    Ok(())
+  }
+}
+
+/// Nonlinear terms of `constraints` and `objectives`, with the ones that depend on each.
+pub(crate) fn nonlinearities<'a>(
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+) -> Vec<Nonlinearity<'a>> {
+  let mut found: Vec<Nonlinearity<'a>> = vec![];
+  let mut position = HashMap::new();
+  let users = constraints
+    .iter()
+    .map(|&c| (nonlinear_terms(vec![], vec![c]), c.to_string()))
+    .chain(objectives.iter().map(|o| {
+      let m = match *o {
+        Objective::Minimize(m) | Objective::Maximize(m) => m,
+      };
+      (nonlinear_terms(vec![m], vec![]), o.to_string())
+    }));
+  for (terms, user) in users {
+    for term in terms {
+      let i = *position.entry(term.addr()).or_insert_with(|| {
+        found.push(Nonlinearity {
+          term,
+          users: vec![],
+          suggestion: suggestion(term),
+        });
+        found.len() - 1
+      });
+      found[i].users.push(user.clone());
+    }
+  }
+  found
+}
+
+/// A linear reformulation of the nonlinear `term`, where one is known.
+pub(crate) fn suggestion<'a>(term: Measure<'a>) -> Option<String> {
+  use MeasureVariant as V;
+  if !nonlinear_terms(vec![term], vec![])
+    .iter()
+    .any(|x| x.addr() == term.addr())
+  {
+    return None;
+  }
+  let mut analysis = Analysis::default();
+  let mut constant = |m| !analysis.measure(m);
+  // A selection between constants distributes over the other operand.
+  let choice = |m: Measure<'a>| match *m.variant {
+    V::Select(cond, l, r) => Some((cond, l, r)),
+    _ => None,
+  };
+  match *term.variant {
+    V::Mul(l, r) => {
+      let (select, other) = match (choice(l), choice(r)) {
+        (Some(x), _) => (x, r),
+        (_, Some(x)) => (x, l),
+        _ => {
+          return Some(format!(
+            "If {} or {} is known before solving, define it as a named constant",
+            l, r
+          ))
+        }
+      };
+      let (cond, a, b) = select;
+      if !constant(a) || !constant(b) {
+        return None;
+      }
+      Some(format!(
+        "Distribute it over the selection, as (select ({}) ({} * {}) ({} * {}))",
+        cond, a, other, b, other
+      ))
+    }
+    V::Div(l, r) => match choice(r) {
+      Some((cond, a, b)) if constant(a) && constant(b) => Some(format!(
+        "Distribute it over the selection, as (select ({}) ({} / {}) ({} / {}))",
+        cond, l, a, l, b
+      )),
+      _ => Some(format!(
+        "If {} is positive, multiply the other side of the comparison by it instead",
+        r
+      )),
+    },
+    _ => None,
+  }
+}
+
+/// Products and quotients that `measures` and `props` depend on whose operands are not constant,
+/// each listed once. Named constants count as constant.
+pub(crate) fn nonlinear_terms<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+) -> Vec<Measure<'a>> {
+  let mut analysis = Analysis::default();
+  for m in measures {
+    analysis.measure(m);
+  }
+  for p in props {
+    analysis.prop(p);
+  }
+  analysis.nonlinear
+}
+
+/// Whether `prop` is a conjunction of linear comparisons of measures without choices, which a
+/// table can express with fixed and proportional sizes, sums of columns and stacked rows.
+/// Disjunctions, negations, free propositions and selections on conditions that are not constant
+/// depend on decisions that a table cannot take.
+pub(crate) fn is_tabular(prop: Prop) -> bool {
+  use PropVariant as V;
+  match *prop.variant {
+    V::Const(_) => true,
+    V::And(l, r) => is_tabular(l) && is_tabular(r),
+    V::Eq(l, r) | V::Lt(l, r) | V::Le(l, r) | V::Gt(l, r) | V::Ge(l, r) => {
+      let mut analysis = Analysis::default();
+      analysis.measure(l);
+      analysis.measure(r);
+      analysis.nonlinear.is_empty() && analysis.choices.is_empty()
+    }
+    V::Unbound | V::Or(..) | V::Not(_) => false,
+  }
+}
+
+/// Finds the nonlinear terms of expressions, visiting shared nodes once.
+#[derive(Default)]
+struct Analysis<'a> {
+  /// Whether each visited node depends on an unbound measure or proposition, by node address.
+  variable: HashMap<usize, bool>,
+  nonlinear: Vec<Measure<'a>>,
+
+  /// Conditions of selections that are not constant.
+  choices: Vec<Prop<'a>>,
+}
+
+impl<'a> Analysis<'a> {
+  fn measure(&mut self, m: Measure<'a>) -> bool {
+    use MeasureVariant as V;
+    if let Some(&x) = self.variable.get(&m.addr()) {
+      return x;
+    }
+    let variable = match *m.variant {
+      V::Unbound => true,
+      V::Const(..) | V::Named(_) => false,
+      V::Add(l, r) | V::Sub(l, r) => self.measure(l) | self.measure(r),
+      V::Mul(l, r) => {
+        let (l, r) = (self.measure(l), self.measure(r));
+        if l && r {
+          self.nonlinear.push(m);
+        }
+        l || r
+      }
+      V::Div(l, r) => {
+        let (l, r) = (self.measure(l), self.measure(r));
+        if r {
+          self.nonlinear.push(m);
+        }
+        l || r
+      }
+      V::Select(cond, l, r) => {
+        let choice = self.prop(cond);
+        if choice {
+          self.choices.push(cond);
+        }
+        choice | self.measure(l) | self.measure(r)
+      }
+    };
+    self.variable.insert(m.addr(), variable);
+    variable
+  }
+
+  fn prop(&mut self, p: Prop<'a>) -> bool {
+    use PropVariant as V;
+    if let Some(&x) = self.variable.get(&p.addr()) {
+      return x;
+    }
+    let variable = match *p.variant {
+      V::Unbound => true,
+      V::Const(_) => false,
+      V::Eq(l, r) | V::Lt(l, r) | V::Le(l, r) | V::Gt(l, r) | V::Ge(l, r) => {
+        self.measure(l) | self.measure(r)
+      }
+      V::Or(l, r) | V::And(l, r) => self.prop(l) | self.prop(r),
+      V::Not(x) => self.prop(x),
+    };
+    self.variable.insert(p.addr(), variable);
+    variable
+  }
+}
@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+use super::{context::LayoutContext, measure::Measure};
+
+/// Number of linear pieces used to approximate a full period of sine and cosine.
+const TRIG_SEGMENTS: usize = 32;
+
+/// Scale used when a trigonometric function of a constant angle is turned into a constant.
+const TRIG_PRECISION: f64 = 1_000_000.0;
+
+/// An angle, stored as a measure in radians.
+#[derive(Copy, Clone, Debug)]
+pub struct Angle<'a> {
+  pub radians: Measure<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Angle<'a> {
+  pub fn from_radians(radians: Measure<'a>) -> Self {
+    Self { radians }
+  }
+
+  pub fn from_degrees(degrees: Measure<'a>) -> Self {
+    if let Some(value) = degrees.const_value() {
+      return Self::degrees(degrees.ctx, value);
+    }
+    Self {
+      radians: degrees * trig_const(degrees.ctx, PI / 180.0),
+    }
+  }
+
+  /// Panics if `value` is not finite or beyond 2147 radians either way.
+  pub fn radians(ctx: &'a LayoutContext, value: f64) -> Self {
+    Self::from_radians(trig_const(ctx, value))
+  }
+
+  /// Panics like `radians`.
+  pub fn degrees(ctx: &'a LayoutContext, value: f64) -> Self {
+    Self::radians(ctx, value.to_radians())
+  }
+
+  /// `n` angles evenly dividing a full turn, starting at `start_degrees`.
+  pub fn evenly_spaced(ctx: &'a LayoutContext, n: usize, start_degrees: f64) -> Vec<Self> {
+    (0..n)
+      .map(|i| Self::degrees(ctx, start_degrees + 360.0 * i as f64 / n as f64))
+      .collect()
+  }
+
+  pub fn as_degrees(&self) -> Measure<'a> {
+    self.radians * trig_const(self.radians.ctx, 180.0 / PI)
+  }
+
+  /// The sine of this angle.
+  ///
+  /// Exact up to 1e-6 for constant angles. Otherwise a piecewise-linear approximation over
+  /// one period, which keeps the constraint system linear; the angle is expected to lie in
+  /// `[-2π, 4π)`.
+  pub fn sin(&self) -> Measure<'a> {
+    self.trig(f64::sin)
+  }
+
+  /// The cosine of this angle. See `sin` for the precision guarantees.
+  pub fn cos(&self) -> Measure<'a> {
+    self.trig(f64::cos)
+  }
+
+  fn trig(&self, f: fn(f64) -> f64) -> Measure<'a> {
+    let ctx = self.radians.ctx;
+    if let Some(value) = self.radians.const_value() {
+      return trig_const(ctx, f(value));
+    }
+
+    let period = trig_const(ctx, 2.0 * PI);
+    let zero = Measure::zero(ctx);
+    let x = self
+      .radians
+      .prop_lt(zero)
+      .select(self.radians + period, self.radians);
+    let x = x.prop_ge(period).select(x - period, x);
+
+    let step = 2.0 * PI / TRIG_SEGMENTS as f64;
+    let mut result = None;
+    for k in (0..TRIG_SEGMENTS).rev() {
+      let x0 = step * k as f64;
+      let y0 = f(x0);
+      let slope = (f(x0 + step) - y0) / step;
+      let piece = (x - trig_const(ctx, x0)) * trig_const(ctx, slope) + trig_const(ctx, y0);
+      result = Some(match result {
+        None => piece,
+        Some(rest) => x.prop_ge(trig_const(ctx, x0)).select(piece, rest),
+      });
+    }
+    result.unwrap()
+  }
+}
+
+/// Panics if `value` cannot be kept at `TRIG_PRECISION` in an `i32` numerator.
+fn trig_const(ctx: &LayoutContext, value: f64) -> Measure<'_> {
+  let scaled = (value * TRIG_PRECISION).round();
+  assert!(
+    scaled.abs() <= i32::MAX as f64,
+    "{} is out of range for a trigonometric constant",
+    value
+  );
+  Measure::new_ratio(ctx, scaled as i32, TRIG_PRECISION as i32).unwrap()
+}
@@ -1,30 +1,426 @@
-use anyhow::Result;
-
+#[cfg(not(feature = "z3"))]
+use super::native;
+#[cfg(feature = "z3")]
+use super::{
+  analysis::nonlinear_terms,
+  context::Z3BuildContext,
+  measure::{named_symbol, to_ratio},
+  partition::{self, Component},
+  session::SolverSession,
+  solution::{named_consts, Bound},
+};
 use super::{
-  context::{LayoutContext, Z3BuildContext},
+  analysis::{nonlinearities, Nonlinearity},
+  cache::{layout_key, layout_structure, CachedLayout, LayoutStore},
+  cancellation::CancellationToken,
+  context::{LayoutContext, PainterGuard},
+  error::{LayoutError, PaintError},
+  geometry::{find_anchor, Point, RectMeasures},
+  linear,
+  measure::Measure,
+  objective::Objective,
+  progress::{Progress, SolvePhase},
   prop::Prop,
-  widget::RawWidget,
+  render::RenderTarget,
+  simplex,
+  smtlib::{ModelValues, Variables},
+  solution::{find_measures, free_measures, Solution, TOLERANCE},
+  tag::Tag,
+  template::{ConstraintTemplate, Params},
+  transform::Transform,
+  unit::{Length, Units},
+  widget::{BoundedWidget, CompositeWidget, Parts, RawWidget, SendPainter},
+};
+use std::{
+  any::Any,
+  collections::{HashMap, HashSet},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
 };
-use thiserror::Error;
+#[cfg(feature = "z3")]
+use z3::ast::{Ast, Bool, Real};
+
+/// Source of the ids that tell builders and checkpoints apart in a `SolverSession`.
+static NEXT_SYNC_ID: AtomicU64 = AtomicU64::new(0);
 
 pub struct LayoutBuilder<'a> {
   layout_ctx: &'a LayoutContext,
   widgets: Vec<Box<dyn RawWidget<'a> + 'a>>,
+  widget_ids: Vec<WidgetId>,
+
+  /// Explicit z-order of widgets, by handle. Others are at zero.
+  z_orders: HashMap<WidgetId, ZOrder<'a>>,
+  tags: HashMap<WidgetId, Tag>,
+
+  /// Anchors registered with `set_anchor`, by widget and name.
+  anchors: HashMap<(WidgetId, String), Point<'a>>,
   constraints: Vec<Prop<'a>>,
+  constraint_ids: Vec<ConstraintId>,
+  next_handle: u64,
+
+  /// Names of the constraint groups, and whether each is enabled.
+  groups: Vec<(String, bool)>,
+
+  /// Index of the group of each grouped constraint.
+  grouped: HashMap<ConstraintId, usize>,
+  objectives: Vec<(Objective<'a>, u32)>,
+  solver_config: SolverConfig,
+  units: Units,
+  cancellation: Option<CancellationToken>,
+  cache: Option<Arc<dyn LayoutStore>>,
+  progress: Option<Progress>,
+  checkpoints: Vec<Checkpoint>,
+
+  /// Constraints holding widgets at their intrinsic height, from `fit_intrinsic_sizes`.
+  intrinsic: HashMap<WidgetId, (f64, ConstraintId)>,
+
+  /// Constraints from the arrange pass of `measure_and_arrange`, by the widget they came from.
+  content: Vec<(WidgetId, ConstraintId)>,
+
+  /// The widget of `push_root` with its bounds, and the constraints keeping the other widgets
+  /// inside them.
+  root: Option<(WidgetId, RectMeasures<'a>)>,
+  contained: Vec<(WidgetId, ConstraintId)>,
+  #[cfg_attr(not(feature = "z3"), allow(dead_code))]
+  id: u64,
+}
+
+/// Handle of a widget pushed to a `LayoutBuilder`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WidgetId(pub(crate) u64);
+
+/// Where a widget is painted among the others.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum ZOrder<'a> {
+  Fixed(i32),
+
+  /// The solved value of the measure.
+  Solved(Measure<'a>),
+}
+
+impl<'a> ZOrder<'a> {
+  pub fn value(self, solution: &Solution) -> Result<f64, LayoutError> {
+    match self {
+      ZOrder::Fixed(z) => Ok(z as f64),
+      ZOrder::Solved(m) => solution
+        .value_of(m)
+        .ok_or_else(|| LayoutError::ModelExtraction {
+          measure: m.to_string(),
+        }),
+    }
+  }
+}
+
+/// Handle of a constraint pushed to a `LayoutBuilder`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConstraintId(u64);
+
+/// A named set of constraints of a `LayoutBuilder` that is enabled or disabled as a unit, like
+/// the constraints of one responsive variant of a layout. Groups start enabled.
+pub struct ConstraintGroup<'b, 'a> {
+  builder: &'b mut LayoutBuilder<'a>,
+  index: usize,
+}
+
+#[allow(dead_code)]
+impl<'b, 'a> ConstraintGroup<'b, 'a> {
+  pub fn push_constraint(&mut self, prop: Prop<'a>) -> ConstraintId {
+    let id = self.builder.push_constraint(prop);
+    self.builder.grouped.insert(id, self.index);
+    id
+  }
+
+  pub fn push_hard_constraint(&mut self, prop: Prop<'a>) -> ConstraintId {
+    self.push_constraint(prop.hard())
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.builder.groups[self.index].1
+  }
+
+  /// Includes the constraints of the group in the layout, or leaves them out.
+  pub fn set_enabled(&mut self, enabled: bool) {
+    if self.is_enabled() != enabled {
+      self.builder.groups[self.index].1 = enabled;
+      self.builder.renew_id();
+    }
+  }
+
+  pub fn enable(&mut self) {
+    self.set_enabled(true);
+  }
+
+  pub fn disable(&mut self) {
+    self.set_enabled(false);
+  }
+}
+
+struct Checkpoint {
+  widgets: usize,
+  constraints: usize,
+  objectives: usize,
+  #[cfg_attr(not(feature = "z3"), allow(dead_code))]
+  id: u64,
+}
+
+/// Resource budget of the solver. `None` means no limit.
+///
+/// Without the `z3` feature, only `relax_on_unsat` has an effect. Layouts are then always
+/// deterministic, and measures left free are zero.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SolverConfig {
+  pub timeout_ms: Option<u32>,
+
+  /// Z3 resource limit, a deterministic alternative to `timeout_ms`.
+  pub rlimit: Option<u32>,
+
+  /// Makes repeated builds of the same layout produce the same solution, for snapshot tests.
+  ///
+  /// Fixes the random seed of the Z3 solvers of the builder, leaving other solvers of the process
+  /// alone, and pulls every measure left free by the constraints towards zero, after all other
+  /// objectives.
+  pub deterministic: bool,
+
+  /// Sets measures that no constraint or objective depends on to zero.
+  pub zero_unconstrained: bool,
+
+  /// When the hard constraints conflict, demotes them to soft ones, a tier of equal weight at a
+  /// time from the lowest weight, until a layout is found. Constraints pushed to the builder are
+  /// demoted first, and the ones of widgets only once none of those is left.
+  pub relax_on_unsat: bool,
+
+  /// Solves groups of constraints and objectives that share no variables, like independent
+  /// clusters of widgets, each in a Z3 context of its own instead of as one problem. With the
+  /// `parallel` feature, the groups are solved at the same time on the rayon thread pool.
+  /// Measures that no constraint or objective depends on are set to zero.
+  ///
+  /// Has no effect with `relax_on_unsat`. The timeout and resource limit apply to each group.
+  pub decompose: bool,
+
+  /// Z3 logic of the layout, whose tactics Z3 then runs. Layouts with only hard constraints and
+  /// no objectives are solved by a solver for the logic instead of the optimizer, which takes no
+  /// logic in the Z3 API. So is finding the conflicting constraints of an unsatisfiable layout.
+  /// `None` picks linear or nonlinear real arithmetic from the terms the layout has.
+  ///
+  /// Layouts are still solved by the optimizer with `relax_on_unsat` or `deterministic`.
+  pub logic: Option<SolverLogic>,
+}
+
+/// How `LayoutBuilder::solve_n` tells layouts apart.
+#[derive(Clone, Debug)]
+pub struct Diversity<'a> {
+  /// Measures compared between layouts. Others may be equal.
+  pub measures: Vec<Measure<'a>>,
+  pub distance: Distance,
+
+  /// Least distance between any two of the layouts.
+  pub min_distance: f64,
+}
+
+/// Distance between the values of the measures of two layouts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Distance {
+  /// Sum of the differences, so small changes to many measures add up.
+  Manhattan,
+
+  /// Largest difference, so some measure has to change by the full distance.
+  Chebyshev,
+}
+
+/// Z3 logic of the constraints of a layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SolverLogic {
+  /// Linear real arithmetic, `QF_LRA`.
+  Lra,
+
+  /// Nonlinear real arithmetic, `QF_NRA`, for products and quotients of measures.
+  Nra,
+
+  /// Linear integer and real arithmetic, `QF_LIRA`.
+  Lira,
+}
+
+impl SolverLogic {
+  /// Name of the logic in SMT-LIB.
+  pub fn name(self) -> &'static str {
+    match self {
+      SolverLogic::Lra => "QF_LRA",
+      SolverLogic::Nra => "QF_NRA",
+      SolverLogic::Lira => "QF_LIRA",
+    }
+  }
 }
 
 #[derive(Debug)]
 pub struct BuildReport<'a> {
   pub satisfied_constraints: Vec<Prop<'a>>,
   pub unsatisfied_constraints: Vec<Prop<'a>>,
+
+  /// Optimal value of each objective, in the order they were optimized.
+  pub objective_values: Vec<(Objective<'a>, f64)>,
+
+  /// Solved values, for querying expressions after the build.
+  pub solution: Solution,
+
+  pub stats: SolveStats,
+
+  /// Unbound measures of the widgets that no constraint or objective depends on. Unless
+  /// `SolverConfig::zero_unconstrained` is set, their values are arbitrary.
+  pub unconstrained_measures: Vec<Measure<'a>>,
+
+  /// Soft constraints that only depend on constants and are false, like `5 < 3`. Found without
+  /// the solver, and also listed in `unsatisfied_constraints`. With
+  /// `SolverConfig::relax_on_unsat`, hard ones are listed too, and in `dropped_constraints`.
+  pub constant_contradictions: Vec<Prop<'a>>,
+
+  /// Hard constraints given up by `SolverConfig::relax_on_unsat` to find a layout.
+  pub dropped_constraints: Vec<Prop<'a>>,
+
+  /// Errors of the painters that failed, in the order their widgets were pushed. The other
+  /// widgets are painted regardless.
+  pub paint_errors: Vec<(WidgetId, PaintError)>,
+
+  /// Solved values of the measures of each widget, in the order of `RawWidget::measures`.
+  metrics: HashMap<WidgetId, Vec<f64>>,
+
+  tags: HashMap<WidgetId, Tag>,
 }
 
-#[derive(Error, Debug)]
-pub enum LayoutUnsatError {
-  #[error("provided constraints cannot be satisfied")]
-  Unsat,
-  #[error("failed to derive a layout under provided constraints")]
-  Unknown,
+/// Constraints and objectives of a layout, gathered for a solve.
+struct Problem<'a> {
+  start: Option<Instant>,
+  measures: Vec<Measure<'a>>,
+  constraints: Vec<Prop<'a>>,
+  objectives: Vec<Objective<'a>>,
+
+  /// Number of objectives of the widgets and the builder, which come before the ones added for
+  /// the solver config.
+  #[cfg_attr(not(feature = "z3"), allow(dead_code))]
+  user_objectives: usize,
+  unconstrained: Vec<Measure<'a>>,
+  contradictions: Vec<Prop<'a>>,
+}
+
+impl<'a> Problem<'a> {
+  fn solved(
+    self,
+    solution: Solution,
+    path: SolvePath,
+    relaxed: Vec<Prop<'a>>,
+    z3: Vec<(String, f64)>,
+  ) -> Solved<'a> {
+    Solved {
+      solution,
+      relaxed,
+      objectives: self.objectives,
+      stats: SolveStats {
+        path,
+        z3,
+        solve_time: self.start.map(|x| x.elapsed()).unwrap_or_default(),
+        ..SolveStats::default()
+      },
+      unconstrained: self.unconstrained,
+      contradictions: self.contradictions,
+    }
+  }
+}
+
+/// Outcome of a successful check, before it is turned into a `BuildReport`.
+struct Solved<'a> {
+  solution: Solution,
+  relaxed: Vec<Prop<'a>>,
+  objectives: Vec<Objective<'a>>,
+  stats: SolveStats,
+  unconstrained: Vec<Measure<'a>>,
+  contradictions: Vec<Prop<'a>>,
+}
+
+/// How a layout was solved.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SolvePath {
+  /// By Z3.
+  #[default]
+  Solver,
+
+  /// By elimination on its equalities, for layouts they fully determine.
+  Elimination,
+
+  /// By the simplex method, for linear layouts whose soft constraints can all hold together.
+  Simplex,
+
+  /// By Z3, in parts that share no variables, with `SolverConfig::decompose`.
+  Decomposed,
+
+  /// By a Z3 solver for the logic of the layout, for layouts with only hard constraints and no
+  /// objectives. See `SolverConfig::logic`.
+  Logic,
+
+  /// By the built-in backend that replaces Z3 without the `z3` feature.
+  Native,
+
+  /// From the store of `LayoutBuilder::with_cache`, for a layout solved before.
+  Cached,
+}
+
+/// Size and cost of a solve, for profiling slow layouts.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolveStats {
+  pub path: SolvePath,
+
+  /// Unbound measures and propositions the layout depends on.
+  pub variables: usize,
+  pub constraints: usize,
+
+  /// Bytes allocated in the arena of the layout context so far.
+  pub arena_bytes: usize,
+
+  /// Statistics reported by Z3 for the last check, by name. Empty without Z3.
+  pub z3: Vec<(String, f64)>,
+
+  /// Wall-clock time of translating the constraints and solving them, excluding painting. Zero
+  /// on `wasm32-unknown-unknown`, which has no clock.
+  pub solve_time: Duration,
+}
+
+impl<'a> BuildReport<'a> {
+  /// The values the painter of the widget of `id` is given, for laying out without painting.
+  pub fn metrics_of(&self, id: WidgetId) -> Option<&[f64]> {
+    self.metrics.get(&id).map(|x| x.as_slice())
+  }
+
+  /// Solved values of the measures of every widget, by id.
+  #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+  pub(crate) fn metrics(&self) -> &HashMap<WidgetId, Vec<f64>> {
+    &self.metrics
+  }
+
+  /// The tag of the widget of `id` when the layout was solved, if it has one of type `T`.
+  pub fn tag_of<T: Any + Send + Sync>(&self, id: WidgetId) -> Option<&T> {
+    self.tags.get(&id)?.downcast_ref()
+  }
+
+  /// Ids of the widgets whose tag is of type `T`, with their tags.
+  pub fn tagged<T: Any + Send + Sync>(&self) -> impl Iterator<Item = (WidgetId, &T)> {
+    self
+      .tags
+      .iter()
+      .filter_map(|(&id, tag)| Some((id, tag.downcast_ref()?)))
+  }
+
+  /// One line per unsatisfied constraint, naming labelled constraints by their label.
+  pub fn violations(&self) -> Vec<String> {
+    self
+      .unsatisfied_constraints
+      .iter()
+      .map(|x| format!("{} violated", x))
+      .collect()
+  }
 }
 
 impl<'a> LayoutBuilder<'a> {
@@ -32,85 +428,2082 @@ impl<'a> LayoutBuilder<'a> {
     Self {
       layout_ctx,
       widgets: vec![],
+      widget_ids: vec![],
+      z_orders: HashMap::new(),
+      tags: HashMap::new(),
+      anchors: HashMap::new(),
       constraints: vec![],
+      constraint_ids: vec![],
+      next_handle: 0,
+      groups: vec![],
+      grouped: HashMap::new(),
+      objectives: vec![],
+      solver_config: SolverConfig::default(),
+      units: Units::default(),
+      cancellation: None,
+      cache: None,
+      progress: None,
+      checkpoints: vec![],
+      intrinsic: HashMap::new(),
+      content: vec![],
+      root: None,
+      contained: vec![],
+      id: NEXT_SYNC_ID.fetch_add(1, Ordering::Relaxed),
     }
   }
 
+  pub fn with_solver_config(mut self, config: SolverConfig) -> Self {
+    self.solver_config = config;
+    self
+  }
+
+  /// Makes `build` give up with `LayoutError::Cancelled` once `token` is cancelled.
+  pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+    self.cancellation = Some(token);
+    self
+  }
+
+  /// Makes builds look the layout up in `store` before solving it, and keep the solved layout
+  /// there for builders of an identical layout.
+  pub fn with_cache(mut self, store: Arc<dyn LayoutStore>) -> Self {
+    self.cache = Some(store);
+    self
+  }
+
+  /// Calls `callback` with the time since the solve started as each phase of a solve starts,
+  /// and periodically while Z3 searches, to drive a progress indicator.
+  pub fn on_progress(
+    mut self,
+    callback: impl Fn(SolvePhase, Duration) + Send + Sync + 'static,
+  ) -> Self {
+    self.progress = Some(Progress::new(callback));
+    self
+  }
+
+  /// Converts the lengths of `length` with `units`, like `Units::print` for PDF output.
+  pub fn with_units(mut self, units: Units) -> Self {
+    self.units = units;
+    self
+  }
+
   pub fn ctx(&self) -> &'a LayoutContext {
     self.layout_ctx
   }
 
-  pub fn push_widget<W: RawWidget<'a> + 'a>(&mut self, widget: W) {
-    let widget: Box<dyn RawWidget<'a> + 'a> = Box::new(widget);
+  pub fn units(&self) -> &Units {
+    &self.units
+  }
+
+  pub(crate) fn solver_config(&self) -> SolverConfig {
+    self.solver_config
+  }
+
+  /// A constant measure of `length`, in the canonical units of the builder.
+  pub fn length(&self, length: Length) -> Result<Measure<'a>, LayoutError> {
+    self.units.measure(self.layout_ctx, length)
+  }
+
+  pub fn push_widget<W: RawWidget<'a> + 'a>(&mut self, widget: W) -> WidgetId {
+    self.push_boxed(Box::new(widget))
+  }
+
+  /// Pushes the parts of `composite` and the constraints between them, and returns the ids of
+  /// the parts in order.
+  pub fn push_composite<C: CompositeWidget<'a>>(&mut self, composite: C) -> Vec<WidgetId> {
+    self.push_parts(composite.expand())
+  }
+
+  /// Instantiates `template` with `params`, pushes the result like `push_composite` and
+  /// returns the ids of its widgets in order.
+  pub fn push_template<T: ConstraintTemplate<'a> + ?Sized>(
+    &mut self,
+    template: &T,
+    params: &Params<'a>,
+  ) -> Result<Vec<WidgetId>, LayoutError> {
+    let parts = template.instantiate(self.layout_ctx, params)?;
+    Ok(self.push_parts(parts))
+  }
+
+  fn push_parts(&mut self, parts: Parts<'a>) -> Vec<WidgetId> {
+    for prop in parts.constraints {
+      self.push_constraint(prop);
+    }
+    parts
+      .widgets
+      .into_iter()
+      .map(|w| self.push_boxed(w))
+      .collect()
+  }
+
+  fn push_boxed(&mut self, widget: Box<dyn RawWidget<'a> + 'a>) -> WidgetId {
+    let widget: Box<dyn RawWidget<'a> + 'a> = Box::new(TrackedWidget {
+      inner: widget,
+      _guard: PainterGuard::new(self.layout_ctx),
+    });
+    let id = WidgetId(self.next_handle());
+    let bounds = widget.bounds();
     self.widgets.push(widget);
+    self.widget_ids.push(id);
+    if let Some(bounds) = bounds {
+      self.contain(id, bounds);
+    }
+    id
+  }
+
+  /// Pushes the root of the layout, like a `Canvas` of a fixed page size. Every other widget
+  /// with `RawWidget::bounds` is kept inside its bounds, including the ones pushed later. A
+  /// root pushed before is replaced, and becomes an ordinary widget.
+  pub fn push_root<W: BoundedWidget<'a> + 'a>(&mut self, widget: W) -> WidgetId {
+    for (_, c) in std::mem::take(&mut self.contained) {
+      self.remove_constraint(c);
+    }
+    let rect = widget.rect();
+    self.root = None;
+    let id = self.push_widget(widget);
+    self.root = Some((id, rect));
+    for i in 0..self.widgets.len() {
+      if let Some(bounds) = self.widgets[i].bounds() {
+        self.contain(self.widget_ids[i], bounds);
+      }
+    }
+    id
+  }
+
+  /// Keeps the widget of `id` inside the root, unless it is the root.
+  fn contain(&mut self, id: WidgetId, bounds: RectMeasures<'a>) {
+    if let Some((root, rect)) = self.root {
+      if root != id {
+        let c = self.push_hard_constraint(rect.contains(&bounds));
+        self.contained.push((id, c));
+      }
+    }
+  }
+
+  /// Removes the widget of `id` from the layout and drops its painter. Returns `false` if the
+  /// widget was already removed or rolled back.
+  pub fn remove_widget(&mut self, id: WidgetId) -> bool {
+    let i = match self.widget_ids.iter().position(|&x| x == id) {
+      Some(i) => i,
+      None => return false,
+    };
+    self.widgets.remove(i);
+    self.widget_ids.remove(i);
+    self.z_orders.remove(&id);
+    self.tags.remove(&id);
+    self.anchors.retain(|x, _| x.0 != id);
+    if let Some((_, c)) = self.intrinsic.remove(&id) {
+      self.remove_constraint(c);
+    }
+    for (_, c) in self.content.clone().into_iter().filter(|x| x.0 == id) {
+      self.remove_constraint(c);
+    }
+    self.content.retain(|x| x.0 != id);
+    if self.root.map(|x| x.0) == Some(id) {
+      self.root = None;
+    }
+    let root = self.root;
+    let contained = self.contained.clone();
+    for (_, c) in contained
+      .into_iter()
+      .filter(|x| root.is_none() || x.0 == id)
+    {
+      self.remove_constraint(c);
+    }
+    self.contained.retain(|x| root.is_some() && x.0 != id);
+    for c in &mut self.checkpoints {
+      if c.widgets > i {
+        c.widgets -= 1;
+      }
+    }
+    self.renew_id();
+    true
+  }
+
+  /// Paints the widget of `id` after the widgets of a lower `z`, and before the ones of a higher
+  /// `z`. Widgets are at zero by default, and widgets at the same z-order are painted in any
+  /// order. Returns `false` if the widget was removed or rolled back.
+  pub fn set_z_order(&mut self, id: WidgetId, z: i32) -> bool {
+    self.set_z(id, ZOrder::Fixed(z))
+  }
+
+  /// Like `set_z_order`, with the z-order of the widget of `id` at the solved value of `z`, so
+  /// that the constraints of the layout decide the stacking, like of widgets that may overlap.
+  pub fn set_z_index(&mut self, id: WidgetId, z: Measure<'a>) -> bool {
+    self.set_z(id, ZOrder::Solved(z))
+  }
+
+  fn set_z(&mut self, id: WidgetId, z: ZOrder<'a>) -> bool {
+    if !self.widget_ids.contains(&id) {
+      return false;
+    }
+    self.z_orders.insert(id, z);
+    true
+  }
+
+  /// Attaches `tag` to the widget of `id`, replacing its previous tag, like the domain object the
+  /// widget shows. Tags are listed in the `BuildReport` and can be read by painters with
+  /// `tag::current_tag`. Returns `false` if the widget was removed or rolled back.
+  pub fn set_tag<T: Any + Send + Sync>(&mut self, id: WidgetId, tag: T) -> bool {
+    if !self.widget_ids.contains(&id) {
+      return false;
+    }
+    self.tags.insert(id, Arc::new(tag));
+    true
   }
 
-  pub fn push_constraint(&mut self, prop: Prop<'a>) {
+  /// The tag of the widget of `id`, if it has one of type `T`.
+  pub fn tag_of<T: Any + Send + Sync>(&self, id: WidgetId) -> Option<&T> {
+    self.tags.get(&id)?.downcast_ref()
+  }
+
+  /// Registers `point` as the anchor `name` of the widget of `id`, like a port that the widget
+  /// does not define itself. Returns `false` if the widget was removed or rolled back.
+  pub fn set_anchor(&mut self, id: WidgetId, name: &str, point: Point<'a>) -> bool {
+    if !self.widget_ids.contains(&id) {
+      return false;
+    }
+    self.anchors.insert((id, name.to_string()), point);
+    true
+  }
+
+  /// The anchor `name` of the widget of `id`, from `set_anchor` or else from
+  /// `RawWidget::anchors`.
+  pub fn anchor(&self, id: WidgetId, name: &str) -> Option<Point<'a>> {
+    if let Some(&point) = self.anchors.get(&(id, name.to_string())) {
+      return Some(point);
+    }
+    let i = self.widget_ids.iter().position(|&x| x == id)?;
+    find_anchor(self.widgets[i].anchors(), name)
+  }
+
+  /// Z-order of the widget of `id`.
+  fn z_order(&self, id: WidgetId) -> ZOrder<'a> {
+    self.z_orders.get(&id).copied().unwrap_or(ZOrder::Fixed(0))
+  }
+
+  /// Drops the painters of all widgets pushed so far without running them, and returns how many
+  /// were dropped. The measures, constraints and objectives of the widgets stay in the layout.
+  pub fn drain_painters(&mut self) -> usize {
+    let before = self.layout_ctx.stats().live_painters;
+    self.drop_painters();
+    before - self.layout_ctx.stats().live_painters
+  }
+
+  pub fn push_constraint(&mut self, prop: Prop<'a>) -> ConstraintId {
+    let id = ConstraintId(self.next_handle());
     self.constraints.push(prop);
+    self.constraint_ids.push(id);
+    id
+  }
+
+  pub fn push_hard_constraint(&mut self, prop: Prop<'a>) -> ConstraintId {
+    self.push_constraint(prop.hard())
+  }
+
+  /// Removes the constraint of `id` from the layout. Returns `false` if the constraint was
+  /// already removed or rolled back.
+  pub fn remove_constraint(&mut self, id: ConstraintId) -> bool {
+    let i = match self.constraint_ids.iter().position(|&x| x == id) {
+      Some(i) => i,
+      None => return false,
+    };
+    self.constraints.remove(i);
+    self.constraint_ids.remove(i);
+    self.grouped.remove(&id);
+    for c in &mut self.checkpoints {
+      if c.constraints > i {
+        c.constraints -= 1;
+      }
+    }
+    self.renew_id();
+    true
   }
 
-  pub fn build(self) -> Result<BuildReport<'a>> {
-    let z3_ctx = z3::Context::new(&z3::Config::new());
-    let mut build_context = Z3BuildContext::new(&z3_ctx);
+  /// Replaces the constraint of `id` with `prop`, which is hard only if `prop` is. Returns
+  /// `false` if the constraint was already removed or rolled back.
+  pub fn replace_constraint(&mut self, id: ConstraintId, prop: Prop<'a>) -> bool {
+    match self.constraint_ids.iter().position(|&x| x == id) {
+      Some(i) => {
+        self.constraints[i] = prop;
+        self.renew_id();
+        true
+      }
+      None => false,
+    }
+  }
 
-    let opt = z3::Optimize::new(&z3_ctx);
+  /// The constraint group named `name`, created enabled if there is none yet.
+  pub fn group(&mut self, name: &str) -> ConstraintGroup<'_, 'a> {
+    let index = match self.groups.iter().position(|(x, _)| x == name) {
+      Some(i) => i,
+      None => {
+        self.groups.push((name.to_string(), true));
+        self.groups.len() - 1
+      }
+    };
+    ConstraintGroup {
+      builder: self,
+      index,
+    }
+  }
 
-    let constraints = self
-      .widgets
+  /// Constraints pushed to the builder, without the ones of disabled groups.
+  fn pushed_constraints(&self) -> impl Iterator<Item = Prop<'a>> + '_ {
+    self
+      .constraints
       .iter()
-      .map(|x| x.constraints().into_iter())
-      .flatten()
-      .chain(self.constraints.iter().copied())
-      .collect::<Vec<_>>();
-    for c in &constraints {
-      opt.assert_soft(&c.build_z3(&mut build_context)?, c.weight, None);
+      .zip(&self.constraint_ids)
+      .filter(move |(_, &id)| self.is_enabled(id))
+      .map(|(&c, _)| c)
+  }
+
+  /// Whether the constraint of `id` is in no group or in an enabled one.
+  fn is_enabled(&self, id: ConstraintId) -> bool {
+    match self.grouped.get(&id) {
+      Some(&g) => self.groups[g].1,
+      None => true,
     }
+  }
 
-    let check_res = opt.check(&[]);
-    match check_res {
-      z3::SatResult::Sat => {}
-      z3::SatResult::Unsat => return Err(LayoutUnsatError::Unsat.into()),
-      z3::SatResult::Unknown => return Err(LayoutUnsatError::Unknown.into()),
+  fn next_handle(&mut self) -> u64 {
+    self.next_handle += 1;
+    self.next_handle - 1
+  }
+
+  /// Gives the builder a new id, so that sessions assert the layout again from scratch instead
+  /// of only what was pushed since they last saw it.
+  fn renew_id(&mut self) {
+    self.id = NEXT_SYNC_ID.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn push_objective(&mut self, objective: Objective<'a>) {
+    self.add_objective(objective, 0);
+  }
+
+  /// Adds an objective that is optimized before every objective of lower priority.
+  ///
+  /// Widget objectives and objectives from `push_objective` have priority 0. Objectives of
+  /// equal priority are optimized in the order they are declared.
+  pub fn add_objective(&mut self, objective: Objective<'a>, priority: u32) {
+    self.objectives.push((objective, priority));
+  }
+
+  /// Like `build`, but solves the layout on a blocking thread of the tokio runtime, so that the
+  /// other tasks keep running. The builder borrows its layout context and cannot be sent there,
+  /// so its constraints and objectives are copied into an `OwnedLayout` for the solve, and the
+  /// widgets are painted with the values solved for it once the solve is done.
+  ///
+  /// The store of `with_cache` is not used, and `SolverConfig::relax_on_unsat` gives up the
+  /// constraints of widgets in the same order as pushed ones. Fails with
+  /// `LayoutError::AsyncRuntime` outside of a tokio runtime.
+  #[cfg(feature = "tokio")]
+  pub async fn build_async(&mut self) -> Result<BuildReport<'a>, LayoutError> {
+    let runtime = tokio::runtime::Handle::try_current().map_err(|_| LayoutError::AsyncRuntime)?;
+    let problem = self.problem()?;
+    let raised = super::owned::OwnedLayout::raise(
+      self.layout_ctx,
+      &problem.measures,
+      &problem.constraints,
+      &problem.objectives[..problem.user_objectives],
+    )?;
+    let (config, cancellation) = (self.solver_config, self.cancellation.clone());
+    let layout = raised.layout;
+    let (owned, path, dropped) = runtime
+      .spawn_blocking(move || layout.solve_report(config, cancellation))
+      .await
+      .map_err(|e| match e.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        Err(_) => LayoutError::Cancelled,
+      })??;
+
+    // Measures the solve did not reach are free, so they are pinned like with
+    // `zero_unconstrained`.
+    let measures = raised
+      .measures
+      .iter()
+      .map(|&(m, id)| (m.addr(), owned.value_of(id).unwrap_or(0.0)));
+    let named = super::solution::named_consts(problem.measures.iter().copied(), vec![])
+      .into_iter()
+      .filter_map(|name| Some((name.to_string(), self.layout_ctx.definition(name)?)))
+      .collect();
+    let mut solution = Solution::from_values(self.layout_ctx.id, measures, named);
+    for &(p, id) in &raised.props {
+      if let Some(holds) = owned.truth_of(id) {
+        solution.set_truth(p, holds);
+      }
     }
+    let relaxed = dropped
+      .into_iter()
+      .filter_map(|i| problem.constraints.get(i).copied())
+      .collect();
+    let solved = problem.solved(solution, path, relaxed, vec![]);
+    self.finish(solved, &mut ())
+  }
 
-    let model = opt
-      .get_model()
-      .expect("check returned sat but failed to get model");
-    for w in self.widgets {
-      let measures = w.measures();
-      let mut refined_values = Vec::with_capacity(measures.len());
-      for m in measures {
-        let value = model
-          .eval(&m.build_z3(&mut build_context)?)
-          .expect("check returned sat but model does not provided value for a measure");
-        let (num, den) = value
-          .as_real()
-          .expect("failed to get value from a evaluated Real");
-        refined_values.push(num as f64 / den as f64);
+  /// Marks a point that `rollback` returns to. Checkpoints nest.
+  pub fn checkpoint(&mut self) {
+    self.checkpoints.push(Checkpoint {
+      widgets: self.widgets.len(),
+      constraints: self.constraints.len(),
+      objectives: self.objectives.len(),
+      id: NEXT_SYNC_ID.fetch_add(1, Ordering::Relaxed),
+    });
+  }
+
+  /// Removes every widget, constraint and objective added since the last checkpoint, and the
+  /// checkpoint itself. Does nothing without a checkpoint.
+  pub fn rollback(&mut self) {
+    if let Some(checkpoint) = self.checkpoints.pop() {
+      self.widgets.truncate(checkpoint.widgets);
+      self.widget_ids.truncate(checkpoint.widgets);
+      let ids = &self.widget_ids;
+      self.tags.retain(|id, _| ids.contains(id));
+      self.anchors.retain(|x, _| ids.contains(&x.0));
+      self.constraints.truncate(checkpoint.constraints);
+      self.constraint_ids.truncate(checkpoint.constraints);
+      let constraint_ids = &self.constraint_ids;
+      self.intrinsic.retain(|_, x| constraint_ids.contains(&x.1));
+      self.content.retain(|x| constraint_ids.contains(&x.1));
+      self.contained.retain(|x| constraint_ids.contains(&x.1));
+      if let Some((root, _)) = self.root {
+        if !ids.contains(&root) {
+          self.root = None;
+        }
       }
-      w.paint(&refined_values)?;
+      self.objectives.truncate(checkpoint.objectives);
     }
+  }
 
-    let mut unsatisfied_constraints = vec![];
-    let mut satisfied_constraints = vec![];
+  /// Solves the layout and runs the painters of the widgets.
+  ///
+  /// The builder is kept, so the layout can be changed and built again. Painters only run once,
+  /// so later builds only paint the widgets pushed since.
+  pub fn build(&mut self) -> Result<BuildReport<'a>, LayoutError> {
+    self.build_into(&mut ())
+  }
 
-    for c in &constraints {
-      let value = model
-        .eval(&c.build_z3(&mut build_context)?)
-        .expect("check returned sat but model does not provided value for a prop");
-      let value = value
-        .as_bool()
-        .expect("failed to get value from a evaluated Bool");
-      if !value {
-        unsatisfied_constraints.push(*c);
-      } else {
-        satisfied_constraints.push(*c);
+  /// Like `build`, with the painters drawing into `target`.
+  #[cfg(feature = "z3")]
+  pub fn build_into(
+    &mut self,
+    target: &mut dyn RenderTarget,
+  ) -> Result<BuildReport<'a>, LayoutError> {
+    let solved = self.optimize(&mut SolverSession::new())?;
+    self.finish(solved, target)
+  }
+
+  /// Like `build`, with the painters drawing into `target`.
+  #[cfg(not(feature = "z3"))]
+  pub fn build_into(
+    &mut self,
+    target: &mut dyn RenderTarget,
+  ) -> Result<BuildReport<'a>, LayoutError> {
+    let solved = self.optimize()?;
+    self.finish(solved, target)
+  }
+
+  /// Like `build`, but reuses the Z3 context, translated terms and asserted constraints of
+  /// `session`.
+  #[cfg(feature = "z3")]
+  pub fn build_with(
+    &mut self,
+    session: &mut SolverSession,
+  ) -> Result<BuildReport<'a>, LayoutError> {
+    let solved = self.optimize(session)?;
+    self.finish(solved, &mut ())
+  }
+
+  /// Reports on `solved` and paints the widgets into `target`.
+  fn finish(
+    &mut self,
+    solved: Solved<'a>,
+    target: &mut dyn RenderTarget,
+  ) -> Result<BuildReport<'a>, LayoutError> {
+    let mut report = self.report(solved)?;
+    self.progress(SolvePhase::Painting);
+    report.paint_errors = report
+      .solution
+      .paint_widgets(self, target)?
+      .into_iter()
+      .map(|(_, id, e)| (id, e))
+      .collect();
+    self.drop_painters();
+    Ok(report)
+  }
+
+  /// Solves the layout without painting. Paint the result with `Solution::paint`.
+  pub fn solve(&self) -> Result<Solution, LayoutError> {
+    Ok(self.dry_run()?.solution)
+  }
+
+  /// Solves the layout like `build`, without painting. Where each widget ended up can be read
+  /// from `BuildReport::metrics_of`.
+  #[cfg(feature = "z3")]
+  pub fn dry_run(&self) -> Result<BuildReport<'a>, LayoutError> {
+    self.check_with(&mut SolverSession::new())
+  }
+
+  /// Solves the layout like `build`, without painting. Where each widget ended up can be read
+  /// from `BuildReport::metrics_of`.
+  #[cfg(not(feature = "z3"))]
+  pub fn dry_run(&self) -> Result<BuildReport<'a>, LayoutError> {
+    let solved = self.optimize()?;
+    self.report(solved)
+  }
+
+  /// Solves the layout again with the measures pinned in `previous`, an earlier solution of it,
+  /// held at their values, like a measure that follows the pointer in an editor. The pins carry
+  /// over to the returned solution, which is not painted.
+  ///
+  /// `previous` is used as a warm start. Linear layouts keep its choices: selections and
+  /// disjunctions take the same branches, and soft constraints that did not hold stay given up,
+  /// which the simplex method solves without Z3. When the pins rule that out, the layout is
+  /// solved from scratch as by `solve`.
+  pub fn resolve(&mut self, previous: &Solution) -> Result<Solution, LayoutError> {
+    let objectives = self.all_objectives();
+    let mut found = find_measures(
+      self.measures(&objectives),
+      self.all_constraints(),
+      previous.pins().iter().map(|&(addr, _)| addr),
+    );
+    let mut pins = Vec::with_capacity(previous.pins().len());
+    for &(addr, value) in previous.pins() {
+      let m = found.remove(&addr).ok_or(LayoutError::UnknownPin)?;
+      if !value.is_finite() {
+        return Err(LayoutError::BadConst { value });
       }
+      pins.push((m, value));
     }
 
-    Ok(BuildReport {
-      unsatisfied_constraints,
-      satisfied_constraints,
-    })
+    let problem = self.problem()?;
+    let warm = simplex::resolve(
+      self.layout_ctx,
+      &problem.measures,
+      &problem.constraints,
+      &problem.objectives,
+      &pins,
+      previous,
+    );
+    let mut solution = match warm {
+      Some(x) => x,
+      None => {
+        let pinned = pins
+          .iter()
+          .map(|&(m, value)| Ok(m.prop_eq(Measure::new_const(self.layout_ctx, value)?)))
+          .collect::<Result<Vec<_>, LayoutError>>()?;
+        self.checkpoint();
+        for p in pinned {
+          self.push_hard_constraint(p);
+        }
+        let res = self.solve();
+        self.rollback();
+        res?
+      }
+    };
+    solution.set_pins(previous.pins().to_vec());
+    Ok(solution)
+  }
+
+  /// `solution`, a solution of the layout, with its measures rounded to multiples of `step`,
+  /// like 1 for whole pixels. Rounding each value on its own can leave 1px gaps between
+  /// widgets that share an edge, so the hard equalities of the layout are kept: a measure that
+  /// they determine from rounded ones, like the left edge of a widget placed after another, is
+  /// derived instead of rounded.
+  ///
+  /// Measures whose rounding would break a hard constraint, like a strict inequality between
+  /// measures that round to the same value, keep their solved value.
+  pub fn snap(&self, solution: &Solution, step: f64) -> Result<Solution, LayoutError> {
+    if !(step.is_finite() && step > 0.0) {
+      return Err(LayoutError::BadConst { value: step });
+    }
+    let objectives = self.all_objectives();
+    let constraints = self.all_constraints();
+    let free = free_measures(self.measures(&objectives), constraints.iter().copied());
+    let mut snapped = linear::snap(self.layout_ctx, &free, &constraints, solution, step);
+    snapped.set_hit_regions(self.hit_regions(&snapped)?);
+    Ok(snapped)
+  }
+
+  /// Products and quotients of measures that are not constant, which make the layout slow or
+  /// impossible to solve, with the constraints and objectives they appear in.
+  pub fn nonlinearities(&self) -> Vec<Nonlinearity<'a>> {
+    nonlinearities(&self.all_constraints(), &self.all_objectives())
+  }
+
+  /// The layout as an SMT-LIB2 script, for solving elsewhere. Paint the model it produces with
+  /// `paint_from_model`.
+  ///
+  /// Solver config settings are not part of the script.
+  pub fn to_smtlib2(&self) -> String {
+    let objectives = self.all_objectives();
+    let constraints = self.all_constraints();
+    self
+      .variables(&objectives, &constraints)
+      .export(self.layout_ctx, &constraints, &objectives)
+  }
+
+  /// Runs the painters with the values of `model`, a model of the script of `to_smtlib2`
+  /// solved elsewhere, and returns the solution read from it.
+  pub fn paint_from_model(&mut self, model: &ModelValues) -> Result<Solution, LayoutError> {
+    let solution = self
+      .variables(&self.all_objectives(), &self.all_constraints())
+      .solution(self.layout_ctx, model);
+    solution.paint(self)?;
+    Ok(solution)
+  }
+
+  /// Fingerprint of the structure of the layout and the solver config, which stays the same
+  /// across builds of the crate.
+  #[cfg(feature = "persist")]
+  pub(crate) fn fingerprint(&self) -> u64 {
+    let objectives = self.all_objectives();
+    let constraints = self.all_constraints();
+    let variables = self.variables(&objectives, &constraints);
+    layout_key(&layout_structure(
+      self.layout_ctx,
+      &variables,
+      &constraints,
+      &objectives,
+      &self.solver_config,
+    ))
+  }
+
+  /// The measures and constraints of each widget with its id, followed by the objective
+  /// measures and constraints of the builder.
+  #[cfg(feature = "persist")]
+  pub(crate) fn groups(&self) -> Vec<(Option<WidgetId>, Vec<Measure<'a>>, Vec<Prop<'a>>)> {
+    let widgets = self.widgets.iter().zip(&self.widget_ids).map(|(w, &id)| {
+      let mut measures = w.measures();
+      measures.extend(objective_measures(&w.objectives()));
+      (Some(id), measures, w.constraints())
+    });
+    let objectives = self.objectives.iter().map(|&(o, _)| o).collect::<Vec<_>>();
+    widgets
+      .chain(std::iter::once((
+        None,
+        objective_measures(&objectives),
+        self.pushed_constraints().collect(),
+      )))
+      .collect()
+  }
+
+  /// Variables of the layout, named for SMT-LIB2.
+  fn variables(&self, objectives: &[Objective<'a>], constraints: &[Prop<'a>]) -> Variables<'a> {
+    Variables::of(self.measures(objectives), constraints.to_vec())
+  }
+
+  /// The widgets of the layout to paint, with their ids, z-order and tags.
+  pub(crate) fn painters(
+    &mut self,
+  ) -> Vec<(
+    WidgetId,
+    ZOrder<'a>,
+    Option<Tag>,
+    &mut (dyn RawWidget<'a> + 'a),
+  )> {
+    let z_orders = self
+      .widget_ids
+      .iter()
+      .map(|&id| (self.z_order(id), self.tags.get(&id).cloned()))
+      .collect::<Vec<_>>();
+    self
+      .widgets
+      .iter_mut()
+      .zip(&self.widget_ids)
+      .zip(z_orders)
+      .map(|((w, id), (z, tag))| (*id, z, tag, &mut **w))
+      .collect()
   }
+
+  /// Drops the widgets of the layout with their painters, leaving their measures, constraints
+  /// and objectives behind.
+  fn drop_painters(&mut self) {
+    for w in &mut self.widgets {
+      *w = Box::new(DrainedWidget {
+        measures: w.measures(),
+        constraints: w.constraints(),
+        objectives: w.objectives(),
+        bounds: w.bounds(),
+        anchors: w.anchors(),
+      });
+    }
+  }
+
+  /// Holds every widget with `RawWidget::intrinsic_height_for` at the height its content
+  /// needs at its solved width, re-solving until the heights stop changing or after
+  /// `max_iterations` solves. Returns whether the heights settled.
+  ///
+  /// The heights are kept as hard constraints for the following builds, and updated by the
+  /// next call.
+  pub fn fit_intrinsic_sizes(&mut self, max_iterations: usize) -> Result<bool, LayoutError> {
+    for _ in 0..max_iterations {
+      let solution = self.solve()?;
+      let mut settled = true;
+      for i in 0..self.widgets.len() {
+        let (w, id) = (&self.widgets[i], self.widget_ids[i]);
+        let bounds = match w.bounds() {
+          Some(x) => x,
+          None => continue,
+        };
+        let height = match solution
+          .value_of(bounds.width())
+          .and_then(|x| w.intrinsic_height_for(x))
+        {
+          Some(x) => (x * 100.0).ceil() / 100.0,
+          None => continue,
+        };
+        let fit = bounds
+          .height()
+          .prop_eq(Measure::new_const(self.layout_ctx, height)?)
+          .hard();
+        match self.intrinsic.get(&id).copied() {
+          Some((previous, _)) if (previous - height).abs() <= TOLERANCE => continue,
+          Some((_, c)) if self.replace_constraint(c, fit) => {}
+          _ => {
+            let c = self.push_constraint(fit);
+            self.intrinsic.insert(id, (height, c));
+          }
+        }
+        self.intrinsic.get_mut(&id).unwrap().0 = height;
+        settled = false;
+      }
+      if settled {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+
+  /// Solves the layout in two passes, like the measure and arrange passes of GUI toolkits. The
+  /// measure pass solves without content constraints, and each widget is asked for its
+  /// `RawWidget::content_constraints` at the sizes it got. The arrange pass solves again with
+  /// them, and its solution is returned.
+  ///
+  /// The content constraints are kept for the following builds, and replaced by the next call.
+  pub fn measure_and_arrange(&mut self) -> Result<Solution, LayoutError> {
+    for (_, c) in std::mem::take(&mut self.content) {
+      self.remove_constraint(c);
+    }
+    let tentative = self.solve()?;
+    let mut content = vec![];
+    for (w, &id) in self.widgets.iter().zip(&self.widget_ids) {
+      let values = tentative.values_of_widget(&**w)?;
+      content.extend(w.content_constraints(&values).into_iter().map(|x| (id, x)));
+    }
+    for (id, prop) in content {
+      let c = self.push_constraint(prop);
+      self.content.push((id, c));
+    }
+    self.solve()
+  }
+
+  /// Solves the layout without painting, keeping the builder for further changes.
+  #[cfg(feature = "z3")]
+  pub fn check_with(&self, session: &mut SolverSession) -> Result<BuildReport<'a>, LayoutError> {
+    let solved = self.optimize(session)?;
+    self.report(solved)
+  }
+
+  /// Solves the layout up to `k` times, each time blocking the layouts found so far, for showing
+  /// alternatives. Fewer layouts are returned once no layout far enough from the others is left.
+  ///
+  /// Like `check_with`, nothing is painted. The builder is left as it was.
+  #[cfg(feature = "z3")]
+  pub fn solve_n(
+    &mut self,
+    session: &mut SolverSession,
+    k: usize,
+    diversity: &Diversity<'a>,
+  ) -> Result<Vec<Solution>, LayoutError> {
+    self.checkpoint();
+    let res = self.distinct_solutions(session, k, diversity);
+    self.rollback();
+    res
+  }
+
+  #[cfg(feature = "z3")]
+  fn distinct_solutions(
+    &mut self,
+    session: &mut SolverSession,
+    k: usize,
+    diversity: &Diversity<'a>,
+  ) -> Result<Vec<Solution>, LayoutError> {
+    let ctx = self.layout_ctx;
+    let min_distance = Measure::new_const(ctx, diversity.min_distance)?;
+    let mut solutions: Vec<Solution> = Vec::with_capacity(k);
+    let mut blocks = vec![];
+    while solutions.len() < k {
+      let solution = match self.check_with(session) {
+        Ok(report) => report.solution,
+        Err(LayoutError::Unsat { .. }) if !solutions.is_empty() => break,
+        Err(e) => return Err(e),
+      };
+      // Relaxation may have given up a block, which makes the layout a repeat.
+      if blocks.iter().any(|&b| solution.truth_of(b) != Some(true)) {
+        break;
+      }
+
+      let mut distance = Measure::zero(ctx);
+      for &m in &diversity.measures {
+        let value = solution
+          .value_of(m)
+          .ok_or_else(|| LayoutError::ModelExtraction {
+            measure: m.to_string(),
+          })?;
+        let difference = (m - Measure::new_const(ctx, value)?).abs();
+        distance = match diversity.distance {
+          Distance::Manhattan => distance + difference,
+          Distance::Chebyshev => distance.max(difference),
+        };
+      }
+      let block = distance.prop_ge(min_distance).hard();
+      self.push_constraint(block);
+      blocks.push(block);
+      solutions.push(solution);
+    }
+    Ok(solutions)
+  }
+
+  /// Brings the assertions of `session` in line with the widgets and constraints of the builder,
+  /// popping frames for rolled back checkpoints and pushing frames for new ones.
+  #[cfg(feature = "z3")]
+  fn sync(&self, session: &mut SolverSession) -> Result<(), LayoutError> {
+    session.attach(self.layout_ctx);
+
+    // The builder opens the outermost frame and each checkpoint opens another one.
+    let ids = std::iter::once(self.id).chain(self.checkpoints.iter().map(|x| x.id));
+    let ends = self
+      .checkpoints
+      .iter()
+      .map(|x| (x.widgets, x.constraints))
+      .chain(std::iter::once((
+        self.widgets.len(),
+        self.constraints.len(),
+      )));
+    let frames = ids.zip(ends).collect::<Vec<_>>();
+
+    while let Some(&(id, (widgets, constraints))) = session.frames.last() {
+      let top = session.frames.len() - 1;
+      let in_sync = match frames.get(top) {
+        Some(&(expected_id, (max_widgets, max_constraints))) => {
+          session.frames[..top] == frames[..top]
+            && id == expected_id
+            && widgets <= max_widgets
+            && constraints <= max_constraints
+        }
+        None => false,
+      };
+      if in_sync {
+        break;
+      }
+      if top == 0 {
+        session.reset_frames();
+      } else {
+        session.pop();
+      }
+    }
+    if session.frames.is_empty() {
+      session.frames.push((self.id, (0, 0)));
+    }
+
+    for level in session.frames.len() - 1..frames.len() {
+      let (from_widgets, from_constraints) = session.frames[level].1;
+      let (to_widgets, to_constraints) = frames[level].1;
+      let widget_constraints = self.widgets[from_widgets..to_widgets]
+        .iter()
+        .flat_map(|x| x.constraints().into_iter())
+        .collect::<Vec<_>>();
+      // Guards of widget constraints go by position, as widgets build new propositions on each
+      // call.
+      let offset = if self.solver_config.relax_on_unsat {
+        self.widgets[..from_widgets]
+          .iter()
+          .map(|x| x.constraints().len())
+          .sum()
+      } else {
+        0
+      };
+      for (i, c) in widget_constraints.iter().enumerate() {
+        let guard = widget_relax_guard(session.z3_ctx(), offset + i);
+        self.assert_constraint(session, c, guard)?;
+      }
+      let ids = &self.constraint_ids[from_constraints..to_constraints];
+      let constraints = self.constraints[from_constraints..to_constraints]
+        .iter()
+        .zip(ids)
+        .filter(|(_, &id)| self.is_enabled(id))
+        .map(|(c, _)| c);
+      for c in constraints {
+        let guard = relax_guard(session.z3_ctx(), c);
+        self.assert_constraint(session, c, guard)?;
+      }
+      session.frames[level].1 = frames[level].1;
+      if let Some(&(id, _)) = frames.get(level + 1) {
+        session.push(id, frames[level].1);
+      }
+    }
+    Ok(())
+  }
+
+  /// Asserts `c` to the optimizer of `session`, behind `guard` if it may be relaxed.
+  #[cfg(feature = "z3")]
+  fn assert_constraint(
+    &self,
+    session: &mut SolverSession,
+    c: &Prop<'a>,
+    guard: Bool<'static>,
+  ) -> Result<(), LayoutError> {
+    let value = c.build_z3(session.build_context())?;
+    if c.hard && self.solver_config.relax_on_unsat {
+      // Holds while its guard is assumed, and is still preferred once relaxed.
+      session.opt.assert(&guard.implies(&value));
+      session.opt.assert_soft(&value, c.weight, None);
+    } else if c.hard {
+      session.opt.assert(&value);
+    } else {
+      session.opt.assert_soft(&value, c.weight, None);
+    }
+    Ok(())
+  }
+
+  /// Gathers the constraints and objectives of the layout for a solve.
+  fn problem(&self) -> Result<Problem<'a>, LayoutError> {
+    let start = now();
+    self.progress(SolvePhase::Lowering);
+
+    // Constant constraints are decided here, so that mistakes like `5 < 3` are caught before
+    // spending time in the solver. Hard ones are left to relaxation when it is on.
+    let constraints = self.all_constraints();
+    let contradictions = constraints
+      .iter()
+      .copied()
+      .filter(|x| x.const_value() == Some(false))
+      .collect::<Vec<_>>();
+    if let Some(c) = contradictions.iter().find(|x| x.hard) {
+      if !self.solver_config.relax_on_unsat {
+        return Err(LayoutError::Unsat {
+          core: vec![c.to_string()],
+        });
+      }
+    }
+    if !contradictions.is_empty() {
+      log::warn!(
+        "{} constraints are constant and false",
+        contradictions.len()
+      );
+    }
+
+    let mut objectives = self.all_objectives();
+    let unconstrained = self.unconstrained_measures(&objectives);
+    if !unconstrained.is_empty() {
+      log::warn!(
+        "{} measures are not mentioned by any constraint or objective",
+        unconstrained.len()
+      );
+    }
+    let user_objectives = objectives.len();
+    if self.solver_config.deterministic && cfg!(feature = "z3") {
+      objectives.push(self.magnitude_objective(&objectives));
+    }
+
+    Ok(Problem {
+      start,
+      measures: self.measures(&objectives),
+      constraints,
+      objectives,
+      user_objectives,
+      unconstrained,
+      contradictions,
+    })
+  }
+
+  /// Solves `problem` without Z3 if it is simple enough.
+  ///
+  /// Layouts fixed by their equalities alone are solved by elimination, since objectives and soft
+  /// constraints cannot change a unique solution. Other linear layouts whose soft constraints can
+  /// all hold go to the simplex method.
+  fn solve_fast(&self, problem: &Problem<'a>) -> Option<(Solution, SolvePath)> {
+    let Problem {
+      measures,
+      constraints,
+      objectives,
+      ..
+    } = problem;
+    linear::solve(self.layout_ctx, measures, constraints)
+      .map(|x| (x, SolvePath::Elimination))
+      .or_else(|| {
+        simplex::solve(self.layout_ctx, measures, constraints, objectives)
+          .map(|x| (x, SolvePath::Simplex))
+      })
+  }
+
+  #[cfg(not(feature = "z3"))]
+  fn optimize(&self) -> Result<Solved<'a>, LayoutError> {
+    self.cached(|problem| self.optimize_problem(problem))
+  }
+
+  #[cfg(feature = "z3")]
+  fn optimize(&self, session: &mut SolverSession) -> Result<Solved<'a>, LayoutError> {
+    self.cached(|problem| self.optimize_problem(problem, session))
+  }
+
+  /// Solves the problem of the layout with `optimize`, unless the store of `with_cache` has it.
+  fn cached(
+    &self,
+    optimize: impl FnOnce(Problem<'a>) -> Result<Solved<'a>, LayoutError>,
+  ) -> Result<Solved<'a>, LayoutError> {
+    let problem = self.problem()?;
+    let store = match &self.cache {
+      Some(x) => x,
+      None => return optimize(problem),
+    };
+    let variables = Variables::of(problem.measures.clone(), problem.constraints.clone());
+    let structure = layout_structure(
+      self.layout_ctx,
+      &variables,
+      &problem.constraints,
+      &problem.objectives,
+      &self.solver_config,
+    );
+    let key = layout_key(&structure);
+    let hit = store.get(key).filter(|x| x.structure == structure);
+    if let Some(cached) = hit {
+      let solution = variables.solution_from(
+        self.layout_ctx,
+        |name| cached.reals.get(name).copied(),
+        |name| cached.bools.get(name).copied(),
+      );
+      let relaxed = cached
+        .relaxed
+        .iter()
+        .filter_map(|&i| problem.constraints.get(i).copied())
+        .collect();
+      return Ok(problem.solved(solution, SolvePath::Cached, relaxed, vec![]));
+    }
+
+    let constraints = problem.constraints.clone();
+    let solved = optimize(problem)?;
+    let (reals, bools) = variables.values(&solved.solution);
+    let relaxed = solved
+      .relaxed
+      .iter()
+      .filter_map(|r| constraints.iter().position(|c| c.addr() == r.addr()))
+      .collect();
+    store.insert(
+      key,
+      CachedLayout {
+        reals,
+        bools,
+        relaxed,
+        structure,
+      },
+    );
+    Ok(solved)
+  }
+
+  #[cfg(not(feature = "z3"))]
+  fn optimize_problem(&self, problem: Problem<'a>) -> Result<Solved<'a>, LayoutError> {
+    self.progress(SolvePhase::Checking);
+    if self.is_cancelled() {
+      return Err(LayoutError::Cancelled);
+    }
+    if let Some((solution, path)) = self.solve_fast(&problem) {
+      return Ok(problem.solved(solution, path, vec![], vec![]));
+    }
+
+    // Constraints pushed to the builder come after the ones of widgets.
+    let mut constraints = problem.constraints.clone();
+    let widget_constraints = constraints.len() - self.pushed_constraints().count();
+    let mut relaxed = vec![];
+    let solution = loop {
+      let res = native::solve(
+        self.layout_ctx,
+        &problem.measures,
+        &constraints,
+        &problem.objectives,
+        self.cancellation.as_ref(),
+      );
+      let tier = constraints
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.hard)
+        .map(|(i, x)| relax_tier(x, i < widget_constraints))
+        .min();
+      match (res, tier) {
+        (Err(LayoutError::Unsat { .. }), Some(tier)) if self.solver_config.relax_on_unsat => {
+          // Demote the lowest tier of hard constraints and try again, like with Z3.
+          for (i, c) in constraints.iter_mut().enumerate() {
+            if c.hard && relax_tier(c, i < widget_constraints) == tier {
+              relaxed.push(*c);
+              c.hard = false;
+            }
+          }
+          log_relaxed(tier);
+        }
+        (res, _) => break res?,
+      }
+    };
+    Ok(problem.solved(solution, SolvePath::Native, relaxed, vec![]))
+  }
+
+  #[cfg(feature = "z3")]
+  fn optimize_problem(
+    &self,
+    problem: Problem<'a>,
+    session: &mut SolverSession,
+  ) -> Result<Solved<'a>, LayoutError> {
+    self.progress(SolvePhase::Checking);
+    if self.is_cancelled() {
+      return Err(LayoutError::Cancelled);
+    }
+    if let Some((solution, path)) = self.solve_fast(&problem) {
+      return Ok(problem.solved(solution, path, vec![], vec![]));
+    }
+
+    if self.solver_config.decompose && !self.solver_config.relax_on_unsat {
+      let components = partition::components(
+        &problem.constraints,
+        &problem.objectives[..problem.user_objectives],
+      );
+      if components.len() > 1 {
+        let (solution, z3) =
+          self.solve_components(session, components, &problem.measures, &problem.objectives)?;
+        return Ok(problem.solved(solution, SolvePath::Decomposed, vec![], z3));
+      }
+    }
+
+    let zeroed = if self.solver_config.zero_unconstrained {
+      &problem.unconstrained[..]
+    } else {
+      &[]
+    };
+    if problem.objectives.is_empty()
+      && problem.constraints.iter().all(|x| x.hard)
+      && !self.solver_config.relax_on_unsat
+    {
+      let (solution, z3) = self.satisfy(session, &problem, zeroed)?;
+      return Ok(problem.solved(solution, SolvePath::Logic, vec![], z3));
+    }
+
+    self.sync(session)?;
+
+    // Objectives live in their own frame, since they have to be declared in priority order.
+    session.opt.push();
+    let res = self.check(session, &problem.constraints, &problem.objectives, zeroed);
+    let z3 = statistics(session.opt.get_statistics());
+    session.opt.pop();
+
+    let (model, relaxed) = res?;
+    let solution = Solution::from_model(
+      &model,
+      session.build_context(),
+      problem.measures.iter().copied(),
+      problem.constraints.iter().copied(),
+    )?;
+    Ok(problem.solved(solution, SolvePath::Solver, relaxed, z3))
+  }
+
+  #[cfg(feature = "z3")]
+  fn check(
+    &self,
+    session: &mut SolverSession,
+    constraints: &[Prop<'a>],
+    objectives: &[Objective<'a>],
+    zeroed: &[Measure<'a>],
+  ) -> Result<(z3::Model<'static>, Vec<Prop<'a>>), LayoutError> {
+    let z3_ctx = session.z3_ctx();
+    session.opt.set_params(&self.params(z3_ctx));
+    for o in objectives {
+      match *o {
+        Objective::Minimize(m) => {
+          let value = m.build_z3(session.build_context())?;
+          session.opt.minimize(&value);
+        }
+        Objective::Maximize(m) => {
+          let value = m.build_z3(session.build_context())?;
+          session.opt.maximize(&value);
+        }
+      }
+    }
+
+    // Named constants and zeroed measures are passed as assumptions, so that changes to them
+    // leave the asserted constraints alone.
+    let mut definitions = self.definitions(z3_ctx, objectives)?;
+    for m in zeroed {
+      let value = m.build_z3(session.build_context())?;
+      definitions.push(value._eq(&Real::from_real(z3_ctx, 0, 1)));
+    }
+
+    // The hard constraints asserted by `sync`, with their guards, of which the ones of widgets
+    // come first.
+    let mut guarded = if self.solver_config.relax_on_unsat {
+      let widget_constraints = constraints.len() - self.pushed_constraints().count();
+      constraints
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.hard)
+        .map(|(i, &c)| {
+          let of_widget = i < widget_constraints;
+          let guard = if of_widget {
+            widget_relax_guard(z3_ctx, i)
+          } else {
+            relax_guard(z3_ctx, &c)
+          };
+          (relax_tier(&c, of_widget), guard, c)
+        })
+        .collect::<Vec<_>>()
+    } else {
+      vec![]
+    };
+    let mut relaxed = vec![];
+    let opt = &session.opt;
+    let check_res = loop {
+      let mut assumptions = definitions.clone();
+      assumptions.extend(guarded.iter().map(|x| x.1.clone()));
+      let res = self.watch(z3_ctx, || opt.check(&assumptions));
+      if res != z3::SatResult::Unsat || guarded.is_empty() {
+        break res;
+      }
+
+      // Demote the lowest tier of guarded constraints and try again.
+      let tier = guarded.iter().map(|x| x.0).min().unwrap();
+      let (dropped, kept): (Vec<_>, Vec<_>) = guarded.into_iter().partition(|x| x.0 == tier);
+      relaxed.extend(dropped.into_iter().map(|x| x.2));
+      guarded = kept;
+      log_relaxed(tier);
+    };
+    match check_res {
+      z3::SatResult::Sat => {}
+      z3::SatResult::Unsat => {
+        let core = self.unsat_core(session, &definitions)?;
+        return Err(LayoutError::Unsat { core });
+      }
+      z3::SatResult::Unknown => return Err(self.unknown_error(opt.get_reason_unknown())),
+    }
+
+    let model = opt.get_model().ok_or_else(|| LayoutError::Solver {
+      reason: "check returned sat but failed to get model".into(),
+    })?;
+    Ok((model, relaxed))
+  }
+
+  /// Finds a layout that meets `problem`, which has only hard constraints and no objectives, with
+  /// a solver for the logic of the layout instead of the optimizer, and returns it with the
+  /// statistics of the solver.
+  #[cfg(feature = "z3")]
+  fn satisfy(
+    &self,
+    session: &mut SolverSession,
+    problem: &Problem<'a>,
+    zeroed: &[Measure<'a>],
+  ) -> Result<(Solution, Vec<(String, f64)>), LayoutError> {
+    session.attach(self.layout_ctx);
+    let z3_ctx = session.z3_ctx();
+    let solver = self.solver(z3_ctx);
+    for c in &problem.constraints {
+      solver.assert(&c.build_z3(session.build_context())?);
+    }
+    let mut definitions = self.definitions(z3_ctx, &problem.objectives)?;
+    for m in zeroed {
+      let value = m.build_z3(session.build_context())?;
+      definitions.push(value._eq(&Real::from_real(z3_ctx, 0, 1)));
+    }
+    match self.watch(z3_ctx, || solver.check_assumptions(&definitions)) {
+      z3::SatResult::Sat => {}
+      z3::SatResult::Unsat => {
+        let core = self.unsat_core(session, &definitions)?;
+        return Err(LayoutError::Unsat { core });
+      }
+      z3::SatResult::Unknown => return Err(self.unknown_error(solver.get_reason_unknown())),
+    }
+
+    let model = solver.get_model().ok_or_else(|| LayoutError::Solver {
+      reason: "check returned sat but failed to get model".into(),
+    })?;
+    let solution = Solution::from_model(
+      &model,
+      session.build_context(),
+      problem.measures.iter().copied(),
+      problem.constraints.iter().copied(),
+    )?;
+    Ok((solution, statistics(solver.get_statistics())))
+  }
+
+  /// Solves each of `components` in its own Z3 context and merges the solutions. Components are
+  /// solved on the rayon thread pool with the `parallel` feature.
+  #[cfg(feature = "z3")]
+  fn solve_components(
+    &self,
+    session: &mut SolverSession,
+    mut components: Vec<Component<'a>>,
+    measures: &[Measure<'a>],
+    objectives: &[Objective<'a>],
+  ) -> Result<(Solution, Vec<(String, f64)>), LayoutError> {
+    let named = named_consts(self.measures(objectives), self.all_constraints())
+      .into_iter()
+      .map(|name| match self.layout_ctx.definition(name) {
+        Some(value) => Ok((name, to_ratio(value)?)),
+        None => Err(LayoutError::Undefined {
+          name: name.to_string(),
+        }),
+      })
+      .collect::<Result<Vec<_>, LayoutError>>()?;
+    if self.solver_config.deterministic {
+      for component in &mut components {
+        let magnitude = free_measures(
+          objective_measures(&component.objectives),
+          component.constraints.clone(),
+        )
+        .into_iter()
+        .fold(Measure::zero(self.layout_ctx), |acc, m| acc + m.abs());
+        component.objectives.push(Objective::Minimize(magnitude));
+      }
+    }
+
+    let (config, cancellation) = (&self.solver_config, self.cancellation.as_ref());
+    let solve = |component| solve_component(component, &named, config, cancellation);
+    let run = || {
+      #[cfg(feature = "parallel")]
+      {
+        use rayon::prelude::*;
+        components.into_par_iter().map(solve).collect::<Vec<_>>()
+      }
+      #[cfg(not(feature = "parallel"))]
+      components.into_iter().map(solve).collect::<Vec<_>>()
+    };
+    let results = match &self.progress {
+      Some(progress) => progress.during(run),
+      None => run(),
+    };
+
+    let mut solution = Solution::default();
+    let mut z3 = vec![];
+    for res in results {
+      let (part, stats) = match res {
+        Ok(x) => x,
+        Err(ComponentFailure::Unsat) => {
+          session.attach(self.layout_ctx);
+          let definitions = self.definitions(session.z3_ctx(), objectives)?;
+          let core = self.unsat_core(session, &definitions)?;
+          return Err(LayoutError::Unsat { core });
+        }
+        Err(ComponentFailure::Unknown(reason)) => return Err(self.unknown_error(reason)),
+        Err(ComponentFailure::Error(e)) => return Err(e),
+      };
+      for (key, value) in stats {
+        match z3.iter_mut().find(|(x, _)| *x == key) {
+          Some((_, total)) => *total += value,
+          None => z3.push((key, value)),
+        }
+      }
+      solution.merge(part);
+    }
+
+    // Measures outside every component are free, so they are pinned like with
+    // `zero_unconstrained`. Named constants take their defined values.
+    let rest = free_measures(measures.iter().copied(), vec![])
+      .into_iter()
+      .filter(|&m| solution.value_of(m).is_none())
+      .map(|m| (m.addr(), 0.0));
+    let named = named_consts(measures.iter().copied(), vec![])
+      .into_iter()
+      .filter_map(|name| Some((name.to_string(), self.layout_ctx.definition(name)?)))
+      .collect();
+    let rest = Solution::from_values(self.layout_ctx.id, rest, named);
+    solution.merge(rest);
+    Ok((solution, z3))
+  }
+
+  /// Optimizer parameters for the solver config.
+  #[cfg(feature = "z3")]
+  fn params<'ctx>(&self, z3_ctx: &'ctx z3::Context) -> z3::Params<'ctx> {
+    optimizer_params(z3_ctx, &self.solver_config)
+  }
+
+  /// Solver for the logic of the solver config, or of the layout without one, with its timeout
+  /// and resource limit.
+  #[cfg(feature = "z3")]
+  fn solver<'ctx>(&self, z3_ctx: &'ctx z3::Context) -> z3::Solver<'ctx> {
+    let config = &self.solver_config;
+    let logic = config.logic.unwrap_or_else(|| {
+      let measures = self.measures(&self.all_objectives());
+      if nonlinear_terms(measures, self.all_constraints()).is_empty() {
+        SolverLogic::Lra
+      } else {
+        SolverLogic::Nra
+      }
+    });
+    let solver =
+      z3::Solver::new_for_logic(z3_ctx, logic.name()).unwrap_or_else(|| z3::Solver::new(z3_ctx));
+    let mut params = z3::Params::new(z3_ctx);
+    if let Some(timeout_ms) = config.timeout_ms {
+      params.set_u32("timeout", timeout_ms);
+    }
+    if let Some(rlimit) = config.rlimit {
+      params.set_u32("rlimit", rlimit);
+    }
+    if config.deterministic {
+      params.set_u32("random_seed", 0);
+    }
+    solver.set_params(&params);
+    solver
+  }
+
+  /// Runs the Z3 check `f`, interrupting it on cancellation and reporting progress while it runs.
+  #[cfg(feature = "z3")]
+  fn watch<T>(&self, z3_ctx: &z3::Context, f: impl FnOnce() -> T) -> T {
+    let check = || match &self.cancellation {
+      Some(token) => token.watch(z3_ctx, f),
+      None => f(),
+    };
+    match &self.progress {
+      Some(progress) => progress.during(check),
+      None => check(),
+    }
+  }
+
+  /// Reports that `phase` of a solve starts to the callback of `on_progress`.
+  fn progress(&self, phase: SolvePhase) {
+    if let Some(progress) = &self.progress {
+      progress.report(phase);
+    }
+  }
+
+  fn is_cancelled(&self) -> bool {
+    self.cancellation.iter().any(|x| x.is_cancelled())
+  }
+
+  /// Error for a check that ended without a result, for the given reason.
+  #[cfg(feature = "z3")]
+  fn unknown_error(&self, reason: Option<String>) -> LayoutError {
+    if self.is_cancelled() {
+      return LayoutError::Cancelled;
+    }
+    // Some versions of Z3 report an expired `timeout` as cancelled, like an interrupt, which
+    // only the cancellation token sends.
+    let reason = reason.unwrap_or_default();
+    if ["timeout", "resource limit", "canceled"]
+      .iter()
+      .any(|x| reason.contains(x))
+    {
+      return LayoutError::Timeout;
+    }
+    for x in self.nonlinearities() {
+      log::warn!("{}", x);
+    }
+    LayoutError::Unknown
+  }
+
+  /// Measures of the widgets and objectives.
+  fn measures(&self, objectives: &[Objective<'a>]) -> Vec<Measure<'a>> {
+    let z_indices = self
+      .widget_ids
+      .iter()
+      .filter_map(|&id| match self.z_order(id) {
+        ZOrder::Solved(m) => Some(m),
+        ZOrder::Fixed(_) => None,
+      });
+    self
+      .widgets
+      .iter()
+      .flat_map(|x| x.measures())
+      .chain(z_indices)
+      .chain(objective_measures(objectives))
+      .collect()
+  }
+
+  /// Unbound measures the widgets and constraints depend on.
+  pub(crate) fn free_measures(&self) -> Vec<Measure<'a>> {
+    free_measures(self.measures(&[]), self.all_constraints())
+  }
+
+  /// Unbound measures of the widgets that no constraint or objective depends on, which the
+  /// solver would set to arbitrary values.
+  fn unconstrained_measures(&self, objectives: &[Objective<'a>]) -> Vec<Measure<'a>> {
+    let mentioned = free_measures(objective_measures(objectives), self.all_constraints())
+      .into_iter()
+      .map(|m| m.addr())
+      .collect::<HashSet<_>>();
+    free_measures(self.widgets.iter().flat_map(|x| x.measures()), vec![])
+      .into_iter()
+      .filter(|m| !mentioned.contains(&m.addr()))
+      .collect()
+  }
+
+  /// Minimizes the total magnitude of the free measures of the layout.
+  fn magnitude_objective(&self, objectives: &[Objective<'a>]) -> Objective<'a> {
+    let measures = self.measures(objectives);
+    let magnitude = free_measures(measures, self.all_constraints())
+      .into_iter()
+      .fold(Measure::zero(self.layout_ctx), |acc, m| acc + m.abs());
+    Objective::Minimize(magnitude)
+  }
+
+  /// Pins each named constant the layout depends on to its value in the layout context.
+  #[cfg(feature = "z3")]
+  fn definitions<'ctx>(
+    &self,
+    z3_ctx: &'ctx z3::Context,
+    objectives: &[Objective<'a>],
+  ) -> Result<Vec<Bool<'ctx>>, LayoutError> {
+    let measures = self.measures(objectives);
+    let mut definitions = vec![];
+    for name in named_consts(measures, self.all_constraints()) {
+      let value = self
+        .layout_ctx
+        .definition(name)
+        .ok_or_else(|| LayoutError::Undefined {
+          name: name.to_string(),
+        })?;
+      let (num, den) = to_ratio(value)?;
+      definitions
+        .push(Real::new_const(z3_ctx, named_symbol(name))._eq(&Real::from_real(z3_ctx, num, den)));
+    }
+    Ok(definitions)
+  }
+
+  /// Infimum and supremum of `measure` in layouts that meet the hard constraints, with the
+  /// measures of `pins` held at their values. See `Solution::bounds_of`.
+  #[cfg(feature = "z3")]
+  pub(crate) fn bounds_of(
+    &self,
+    session: &mut SolverSession,
+    measure: Measure<'a>,
+    pins: &[(usize, f64)],
+  ) -> Result<(Bound, Bound), LayoutError> {
+    let mut found = find_measures(
+      self.measures(&[Objective::Minimize(measure)]),
+      self.all_constraints(),
+      pins.iter().map(|&(addr, _)| addr),
+    );
+    let mut hard = self
+      .all_constraints()
+      .into_iter()
+      .filter(|x| x.hard)
+      .collect::<Vec<_>>();
+    for &(addr, value) in pins {
+      let m = found.remove(&addr).ok_or(LayoutError::UnknownPin)?;
+      hard.push(m.prop_eq(Measure::new_const(self.layout_ctx, value)?));
+    }
+
+    session.attach(self.layout_ctx);
+    let z3_ctx = session.z3_ctx();
+    let definitions = self.definitions(z3_ctx, &[Objective::Minimize(measure)])?;
+    let mut exact = definitions.clone();
+    let mut closure = definitions.clone();
+    for c in hard {
+      exact.push(c.build_z3(session.build_context())?);
+      closure.push(c.closure().build_z3(session.build_context())?);
+    }
+    let value = measure.build_z3(session.build_context())?;
+
+    let solver = self.solver(z3_ctx);
+    for x in &exact {
+      solver.assert(x);
+    }
+    if solver.check() == z3::SatResult::Unsat {
+      let core = self.unsat_core(session, &definitions)?;
+      return Err(LayoutError::Unsat { core });
+    }
+    let min = self.extremum(z3_ctx, &exact, &closure, &value, false)?;
+    let max = self.extremum(z3_ctx, &exact, &closure, &value, true)?;
+    Ok((min, max))
+  }
+
+  /// Infimum or supremum of `value` in the models of `exact`, found as the optimum under
+  /// `closure`, the same constraints with their strict comparisons made non-strict.
+  #[cfg(feature = "z3")]
+  fn extremum<'ctx>(
+    &self,
+    z3_ctx: &'ctx z3::Context,
+    exact: &[Bool<'ctx>],
+    closure: &[Bool<'ctx>],
+    value: &Real<'ctx>,
+    maximize: bool,
+  ) -> Result<Bound, LayoutError> {
+    let opt = z3::Optimize::new(z3_ctx);
+    opt.set_params(&self.params(z3_ctx));
+    for x in closure {
+      opt.assert(x);
+    }
+    if maximize {
+      opt.maximize(value);
+    } else {
+      opt.minimize(value);
+    }
+    let res = match &self.cancellation {
+      Some(token) => token.watch(z3_ctx, || opt.check(&[])),
+      None => opt.check(&[]),
+    };
+    match res {
+      z3::SatResult::Sat => {}
+      z3::SatResult::Unsat => return Err(LayoutError::Unsat { core: vec![] }),
+      z3::SatResult::Unknown => return Err(self.unknown_error(opt.get_reason_unknown())),
+    }
+    let optimum =
+      opt
+        .get_model()
+        .and_then(|x| x.eval(value))
+        .ok_or_else(|| LayoutError::ModelExtraction {
+          measure: value.to_string(),
+        })?;
+
+    // For an unbounded objective the model holds an arbitrary value, so check that nothing
+    // goes past it.
+    let solver = self.solver(z3_ctx);
+    for x in closure {
+      solver.assert(x);
+    }
+    let (beyond, infinity) = if maximize {
+      (value.gt(&optimum), f64::INFINITY)
+    } else {
+      (value.lt(&optimum), f64::NEG_INFINITY)
+    };
+    solver.assert(&beyond);
+    if solver.check() == z3::SatResult::Sat {
+      return Ok(Bound {
+        value: infinity,
+        closed: false,
+      });
+    }
+
+    // The optimum of the closure is only attained when no strict comparison keeps it out.
+    let solver = self.solver(z3_ctx);
+    for x in exact {
+      solver.assert(x);
+    }
+    solver.assert(&value._eq(&optimum));
+    let closed = solver.check() == z3::SatResult::Sat;
+    let (num, den) = optimum
+      .as_real()
+      .ok_or_else(|| LayoutError::ModelExtraction {
+        measure: value.to_string(),
+      })?;
+    Ok(Bound {
+      value: num as f64 / den as f64,
+      closed,
+    })
+  }
+
+  /// Finds a minimal set of conflicting hard constraints, rendered with `Display`.
+  #[cfg(feature = "z3")]
+  fn unsat_core(
+    &self,
+    session: &mut SolverSession,
+    definitions: &[Bool<'static>],
+  ) -> Result<Vec<String>, LayoutError> {
+    let z3_ctx = session.z3_ctx();
+    let solver = self.solver(z3_ctx);
+    for d in definitions {
+      solver.assert(d);
+    }
+    let hard = self
+      .all_constraints()
+      .into_iter()
+      .filter(|x| x.hard)
+      .collect::<Vec<_>>();
+    let mut literals = HashMap::new();
+    let mut assumptions = Vec::with_capacity(hard.len());
+    for (i, c) in hard.iter().enumerate() {
+      let literal = Bool::new_const(z3_ctx, format!("core_{}", i));
+      solver.assert(&literal.implies(&c.build_z3(session.build_context())?));
+      literals.insert(literal.clone(), i);
+      assumptions.push(literal);
+    }
+
+    let mut core = match solver.check_assumptions(&assumptions) {
+      z3::SatResult::Unsat => solver
+        .get_unsat_core()
+        .iter()
+        .filter_map(|x| literals.get(x).copied())
+        .collect::<Vec<_>>(),
+      _ => return Ok(vec![]),
+    };
+
+    // Drop every constraint the conflict does not depend on.
+    let mut i = 0;
+    while i < core.len() {
+      let rest = core
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(_, &k)| assumptions[k].clone())
+        .collect::<Vec<_>>();
+      if solver.check_assumptions(&rest) == z3::SatResult::Unsat {
+        core.remove(i);
+      } else {
+        i += 1;
+      }
+    }
+    Ok(core.into_iter().map(|k| hard[k].to_string()).collect())
+  }
+
+  /// Objectives of the widgets and the builder, highest priority first.
+  pub(crate) fn all_objectives(&self) -> Vec<Objective<'a>> {
+    let mut objectives = self
+      .widgets
+      .iter()
+      .flat_map(|x| x.objectives().into_iter().map(|o| (o, 0)))
+      .chain(self.objectives.iter().copied())
+      .collect::<Vec<_>>();
+    objectives.sort_by_key(|&(_, priority)| std::cmp::Reverse(priority));
+    objectives.into_iter().map(|(o, _)| o).collect()
+  }
+
+  pub(crate) fn all_constraints(&self) -> Vec<Prop<'a>> {
+    self
+      .widgets
+      .iter()
+      .flat_map(|x| x.constraints().into_iter())
+      .chain(self.pushed_constraints())
+      .collect()
+  }
+
+  /// Solved bounds of the widgets that have them, topmost first.
+  pub(crate) fn hit_regions(
+    &self,
+    solution: &Solution,
+  ) -> Result<Vec<(WidgetId, [f64; 4])>, LayoutError> {
+    // Widgets painted last are on top.
+    let mut hit_regions = vec![];
+    for (w, &id) in self.widgets.iter().zip(&self.widget_ids).rev() {
+      let rect = match w.bounds() {
+        Some(x) => x,
+        None => continue,
+      };
+      let edges = [rect.left, rect.right, rect.top, rect.bottom]
+        .iter()
+        .map(|&m| solution.value_of(m))
+        .collect::<Option<Vec<_>>>();
+      if let Some(edges) = edges {
+        let z = self.z_order(id).value(solution)?;
+        hit_regions.push((z, id, [edges[0], edges[1], edges[2], edges[3]]));
+      }
+    }
+    hit_regions.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(hit_regions.into_iter().map(|(_, id, x)| (id, x)).collect())
+  }
+
+  fn report(&self, solved: Solved<'a>) -> Result<BuildReport<'a>, LayoutError> {
+    self.progress(SolvePhase::Extracting);
+    let Solved {
+      mut solution,
+      relaxed,
+      objectives,
+      mut stats,
+      unconstrained,
+      contradictions,
+    } = solved;
+
+    let mut objective_values = Vec::with_capacity(objectives.len());
+    for o in objectives {
+      let m = match o {
+        Objective::Minimize(m) | Objective::Maximize(m) => m,
+      };
+      let value = solution
+        .value_of(m)
+        .ok_or_else(|| LayoutError::ModelExtraction {
+          measure: m.to_string(),
+        })?;
+      objective_values.push((o, value));
+    }
+
+    let constraints = self.all_constraints();
+    let mut unsatisfied_constraints = vec![];
+    let mut satisfied_constraints = vec![];
+
+    for c in &constraints {
+      let value = solution
+        .truth_of(*c)
+        .ok_or_else(|| LayoutError::ModelExtraction {
+          measure: c.to_string(),
+        })?;
+      if !value {
+        unsatisfied_constraints.push(*c);
+      } else {
+        satisfied_constraints.push(*c);
+      }
+    }
+
+    solution.set_hit_regions(self.hit_regions(&solution)?);
+    // Widgets build new propositions on each call, so relaxed ones are checked by themselves.
+    let dropped_constraints = relaxed
+      .into_iter()
+      .filter(|&c| solution.truth_of(c) == Some(false))
+      .collect();
+
+    let metrics = self
+      .widgets
+      .iter()
+      .zip(&self.widget_ids)
+      .filter_map(|(w, &id)| {
+        let values = w.measures().into_iter().map(|m| solution.value_of(m));
+        Some((id, values.collect::<Option<Vec<_>>>()?))
+      })
+      .collect();
+
+    stats.variables = solution.variable_count();
+    stats.constraints = constraints.len();
+    stats.arena_bytes = self.layout_ctx.stats().allocated_bytes;
+
+    Ok(BuildReport {
+      satisfied_constraints,
+      objective_values,
+      solution,
+      stats,
+      unconstrained_measures: unconstrained,
+      constant_contradictions: contradictions,
+      dropped_constraints,
+      unsatisfied_constraints,
+      paint_errors: vec![],
+      metrics,
+      tags: self.tags.clone(),
+    })
+  }
+}
+
+/// A pushed widget, counted in the live painters of its context until painted or dropped.
+struct TrackedWidget<'a> {
+  inner: Box<dyn RawWidget<'a> + 'a>,
+  _guard: PainterGuard<'a>,
+}
+
+impl<'a> RawWidget<'a> for TrackedWidget<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self.inner.measures()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    self.inner.constraints()
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.inner.objectives()
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.inner.paint(measures, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.inner.paint_at(measures, transform, target)
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    self.inner.on_solved(measures)
+  }
+
+  fn send_painter(&mut self) -> Option<&mut SendPainter<'a>> {
+    self.inner.send_painter()
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    self.inner.bounds()
+  }
+
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    self.inner.intrinsic_height_for(width)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    self.inner.content_constraints(measures)
+  }
+
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    self.inner.anchors()
+  }
+}
+
+/// What is left of a widget after its painter is run or dropped.
+struct DrainedWidget<'a> {
+  measures: Vec<Measure<'a>>,
+  constraints: Vec<Prop<'a>>,
+  objectives: Vec<Objective<'a>>,
+  bounds: Option<RectMeasures<'a>>,
+  anchors: Vec<(&'static str, Point<'a>)>,
+}
+
+impl<'a> RawWidget<'a> for DrainedWidget<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self.measures.clone()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    self.constraints.clone()
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.objectives.clone()
+  }
+
+  fn paint(&mut self, _: &[f64], _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    Ok(())
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    self.bounds
+  }
+
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    self.anchors.clone()
+  }
+}
+
+/// Measures that `objectives` optimize.
+fn objective_measures<'a>(objectives: &[Objective<'a>]) -> Vec<Measure<'a>> {
+  objectives
+    .iter()
+    .map(|&o| match o {
+      Objective::Minimize(m) | Objective::Maximize(m) => m,
+    })
+    .collect()
+}
+
+/// The current time, where there is a clock.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn now() -> Option<Instant> {
+  Some(Instant::now())
+}
+
+/// `Instant::now` panics on `wasm32-unknown-unknown`.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn now() -> Option<Instant> {
+  None
+}
+
+/// Statistics of the last check of `opt`, by name.
+#[cfg(feature = "z3")]
+fn statistics(stats: z3::Statistics<'_>) -> Vec<(String, f64)> {
+  stats
+    .entries()
+    .map(|x| {
+      let value = match x.value {
+        z3::StatisticsValue::UInt(v) => v as f64,
+        z3::StatisticsValue::Double(v) => v,
+      };
+      (x.key, value)
+    })
+    .collect()
+}
+
+/// Optimizer parameters for `config`.
+#[cfg(feature = "z3")]
+fn optimizer_params<'ctx>(z3_ctx: &'ctx z3::Context, config: &SolverConfig) -> z3::Params<'ctx> {
+  let mut params = z3::Params::new(z3_ctx);
+  params.set_symbol("priority", "lex");
+  if let Some(timeout_ms) = config.timeout_ms {
+    params.set_u32("timeout", timeout_ms);
+  }
+  if let Some(rlimit) = config.rlimit {
+    params.set_u32("rlimit", rlimit);
+  }
+  if config.deterministic {
+    params.set_u32("random_seed", 0);
+  }
+  params
+}
+
+/// Why a component of a layout has no solution, before the builder explains it.
+#[cfg(feature = "z3")]
+enum ComponentFailure {
+  Unsat,
+  Unknown(Option<String>),
+  Error(LayoutError),
+}
+
+/// Solves `component` in a Z3 context of its own, with the named constants pinned to `named`,
+/// and returns the solution with the statistics of the optimizer.
+#[cfg(feature = "z3")]
+fn solve_component<'a>(
+  component: Component<'a>,
+  named: &[(&str, (i32, i32))],
+  config: &SolverConfig,
+  cancellation: Option<&CancellationToken>,
+) -> Result<(Solution, Vec<(String, f64)>), ComponentFailure> {
+  let z3_ctx = z3::Context::new(&z3::Config::new());
+  let mut build_ctx = Z3BuildContext::new(&z3_ctx);
+  let definitions = named
+    .iter()
+    .map(|&(name, (num, den))| {
+      Real::new_const(&z3_ctx, named_symbol(name))._eq(&Real::from_real(&z3_ctx, num, den))
+    })
+    .collect::<Vec<_>>();
+  let opt = z3::Optimize::new(&z3_ctx);
+  opt.set_params(&optimizer_params(&z3_ctx, config));
+  for c in &component.constraints {
+    let value = c
+      .build_z3(&mut build_ctx)
+      .map_err(ComponentFailure::Error)?;
+    if c.hard {
+      opt.assert(&value);
+    } else {
+      opt.assert_soft(&value, c.weight, None);
+    }
+  }
+  for o in &component.objectives {
+    match *o {
+      Objective::Minimize(m) => opt.minimize(
+        &m.build_z3(&mut build_ctx)
+          .map_err(ComponentFailure::Error)?,
+      ),
+      Objective::Maximize(m) => opt.maximize(
+        &m.build_z3(&mut build_ctx)
+          .map_err(ComponentFailure::Error)?,
+      ),
+    }
+  }
+
+  let res = match cancellation {
+    Some(token) => token.watch(&z3_ctx, || opt.check(&definitions)),
+    None => opt.check(&definitions),
+  };
+  match res {
+    z3::SatResult::Sat => {}
+    z3::SatResult::Unsat => return Err(ComponentFailure::Unsat),
+    z3::SatResult::Unknown => return Err(ComponentFailure::Unknown(opt.get_reason_unknown())),
+  }
+  let model = opt.get_model().ok_or_else(|| {
+    ComponentFailure::Error(LayoutError::Solver {
+      reason: "check returned sat but failed to get model".into(),
+    })
+  })?;
+  let solution = Solution::from_model(
+    &model,
+    &mut build_ctx,
+    objective_measures(&component.objectives),
+    component.constraints,
+  )
+  .map_err(ComponentFailure::Error)?;
+  Ok((solution, statistics(opt.get_statistics())))
+}
+
+/// Assumption that keeps the hard constraint `c` in force when relaxation is enabled.
+#[cfg(feature = "z3")]
+fn relax_guard<'ctx>(z3_ctx: &'ctx z3::Context, c: &Prop<'_>) -> Bool<'ctx> {
+  Bool::new_const(z3_ctx, format!("relax_{}", c.addr()))
+}
+
+/// Guard of the widget constraint at `index` among the constraints of all widgets.
+#[cfg(feature = "z3")]
+fn widget_relax_guard(z3_ctx: &z3::Context, index: usize) -> Bool<'_> {
+  Bool::new_const(z3_ctx, format!("relax_widget_{}", index))
+}
+
+/// Order in which `SolverConfig::relax_on_unsat` demotes the hard constraint `c`: by weight,
+/// constraints pushed to the builder before the ones of widgets.
+fn relax_tier(c: &Prop<'_>, of_widget: bool) -> (bool, u32) {
+  (of_widget, c.weight)
+}
+
+fn log_relaxed((of_widget, weight): (bool, u32)) {
+  log::warn!(
+    "relaxed hard constraints {}of weight {} to find a layout",
+    if of_widget { "of widgets " } else { "" },
+    weight
+  );
 }
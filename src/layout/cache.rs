@@ -0,0 +1,191 @@
+//! Reuse of solved layouts when an identical layout is built again.
+//!
+//! Layouts are keyed by a hash of their structure, in which unbound measures and propositions
+//! are told apart by their position like in `smtlib`, so a layout rebuilt from scratch with the
+//! same widgets and constraints gets the same key. The structure is stored with the layout and
+//! compared on a hit, so that layouts whose keys collide are never mixed up.
+
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Mutex,
+};
+
+use super::{
+  builder::SolverConfig,
+  context::LayoutContext,
+  measure::{Measure, MeasureVariant},
+  objective::Objective,
+  prop::{Prop, PropVariant},
+  smtlib::Variables,
+};
+
+/// Values of a solved layout by the `smtlib` names of its variables.
+#[derive(Clone, Debug, Default)]
+pub struct CachedLayout {
+  pub reals: HashMap<String, f64>,
+  pub bools: HashMap<String, bool>,
+
+  /// Positions of the constraints given up by `SolverConfig::relax_on_unsat`, among the
+  /// constraints of the widgets followed by the ones of the builder.
+  pub relaxed: Vec<usize>,
+
+  /// Structure of the layout, which a layout found under its key must have to be reused.
+  pub structure: String,
+}
+
+/// Storage of solved layouts by key, shared by the builders of `LayoutBuilder::with_cache`.
+pub trait LayoutStore: Send + Sync {
+  fn get(&self, key: u64) -> Option<CachedLayout>;
+  fn insert(&self, key: u64, layout: CachedLayout);
+}
+
+/// An in-memory store that keeps the latest `capacity` layouts.
+pub struct MemoryStore {
+  capacity: usize,
+  entries: Mutex<(HashMap<u64, CachedLayout>, VecDeque<u64>)>,
+}
+
+impl MemoryStore {
+  pub fn new(capacity: usize) -> Self {
+    MemoryStore {
+      capacity,
+      entries: Mutex::new((HashMap::new(), VecDeque::new())),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.lock().unwrap().0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn clear(&self) {
+    let mut entries = self.entries.lock().unwrap();
+    entries.0.clear();
+    entries.1.clear();
+  }
+}
+
+impl LayoutStore for MemoryStore {
+  fn get(&self, key: u64) -> Option<CachedLayout> {
+    self.entries.lock().unwrap().0.get(&key).cloned()
+  }
+
+  fn insert(&self, key: u64, layout: CachedLayout) {
+    let (layouts, order) = &mut *self.entries.lock().unwrap();
+    if layouts.insert(key, layout).is_none() {
+      order.push_back(key);
+    }
+    while layouts.len() > self.capacity {
+      match order.pop_front() {
+        Some(oldest) => layouts.remove(&oldest),
+        None => break,
+      };
+    }
+  }
+}
+
+/// Structure of a layout with the given `constraints` and `objectives`, solved with `config`.
+///
+/// Nodes are listed once each, in the order they are first reached, and refer to their operands
+/// by position in the list, so that shared nodes are not repeated.
+pub(crate) fn layout_structure<'a>(
+  ctx: &LayoutContext,
+  variables: &Variables<'a>,
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+  config: &SolverConfig,
+) -> String {
+  let mut structure = Structure {
+    ctx,
+    variables,
+    positions: HashMap::new(),
+    nodes: vec![],
+  };
+  let mut roots = vec![format!("config {:?}", config)];
+  for &c in constraints {
+    let kind = if c.hard { "hard" } else { "soft" };
+    roots.push(format!("{} {} {}", kind, c.weight, structure.prop(c)));
+  }
+  for o in objectives {
+    match *o {
+      Objective::Minimize(m) => roots.push(format!("minimize {}", structure.measure(m))),
+      Objective::Maximize(m) => roots.push(format!("maximize {}", structure.measure(m))),
+    }
+  }
+  let mut out = structure.nodes.join("\n");
+  out.push_str("\n;\n");
+  out.push_str(&roots.join("\n"));
+  out
+}
+
+/// Key of a layout of the given structure, the same in every build of the crate.
+pub(crate) fn layout_key(structure: &str) -> u64 {
+  // FNV-1a, since the hashers of the standard library may change between releases.
+  structure.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+    (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+/// Lists the nodes of expressions, visiting shared nodes once.
+struct Structure<'a, 'b> {
+  ctx: &'b LayoutContext,
+  variables: &'b Variables<'a>,
+
+  /// Position of each visited node in `nodes`, by node address.
+  positions: HashMap<usize, usize>,
+  nodes: Vec<String>,
+}
+
+impl<'a, 'b> Structure<'a, 'b> {
+  fn measure(&mut self, m: Measure<'a>) -> usize {
+    use MeasureVariant as V;
+    if let Some(&x) = self.positions.get(&m.addr()) {
+      return x;
+    }
+    let node = match *m.variant {
+      V::Unbound => format!("real {}", self.variables.name_of(m.addr()).unwrap_or("?")),
+      V::Const(num, den) => format!("const {}/{}", num, den),
+      V::Named(name) => format!("named {:?} {:?}", name, self.ctx.definition(name)),
+      V::Add(l, r) => format!("+ {} {}", self.measure(l), self.measure(r)),
+      V::Sub(l, r) => format!("- {} {}", self.measure(l), self.measure(r)),
+      V::Mul(l, r) => format!("* {} {}", self.measure(l), self.measure(r)),
+      V::Div(l, r) => format!("/ {} {}", self.measure(l), self.measure(r)),
+      V::Select(cond, l, r) => format!(
+        "ite {} {} {}",
+        self.prop(cond),
+        self.measure(l),
+        self.measure(r)
+      ),
+    };
+    self.push(m.addr(), node)
+  }
+
+  fn prop(&mut self, p: Prop<'a>) -> usize {
+    use PropVariant as V;
+    if let Some(&x) = self.positions.get(&p.addr()) {
+      return x;
+    }
+    let node = match *p.variant {
+      V::Unbound => format!("bool {}", self.variables.name_of(p.addr()).unwrap_or("?")),
+      V::Const(x) => format!("{}", x),
+      V::Eq(l, r) => format!("= {} {}", self.measure(l), self.measure(r)),
+      V::Lt(l, r) => format!("< {} {}", self.measure(l), self.measure(r)),
+      V::Le(l, r) => format!("<= {} {}", self.measure(l), self.measure(r)),
+      V::Gt(l, r) => format!("> {} {}", self.measure(l), self.measure(r)),
+      V::Ge(l, r) => format!(">= {} {}", self.measure(l), self.measure(r)),
+      V::Or(l, r) => format!("or {} {}", self.prop(l), self.prop(r)),
+      V::And(l, r) => format!("and {} {}", self.prop(l), self.prop(r)),
+      V::Not(x) => format!("not {}", self.prop(x)),
+    };
+    self.push(p.addr(), node)
+  }
+
+  fn push(&mut self, addr: usize, node: String) -> usize {
+    self.nodes.push(node);
+    self.positions.insert(addr, self.nodes.len() - 1);
+    self.nodes.len() - 1
+  }
+}
@@ -0,0 +1,57 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+#[cfg(feature = "z3")]
+use std::{thread, time::Duration};
+
+/// How often a running solve checks whether it has been cancelled.
+#[cfg(feature = "z3")]
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A shared flag that aborts a running `LayoutBuilder::build` when set.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+  }
+
+  /// Runs `f` while interrupting `z3_ctx` as soon as the token is cancelled.
+  #[cfg(feature = "z3")]
+  pub(crate) fn watch<T>(&self, z3_ctx: &z3::Context, f: impl FnOnce() -> T) -> T {
+    let done = AtomicBool::new(false);
+    let handle = z3_ctx.handle();
+    thread::scope(|s| {
+      s.spawn(|| {
+        while !done.load(Ordering::SeqCst) {
+          if self.is_cancelled() {
+            handle.interrupt();
+            return;
+          }
+          thread::sleep(CANCELLATION_POLL_INTERVAL);
+        }
+      });
+      let res = f();
+      done.store(true, Ordering::SeqCst);
+      res
+    })
+  }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+  fn from(cancelled: Arc<AtomicBool>) -> Self {
+    Self { cancelled }
+  }
+}
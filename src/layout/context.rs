@@ -1,24 +1,179 @@
-use std::collections::HashMap;
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Mutex, RwLock, RwLockReadGuard,
+  },
+};
 
 use bumpalo::Bump;
+#[cfg(feature = "z3")]
 use z3::ast::{Bool, Real};
 
+use super::{format::DisplayFormat, measure::MeasureVariant, prop::PropVariant};
+
+static NEXT_LAYOUT_CONTEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Owns the measures and propositions of a layout. It is `Send` and `Sync`, so measures,
+/// propositions and solutions can be shared with other threads.
 pub struct LayoutContext {
-  pub alloc: Bump,
+  arena: Mutex<Bump>,
+
+  /// Unique across the process, unlike the addresses of the nodes allocated in `arena`.
+  pub(crate) id: u64,
+
+  /// Pool of the small integer constants, shared by the measures of this context.
+  pub(crate) small_consts: [MeasureVariant<'static>; 16],
+
+  display_format: RwLock<DisplayFormat>,
+  live_painters: AtomicUsize,
+  measure_nodes: AtomicUsize,
+  prop_nodes: AtomicUsize,
+  definitions: RwLock<HashMap<String, f64>>,
+}
+
+/// Memory held by a `LayoutContext`, for spotting leaks in long-running programs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArenaStats {
+  /// Bytes allocated for measures, propositions and labels. Only freed with the context, or
+  /// reused after `LayoutContext::reset`.
+  pub allocated_bytes: usize,
+
+  /// Widgets pushed to a builder whose painters have neither run nor been dropped yet.
+  pub live_painters: usize,
+}
+
+/// Nodes allocated in a `LayoutContext` since it was created or last reset.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeCounts {
+  /// Measures, not counting the pooled small integer constants.
+  pub measures: usize,
+  pub props: usize,
 }
 
 impl LayoutContext {
   pub fn new() -> Self {
-    LayoutContext { alloc: Bump::new() }
+    LayoutContext {
+      arena: Mutex::new(Bump::new()),
+      id: NEXT_LAYOUT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed),
+      small_consts: std::array::from_fn(|i| MeasureVariant::Const(i as i32, 1)),
+      display_format: RwLock::new(DisplayFormat::default()),
+      live_painters: AtomicUsize::new(0),
+      measure_nodes: AtomicUsize::new(0),
+      prop_nodes: AtomicUsize::new(0),
+      definitions: RwLock::new(HashMap::new()),
+    }
+  }
+
+  pub(crate) fn alloc_measure<'a>(&'a self, variant: MeasureVariant<'a>) -> &'a MeasureVariant<'a> {
+    self.measure_nodes.fetch_add(1, Ordering::Relaxed);
+    self.alloc(variant)
+  }
+
+  pub(crate) fn alloc_prop<'a>(&'a self, variant: PropVariant<'a>) -> &'a PropVariant<'a> {
+    self.prop_nodes.fetch_add(1, Ordering::Relaxed);
+    self.alloc(variant)
+  }
+
+  /// Moves `value` into the arena of the context.
+  fn alloc<T>(&self, value: T) -> &T {
+    let arena = self.arena.lock().unwrap();
+    let value: *const T = arena.alloc(value);
+    // SAFETY: `Bump` neither moves nor frees its allocations until it is dropped, and it is
+    // only dropped with the context. The lock only guards the bump pointer.
+    unsafe { &*value }
+  }
+
+  /// Copies `value` into the arena of the context.
+  pub(crate) fn alloc_str(&self, value: &str) -> &str {
+    let arena = self.arena.lock().unwrap();
+    let value: *const str = arena.alloc_str(value);
+    // SAFETY: See `alloc`.
+    unsafe { &*value }
+  }
+
+  /// Sets the value of the named constant `name` for the following builds. Solver sessions keep
+  /// their asserted constraints when a value changes.
+  pub fn define(&self, name: &str, value: f64) {
+    self
+      .definitions
+      .write()
+      .unwrap()
+      .insert(name.to_string(), value);
+  }
+
+  pub fn definition(&self, name: &str) -> Option<f64> {
+    self.definitions.read().unwrap().get(name).copied()
+  }
+
+  /// Bytes allocated for measures, propositions and labels.
+  pub fn allocated_bytes(&self) -> usize {
+    self.arena.lock().unwrap().allocated_bytes()
+  }
+
+  pub fn node_counts(&self) -> NodeCounts {
+    NodeCounts {
+      measures: self.measure_nodes.load(Ordering::Relaxed),
+      props: self.prop_nodes.load(Ordering::Relaxed),
+    }
+  }
+
+  /// Frees all measures and propositions of the context, keeping the memory of the arena for the
+  /// next layout. Definitions and the display format are kept, except for names of nodes.
+  ///
+  /// Taking `&mut self` ensures no measure, proposition or painter of the context is alive.
+  /// Solutions and solver sessions of the context stop matching it, as the context gets a new id.
+  pub fn reset(&mut self) {
+    self.arena.get_mut().unwrap().reset();
+    self.id = NEXT_LAYOUT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed);
+    self.display_format.get_mut().unwrap().clear_names();
+    *self.measure_nodes.get_mut() = 0;
+    *self.prop_nodes.get_mut() = 0;
+  }
+
+  pub fn stats(&self) -> ArenaStats {
+    ArenaStats {
+      allocated_bytes: self.allocated_bytes(),
+      live_painters: self.live_painters.load(Ordering::Relaxed),
+    }
+  }
+
+  /// Sets how measures and propositions of this context are rendered by `Display`.
+  pub fn set_display_format(&self, format: DisplayFormat) {
+    *self.display_format.write().unwrap() = format;
+  }
+
+  /// Do not hold the guard while formatting the operands of a measure or proposition: they read
+  /// it again.
+  pub fn display_format(&self) -> RwLockReadGuard<'_, DisplayFormat> {
+    self.display_format.read().unwrap()
+  }
+}
+
+/// Counts a pending painter in `live_painters` of its context for as long as it is alive.
+pub(crate) struct PainterGuard<'a>(&'a LayoutContext);
+
+impl<'a> PainterGuard<'a> {
+  pub(crate) fn new(ctx: &'a LayoutContext) -> Self {
+    ctx.live_painters.fetch_add(1, Ordering::Relaxed);
+    Self(ctx)
+  }
+}
+
+impl<'a> Drop for PainterGuard<'a> {
+  fn drop(&mut self) {
+    self.0.live_painters.fetch_sub(1, Ordering::Relaxed);
   }
 }
 
+#[cfg(feature = "z3")]
 pub struct Z3BuildContext<'ctx> {
   pub prop_cache: HashMap<usize, Bool<'ctx>>,
   pub measure_cache: HashMap<usize, Real<'ctx>>,
   pub z3_ctx: &'ctx z3::Context,
 }
 
+#[cfg(feature = "z3")]
 impl<'ctx> Z3BuildContext<'ctx> {
   pub fn new(z3_ctx: &'ctx z3::Context) -> Self {
     Self {
@@ -0,0 +1,47 @@
+use std::error::Error as StdError;
+
+use thiserror::Error;
+
+/// Why a painter failed, of whatever type the painter reports, like an error of the graphics
+/// library it draws with.
+pub type PaintError = Box<dyn StdError + Send + Sync>;
+
+/// Ways laying out can fail, for applications to tell apart.
+#[derive(Error, Debug)]
+pub enum LayoutError {
+  /// `core` is a minimal set of hard constraints that conflict with each other.
+  #[error("provided constraints cannot be satisfied: {}", core.join("; "))]
+  Unsat { core: Vec<String> },
+  #[error("failed to derive a layout under provided constraints")]
+  Unknown,
+  #[error("solver budget exhausted before a layout was found")]
+  Timeout,
+  #[error("layout was cancelled")]
+  Cancelled,
+  #[error("solver did not provide a value for measure {measure}")]
+  ModelExtraction { measure: String },
+
+  /// `widget` is the position of the widget among the widgets of its builder.
+  #[error("painter of widget {widget} failed: {source}")]
+  Painter { widget: usize, source: PaintError },
+  #[error("bad const {value}")]
+  BadConst { value: f64 },
+  #[error("named constant {name} is not defined")]
+  Undefined { name: String },
+  #[error("template parameter {name} is not bound")]
+  Unbound { name: String },
+
+  /// The layout needs Z3, but the crate was built without the `z3` feature.
+  #[error("layout is not supported without Z3: {reason}")]
+  Unsupported { reason: String },
+  #[error("pinned measure is not part of the layout")]
+  UnknownPin,
+
+  /// `LayoutBuilder::build_async` was awaited outside of a tokio runtime.
+  #[error("build_async needs a tokio runtime")]
+  AsyncRuntime,
+
+  /// Z3 misbehaved, like by reporting a layout as solved without a model of it.
+  #[error("solver error: {reason}")]
+  Solver { reason: String },
+}
@@ -0,0 +1,78 @@
+use std::{collections::HashMap, fmt};
+
+use super::{measure::Measure, prop::Prop, reflect::ReflectMeasures};
+
+/// How measures and propositions are rendered by `Display`, and so by reports and errors.
+///
+/// Set on a `LayoutContext` with `set_display_format`.
+#[derive(Clone, Debug, Default)]
+pub struct DisplayFormat {
+  /// Maximum number of decimal places of constants. Trailing zeros are dropped.
+  pub precision: Option<usize>,
+
+  /// Render constants as `num/den` instead of decimals.
+  pub rational: bool,
+
+  /// Names substituted for unbound variables, keyed by node address.
+  names: HashMap<usize, String>,
+}
+
+impl DisplayFormat {
+  pub fn with_precision(mut self, precision: usize) -> Self {
+    self.precision = Some(precision);
+    self
+  }
+
+  pub fn rational(mut self) -> Self {
+    self.rational = true;
+    self
+  }
+
+  /// Renders `measure` as `name` wherever it appears.
+  pub fn name_measure(&mut self, measure: Measure, name: &str) {
+    self.names.insert(measure.addr(), name.to_string());
+  }
+
+  /// Renders `prop` as `name` wherever it appears.
+  pub fn name_prop(&mut self, prop: Prop, name: &str) {
+    self.names.insert(prop.addr(), name.to_string());
+  }
+
+  /// Names all measures of `widget` as `prefix.<measure name>`.
+  pub fn name_reflected<'a>(&mut self, prefix: &str, widget: &impl ReflectMeasures<'a>) {
+    for (name, measure) in widget.named_measures() {
+      self.name_measure(measure, &format!("{}.{}", prefix, name));
+    }
+  }
+
+  pub(crate) fn clear_names(&mut self) {
+    self.names.clear();
+  }
+
+  pub(crate) fn name_of(&self, addr: usize) -> Option<&str> {
+    self.names.get(&addr).map(|x| x.as_str())
+  }
+
+  pub(crate) fn write_const(&self, f: &mut fmt::Formatter<'_>, num: i32, den: i32) -> fmt::Result {
+    if self.rational {
+      return if den == 1 {
+        write!(f, "{}", num)
+      } else {
+        write!(f, "{}/{}", num, den)
+      };
+    }
+    let value = num as f64 / den as f64;
+    match self.precision {
+      Some(precision) => {
+        let s = format!("{:.*}", precision, value);
+        let s = if s.contains('.') {
+          s.trim_end_matches('0').trim_end_matches('.')
+        } else {
+          &s
+        };
+        write!(f, "{}", s)
+      }
+      None => write!(f, "{}", value),
+    }
+  }
+}
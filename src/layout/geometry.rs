@@ -0,0 +1,249 @@
+//! Geometric primitives built from measures, usable by any widget.
+
+use super::{angle::Angle, context::LayoutContext, measure::Measure, prop::Prop};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Point<'a> {
+  pub x: Measure<'a>,
+  pub y: Measure<'a>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Size<'a> {
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+}
+
+/// An axis-aligned rectangle described by its edges.
+#[derive(Copy, Clone, Debug)]
+pub struct RectMeasures<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+}
+
+/// Space kept clear around a widget by its neighbours, like the margins of CSS. Margins may be
+/// negative, to pull neighbours closer.
+#[derive(Copy, Clone, Debug)]
+pub struct Margins<'a> {
+  pub top: Measure<'a>,
+  pub right: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub left: Measure<'a>,
+}
+
+impl<'a> Point<'a> {
+  /// The point at `angle` on the circle around `center`, measured clockwise from the positive
+  /// x axis in screen coordinates.
+  ///
+  /// This stays linear in `radius` as long as `angle` is a constant.
+  pub fn on_circle(center: Point<'a>, radius: Measure<'a>, angle: Angle<'a>) -> Self {
+    Point {
+      x: center.x + radius * angle.cos(),
+      y: center.y + radius * angle.sin(),
+    }
+  }
+}
+
+impl<'a> Size<'a> {
+  pub fn area(&self) -> Measure<'a> {
+    self.width * self.height
+  }
+
+  pub fn fits_in(&self, that: &Size<'a>) -> Prop<'a> {
+    self.width.prop_le(that.width) & self.height.prop_le(that.height)
+  }
+}
+
+#[allow(dead_code)]
+impl<'a> RectMeasures<'a> {
+  pub fn from_origin_and_size(origin: Point<'a>, size: Size<'a>) -> Self {
+    Self {
+      left: origin.x,
+      right: origin.x + size.width,
+      top: origin.y,
+      bottom: origin.y + size.height,
+    }
+  }
+
+  /// The smallest rectangle around `points`, or `None` when there are none.
+  pub fn around(points: &[Point<'a>]) -> Option<Self> {
+    let (first, rest) = points.split_first()?;
+    let start = Self {
+      left: first.x,
+      right: first.x,
+      top: first.y,
+      bottom: first.y,
+    };
+    Some(rest.iter().fold(start, |rect, point| Self {
+      left: rect.left.min(point.x),
+      right: rect.right.max(point.x),
+      top: rect.top.min(point.y),
+      bottom: rect.bottom.max(point.y),
+    }))
+  }
+
+  pub fn width(&self) -> Measure<'a> {
+    self.right - self.left
+  }
+
+  pub fn height(&self) -> Measure<'a> {
+    self.bottom - self.top
+  }
+
+  pub fn size(&self) -> Size<'a> {
+    Size {
+      width: self.width(),
+      height: self.height(),
+    }
+  }
+
+  pub fn center(&self) -> Point<'a> {
+    Point {
+      x: (self.left + self.right) / 2.0,
+      y: (self.top + self.bottom) / 2.0,
+    }
+  }
+
+  /// Named attachment points of the rectangle: its corners like `top-left`, the midpoints of
+  /// its edges like `top-center`, its `center`, and the ports `out-north`, `out-east`,
+  /// `out-south` and `out-west` at the midpoints of its edges.
+  pub fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    let center = self.center();
+    let (top, bottom) = (
+      Point {
+        y: self.top,
+        ..center
+      },
+      Point {
+        y: self.bottom,
+        ..center
+      },
+    );
+    let (left, right) = (
+      Point {
+        x: self.left,
+        ..center
+      },
+      Point {
+        x: self.right,
+        ..center
+      },
+    );
+    let corner = |x, y| Point { x, y };
+    vec![
+      ("top-left", corner(self.left, self.top)),
+      ("top-center", top),
+      ("top-right", corner(self.right, self.top)),
+      ("center-left", left),
+      ("center", center),
+      ("center-right", right),
+      ("bottom-left", corner(self.left, self.bottom)),
+      ("bottom-center", bottom),
+      ("bottom-right", corner(self.right, self.bottom)),
+      ("out-north", top),
+      ("out-east", right),
+      ("out-south", bottom),
+      ("out-west", left),
+    ]
+  }
+
+  /// The anchor of `self` named `name`. See `anchors`.
+  pub fn anchor(&self, name: &str) -> Option<Point<'a>> {
+    find_anchor(self.anchors(), name)
+  }
+
+  /// Holds when `that` lies entirely inside `self`.
+  pub fn contains(&self, that: &RectMeasures<'a>) -> Prop<'a> {
+    that.left.prop_ge(self.left)
+      & that.right.prop_le(self.right)
+      & that.top.prop_ge(self.top)
+      & that.bottom.prop_le(self.bottom)
+  }
+
+  pub fn contains_point(&self, point: Point<'a>) -> Prop<'a> {
+    point.x.prop_ge(self.left)
+      & point.x.prop_le(self.right)
+      & point.y.prop_ge(self.top)
+      & point.y.prop_le(self.bottom)
+  }
+
+  /// Holds when the intersection of `self` and `that` is empty. Touching edges do not intersect.
+  pub fn disjoint(&self, that: &RectMeasures<'a>) -> Prop<'a> {
+    self.right.prop_le(that.left)
+      | that.right.prop_le(self.left)
+      | self.bottom.prop_le(that.top)
+      | that.bottom.prop_le(self.top)
+  }
+
+  pub fn intersects(&self, that: &RectMeasures<'a>) -> Prop<'a> {
+    !self.disjoint(that)
+  }
+
+  /// The bounding box of `self` and `that`.
+  pub fn union(&self, that: &RectMeasures<'a>) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left.min(that.left),
+      right: self.right.max(that.right),
+      top: self.top.min(that.top),
+      bottom: self.bottom.max(that.bottom),
+    }
+  }
+
+  /// Grows the rectangle by `amount` on every side.
+  pub fn inflate(&self, amount: Measure<'a>) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left - amount,
+      right: self.right + amount,
+      top: self.top - amount,
+      bottom: self.bottom + amount,
+    }
+  }
+
+  /// Shrinks the rectangle by `amount` on every side.
+  pub fn deflate(&self, amount: Measure<'a>) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left + amount,
+      right: self.right - amount,
+      top: self.top + amount,
+      bottom: self.bottom - amount,
+    }
+  }
+}
+
+#[allow(dead_code)]
+impl<'a> Margins<'a> {
+  /// Margins in the order of CSS: top, right, bottom and left.
+  pub fn new(top: Measure<'a>, right: Measure<'a>, bottom: Measure<'a>, left: Measure<'a>) -> Self {
+    Self {
+      top,
+      right,
+      bottom,
+      left,
+    }
+  }
+
+  pub fn uniform(margin: Measure<'a>) -> Self {
+    Self::new(margin, margin, margin, margin)
+  }
+
+  pub fn zero(ctx: &'a LayoutContext) -> Self {
+    Self::uniform(Measure::zero(ctx))
+  }
+
+  /// The margin box of `rect`, which neighbours stay out of.
+  pub fn around(&self, rect: &RectMeasures<'a>) -> RectMeasures<'a> {
+    RectMeasures {
+      left: rect.left - self.left,
+      right: rect.right + self.right,
+      top: rect.top - self.top,
+      bottom: rect.bottom + self.bottom,
+    }
+  }
+}
+
+/// The point named `name` among `anchors`.
+pub fn find_anchor<'a>(anchors: Vec<(&'static str, Point<'a>)>, name: &str) -> Option<Point<'a>> {
+  anchors.into_iter().find(|x| x.0 == name).map(|x| x.1)
+}
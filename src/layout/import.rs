@@ -0,0 +1,256 @@
+//! Layouts built in another `LayoutContext` and imported into a page, like components that
+//! teams develop and test on their own.
+
+use std::collections::HashMap;
+
+use super::{
+  builder::LayoutBuilder,
+  context::LayoutContext,
+  error::PaintError,
+  geometry::Point,
+  measure::{Measure, MeasureVariant},
+  objective::Objective,
+  prop::{Prop, PropVariant},
+  render::RenderTarget,
+  solution::Solution,
+  tag::with_current,
+  transform::Transform,
+  widget::RawWidget,
+};
+
+/// Copies measures and propositions of the context `'b` into the context `'a`, each node once,
+/// so that copies of the same unbound measure stay the same variable.
+struct Importer<'a, 'b> {
+  ctx: &'a LayoutContext,
+  from: &'b LayoutContext,
+  measures: HashMap<usize, Measure<'a>>,
+  props: HashMap<usize, Prop<'a>>,
+
+  /// Values to hold unbound measures and propositions at, for solved layouts.
+  solution: Option<Solution>,
+  pins: Vec<Prop<'a>>,
+}
+
+impl<'a, 'b> Importer<'a, 'b> {
+  fn measure(&mut self, m: Measure<'b>) -> Measure<'a> {
+    assert!(
+      std::ptr::eq(m.ctx, self.from),
+      "measure is not of the imported layout"
+    );
+    if let Some(&x) = self.measures.get(&m.addr()) {
+      return x;
+    }
+    let ctx = self.ctx;
+    let x = match *m.variant {
+      MeasureVariant::Unbound => {
+        let x = Measure::new_unbound(ctx);
+        let value = self.solution.as_ref().and_then(|s| s.value_of(m));
+        if let Some(Ok(value)) = value.map(|v| Measure::new_const(ctx, v)) {
+          self.pins.push(x.prop_eq(value).hard());
+        }
+        x
+      }
+      MeasureVariant::Const(num, den) => Measure::new_ratio(ctx, num, den).unwrap(),
+      MeasureVariant::Named(name) => {
+        if let (None, Some(value)) = (ctx.definition(name), self.from.definition(name)) {
+          ctx.define(name, value);
+        }
+        Measure::named_const(ctx, name)
+      }
+      MeasureVariant::Add(l, r) => self.measure(l) + self.measure(r),
+      MeasureVariant::Sub(l, r) => self.measure(l) - self.measure(r),
+      MeasureVariant::Mul(l, r) => self.measure(l) * self.measure(r),
+      MeasureVariant::Div(l, r) => self.measure(l) / self.measure(r),
+      MeasureVariant::Select(cond, l, r) => {
+        let cond = self.prop(cond);
+        cond.select(self.measure(l), self.measure(r))
+      }
+    };
+    self.measures.insert(m.addr(), x);
+    x
+  }
+
+  fn prop(&mut self, p: Prop<'b>) -> Prop<'a> {
+    if let Some(&x) = self.props.get(&p.addr()) {
+      return x;
+    }
+    let ctx = self.ctx;
+    let x = match *p.variant {
+      PropVariant::Unbound => {
+        let x = Prop::new_unbound(ctx);
+        if let Some(holds) = self.solution.as_ref().and_then(|s| s.truth_of(p)) {
+          self.pins.push(if holds { x } else { !x }.hard());
+        }
+        x
+      }
+      PropVariant::Const(x) => Prop::new_const(ctx, x),
+      PropVariant::Eq(l, r) => self.measure(l).prop_eq(self.measure(r)),
+      PropVariant::Lt(l, r) => self.measure(l).prop_lt(self.measure(r)),
+      PropVariant::Le(l, r) => self.measure(l).prop_le(self.measure(r)),
+      PropVariant::Gt(l, r) => self.measure(l).prop_gt(self.measure(r)),
+      PropVariant::Ge(l, r) => self.measure(l).prop_ge(self.measure(r)),
+      PropVariant::Or(l, r) => self.prop(l) | self.prop(r),
+      PropVariant::And(l, r) => self.prop(l) & self.prop(r),
+      PropVariant::Not(x) => !self.prop(x),
+    };
+    let x = Prop {
+      weight: p.weight,
+      hard: p.hard,
+      label: p.label.map(|x| ctx.alloc_str(x)),
+      ..x
+    };
+    self.props.insert(p.addr(), x);
+    x
+  }
+}
+
+/// The widgets, constraints and objectives of a builder of another context, pushed to a
+/// builder as one widget. The imported layout keeps its own coordinates, with its origin at
+/// `origin`, and its widgets are painted in the order they were pushed.
+///
+/// Named constants are looked up in the importing context, and copied from the imported one
+/// where it does not define them.
+pub struct Sublayout<'a, 'b> {
+  /// Where the origin of the imported layout is, in the coordinates of the importing one.
+  pub origin: Point<'a>,
+
+  layout: LayoutBuilder<'b>,
+  importer: Importer<'a, 'b>,
+  measures: Vec<Vec<Measure<'a>>>,
+  constraints: Vec<Prop<'a>>,
+  objectives: Vec<Objective<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a, 'b> Sublayout<'a, 'b> {
+  /// Imports `layout` into `ctx`, to be solved with the importing layout.
+  pub fn new(ctx: &'a LayoutContext, layout: LayoutBuilder<'b>) -> Self {
+    Self::import(ctx, layout, None)
+  }
+
+  /// Imports `layout` as solved in `solution`, so that only its origin is left to the
+  /// importing layout.
+  pub fn solved(ctx: &'a LayoutContext, layout: LayoutBuilder<'b>, solution: &Solution) -> Self {
+    Self::import(ctx, layout, Some(solution.clone()))
+  }
+
+  fn import(
+    ctx: &'a LayoutContext,
+    mut layout: LayoutBuilder<'b>,
+    solution: Option<Solution>,
+  ) -> Self {
+    let mut importer = Importer {
+      ctx,
+      from: layout.ctx(),
+      measures: HashMap::new(),
+      props: HashMap::new(),
+      solution,
+      pins: vec![],
+    };
+    let measures = layout
+      .painters()
+      .into_iter()
+      .map(|(_, _, _, w)| {
+        w.measures()
+          .into_iter()
+          .map(|m| importer.measure(m))
+          .collect()
+      })
+      .collect();
+    let mut constraints = layout
+      .all_constraints()
+      .into_iter()
+      .map(|p| importer.prop(p))
+      .collect::<Vec<_>>();
+    let objectives = layout
+      .all_objectives()
+      .into_iter()
+      .map(|o| match o {
+        Objective::Minimize(m) => Objective::Minimize(importer.measure(m)),
+        Objective::Maximize(m) => Objective::Maximize(importer.measure(m)),
+      })
+      .collect();
+    constraints.append(&mut importer.pins);
+    Self {
+      origin: Point {
+        x: Measure::new_unbound(ctx),
+        y: Measure::new_unbound(ctx),
+      },
+      layout,
+      importer,
+      measures,
+      constraints,
+      objectives,
+    }
+  }
+
+  /// `m` of the imported layout as a measure of the importing one, in the coordinates of the
+  /// imported layout.
+  pub fn measure(&mut self, m: Measure<'b>) -> Measure<'a> {
+    let x = self.importer.measure(m);
+    self.constraints.append(&mut self.importer.pins);
+    x
+  }
+
+  /// `p` of the imported layout as a proposition of the importing one.
+  pub fn prop(&mut self, p: Prop<'b>) -> Prop<'a> {
+    let x = self.importer.prop(p);
+    self.constraints.append(&mut self.importer.pins);
+    x
+  }
+
+  /// `p` of the imported layout in the coordinates of the importing one.
+  pub fn point(&mut self, p: Point<'b>) -> Point<'a> {
+    Point {
+      x: self.origin.x + self.measure(p.x),
+      y: self.origin.y + self.measure(p.y),
+    }
+  }
+}
+
+impl<'a, 'b> RawWidget<'a> for Sublayout<'a, 'b> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let mut measures = vec![self.origin.x, self.origin.y];
+    measures.extend(self.measures.iter().flatten().copied());
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    self.constraints.clone()
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.objectives.clone()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    let mut offset = 2;
+    for (_, _, tag, w) in self.layout.painters() {
+      let len = w.measures().len();
+      with_current(tag, || w.on_solved(&measures[offset..offset + len]));
+      offset += len;
+    }
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let local = transform.then(Transform::translate(measures[0], measures[1]));
+    let mut offset = 2;
+    for (_, _, tag, w) in self.layout.painters() {
+      let len = w.measures().len();
+      with_current(tag, || {
+        w.paint_at(&measures[offset..offset + len], local, target)
+      })?;
+      offset += len;
+    }
+    Ok(())
+  }
+}
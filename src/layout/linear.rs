@@ -0,0 +1,429 @@
+//! Solving of fully determined layouts by elimination, without Z3.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{
+  context::LayoutContext,
+  measure::{Measure, MeasureVariant},
+  prop::{Prop, PropVariant},
+  solution::{Solution, TOLERANCE},
+};
+
+/// Coefficients smaller than this are treated as zero during elimination.
+const EPSILON: f64 = 1e-12;
+
+/// A linear combination of unbound measures plus a constant.
+#[derive(Clone, Debug, Default)]
+pub(super) struct LinExpr {
+  pub terms: BTreeMap<usize, f64>,
+  pub constant: f64,
+}
+
+impl LinExpr {
+  fn constant(value: f64) -> Self {
+    LinExpr {
+      terms: BTreeMap::new(),
+      constant: value,
+    }
+  }
+
+  fn as_constant(&self) -> Option<f64> {
+    if self.terms.is_empty() {
+      Some(self.constant)
+    } else {
+      None
+    }
+  }
+
+  pub fn add_scaled(&mut self, that: &LinExpr, factor: f64) {
+    for (&var, &coeff) in &that.terms {
+      let entry = self.terms.entry(var).or_insert(0.0);
+      *entry += coeff * factor;
+      if entry.abs() < EPSILON {
+        self.terms.remove(&var);
+      }
+    }
+    self.constant += that.constant * factor;
+  }
+
+  pub fn scaled(mut self, factor: f64) -> Self {
+    for coeff in self.terms.values_mut() {
+      *coeff *= factor;
+    }
+    self.constant *= factor;
+    self
+  }
+}
+
+/// Turns measures into linear expressions over numbered variables.
+pub(super) struct Linearizer<'a> {
+  ctx: &'a LayoutContext,
+
+  /// Variable number of each unbound measure, by node address.
+  pub vars: HashMap<usize, usize>,
+
+  /// Values of the named constants seen so far.
+  pub named: HashMap<String, f64>,
+
+  /// Truth of conditions of selections that are not constant, by node address.
+  pub decisions: HashMap<usize, bool>,
+
+  /// Conditions of selections taken so far, with the truth they were taken with. They have to
+  /// be asserted for the expressions to hold.
+  pub conditions: Vec<(Prop<'a>, bool)>,
+  cache: HashMap<usize, LinExpr>,
+}
+
+/// Why a measure is not a linear expression.
+#[cfg_attr(feature = "z3", allow(dead_code))]
+pub(super) enum Unlinear<'a> {
+  /// Product of two measures that are not constant.
+  Product(Measure<'a>),
+
+  /// Quotient by a measure that is not constant, or by zero.
+  Quotient(Measure<'a>),
+  Undefined(&'a str),
+
+  /// Selection on a condition that is not constant and has no decision.
+  Undecided(Prop<'a>),
+}
+
+impl<'a> Linearizer<'a> {
+  pub fn new(ctx: &'a LayoutContext) -> Self {
+    Linearizer {
+      ctx,
+      vars: HashMap::new(),
+      named: HashMap::new(),
+      decisions: HashMap::new(),
+      conditions: vec![],
+      cache: HashMap::new(),
+    }
+  }
+
+  pub fn measure(&mut self, m: Measure<'a>) -> Result<LinExpr, Unlinear<'a>> {
+    if let Some(x) = self.cache.get(&m.addr()) {
+      return Ok(x.clone());
+    }
+    use MeasureVariant as V;
+    let res = match *m.variant {
+      V::Unbound => {
+        let mut x = LinExpr::default();
+        x.terms.insert(self.variable(m), 1.0);
+        x
+      }
+      V::Const(num, den) => LinExpr::constant(num as f64 / den as f64),
+      V::Named(name) => {
+        let value = self.ctx.definition(name).ok_or(Unlinear::Undefined(name))?;
+        self.named.insert(name.to_string(), value);
+        LinExpr::constant(value)
+      }
+      V::Add(l, r) => {
+        let mut x = self.measure(l)?;
+        x.add_scaled(&self.measure(r)?, 1.0);
+        x
+      }
+      V::Sub(l, r) => {
+        let mut x = self.measure(l)?;
+        x.add_scaled(&self.measure(r)?, -1.0);
+        x
+      }
+      V::Mul(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        match (l.as_constant(), r.as_constant()) {
+          (Some(c), _) => r.scaled(c),
+          (_, Some(c)) => l.scaled(c),
+          _ => return Err(Unlinear::Product(m)),
+        }
+      }
+      V::Div(l, r) => match self.measure(r)?.as_constant() {
+        Some(c) if c != 0.0 => self.measure(l)?.scaled(1.0 / c),
+        _ => return Err(Unlinear::Quotient(m)),
+      },
+      V::Select(cond, l, r) => {
+        let holds = match cond.const_value() {
+          Some(x) => x,
+          None => {
+            let x = *self
+              .decisions
+              .get(&cond.addr())
+              .ok_or(Unlinear::Undecided(cond))?;
+            self.conditions.push((cond, x));
+            x
+          }
+        };
+        if holds {
+          self.measure(l)?
+        } else {
+          self.measure(r)?
+        }
+      }
+    };
+    self.cache.insert(m.addr(), res.clone());
+    Ok(res)
+  }
+
+  /// Registers the unbound measure `m` as a variable, if it is not one yet.
+  pub fn variable(&mut self, m: Measure<'a>) -> usize {
+    let next = self.vars.len();
+    *self.vars.entry(m.addr()).or_insert(next)
+  }
+
+  /// Adds the equations `prop` asserts to `rows`, or returns `None` if it asserts anything else.
+  fn equations(&mut self, prop: Prop<'a>, rows: &mut Vec<LinExpr>) -> Option<()> {
+    match *prop.variant {
+      PropVariant::Eq(l, r) => {
+        let mut row = self.measure(l).ok()?;
+        row.add_scaled(&self.measure(r).ok()?, -1.0);
+        rows.push(row);
+        Some(())
+      }
+      PropVariant::And(l, r) => {
+        self.equations(l, rows)?;
+        self.equations(r, rows)
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Solves the layout without Z3 if the hard equalities among `constraints` determine every
+/// unbound measure of `constraints` and `measures`.
+///
+/// Returns `None` when the system is not linear, not fully determined or inconsistent, or when
+/// the other hard constraints do not hold, and the solver is needed.
+pub(crate) fn solve<'a>(
+  ctx: &'a LayoutContext,
+  measures: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+) -> Option<Solution> {
+  let mut lin = Linearizer::new(ctx);
+  let mut rows = vec![];
+  for c in constraints.iter().filter(|x| x.hard) {
+    // Hard constraints other than equalities are checked against the solution below.
+    let mut equations = vec![];
+    if lin.equations(*c, &mut equations).is_some() {
+      rows.extend(equations);
+    }
+  }
+  for &m in measures {
+    lin.measure(m).ok()?;
+  }
+  for c in constraints {
+    collect_vars(&mut lin, *c)?;
+  }
+  let n = lin.vars.len();
+  if rows.len() < n {
+    return None;
+  }
+
+  // Forward elimination on sparse rows. Each pivot row is reduced against all earlier pivots,
+  // so eliminating the pivots of a new row in creation order leaves none of them behind.
+  let mut pivots: Vec<(usize, LinExpr)> = vec![];
+  let mut pivot_of_var: HashMap<usize, usize> = HashMap::new();
+  for mut row in rows {
+    let mut pending = row
+      .terms
+      .keys()
+      .filter_map(|var| pivot_of_var.get(var).copied())
+      .collect::<BTreeSet<_>>();
+    while let Some(k) = pending.iter().next().copied() {
+      pending.remove(&k);
+      let (var, pivot_row) = &pivots[k];
+      let coeff = match row.terms.get(var) {
+        Some(&x) => x,
+        None => continue,
+      };
+      row.add_scaled(pivot_row, -coeff / pivot_row.terms[var]);
+      pending.extend(
+        pivot_row
+          .terms
+          .keys()
+          .filter_map(|var| pivot_of_var.get(var).copied())
+          .filter(|&j| j > k),
+      );
+    }
+    let var = match row
+      .terms
+      .iter()
+      .max_by(|a, b| a.1.abs().partial_cmp(&b.1.abs()).unwrap())
+    {
+      Some((&var, _)) => var,
+      None if row.constant.abs() < 1e-9 => continue,
+      None => return None,
+    };
+    pivot_of_var.insert(var, pivots.len());
+    pivots.push((var, row));
+  }
+  if pivots.len() < n {
+    return None;
+  }
+
+  // Back substitution, latest pivot first.
+  let mut values = vec![0.0; n];
+  for (var, row) in pivots.iter().rev() {
+    let rest = row
+      .terms
+      .iter()
+      .filter(|&(v, _)| v != var)
+      .map(|(&v, &coeff)| coeff * values[v])
+      .sum::<f64>();
+    values[*var] = -(row.constant + rest) / row.terms[var];
+  }
+
+  let solution = Solution::from_values(
+    ctx.id,
+    lin.vars.iter().map(|(&addr, &var)| (addr, values[var])),
+    lin.named,
+  );
+  if constraints
+    .iter()
+    .filter(|x| x.hard)
+    .all(|c| solution.truth_of(*c) == Some(true))
+  {
+    Some(solution)
+  } else {
+    None
+  }
+}
+
+/// `solution` with the values of `free`, unbound measures of `constraints`, rounded to multiples
+/// of `step`, keeping the hard equalities among `constraints` that hold in it.
+///
+/// Measures are rounded in the order of `free`. After each, the measures that an equality then
+/// determines are derived from the values so far instead of rounded. A measure whose rounding
+/// breaks a hard constraint that held keeps its value.
+pub(crate) fn snap<'a>(
+  ctx: &'a LayoutContext,
+  free: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+  solution: &Solution,
+  step: f64,
+) -> Solution {
+  let hard = constraints
+    .iter()
+    .copied()
+    .filter(|x| x.hard && solution.truth_of(*x) == Some(true))
+    .collect::<Vec<_>>();
+  let mut lin = Linearizer::new(ctx);
+  let mut rows = vec![];
+  for &c in &hard {
+    let mut equations = vec![];
+    if lin.equations(c, &mut equations).is_some() {
+      rows.extend(equations);
+    }
+  }
+
+  // Rows over variables that are all among `free`, with the variables by position in `free`.
+  let index = free
+    .iter()
+    .enumerate()
+    .filter_map(|(i, m)| Some((*lin.vars.get(&m.addr())?, i)))
+    .collect::<HashMap<_, _>>();
+  let rows = rows
+    .into_iter()
+    .filter_map(|row| {
+      let terms = row
+        .terms
+        .iter()
+        .map(|(var, &coeff)| Some((*index.get(var)?, coeff)))
+        .collect::<Option<Vec<_>>>()?;
+      Some((terms, row.constant))
+    })
+    .collect::<Vec<_>>();
+
+  let mut snapping = Snapping {
+    values: free.iter().map(|&m| solution.value_of(m)).collect(),
+    fixed: vec![false; free.len()],
+    rows,
+    step,
+  };
+  let holds = |values: &[Option<f64>]| {
+    let snapped = solution.with_values(
+      free
+        .iter()
+        .zip(values)
+        .filter_map(|(m, x)| Some((m.addr(), (*x)?))),
+    );
+    hard.iter().all(|&c| snapped.truth_of(c) == Some(true))
+  };
+  for i in 0..free.len() {
+    let value = match snapping.values[i] {
+      Some(x) if !snapping.fixed[i] => x,
+      _ => continue,
+    };
+    let (values, fixed) = (snapping.values.clone(), snapping.fixed.clone());
+    snapping.fix(i, (value / step).round() * step);
+    if !holds(&snapping.values) {
+      snapping.values = values;
+      snapping.fixed = fixed;
+      snapping.fix(i, value);
+    }
+  }
+  solution.with_values(
+    free
+      .iter()
+      .zip(snapping.values)
+      .filter_map(|(m, x)| Some((m.addr(), x?))),
+  )
+}
+
+/// Values of the measures of `snap`, and the equalities between them as terms by position and
+/// a constant.
+struct Snapping {
+  values: Vec<Option<f64>>,
+  fixed: Vec<bool>,
+  rows: Vec<(Vec<(usize, f64)>, f64)>,
+  step: f64,
+}
+
+impl Snapping {
+  /// Fixes the measure at `i` to `value`, and derives the measures that are then determined.
+  fn fix(&mut self, i: usize, value: f64) {
+    self.values[i] = Some(value);
+    self.fixed[i] = true;
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for (terms, constant) in &self.rows {
+        let mut open = terms.iter().filter(|&&(j, _)| !self.fixed[j]);
+        let (j, coeff) = match (open.next(), open.next()) {
+          (Some(&x), None) => x,
+          _ => continue,
+        };
+        let rest = terms
+          .iter()
+          .filter(|&&(k, _)| k != j)
+          .map(|&(k, c)| c * self.values[k].unwrap_or(0.0))
+          .sum::<f64>();
+        let mut value = -(constant + rest) / coeff;
+        // Keep derived values on the grid when they only miss it by rounding errors.
+        let rounded = (value / self.step).round() * self.step;
+        if (value - rounded).abs() <= TOLERANCE {
+          value = rounded;
+        }
+        self.values[j] = Some(value);
+        self.fixed[j] = true;
+        changed = true;
+      }
+    }
+  }
+}
+
+/// Registers the unbound measures of `prop` as variables, failing on anything non-linear.
+fn collect_vars<'a>(lin: &mut Linearizer<'a>, prop: Prop<'a>) -> Option<()> {
+  use PropVariant as V;
+  match *prop.variant {
+    V::Unbound => None,
+    V::Const(_) => Some(()),
+    V::Eq(l, r) | V::Lt(l, r) | V::Le(l, r) | V::Gt(l, r) | V::Ge(l, r) => {
+      lin.measure(l).ok()?;
+      lin.measure(r).ok()?;
+      Some(())
+    }
+    V::Or(l, r) | V::And(l, r) => {
+      collect_vars(lin, l)?;
+      collect_vars(lin, r)
+    }
+    V::Not(x) => collect_vars(lin, x),
+  }
+}
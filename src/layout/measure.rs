@@ -4,13 +4,15 @@ use std::{
   ops::{Add, Div, Mul, Sub},
 };
 
-use anyhow::Result;
 use fraction::GenericFraction;
-use thiserror::Error;
+#[cfg(feature = "z3")]
 use z3::ast::Real;
 
+#[cfg(feature = "z3")]
+use super::context::Z3BuildContext;
 use super::{
-  context::{LayoutContext, Z3BuildContext},
+  context::LayoutContext,
+  error::LayoutError,
   prop::{Prop, PropVariant},
 };
 use std::fmt::Debug;
@@ -28,16 +30,11 @@ impl<'a> Debug for Measure<'a> {
   }
 }
 
-#[derive(Error, Debug)]
-pub enum MeasureError {
-  #[error("bad const")]
-  BadConst,
-}
-
 #[derive(Copy, Clone, Debug)]
 pub enum MeasureVariant<'a> {
   Unbound,
   Const(i32, i32),
+  Named(&'a str),
   Add(Measure<'a>, Measure<'a>),
   Sub(Measure<'a>, Measure<'a>),
   Mul(Measure<'a>, Measure<'a>),
@@ -45,45 +42,21 @@ pub enum MeasureVariant<'a> {
   Select(Prop<'a>, Measure<'a>, Measure<'a>),
 }
 
-struct UnsafelyAssumeThreadSafe<T>(T);
-unsafe impl<T> Send for UnsafelyAssumeThreadSafe<T> {}
-unsafe impl<T> Sync for UnsafelyAssumeThreadSafe<T> {}
-
-static SMALL_MEASURE_CONSTS: UnsafelyAssumeThreadSafe<[MeasureVariant<'static>; 16]> =
-  UnsafelyAssumeThreadSafe([
-    MeasureVariant::Const(0, 1),
-    MeasureVariant::Const(1, 1),
-    MeasureVariant::Const(2, 1),
-    MeasureVariant::Const(3, 1),
-    MeasureVariant::Const(4, 1),
-    MeasureVariant::Const(5, 1),
-    MeasureVariant::Const(6, 1),
-    MeasureVariant::Const(7, 1),
-    MeasureVariant::Const(8, 1),
-    MeasureVariant::Const(9, 1),
-    MeasureVariant::Const(10, 1),
-    MeasureVariant::Const(11, 1),
-    MeasureVariant::Const(12, 1),
-    MeasureVariant::Const(13, 1),
-    MeasureVariant::Const(14, 1),
-    MeasureVariant::Const(15, 1),
-  ]);
-
 #[allow(dead_code)]
 impl<'a> Measure<'a> {
   pub fn zero(ctx: &'a LayoutContext) -> Self {
     Measure {
       ctx,
-      variant: &SMALL_MEASURE_CONSTS.0[0],
+      variant: &ctx.small_consts[0],
     }
   }
 
-  pub fn new_const(ctx: &'a LayoutContext, value: f64) -> Result<Self, MeasureError> {
+  pub fn new_const(ctx: &'a LayoutContext, value: f64) -> Result<Self, LayoutError> {
     let value = ((value * 100.0) as i64) as f64 / 100.0;
 
     // Small integer pool
     if ((value as i64) as f64 - value).abs() < EPSILON {
-      let candidates = &SMALL_MEASURE_CONSTS.0;
+      let candidates = &ctx.small_consts;
       let index = value as i64;
       if index >= 0 && index < candidates.len() as i64 {
         return Ok(Measure {
@@ -93,21 +66,39 @@ impl<'a> Measure<'a> {
       }
     }
 
-    let frac = GenericFraction::<i32>::from(value);
-    let sign: i32 = if value < 0.0 { -1 } else { 1 };
+    let (num, den) = to_ratio(value)?;
+    Ok(Measure {
+      ctx,
+      variant: ctx.alloc_measure(MeasureVariant::Const(num, den)),
+    })
+  }
+
+  /// Creates the exact constant `num / den`, without the rounding applied by `new_const`.
+  pub fn new_ratio(ctx: &'a LayoutContext, num: i32, den: i32) -> Result<Self, LayoutError> {
+    if den == 0 {
+      return Err(LayoutError::BadConst {
+        value: num as f64 / den as f64,
+      });
+    }
     Ok(Measure {
       ctx,
-      variant: ctx.alloc.alloc(MeasureVariant::Const(
-        *frac.numer().ok_or_else(|| MeasureError::BadConst)? * sign,
-        *frac.denom().ok_or_else(|| MeasureError::BadConst)?,
-      )),
+      variant: ctx.alloc_measure(MeasureVariant::Const(num, den)),
     })
   }
 
+  /// A constant whose value is looked up by `name` in the definitions of the context when the
+  /// layout is solved. See `LayoutContext::define`.
+  pub fn named_const(ctx: &'a LayoutContext, name: &str) -> Self {
+    Measure {
+      ctx,
+      variant: ctx.alloc_measure(MeasureVariant::Named(ctx.alloc_str(name))),
+    }
+  }
+
   pub fn new_unbound(ctx: &'a LayoutContext) -> Self {
     Measure {
       ctx,
-      variant: ctx.alloc.alloc(MeasureVariant::Unbound),
+      variant: ctx.alloc_measure(MeasureVariant::Unbound),
     }
   }
 
@@ -118,8 +109,30 @@ impl<'a> Measure<'a> {
     }
   }
 
-  pub fn build_z3<'ctx>(self, build_ctx: &mut Z3BuildContext<'ctx>) -> Result<Real<'ctx>> {
-    let key = self.variant as *const _ as usize;
+  /// Identity of the underlying node, shared by copies of this measure.
+  pub(crate) fn addr(&self) -> usize {
+    self.variant as *const _ as usize
+  }
+
+  /// Evaluates the measure if it only depends on constants.
+  pub fn const_value(&self) -> Option<f64> {
+    use MeasureVariant as V;
+    match *self.variant {
+      V::Unbound | V::Named(_) | V::Select(..) => None,
+      V::Const(num, den) => Some(num as f64 / den as f64),
+      V::Add(left, right) => Some(left.const_value()? + right.const_value()?),
+      V::Sub(left, right) => Some(left.const_value()? - right.const_value()?),
+      V::Mul(left, right) => Some(left.const_value()? * right.const_value()?),
+      V::Div(left, right) => Some(left.const_value()? / right.const_value()?),
+    }
+  }
+
+  #[cfg(feature = "z3")]
+  pub fn build_z3<'ctx>(
+    self,
+    build_ctx: &mut Z3BuildContext<'ctx>,
+  ) -> Result<Real<'ctx>, LayoutError> {
+    let key = self.addr();
     if let Some(x) = build_ctx.measure_cache.get(&key) {
       return Ok(x.clone());
     }
@@ -128,12 +141,17 @@ impl<'a> Measure<'a> {
     Ok(res)
   }
 
-  fn do_build_z3<'ctx>(self, build_ctx: &mut Z3BuildContext<'ctx>) -> Result<Real<'ctx>> {
+  #[cfg(feature = "z3")]
+  fn do_build_z3<'ctx>(
+    self,
+    build_ctx: &mut Z3BuildContext<'ctx>,
+  ) -> Result<Real<'ctx>, LayoutError> {
     use MeasureVariant as V;
     let z3_ctx = build_ctx.z3_ctx;
     Ok(match *self.variant {
       V::Unbound => Real::fresh_const(z3_ctx, "measure_"),
       V::Const(num, den) => Real::from_real(z3_ctx, num, den),
+      V::Named(name) => Real::new_const(z3_ctx, named_symbol(name)),
       V::Add(left, right) => left.build_z3(build_ctx)?.add(right.build_z3(build_ctx)?),
       V::Sub(left, right) => left.build_z3(build_ctx)?.sub(right.build_z3(build_ctx)?),
       V::Mul(left, right) => left.build_z3(build_ctx)?.mul(right.build_z3(build_ctx)?),
@@ -147,40 +165,50 @@ impl<'a> Measure<'a> {
   pub fn prop_eq(self, that: Self) -> Prop<'a> {
     Prop {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Eq(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Eq(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 
   pub fn prop_lt(self, that: Self) -> Prop<'a> {
     Prop {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Lt(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Lt(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 
   pub fn prop_le(self, that: Self) -> Prop<'a> {
     Prop {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Le(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Le(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 
   pub fn prop_gt(self, that: Self) -> Prop<'a> {
     Prop {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Gt(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Gt(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 
   pub fn prop_ge(self, that: Self) -> Prop<'a> {
     Prop {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Ge(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Ge(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 
@@ -191,21 +219,46 @@ impl<'a> Measure<'a> {
   pub fn max(self, that: Self) -> Measure<'a> {
     self.prop_gt(that).select(self, that)
   }
+
+  pub fn abs(self) -> Measure<'a> {
+    let zero = Measure::zero(self.ctx);
+    self.prop_lt(zero).select(zero - self, self)
+  }
+}
+
+/// Z3 symbol of the named constant `name`. Named constants are shared by name, unlike unbound
+/// measures.
+pub(crate) fn named_symbol(name: &str) -> String {
+  format!("named_{}", name)
+}
+
+/// Converts `value` to the `i32` ratio of a constant.
+pub(crate) fn to_ratio(value: f64) -> Result<(i32, i32), LayoutError> {
+  let frac = GenericFraction::<i32>::from(value);
+  let sign: i32 = if value < 0.0 { -1 } else { 1 };
+  Ok((
+    *frac.numer().ok_or(LayoutError::BadConst { value })? * sign,
+    *frac.denom().ok_or(LayoutError::BadConst { value })?,
+  ))
 }
 
 impl<'a> Display for Measure<'a> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self.variant {
-      MeasureVariant::Unbound => write!(f, "<{:p}>", self.variant),
-      MeasureVariant::Const(num, den) => write!(f, "{}", *num as f64 / *den as f64),
+      MeasureVariant::Unbound => match self.ctx.display_format().name_of(self.addr()) {
+        Some(name) => write!(f, "{}", name),
+        None => write!(f, "<{:p}>", self.variant),
+      },
+      MeasureVariant::Const(num, den) => self.ctx.display_format().write_const(f, *num, *den),
+      MeasureVariant::Named(name) => write!(f, "{}", name),
       MeasureVariant::Add(l, r)
-        if r.variant as *const _ == &SMALL_MEASURE_CONSTS.0[0] as *const _ =>
+        if r.variant as *const _ == &self.ctx.small_consts[0] as *const _ =>
       {
         write!(f, "{}", l)
       }
       MeasureVariant::Add(l, r) => write!(f, "({} + {})", l, r),
       MeasureVariant::Sub(l, r)
-        if r.variant as *const _ == &SMALL_MEASURE_CONSTS.0[0] as *const _ =>
+        if r.variant as *const _ == &self.ctx.small_consts[0] as *const _ =>
       {
         write!(f, "{}", l)
       }
@@ -237,7 +290,7 @@ impl<'a> Add for Measure<'a> {
   fn add(self, other: Self) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(MeasureVariant::Add(self, other)),
+      variant: self.ctx.alloc_measure(MeasureVariant::Add(self, other)),
     }
   }
 }
@@ -256,7 +309,7 @@ impl<'a> Sub for Measure<'a> {
   fn sub(self, other: Self) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(MeasureVariant::Sub(self, other)),
+      variant: self.ctx.alloc_measure(MeasureVariant::Sub(self, other)),
     }
   }
 }
@@ -275,7 +328,7 @@ impl<'a> Mul for Measure<'a> {
   fn mul(self, other: Self) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(MeasureVariant::Mul(self, other)),
+      variant: self.ctx.alloc_measure(MeasureVariant::Mul(self, other)),
     }
   }
 }
@@ -294,7 +347,7 @@ impl<'a> Div for Measure<'a> {
   fn div(self, other: Self) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(MeasureVariant::Div(self, other)),
+      variant: self.ctx.alloc_measure(MeasureVariant::Div(self, other)),
     }
   }
 }
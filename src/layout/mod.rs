@@ -1,7 +1,39 @@
 //! Low-level layout primitives.
 
+pub mod analysis;
+pub mod angle;
 pub mod builder;
+pub mod cache;
+pub mod cancellation;
 pub mod context;
+pub mod error;
+pub mod format;
+pub mod geometry;
+pub mod import;
+mod linear;
 pub mod measure;
+#[cfg(not(feature = "z3"))]
+mod native;
+pub mod objective;
+pub mod owned;
+#[cfg(feature = "z3")]
+mod partition;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod placement;
+pub mod progress;
 pub mod prop;
+pub mod reflect;
+pub mod render;
+#[cfg(feature = "z3")]
+pub mod session;
+mod simplex;
+pub mod smtlib;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod solution;
+pub mod tag;
+pub mod template;
+pub mod transform;
+pub mod unit;
 pub mod widget;
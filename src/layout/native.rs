@@ -0,0 +1,446 @@
+//! Solving of layouts without Z3, for builds without the `z3` feature.
+//!
+//! Covers linear constraints and objectives over selections, disjunctions and unbound
+//! propositions, by a search over the truth of conditions, with the simplex method at each step.
+//! Soft constraints do not branch the search: the ones to give up are found by relaxing each
+//! with slack variables, whose sum weighted by the weights of the constraints is minimized.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+  analysis::suggestion,
+  cancellation::CancellationToken,
+  context::LayoutContext,
+  error::LayoutError,
+  linear::{LinExpr, Linearizer, Unlinear},
+  measure::Measure,
+  objective::Objective,
+  prop::{Prop, PropVariant},
+  simplex::{self, Infeasible, Relation},
+  solution::{free_measures, Solution, TOLERANCE},
+};
+
+/// Margin by which strict inequalities hold, since the simplex method only attains non-strict
+/// ones.
+const STRICT_MARGIN: f64 = 1e-3;
+
+/// Steps after which the search gives up.
+const MAX_STEPS: usize = 4096;
+
+/// Solves the layout without Z3.
+///
+/// Like with Z3, soft constraints come before objectives, which are optimized in order.
+/// Objectives that are unbounded are left at any value. The soft constraints given up are the
+/// ones that least weighted violation cannot meet, which is usually, but not always, the least
+/// total weight that Z3 gives up.
+pub(crate) fn solve<'a>(
+  ctx: &'a LayoutContext,
+  measures: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+  cancellation: Option<&CancellationToken>,
+) -> Result<Solution, LayoutError> {
+  let mut search = Search::new(ctx, measures, constraints, objectives, cancellation);
+  search.visit()?;
+  if let Some((_, solution)) = search.best {
+    return Ok(solution);
+  }
+
+  // Drop every hard constraint the conflict does not depend on.
+  let mut core = constraints
+    .iter()
+    .copied()
+    .filter(|x| x.hard)
+    .collect::<Vec<_>>();
+  let mut i = 0;
+  while i < core.len() {
+    let mut rest = core.clone();
+    rest.remove(i);
+    let mut search = Search::new(ctx, &[], &rest, &[], cancellation);
+    search.visit()?;
+    if search.best.is_none() {
+      core = rest;
+    } else {
+      i += 1;
+    }
+  }
+  Err(LayoutError::Unsat {
+    core: core.iter().map(|x| x.to_string()).collect(),
+  })
+}
+
+/// Why a step of the search cannot go on.
+enum Halt<'a> {
+  /// The truth of the proposition has to be decided first.
+  Decide(Prop<'a>),
+
+  /// The asserted constraints conflict.
+  Conflict,
+  Fail(LayoutError),
+}
+
+impl<'a> From<Unlinear<'a>> for Halt<'a> {
+  fn from(x: Unlinear<'a>) -> Self {
+    Halt::Fail(match x {
+      Unlinear::Undecided(cond) => return Halt::Decide(cond),
+      Unlinear::Product(m) => LayoutError::Unsupported {
+        reason: with_suggestion(
+          format!("`{}` multiplies measures that are not constant", m),
+          m,
+        ),
+      },
+      Unlinear::Quotient(m) => LayoutError::Unsupported {
+        reason: with_suggestion(
+          format!(
+            "`{}` divides by a measure that is not a nonzero constant",
+            m
+          ),
+          m,
+        ),
+      },
+      Unlinear::Undefined(name) => LayoutError::Undefined {
+        name: name.to_string(),
+      },
+    })
+  }
+}
+
+/// `reason`, followed by a linear reformulation of `term` where one is known.
+fn with_suggestion(reason: String, term: Measure) -> String {
+  match suggestion(term) {
+    Some(x) => format!("{}. {}", reason, x),
+    None => reason,
+  }
+}
+
+/// Depth-first search over decisions.
+struct Search<'a, 'b> {
+  ctx: &'a LayoutContext,
+  measures: &'b [Measure<'a>],
+  constraints: &'b [Prop<'a>],
+  objectives: &'b [Objective<'a>],
+  cancellation: Option<&'b CancellationToken>,
+
+  /// Propositions decided so far with their truth, by node address.
+  decisions: HashMap<usize, (Prop<'a>, bool)>,
+  steps: usize,
+
+  /// Best solution so far, with its weight of failed soft constraints followed by its objective
+  /// values, all to be minimized.
+  best: Option<(Vec<f64>, Solution)>,
+}
+
+impl<'a, 'b> Search<'a, 'b> {
+  fn new(
+    ctx: &'a LayoutContext,
+    measures: &'b [Measure<'a>],
+    constraints: &'b [Prop<'a>],
+    objectives: &'b [Objective<'a>],
+    cancellation: Option<&'b CancellationToken>,
+  ) -> Self {
+    Search {
+      ctx,
+      measures,
+      constraints,
+      objectives,
+      cancellation,
+      decisions: HashMap::new(),
+      steps: 0,
+      best: None,
+    }
+  }
+
+  fn visit(&mut self) -> Result<(), LayoutError> {
+    if self.cancellation.iter().any(|x| x.is_cancelled()) {
+      return Err(LayoutError::Cancelled);
+    }
+    self.steps += 1;
+    if self.steps > MAX_STEPS {
+      return Err(LayoutError::Unsupported {
+        reason: "the layout has too many alternatives".into(),
+      });
+    }
+
+    match self.attempt() {
+      Ok(Some(solution)) => {
+        self.record(solution);
+        Ok(())
+      }
+      Ok(None) | Err(Halt::Conflict) => Ok(()),
+      Err(Halt::Decide(p)) => {
+        for &holds in &[true, false] {
+          self.decisions.insert(p.addr(), (p, holds));
+          self.visit()?;
+        }
+        self.decisions.remove(&p.addr());
+        Ok(())
+      }
+      Err(Halt::Fail(e)) => Err(e),
+    }
+  }
+
+  /// Optimizes the objectives under the hard constraints and the soft ones that `relax` keeps.
+  /// Returns `None` if the hard constraints conflict.
+  fn attempt(&self) -> Result<Option<Solution>, Halt<'a>> {
+    let mut lin = Linearizer::new(self.ctx);
+    lin.decisions = self
+      .decisions
+      .iter()
+      .map(|(&addr, &(_, holds))| (addr, holds))
+      .collect();
+    let mut rows = vec![];
+    let mut soft = vec![];
+    for c in self.constraints {
+      let mut relations = vec![];
+      match self.assert(&mut lin, *c, false, &mut relations) {
+        Ok(()) if c.hard => rows.extend(relations),
+        Ok(()) => soft.push((c.weight as f64, relations)),
+        // A soft constraint that cannot hold under the decisions taken is given up.
+        Err(Halt::Conflict) if !c.hard => {}
+        Err(e) => return Err(e),
+      }
+    }
+    let mut costs = Vec::with_capacity(self.objectives.len());
+    for o in self.objectives {
+      costs.push(match *o {
+        Objective::Minimize(m) => lin.measure(m)?,
+        Objective::Maximize(m) => lin.measure(m)?.scaled(-1.0),
+      });
+    }
+    for &m in self.measures {
+      lin.measure(m)?;
+    }
+
+    // Selections taken while linearizing hold only under their conditions, which may take
+    // further selections in turn.
+    let mut asserted = HashSet::new();
+    while let Some((cond, holds)) = lin.conditions.pop() {
+      if asserted.insert(cond.addr()) {
+        self.assert(&mut lin, cond, !holds, &mut rows)?;
+      }
+    }
+
+    // Measures under selections that were not taken still get a value.
+    let free = free_measures(
+      self.measures.iter().copied(),
+      self.constraints.iter().copied(),
+    );
+    for m in free {
+      lin.variable(m);
+    }
+
+    if !soft.is_empty() {
+      let kept = match relax(lin.vars.len(), &rows, &soft)? {
+        Some(x) => x,
+        None => return Ok(None),
+      };
+      for (relations, keep) in soft.into_iter().map(|x| x.1).zip(kept) {
+        if keep {
+          rows.extend(relations);
+        }
+      }
+    }
+    let values = match optimize(lin.vars.len(), &rows, &costs)? {
+      Some(x) => x,
+      None => return Ok(None),
+    };
+    let mut solution = Solution::from_values(
+      self.ctx.id,
+      lin.vars.iter().map(|(&addr, &var)| (addr, values[var])),
+      lin.named,
+    );
+    for &(p, holds) in self.decisions.values() {
+      if let PropVariant::Unbound = *p.variant {
+        solution.set_truth(p, holds);
+      }
+    }
+    Ok(Some(solution))
+  }
+
+  /// Adds the linear relations that `prop`, or its negation, asserts to `rows`.
+  fn assert(
+    &self,
+    lin: &mut Linearizer<'a>,
+    prop: Prop<'a>,
+    negated: bool,
+    rows: &mut Vec<Relation>,
+  ) -> Result<(), Halt<'a>> {
+    use PropVariant as V;
+    match (*prop.variant, negated) {
+      (V::Const(x), _) if x == negated => return Err(Halt::Conflict),
+      (V::Const(_), _) => {}
+      (V::Unbound, _) => match self.decisions.get(&prop.addr()) {
+        Some(&(_, holds)) if holds == negated => return Err(Halt::Conflict),
+        Some(_) => {}
+        None => return Err(Halt::Decide(prop)),
+      },
+      (V::Eq(l, r), false) => rows.push(Relation::Eq(difference(lin, l, r, 0.0)?)),
+      (V::Eq(..), true) => {
+        return Err(Halt::Fail(LayoutError::Unsupported {
+          reason: format!("`{}` is negated", prop),
+        }))
+      }
+      (V::Le(l, r), false) | (V::Gt(l, r), true) => {
+        rows.push(Relation::Le(difference(lin, l, r, 0.0)?))
+      }
+      (V::Ge(l, r), false) | (V::Lt(l, r), true) => {
+        rows.push(Relation::Le(difference(lin, r, l, 0.0)?))
+      }
+      (V::Lt(l, r), false) | (V::Ge(l, r), true) => {
+        rows.push(Relation::Le(difference(lin, l, r, STRICT_MARGIN)?))
+      }
+      (V::Gt(l, r), false) | (V::Le(l, r), true) => {
+        rows.push(Relation::Le(difference(lin, r, l, STRICT_MARGIN)?))
+      }
+      (V::And(l, r), false) | (V::Or(l, r), true) => {
+        self.assert(lin, l, negated, rows)?;
+        self.assert(lin, r, negated, rows)?;
+      }
+      // One side of a disjunction holds, as decided by the truth of the left one. The left one
+      // is not negated when the right one is taken, which would need `!=` for equalities, so
+      // such solutions are checked against the propositions afterwards.
+      (V::Or(l, r), false) | (V::And(l, r), true) => {
+        if self.truth(l)? != negated {
+          self.assert(lin, l, negated, rows)?;
+        } else {
+          self.assert(lin, r, negated, rows)?;
+        }
+      }
+      (V::Not(x), _) => self.assert(lin, x, !negated, rows)?,
+    }
+    Ok(())
+  }
+
+  /// The truth of `prop` as decided so far. Negations share the decision of their operand, so
+  /// that conditions restated with `Prop::when` are decided once.
+  fn truth(&self, prop: Prop<'a>) -> Result<bool, Halt<'a>> {
+    if let Some(x) = prop.const_value() {
+      return Ok(x);
+    }
+    match *prop.variant {
+      PropVariant::Not(x) => Ok(!self.truth(x)?),
+      _ => Ok(
+        self
+          .decisions
+          .get(&prop.addr())
+          .ok_or(Halt::Decide(prop))?
+          .1,
+      ),
+    }
+  }
+
+  /// Keeps `solution` if it meets the hard constraints and beats the best one so far.
+  fn record(&mut self, solution: Solution) {
+    if self
+      .constraints
+      .iter()
+      .any(|c| c.hard && solution.truth_of(*c) != Some(true))
+    {
+      return;
+    }
+    let failed = self
+      .constraints
+      .iter()
+      .filter(|c| !c.hard && solution.truth_of(**c) != Some(true))
+      .map(|c| c.weight as f64)
+      .sum::<f64>();
+    let mut score = vec![failed];
+    for o in self.objectives {
+      score.push(match *o {
+        Objective::Minimize(m) => solution.value_of(m).unwrap_or(f64::NAN),
+        Objective::Maximize(m) => -solution.value_of(m).unwrap_or(f64::NAN),
+      });
+    }
+    let better = match &self.best {
+      Some((best, _)) => matches!(
+        score
+          .iter()
+          .zip(best)
+          .find(|&(x, y)| (x - y).abs() > TOLERANCE * y.abs().max(1.0)),
+        Some((x, y)) if x < y
+      ),
+      None => true,
+    };
+    if better {
+      self.best = Some((score, solution));
+    }
+  }
+}
+
+/// `l - r + margin`.
+fn difference<'a>(
+  lin: &mut Linearizer<'a>,
+  l: Measure<'a>,
+  r: Measure<'a>,
+  margin: f64,
+) -> Result<LinExpr, Unlinear<'a>> {
+  let mut x = lin.measure(l)?;
+  x.add_scaled(&lin.measure(r)?, -1.0);
+  x.constant += margin;
+  Ok(x)
+}
+
+/// Which of the `soft` constraints, each a weight and the relations it asserts, to keep with the
+/// `rows` of the hard ones over `vars` variables, or `None` if the hard ones conflict.
+///
+/// Each relation of a soft constraint holds up to non-negative slack, and the sum of the slack
+/// weighted by the weights of the constraints is minimized. The constraints left without slack
+/// are kept, which usually gives up the least total weight, like Z3 does.
+fn relax<'a>(
+  vars: usize,
+  rows: &[Relation],
+  soft: &[(f64, Vec<Relation>)],
+) -> Result<Option<Vec<bool>>, Halt<'a>> {
+  let mut rows = rows.to_vec();
+  let mut violation = LinExpr::default();
+  let mut slacks = Vec::with_capacity(soft.len());
+  let mut next = vars;
+  for (weight, relations) in soft {
+    let first = next;
+    for relation in relations {
+      let (mut expr, signs, equal): (_, &[f64], _) = match relation.clone() {
+        Relation::Eq(x) => (x, &[-1.0, 1.0], true),
+        Relation::Le(x) => (x, &[-1.0], false),
+      };
+      for &sign in signs {
+        expr.terms.insert(next, sign);
+        violation.terms.insert(next, *weight);
+        let mut nonnegative = LinExpr::default();
+        nonnegative.terms.insert(next, -1.0);
+        rows.push(Relation::Le(nonnegative));
+        next += 1;
+      }
+      rows.push(if equal {
+        Relation::Eq(expr)
+      } else {
+        Relation::Le(expr)
+      });
+    }
+    slacks.push(first..next);
+  }
+  let values = match optimize(next, &rows, &[violation])? {
+    Some(x) => x,
+    None => return Ok(None),
+  };
+  Ok(Some(
+    slacks
+      .into_iter()
+      .map(|range| values[range].iter().all(|&x| x <= TOLERANCE))
+      .collect(),
+  ))
+}
+
+/// `simplex::optimize`, with `None` for relations that conflict.
+fn optimize<'a>(
+  vars: usize,
+  rows: &[Relation],
+  costs: &[LinExpr],
+) -> Result<Option<Vec<f64>>, Halt<'a>> {
+  match simplex::optimize(vars, rows, costs) {
+    Ok((values, _)) => Ok(Some(values)),
+    Err(Infeasible::Conflict) => Ok(None),
+    Err(Infeasible::Stalled) => Err(Halt::Fail(LayoutError::Unsupported {
+      reason: "the simplex method did not converge".into(),
+    })),
+  }
+}
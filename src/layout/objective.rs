@@ -0,0 +1,19 @@
+use std::fmt::Display;
+
+use super::measure::Measure;
+
+/// A quantity to optimize once as many soft constraints as possible are satisfied.
+#[derive(Copy, Clone, Debug)]
+pub enum Objective<'a> {
+  Minimize(Measure<'a>),
+  Maximize(Measure<'a>),
+}
+
+impl<'a> Display for Objective<'a> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Objective::Minimize(x) => write!(f, "minimize {}", x),
+      Objective::Maximize(x) => write!(f, "maximize {}", x),
+    }
+  }
+}
@@ -0,0 +1,518 @@
+//! A layout API without the lifetime of `LayoutContext`.
+//!
+//! Measures and propositions are `Copy` handles into an `OwnedLayout`, which owns their
+//! definitions. Handles can be stored in long-lived structs, and the layout can be sent across
+//! threads and `await` points. Solving lowers the layout into a fresh `LayoutContext`, so no
+//! arena outlives a solve. Apps that solve every frame can reuse one with `solve_in`.
+//!
+//! Handles are only meaningful for the layout that made them. Handles of another layout may
+//! panic or refer to other measures.
+
+use std::collections::HashMap;
+
+use super::{
+  builder::{LayoutBuilder, SolvePath, SolverConfig},
+  cancellation::CancellationToken,
+  context::LayoutContext,
+  error::LayoutError,
+  measure::{to_ratio, Measure, MeasureVariant},
+  objective::Objective,
+  prop::{Prop, PropVariant},
+};
+
+/// A measure of an `OwnedLayout`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeasureId(u32);
+
+/// A proposition of an `OwnedLayout`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PropId(u32);
+
+#[derive(Clone, Debug)]
+enum MeasureNode {
+  Unbound,
+  Const(f64),
+  Named(String),
+  Add(MeasureId, MeasureId),
+  Sub(MeasureId, MeasureId),
+  Mul(MeasureId, MeasureId),
+  Div(MeasureId, MeasureId),
+  Select(PropId, MeasureId, MeasureId),
+}
+
+#[derive(Clone, Debug)]
+enum PropNode {
+  Unbound,
+  Const(bool),
+  Eq(MeasureId, MeasureId),
+  Lt(MeasureId, MeasureId),
+  Le(MeasureId, MeasureId),
+  Gt(MeasureId, MeasureId),
+  Ge(MeasureId, MeasureId),
+  Or(PropId, PropId),
+  And(PropId, PropId),
+  Not(PropId),
+}
+
+/// How a constraint of an `OwnedLayout` is enforced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Strength {
+  Hard,
+  Soft(u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OwnedObjective {
+  Minimize(MeasureId),
+  Maximize(MeasureId),
+}
+
+/// A layout of measures and propositions by handle. Unlike `LayoutBuilder`, it holds no
+/// widgets: painting is left to the application, with the values of its `OwnedSolution`.
+#[derive(Clone, Debug, Default)]
+pub struct OwnedLayout {
+  measures: Vec<MeasureNode>,
+  props: Vec<PropNode>,
+  constraints: Vec<(PropId, Strength)>,
+  objectives: Vec<OwnedObjective>,
+  definitions: HashMap<String, f64>,
+
+  /// Labels of constraints raised from a `LayoutContext`, by their position in `constraints`.
+  labels: HashMap<usize, String>,
+}
+
+/// Solved values of an `OwnedLayout`, by handle.
+#[derive(Clone, Debug)]
+pub struct OwnedSolution {
+  values: Vec<Option<f64>>,
+  truths: Vec<Option<bool>>,
+}
+
+#[allow(dead_code)]
+impl OwnedLayout {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn measure(&mut self, node: MeasureNode) -> MeasureId {
+    self.measures.push(node);
+    MeasureId(self.measures.len() as u32 - 1)
+  }
+
+  fn prop(&mut self, node: PropNode) -> PropId {
+    self.props.push(node);
+    PropId(self.props.len() as u32 - 1)
+  }
+
+  pub fn new_unbound(&mut self) -> MeasureId {
+    self.measure(MeasureNode::Unbound)
+  }
+
+  /// A constant, rounded like `Measure::new_const`.
+  pub fn new_const(&mut self, value: f64) -> Result<MeasureId, LayoutError> {
+    to_ratio(value)?;
+    Ok(self.measure(MeasureNode::Const(value)))
+  }
+
+  /// See `Measure::named_const`. Values are set with `define`.
+  pub fn named_const(&mut self, name: &str) -> MeasureId {
+    self.measure(MeasureNode::Named(name.to_string()))
+  }
+
+  pub fn define(&mut self, name: &str, value: f64) {
+    self.definitions.insert(name.to_string(), value);
+  }
+
+  pub fn add(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    self.measure(MeasureNode::Add(l, r))
+  }
+
+  pub fn sub(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    self.measure(MeasureNode::Sub(l, r))
+  }
+
+  pub fn mul(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    self.measure(MeasureNode::Mul(l, r))
+  }
+
+  pub fn div(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    self.measure(MeasureNode::Div(l, r))
+  }
+
+  /// `l` where `cond` holds, `r` elsewhere. See `Prop::select`.
+  pub fn select(&mut self, cond: PropId, l: MeasureId, r: MeasureId) -> MeasureId {
+    self.measure(MeasureNode::Select(cond, l, r))
+  }
+
+  pub fn min(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    let cond = self.prop_lt(l, r);
+    self.select(cond, l, r)
+  }
+
+  pub fn max(&mut self, l: MeasureId, r: MeasureId) -> MeasureId {
+    let cond = self.prop_gt(l, r);
+    self.select(cond, l, r)
+  }
+
+  pub fn new_unbound_prop(&mut self) -> PropId {
+    self.prop(PropNode::Unbound)
+  }
+
+  pub fn new_const_prop(&mut self, value: bool) -> PropId {
+    self.prop(PropNode::Const(value))
+  }
+
+  pub fn prop_eq(&mut self, l: MeasureId, r: MeasureId) -> PropId {
+    self.prop(PropNode::Eq(l, r))
+  }
+
+  pub fn prop_lt(&mut self, l: MeasureId, r: MeasureId) -> PropId {
+    self.prop(PropNode::Lt(l, r))
+  }
+
+  pub fn prop_le(&mut self, l: MeasureId, r: MeasureId) -> PropId {
+    self.prop(PropNode::Le(l, r))
+  }
+
+  pub fn prop_gt(&mut self, l: MeasureId, r: MeasureId) -> PropId {
+    self.prop(PropNode::Gt(l, r))
+  }
+
+  pub fn prop_ge(&mut self, l: MeasureId, r: MeasureId) -> PropId {
+    self.prop(PropNode::Ge(l, r))
+  }
+
+  pub fn or(&mut self, l: PropId, r: PropId) -> PropId {
+    self.prop(PropNode::Or(l, r))
+  }
+
+  pub fn and(&mut self, l: PropId, r: PropId) -> PropId {
+    self.prop(PropNode::And(l, r))
+  }
+
+  pub fn not(&mut self, x: PropId) -> PropId {
+    self.prop(PropNode::Not(x))
+  }
+
+  /// A soft constraint with the default weight of `Prop`.
+  pub fn push_constraint(&mut self, prop: PropId) {
+    self.push_weighted_constraint(prop, 10);
+  }
+
+  pub fn push_weighted_constraint(&mut self, prop: PropId, weight: u32) {
+    self.constraints.push((prop, Strength::Soft(weight)));
+  }
+
+  pub fn push_hard_constraint(&mut self, prop: PropId) {
+    self.constraints.push((prop, Strength::Hard));
+  }
+
+  pub fn push_objective(&mut self, objective: OwnedObjective) {
+    self.objectives.push(objective);
+  }
+
+  /// Solves the layout with the solver of `LayoutBuilder::solve`.
+  pub fn solve(&self) -> Result<OwnedSolution, LayoutError> {
+    self.solve_in(&mut LayoutContext::new())
+  }
+
+  /// Like `solve`, lowering into `ctx` after resetting it, so its arena is reused.
+  pub fn solve_in(&self, ctx: &mut LayoutContext) -> Result<OwnedSolution, LayoutError> {
+    Ok(self.dry_run_in(ctx, |x| x)?.0)
+  }
+
+  /// Like `solve`, with `config` and `cancellation` for the solver. Also returns how the layout
+  /// was solved and the positions of the constraints that `SolverConfig::relax_on_unsat` gave
+  /// up.
+  pub(crate) fn solve_report(
+    &self,
+    config: SolverConfig,
+    cancellation: Option<CancellationToken>,
+  ) -> Result<(OwnedSolution, SolvePath, Vec<usize>), LayoutError> {
+    self.dry_run_in(&mut LayoutContext::new(), |builder| {
+      let builder = builder.with_solver_config(config);
+      match cancellation {
+        Some(token) => builder.with_cancellation(token),
+        None => builder,
+      }
+    })
+  }
+
+  fn dry_run_in<'a>(
+    &self,
+    ctx: &'a mut LayoutContext,
+    configure: impl FnOnce(LayoutBuilder<'a>) -> LayoutBuilder<'a>,
+  ) -> Result<(OwnedSolution, SolvePath, Vec<usize>), LayoutError> {
+    ctx.reset();
+    let ctx = &*ctx;
+    for (name, &value) in &self.definitions {
+      ctx.define(name, value);
+    }
+    let mut lowering = Lowering {
+      layout: self,
+      ctx,
+      measures: vec![None; self.measures.len()],
+      props: vec![None; self.props.len()],
+    };
+
+    let mut builder = configure(LayoutBuilder::new(ctx));
+    let mut pushed = vec![];
+    for (i, &(prop, strength)) in self.constraints.iter().enumerate() {
+      let mut prop = lowering.prop(prop)?;
+      if let Some(label) = self.labels.get(&i) {
+        prop = prop.with_label(label);
+      }
+      pushed.push(prop.addr());
+      builder.push_constraint(match strength {
+        Strength::Hard => prop.hard(),
+        Strength::Soft(weight) => prop.with_weight(weight),
+      });
+    }
+    for &objective in &self.objectives {
+      builder.push_objective(match objective {
+        OwnedObjective::Minimize(m) => Objective::Minimize(lowering.measure(m)?),
+        OwnedObjective::Maximize(m) => Objective::Maximize(lowering.measure(m)?),
+      });
+    }
+    let report = builder.dry_run()?;
+    let solution = report.solution;
+    let dropped = report
+      .dropped_constraints
+      .iter()
+      .filter_map(|c| pushed.iter().position(|&x| x == c.addr()))
+      .collect();
+
+    let values = (0..self.measures.len() as u32)
+      .map(|i| Ok(solution.value_of(lowering.measure(MeasureId(i))?)))
+      .collect::<Result<_, LayoutError>>()?;
+    let truths = (0..self.props.len() as u32)
+      .map(|i| Ok(solution.truth_of(lowering.prop(PropId(i))?)))
+      .collect::<Result<_, LayoutError>>()?;
+    Ok((OwnedSolution { values, truths }, report.stats.path, dropped))
+  }
+
+  /// Copies `constraints` and `objectives` of a `LayoutContext` into a new layout, with the
+  /// definitions of their named constants. Unbound measures of `measures` that they do not
+  /// mention are copied too.
+  pub(crate) fn raise<'a>(
+    ctx: &'a LayoutContext,
+    measures: &[Measure<'a>],
+    constraints: &[Prop<'a>],
+    objectives: &[Objective<'a>],
+  ) -> Result<Raised<'a>, LayoutError> {
+    let mut raising = Raising {
+      ctx,
+      raised: Raised {
+        layout: OwnedLayout::new(),
+        measures: vec![],
+        props: vec![],
+      },
+      measures: HashMap::new(),
+      props: HashMap::new(),
+    };
+    for &c in constraints {
+      let prop = raising.prop(c)?;
+      let layout = &mut raising.raised.layout;
+      if let Some(label) = c.label() {
+        layout
+          .labels
+          .insert(layout.constraints.len(), label.to_string());
+      }
+      if c.is_hard() {
+        layout.push_hard_constraint(prop);
+      } else {
+        layout.push_weighted_constraint(prop, c.weight);
+      }
+    }
+    for &objective in objectives {
+      let objective = match objective {
+        Objective::Minimize(m) => OwnedObjective::Minimize(raising.measure(m)?),
+        Objective::Maximize(m) => OwnedObjective::Maximize(raising.measure(m)?),
+      };
+      raising.raised.layout.push_objective(objective);
+    }
+    for &m in measures {
+      raising.measure(m)?;
+    }
+    Ok(raising.raised)
+  }
+}
+
+/// An `OwnedLayout` raised from a `LayoutContext`, with the handles of its unbound measures and
+/// propositions.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+pub(crate) struct Raised<'a> {
+  pub layout: OwnedLayout,
+  pub measures: Vec<(Measure<'a>, MeasureId)>,
+  pub props: Vec<(Prop<'a>, PropId)>,
+}
+
+/// Measures and propositions of a `LayoutContext` in an `OwnedLayout`, raised on first use.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+struct Raising<'a> {
+  ctx: &'a LayoutContext,
+  raised: Raised<'a>,
+  measures: HashMap<usize, MeasureId>,
+  props: HashMap<usize, PropId>,
+}
+
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+impl<'a> Raising<'a> {
+  fn measure(&mut self, m: Measure<'a>) -> Result<MeasureId, LayoutError> {
+    if let Some(&id) = self.measures.get(&m.addr()) {
+      return Ok(id);
+    }
+    use MeasureVariant as V;
+    let id = match *m.variant {
+      V::Unbound => {
+        let id = self.raised.layout.new_unbound();
+        self.raised.measures.push((m, id));
+        id
+      }
+      V::Const(num, den) => self.raised.layout.new_const(num as f64 / den as f64)?,
+      V::Named(name) => {
+        let value = self
+          .ctx
+          .definition(name)
+          .ok_or_else(|| LayoutError::Undefined {
+            name: name.to_string(),
+          })?;
+        self.raised.layout.define(name, value);
+        self.raised.layout.named_const(name)
+      }
+      V::Add(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.add(l, r)
+      }
+      V::Sub(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.sub(l, r)
+      }
+      V::Mul(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.mul(l, r)
+      }
+      V::Div(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.div(l, r)
+      }
+      V::Select(cond, l, r) => {
+        let (cond, l, r) = (self.prop(cond)?, self.measure(l)?, self.measure(r)?);
+        self.raised.layout.select(cond, l, r)
+      }
+    };
+    self.measures.insert(m.addr(), id);
+    Ok(id)
+  }
+
+  fn prop(&mut self, p: Prop<'a>) -> Result<PropId, LayoutError> {
+    if let Some(&id) = self.props.get(&p.addr()) {
+      return Ok(id);
+    }
+    use PropVariant as V;
+    let id = match *p.variant {
+      V::Unbound => {
+        let id = self.raised.layout.new_unbound_prop();
+        self.raised.props.push((p, id));
+        id
+      }
+      V::Const(value) => self.raised.layout.new_const_prop(value),
+      V::Eq(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.prop_eq(l, r)
+      }
+      V::Lt(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.prop_lt(l, r)
+      }
+      V::Le(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.prop_le(l, r)
+      }
+      V::Gt(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.prop_gt(l, r)
+      }
+      V::Ge(l, r) => {
+        let (l, r) = (self.measure(l)?, self.measure(r)?);
+        self.raised.layout.prop_ge(l, r)
+      }
+      V::Or(l, r) => {
+        let (l, r) = (self.prop(l)?, self.prop(r)?);
+        self.raised.layout.or(l, r)
+      }
+      V::And(l, r) => {
+        let (l, r) = (self.prop(l)?, self.prop(r)?);
+        self.raised.layout.and(l, r)
+      }
+      V::Not(x) => {
+        let x = self.prop(x)?;
+        self.raised.layout.not(x)
+      }
+    };
+    self.props.insert(p.addr(), id);
+    Ok(id)
+  }
+}
+
+impl OwnedSolution {
+  /// The solved value of `measure`, if the layout depends on it.
+  pub fn value_of(&self, measure: MeasureId) -> Option<f64> {
+    self.values[measure.0 as usize]
+  }
+
+  /// Whether `prop` holds in the solution, if the layout decides it.
+  pub fn truth_of(&self, prop: PropId) -> Option<bool> {
+    self.truths[prop.0 as usize]
+  }
+}
+
+/// Measures and propositions of an `OwnedLayout` in a `LayoutContext`, lowered on first use.
+struct Lowering<'l, 'a> {
+  layout: &'l OwnedLayout,
+  ctx: &'a LayoutContext,
+  measures: Vec<Option<Measure<'a>>>,
+  props: Vec<Option<Prop<'a>>>,
+}
+
+impl<'l, 'a> Lowering<'l, 'a> {
+  fn measure(&mut self, id: MeasureId) -> Result<Measure<'a>, LayoutError> {
+    if let Some(m) = self.measures[id.0 as usize] {
+      return Ok(m);
+    }
+    use MeasureNode as N;
+    let m = match self.layout.measures[id.0 as usize] {
+      N::Unbound => Measure::new_unbound(self.ctx),
+      N::Const(value) => Measure::new_const(self.ctx, value)?,
+      N::Named(ref name) => Measure::named_const(self.ctx, name),
+      N::Add(l, r) => self.measure(l)? + self.measure(r)?,
+      N::Sub(l, r) => self.measure(l)? - self.measure(r)?,
+      N::Mul(l, r) => self.measure(l)? * self.measure(r)?,
+      N::Div(l, r) => self.measure(l)? / self.measure(r)?,
+      N::Select(cond, l, r) => self.prop(cond)?.select(self.measure(l)?, self.measure(r)?),
+    };
+    self.measures[id.0 as usize] = Some(m);
+    Ok(m)
+  }
+
+  fn prop(&mut self, id: PropId) -> Result<Prop<'a>, LayoutError> {
+    if let Some(p) = self.props[id.0 as usize] {
+      return Ok(p);
+    }
+    use PropNode as N;
+    let p = match self.layout.props[id.0 as usize] {
+      N::Unbound => Prop::new_unbound(self.ctx),
+      N::Const(value) => Prop::new_const(self.ctx, value),
+      N::Eq(l, r) => self.measure(l)?.prop_eq(self.measure(r)?),
+      N::Lt(l, r) => self.measure(l)?.prop_lt(self.measure(r)?),
+      N::Le(l, r) => self.measure(l)?.prop_le(self.measure(r)?),
+      N::Gt(l, r) => self.measure(l)?.prop_gt(self.measure(r)?),
+      N::Ge(l, r) => self.measure(l)?.prop_ge(self.measure(r)?),
+      N::Or(l, r) => self.prop(l)? | self.prop(r)?,
+      N::And(l, r) => self.prop(l)? & self.prop(r)?,
+      N::Not(x) => !self.prop(x)?,
+    };
+    self.props[id.0 as usize] = Some(p);
+    Ok(p)
+  }
+}
@@ -0,0 +1,67 @@
+//! Splitting of layouts into parts that share no variables and can be solved separately.
+
+use std::collections::HashMap;
+
+use super::{objective::Objective, prop::Prop, solution::free_variables};
+
+/// Constraints and objectives that depend on each other through shared variables.
+#[derive(Default)]
+pub(crate) struct Component<'a> {
+  pub constraints: Vec<Prop<'a>>,
+  pub objectives: Vec<Objective<'a>>,
+}
+
+/// Groups `constraints` and `objectives` into components, keeping their order within each.
+///
+/// Constraints and objectives without variables are left out, since they do not affect the
+/// solution.
+pub(crate) fn components<'a>(
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+) -> Vec<Component<'a>> {
+  let n = constraints.len() + objectives.len();
+  let mut parent = (0..n).collect::<Vec<_>>();
+  let mut owner = HashMap::new();
+  let mut has_variables = vec![false; n];
+  for i in 0..n {
+    let (measures, props) = if i < constraints.len() {
+      (vec![], vec![constraints[i]])
+    } else {
+      match objectives[i - constraints.len()] {
+        Objective::Minimize(m) | Objective::Maximize(m) => (vec![m], vec![]),
+      }
+    };
+    for var in free_variables(measures, props) {
+      has_variables[i] = true;
+      let j = *owner.entry(var).or_insert(i);
+      let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+      parent[a.max(b)] = a.min(b);
+    }
+  }
+
+  let mut components: Vec<Component<'a>> = vec![];
+  let mut index_of_root = HashMap::new();
+  for i in (0..n).filter(|&i| has_variables[i]) {
+    let root = find(&mut parent, i);
+    let k = *index_of_root.entry(root).or_insert_with(|| {
+      components.push(Component::default());
+      components.len() - 1
+    });
+    if i < constraints.len() {
+      components[k].constraints.push(constraints[i]);
+    } else {
+      components[k]
+        .objectives
+        .push(objectives[i - constraints.len()]);
+    }
+  }
+  components
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+  while parent[i] != i {
+    parent[i] = parent[parent[i]];
+    i = parent[i];
+  }
+  i
+}
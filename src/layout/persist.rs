@@ -0,0 +1,127 @@
+//! Saving solved layouts to disk and loading them back, to skip solving unchanged documents.
+//!
+//! Unbound measures and propositions are stored under the id of the first widget that depends
+//! on them, by their position among its leaves, or under the builder when only its own
+//! constraints and objectives do. Named constants are not stored, as they take their values in
+//! the layout context.
+//!
+//! Files also hold a fingerprint of the structure of the layout, like the keys of `cache` but
+//! the same in every build of the crate, to tell when the layout changed.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+  builder::LayoutBuilder,
+  measure::Measure,
+  prop::Prop,
+  solution::{leaves_by_group, named_consts, Solution},
+};
+
+#[derive(Error, Debug)]
+pub enum PersistError {
+  #[error("failed to access solution file: {0}")]
+  Io(#[from] io::Error),
+  #[error("invalid solution file: {0}")]
+  Parse(#[from] serde_json::Error),
+
+  /// The layout of the builder is not the one the file was saved from.
+  #[error("solution file does not match the layout")]
+  Mismatch,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedSolution {
+  fingerprint: u64,
+  widgets: BTreeMap<u64, SavedLeaves>,
+  builder: SavedLeaves,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SavedLeaves {
+  measures: Vec<Option<f64>>,
+  props: Vec<Option<bool>>,
+}
+
+impl Solution {
+  /// Writes the solved values of the layout of `builder` to `path` as JSON.
+  pub fn save(&self, path: impl AsRef<Path>, builder: &LayoutBuilder) -> Result<(), PersistError> {
+    let (groups, _) = layout(builder);
+    let mut saved = SavedSolution {
+      fingerprint: builder.fingerprint(),
+      ..SavedSolution::default()
+    };
+    for (id, (measures, props)) in groups {
+      let leaves = SavedLeaves {
+        measures: measures.iter().map(|&m| self.value_of(m)).collect(),
+        props: props.iter().map(|&p| self.truth_of(p)).collect(),
+      };
+      match id {
+        Some(id) => {
+          saved.widgets.insert(id, leaves);
+        }
+        None => saved.builder = leaves,
+      }
+    }
+    fs::write(path, serde_json::to_string(&saved)?)?;
+    Ok(())
+  }
+
+  /// Reads a solution of the layout of `builder` from `path`, as written by `save`. Fails with
+  /// `PersistError::Mismatch` if the widgets, constraints or values of named constants changed
+  /// since, so that the layout is solved again.
+  pub fn load(path: impl AsRef<Path>, builder: &LayoutBuilder) -> Result<Solution, PersistError> {
+    let saved: SavedSolution = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let (groups, named) = layout(builder);
+    if saved.fingerprint != builder.fingerprint() || saved.widgets.len() + 1 != groups.len() {
+      return Err(PersistError::Mismatch);
+    }
+    let mut values = vec![];
+    let mut truths = vec![];
+    for (id, (measures, props)) in groups {
+      let leaves = match id {
+        Some(id) => saved.widgets.get(&id).ok_or(PersistError::Mismatch)?,
+        None => &saved.builder,
+      };
+      if leaves.measures.len() != measures.len() || leaves.props.len() != props.len() {
+        return Err(PersistError::Mismatch);
+      }
+      for (m, &value) in measures.into_iter().zip(&leaves.measures) {
+        values.extend(value.map(|x| (m.addr(), x)));
+      }
+      truths.extend(props.into_iter().zip(leaves.props.iter().copied()));
+    }
+    let named = named
+      .into_iter()
+      .filter_map(|name| Some((name.to_string(), builder.ctx().definition(name)?)))
+      .collect();
+    let mut solution = Solution::from_values(builder.ctx().id, values, named);
+    for (p, holds) in truths {
+      if let Some(holds) = holds {
+        solution.set_truth(p, holds);
+      }
+    }
+    Ok(solution)
+  }
+}
+
+/// Unbound measures and propositions of a widget or the builder.
+type Leaves<'a> = (Vec<Measure<'a>>, Vec<Prop<'a>>);
+
+/// Leaves of the layout of `builder` by widget id, with `None` for the builder, and the names of
+/// its named constants.
+fn layout<'a>(builder: &LayoutBuilder<'a>) -> (Vec<(Option<u64>, Leaves<'a>)>, Vec<&'a str>) {
+  let groups = builder.groups();
+  let named = named_consts(
+    groups.iter().flat_map(|(_, m, _)| m.iter().copied()),
+    groups.iter().flat_map(|(_, _, p)| p.iter().copied()),
+  );
+  let ids = groups
+    .iter()
+    .map(|(id, _, _)| id.map(|x| x.0))
+    .collect::<Vec<_>>();
+  let leaves = leaves_by_group(groups.into_iter().map(|(_, m, p)| (m, p)));
+  (ids.into_iter().zip(leaves).collect(), named)
+}
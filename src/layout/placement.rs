@@ -0,0 +1,182 @@
+//! Placement strategies for floating rectangles.
+
+use super::{
+  geometry::{Point, RectMeasures, Size},
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+};
+
+/// Scale applied to distances when they are turned into candidate weights.
+const PIP_WEIGHT_SCALE: f64 = 100.0;
+
+/// Snap anchors as fractions of the free space along each axis: the four corners first, then
+/// the four edge midpoints.
+const PIP_ANCHORS: [(f64, f64); 8] = [
+  (0.0, 0.0),
+  (1.0, 0.0),
+  (0.0, 1.0),
+  (1.0, 1.0),
+  (0.5, 0.0),
+  (0.0, 0.5),
+  (1.0, 0.5),
+  (0.5, 1.0),
+];
+
+/// A floating rectangle snapped to a corner or an edge of its container.
+pub struct Pip<'a> {
+  pub rect: RectMeasures<'a>,
+  candidates: Vec<(Prop<'a>, u32)>,
+  obstacles: Vec<RectMeasures<'a>>,
+  objective: Option<Objective<'a>>,
+}
+
+/// Places a rectangle of `size` in a corner or at an edge midpoint of `container`, preferring
+/// the candidate closest to `hint`, as a floating video window does after being dragged.
+///
+/// Candidates are weighted by their distance to `hint` when both `hint` and `container` are
+/// constant. Otherwise they are weighted alike, and `Pip::objective` minimizes the distance from
+/// the center of the rectangle to `hint`.
+pub fn pip<'a>(container: RectMeasures<'a>, size: Size<'a>, hint: Point<'a>) -> Pip<'a> {
+  let ctx = container.left.ctx;
+  let origin = Point {
+    x: Measure::new_unbound(ctx),
+    y: Measure::new_unbound(ctx),
+  };
+  let rect = RectMeasures::from_origin_and_size(origin, size);
+
+  let free_x = container.width() - size.width;
+  let free_y = container.height() - size.height;
+  let positions = PIP_ANCHORS
+    .iter()
+    .map(|&(fx, fy)| (container.left + free_x * fx, container.top + free_y * fy))
+    .collect::<Vec<_>>();
+
+  let distances = positions
+    .iter()
+    .map(|&(x, y)| {
+      let cx = (x + size.width / 2.0).const_value()?;
+      let cy = (y + size.height / 2.0).const_value()?;
+      let dx = cx - hint.x.const_value()?;
+      let dy = cy - hint.y.const_value()?;
+      Some((dx * dx + dy * dy).sqrt())
+    })
+    .collect::<Option<Vec<_>>>();
+  let (weights, objective) = match distances {
+    Some(distances) => {
+      let farthest = distances.iter().copied().fold(0.0, f64::max);
+      let weights = distances
+        .iter()
+        .map(|d| ((farthest - d) * PIP_WEIGHT_SCALE) as u32 + 1)
+        .collect();
+      (weights, None)
+    }
+    None => {
+      let center = rect.center();
+      let distance = (center.x - hint.x).abs() + (center.y - hint.y).abs();
+      (
+        vec![1; PIP_ANCHORS.len()],
+        Some(Objective::Minimize(distance)),
+      )
+    }
+  };
+
+  let candidates = positions
+    .into_iter()
+    .zip(weights)
+    .map(|((x, y), weight)| (origin.x.prop_eq(x) & origin.y.prop_eq(y), weight))
+    .collect();
+  Pip {
+    rect,
+    candidates,
+    obstacles: vec![],
+    objective,
+  }
+}
+
+#[allow(dead_code)]
+impl<'a> Pip<'a> {
+  /// Keeps the floating rectangle clear of `obstacle`. Touching edges are allowed.
+  pub fn avoid(&mut self, obstacle: RectMeasures<'a>) {
+    self.obstacles.push(obstacle);
+  }
+
+  /// Holds when the rectangle sits at one of the candidate positions.
+  pub fn snapped(&self) -> Prop<'a> {
+    self
+      .candidates
+      .iter()
+      .map(|&(candidate, _)| candidate)
+      .reduce(|a, b| a | b)
+      .unwrap()
+  }
+
+  /// Constraints to push into the builder. The layout is unsatisfiable when obstacles block
+  /// every candidate.
+  pub fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = vec![self.snapped().hard()];
+    for obstacle in &self.obstacles {
+      constraints.push(self.rect.disjoint(obstacle).hard());
+    }
+    for &(candidate, weight) in &self.candidates {
+      constraints.push(candidate.with_weight(weight));
+    }
+    constraints
+  }
+
+  /// Minimizes the distance to the hint, when the candidates could not be weighted by it.
+  pub fn objective(&self) -> Option<Objective<'a>> {
+    self.objective
+  }
+}
+
+/// An element that stays fully on screen and close to where it was in the previous layout, for
+/// toolbars and palettes in resizable windows.
+pub struct Sticky<'a> {
+  pub rect: RectMeasures<'a>,
+  pub screen: RectMeasures<'a>,
+  pub previous: Option<RectMeasures<'a>>,
+}
+
+/// Keeps `rect` inside `screen` and, if given, overlapping `previous`.
+pub fn sticky<'a>(
+  rect: RectMeasures<'a>,
+  screen: RectMeasures<'a>,
+  previous: Option<RectMeasures<'a>>,
+) -> Sticky<'a> {
+  Sticky {
+    rect,
+    screen,
+    previous,
+  }
+}
+
+#[allow(dead_code)]
+impl<'a> Sticky<'a> {
+  /// Containment is hard, and so is overlap with the previous location, unless the screen no
+  /// longer reaches it. Staying at the previous location is only preferred.
+  pub fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = vec![self.screen.contains(&self.rect).hard()];
+    if let Some(previous) = &self.previous {
+      constraints.push(
+        self
+          .rect
+          .intersects(previous)
+          .when(self.screen.intersects(previous))
+          .hard(),
+      );
+      constraints.push(self.rect.left.prop_eq(previous.left));
+      constraints.push(self.rect.top.prop_eq(previous.top));
+    }
+    constraints
+  }
+
+  /// Minimizes the distance the element moved since the previous layout.
+  pub fn objective(&self) -> Option<Objective<'a>> {
+    self.previous.map(|previous| {
+      Objective::Minimize(
+        (self.rect.left - previous.left).abs() + (self.rect.top - previous.top).abs(),
+      )
+    })
+  }
+}
@@ -0,0 +1,78 @@
+//! Progress reports of long solves, for progress indicators.
+
+use std::{
+  cell::Cell,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+#[cfg(feature = "z3")]
+use std::{sync::mpsc, thread};
+
+use super::builder::now;
+
+/// How often a running Z3 check reports `SolvePhase::Checking` again.
+#[cfg(feature = "z3")]
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stage of a solve, in the order they start.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SolvePhase {
+  /// Gathering the constraints and objectives of the layout and deciding the constant ones.
+  Lowering,
+
+  /// Translating the layout for the solver and searching for a solution. Reported again
+  /// periodically while Z3 searches.
+  Checking,
+
+  /// Reading the values of the objectives and constraints from the solution.
+  Extracting,
+
+  /// Running the painters of the widgets.
+  Painting,
+}
+
+/// Callback of `LayoutBuilder::on_progress`, with the time since the solve started.
+pub(crate) struct Progress {
+  callback: Arc<dyn Fn(SolvePhase, Duration) + Send + Sync>,
+  start: Cell<Option<Instant>>,
+}
+
+impl Progress {
+  pub fn new(callback: impl Fn(SolvePhase, Duration) + Send + Sync + 'static) -> Self {
+    Progress {
+      callback: Arc::new(callback),
+      start: Cell::new(None),
+    }
+  }
+
+  /// Reports that `phase` starts. `SolvePhase::Lowering` starts the clock of a new solve.
+  pub fn report(&self, phase: SolvePhase) {
+    if phase == SolvePhase::Lowering || self.start.get().is_none() {
+      self.start.set(now());
+    }
+    (self.callback)(phase, elapsed(self.start.get()));
+  }
+
+  /// Runs the Z3 check `f`, reporting `SolvePhase::Checking` every `PROGRESS_INTERVAL` until it
+  /// returns.
+  #[cfg(feature = "z3")]
+  pub fn during<T>(&self, f: impl FnOnce() -> T) -> T {
+    let (done, wait) = mpsc::channel::<()>();
+    let start = self.start.get();
+    let callback = &self.callback;
+    thread::scope(|s| {
+      s.spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = wait.recv_timeout(PROGRESS_INTERVAL) {
+          callback(SolvePhase::Checking, elapsed(start));
+        }
+      });
+      let res = f();
+      drop(done);
+      res
+    })
+  }
+}
+
+fn elapsed(start: Option<Instant>) -> Duration {
+  start.map(|x| x.elapsed()).unwrap_or_default()
+}
@@ -1,13 +1,12 @@
-use anyhow::Result;
 use std::fmt::{Debug, Display};
 use std::ops::{BitAnd, BitOr, Not};
+#[cfg(feature = "z3")]
 use z3::ast::{Ast, Bool};
 
 use super::measure::MeasureVariant;
-use super::{
-  context::{LayoutContext, Z3BuildContext},
-  measure::Measure,
-};
+use super::{context::LayoutContext, measure::Measure, solution::TOLERANCE};
+#[cfg(feature = "z3")]
+use super::{context::Z3BuildContext, error::LayoutError};
 
 /// A proposition on measurements or other propositions.
 #[derive(Copy, Clone)]
@@ -15,16 +14,27 @@ pub struct Prop<'a> {
   pub ctx: &'a LayoutContext,
   pub(super) variant: &'a PropVariant<'a>,
   pub(super) weight: u32,
+  pub(super) hard: bool,
+  pub(super) label: Option<&'a str>,
 }
 
 impl<'a> Debug for Prop<'a> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "Prop({}) {{ {:?} }}", self.weight, self.variant)
+    if let Some(label) = self.label {
+      write!(f, "{:?} ", label)?;
+    }
+    if self.hard {
+      write!(f, "Prop(hard) {{ {:?} }}", self.variant)
+    } else {
+      write!(f, "Prop({}) {{ {:?} }}", self.weight, self.variant)
+    }
   }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum PropVariant<'a> {
+  Unbound,
+  Const(bool),
   Eq(Measure<'a>, Measure<'a>),
   Lt(Measure<'a>, Measure<'a>),
   Le(Measure<'a>, Measure<'a>),
@@ -37,23 +47,147 @@ pub enum PropVariant<'a> {
 
 #[allow(dead_code)]
 impl<'a> Prop<'a> {
+  /// A free boolean decision, whose value is chosen by the solver.
+  pub fn new_unbound(ctx: &'a LayoutContext) -> Self {
+    Prop {
+      ctx,
+      variant: ctx.alloc_prop(PropVariant::Unbound),
+      weight: 10,
+      hard: false,
+      label: None,
+    }
+  }
+
+  pub fn new_const(ctx: &'a LayoutContext, value: bool) -> Self {
+    Prop {
+      ctx,
+      variant: ctx.alloc_prop(PropVariant::Const(value)),
+      weight: 10,
+      hard: false,
+      label: None,
+    }
+  }
+
   pub fn with_weight(mut self, weight: u32) -> Self {
     self.weight = weight;
     self
   }
 
+  /// Marks the proposition as an invariant that must hold in every layout, instead of a soft
+  /// constraint that may be violated at the cost of its weight.
+  pub fn hard(mut self) -> Self {
+    self.hard = true;
+    self
+  }
+
+  pub fn is_hard(&self) -> bool {
+    self.hard
+  }
+
+  /// Names the proposition in reports and error messages, which then show the label instead of
+  /// the expression.
+  pub fn with_label(mut self, label: &str) -> Self {
+    self.label = Some(self.ctx.alloc_str(label));
+    self
+  }
+
+  pub fn label(&self) -> Option<&'a str> {
+    self.label
+  }
+
+  /// The proposition restricted to layouts where `cond` holds, keeping its weight, hardness and
+  /// label. Where `cond` does not hold, it is trivially satisfied.
+  pub fn when(self, cond: Prop<'a>) -> Self {
+    Self {
+      weight: self.weight,
+      hard: self.hard,
+      label: self.label,
+      ..!cond | self
+    }
+  }
+
   pub fn select(self, left: Measure<'a>, right: Measure<'a>) -> Measure<'a> {
     Measure {
       ctx: self.ctx,
       variant: self
         .ctx
-        .alloc
-        .alloc(MeasureVariant::Select(self, left, right)),
+        .alloc_measure(MeasureVariant::Select(self, left, right)),
+    }
+  }
+
+  /// Number of propositions in `props` that hold.
+  pub fn count(ctx: &'a LayoutContext, props: &[Prop<'a>]) -> Measure<'a> {
+    let zero = Measure::zero(ctx);
+    let one = Measure::new_const(ctx, 1.0).unwrap();
+    props
+      .iter()
+      .fold(zero, |acc, prop| acc + prop.select(one, zero))
+  }
+
+  pub fn at_most(ctx: &'a LayoutContext, props: &[Prop<'a>], k: usize) -> Prop<'a> {
+    Self::count(ctx, props).prop_le(Measure::new_const(ctx, k as f64).unwrap())
+  }
+
+  pub fn at_least(ctx: &'a LayoutContext, props: &[Prop<'a>], k: usize) -> Prop<'a> {
+    Self::count(ctx, props).prop_ge(Measure::new_const(ctx, k as f64).unwrap())
+  }
+
+  /// The proposition with its strict comparisons made non-strict, which holds on the closure of
+  /// the layouts where it holds. Comparisons in conditions of measures are kept as they are.
+  pub(crate) fn closure(self) -> Self {
+    Self {
+      weight: self.weight,
+      hard: self.hard,
+      label: self.label,
+      ..self.closure_with(true)
     }
   }
 
-  pub fn build_z3<'ctx>(self, build_ctx: &mut Z3BuildContext<'ctx>) -> Result<Bool<'ctx>> {
-    let key = self.variant as *const _ as usize;
+  /// `closure`, or under a negation when `positive` is false, the interior that the negation
+  /// turns into a closure.
+  fn closure_with(self, positive: bool) -> Self {
+    use PropVariant as V;
+    match *self.variant {
+      V::Lt(l, r) if positive => l.prop_le(r),
+      V::Gt(l, r) if positive => l.prop_ge(r),
+      V::Le(l, r) if !positive => l.prop_lt(r),
+      V::Ge(l, r) if !positive => l.prop_gt(r),
+      V::Eq(..) if !positive => Prop::new_const(self.ctx, false),
+      V::Or(l, r) => l.closure_with(positive) | r.closure_with(positive),
+      V::And(l, r) => l.closure_with(positive) & r.closure_with(positive),
+      V::Not(x) => !x.closure_with(!positive),
+      _ => self,
+    }
+  }
+
+  /// Identity of the underlying node, shared by copies of this proposition.
+  pub(crate) fn addr(&self) -> usize {
+    self.variant as *const _ as usize
+  }
+
+  /// Evaluates the proposition if it only depends on constants.
+  pub fn const_value(&self) -> Option<bool> {
+    use PropVariant as V;
+    match *self.variant {
+      V::Unbound => None,
+      V::Const(x) => Some(x),
+      V::Eq(l, r) => Some((l.const_value()? - r.const_value()?).abs() <= TOLERANCE),
+      V::Lt(l, r) => Some(l.const_value()? < r.const_value()? - TOLERANCE),
+      V::Le(l, r) => Some(l.const_value()? <= r.const_value()? + TOLERANCE),
+      V::Gt(l, r) => Some(l.const_value()? > r.const_value()? + TOLERANCE),
+      V::Ge(l, r) => Some(l.const_value()? >= r.const_value()? - TOLERANCE),
+      V::Or(l, r) => Some(l.const_value()? || r.const_value()?),
+      V::And(l, r) => Some(l.const_value()? && r.const_value()?),
+      V::Not(x) => Some(!x.const_value()?),
+    }
+  }
+
+  #[cfg(feature = "z3")]
+  pub fn build_z3<'ctx>(
+    self,
+    build_ctx: &mut Z3BuildContext<'ctx>,
+  ) -> Result<Bool<'ctx>, LayoutError> {
+    let key = self.addr();
     if let Some(x) = build_ctx.prop_cache.get(&key) {
       return Ok(x.clone());
     }
@@ -62,10 +196,16 @@ impl<'a> Prop<'a> {
     Ok(res)
   }
 
-  fn do_build_z3<'ctx>(self, build_ctx: &mut Z3BuildContext<'ctx>) -> Result<Bool<'ctx>> {
+  #[cfg(feature = "z3")]
+  fn do_build_z3<'ctx>(
+    self,
+    build_ctx: &mut Z3BuildContext<'ctx>,
+  ) -> Result<Bool<'ctx>, LayoutError> {
     use PropVariant as V;
     let z3_ctx = build_ctx.z3_ctx;
     Ok(match *self.variant {
+      V::Unbound => Bool::fresh_const(z3_ctx, "prop_"),
+      V::Const(value) => Bool::from_bool(z3_ctx, value),
       V::Eq(left, right) => left.build_z3(build_ctx)?._eq(&right.build_z3(build_ctx)?),
       V::Lt(left, right) => left.build_z3(build_ctx)?.lt(&right.build_z3(build_ctx)?),
       V::Le(left, right) => left.build_z3(build_ctx)?.le(&right.build_z3(build_ctx)?),
@@ -86,7 +226,15 @@ impl<'a> Prop<'a> {
 
 impl<'a> Display for Prop<'a> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(label) = self.label {
+      return write!(f, "{}", label);
+    }
     match self.variant {
+      PropVariant::Unbound => match self.ctx.display_format().name_of(self.addr()) {
+        Some(name) => write!(f, "{}", name),
+        None => write!(f, "<{:p}>", self.variant),
+      },
+      PropVariant::Const(x) => write!(f, "{}", x),
       PropVariant::Eq(l, r) => write!(f, "{} == {}", l, r),
       PropVariant::Lt(l, r) => write!(f, "{} < {}", l, r),
       PropVariant::Le(l, r) => write!(f, "{} <= {}", l, r),
@@ -104,8 +252,10 @@ impl<'a> BitOr for Prop<'a> {
   fn bitor(self, that: Prop<'a>) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Or(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::Or(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 }
@@ -115,8 +265,10 @@ impl<'a> BitAnd for Prop<'a> {
   fn bitand(self, that: Prop<'a>) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::And(self, that)),
+      variant: self.ctx.alloc_prop(PropVariant::And(self, that)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 }
@@ -126,8 +278,10 @@ impl<'a> Not for Prop<'a> {
   fn not(self) -> Self {
     Self {
       ctx: self.ctx,
-      variant: self.ctx.alloc.alloc(PropVariant::Not(self)),
+      variant: self.ctx.alloc_prop(PropVariant::Not(self)),
       weight: 10,
+      hard: false,
+      label: None,
     }
   }
 }
@@ -0,0 +1,11 @@
+use super::measure::Measure;
+
+pub use liquid_layout_derive::ReflectMeasures;
+
+/// Enumerates the measures of a widget by name, so that exporters and inspectors can handle
+/// widgets they do not know about.
+///
+/// Usually derived, see `liquid_layout_derive::ReflectMeasures`.
+pub trait ReflectMeasures<'a> {
+  fn named_measures(&self) -> Vec<(String, Measure<'a>)>;
+}
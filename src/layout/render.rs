@@ -0,0 +1,57 @@
+//! Painters and the drawing surface they share during a build.
+
+use std::any::Any;
+
+use super::error::PaintError;
+
+/// A drawing surface that all painters of a build draw into, like an SVG document or a raster
+/// canvas. Any `'static` type is one, and painters get theirs back with `downcast_mut`.
+pub trait RenderTarget: Any {
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> RenderTarget for T {
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+impl dyn RenderTarget {
+  /// The surface as a `T`, if it is one.
+  pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+    self.as_any_mut().downcast_mut()
+  }
+}
+
+/// Draws a widget from its metrics of type `M`.
+///
+/// Closures taking the metrics are painters that ignore the render target. Wrap closures that
+/// draw into it with `with_target`.
+pub trait Painter<M> {
+  fn paint(&mut self, metrics: M, target: &mut dyn RenderTarget) -> Result<(), PaintError>;
+}
+
+impl<M, F: FnMut(M) -> Result<(), PaintError>> Painter<M> for F {
+  fn paint(&mut self, metrics: M, _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self(metrics)
+  }
+}
+
+/// A painter of the closure `f`, which is given the render target along with the metrics.
+pub fn with_target<M, F>(f: F) -> TargetPainter<F>
+where
+  F: FnMut(M, &mut dyn RenderTarget) -> Result<(), PaintError>,
+{
+  TargetPainter(f)
+}
+
+/// Painter of `with_target`.
+pub struct TargetPainter<F>(F);
+
+impl<M, F: FnMut(M, &mut dyn RenderTarget) -> Result<(), PaintError>> Painter<M>
+  for TargetPainter<F>
+{
+  fn paint(&mut self, metrics: M, target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    (self.0)(metrics, target)
+  }
+}
@@ -0,0 +1,102 @@
+use std::ptr::NonNull;
+
+use super::context::{LayoutContext, Z3BuildContext};
+
+/// Z3 state reused across builds, to avoid paying for setup on every solve.
+///
+/// The session owns its `z3::Context`, so it can be kept for as long as the app runs, like in
+/// the state of a window that re-solves its layout every frame. Translated terms and asserted
+/// constraints stay cached as long as the builds share the same `LayoutContext`. Checkpoints of
+/// the builder map to solver frames, so rolling back only retracts the constraints added since
+/// the checkpoint.
+pub struct SolverSession {
+  z3_ctx: &'static z3::Context,
+  build_ctx: Z3BuildContext<'static>,
+  layout_ctx_id: Option<u64>,
+  pub(crate) opt: z3::Optimize<'static>,
+
+  /// For each frame, the builder or checkpoint that opened it and the builder position, as
+  /// numbers of widgets and constraints, asserted so far.
+  pub(crate) frames: Vec<(u64, (usize, usize))>,
+
+  /// Frees the context that `z3_ctx` points to. Declared last, so that it is dropped after the
+  /// terms and solvers above.
+  _owner: OwnedContext,
+}
+
+/// A `z3::Context` on the heap, freed on drop.
+struct OwnedContext(NonNull<z3::Context>);
+
+impl Drop for OwnedContext {
+  fn drop(&mut self) {
+    drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+  }
+}
+
+impl SolverSession {
+  pub fn new() -> Self {
+    Self::with_config(&z3::Config::new())
+  }
+
+  /// A session whose context is created with `config`.
+  pub fn with_config(config: &z3::Config) -> Self {
+    let owner = OwnedContext(NonNull::from(Box::leak(Box::new(z3::Context::new(config)))));
+    // The context lives until `owner` is dropped, which is after every field that borrows it.
+    // The `'static` lifetime never leaves the crate: `z3_ctx` is only handed to code that
+    // borrows the session.
+    let z3_ctx: &'static z3::Context = unsafe { &*owner.0.as_ptr() };
+    Self {
+      z3_ctx,
+      build_ctx: Z3BuildContext::new(z3_ctx),
+      layout_ctx_id: None,
+      opt: z3::Optimize::new(z3_ctx),
+      frames: vec![],
+      _owner: owner,
+    }
+  }
+
+  /// The context of the session. Terms made from it must not outlive the session.
+  pub(crate) fn z3_ctx(&self) -> &'static z3::Context {
+    self.z3_ctx
+  }
+
+  /// Forgets every cached term and asserted constraint.
+  pub fn reset(&mut self) {
+    self.build_ctx = Z3BuildContext::new(self.z3_ctx);
+    self.layout_ctx_id = None;
+    self.reset_frames();
+  }
+
+  pub(crate) fn reset_frames(&mut self) {
+    self.opt = z3::Optimize::new(self.z3_ctx);
+    self.frames = vec![];
+  }
+
+  /// Resets the session unless it was last used with `layout_ctx`.
+  pub(crate) fn attach(&mut self, layout_ctx: &LayoutContext) {
+    if self.layout_ctx_id != Some(layout_ctx.id) {
+      self.reset();
+      self.layout_ctx_id = Some(layout_ctx.id);
+    }
+  }
+
+  pub(crate) fn build_context(&mut self) -> &mut Z3BuildContext<'static> {
+    &mut self.build_ctx
+  }
+
+  pub(crate) fn push(&mut self, id: u64, position: (usize, usize)) {
+    self.opt.push();
+    self.frames.push((id, position));
+  }
+
+  pub(crate) fn pop(&mut self) {
+    self.opt.pop();
+    self.frames.pop();
+  }
+}
+
+impl Default for SolverSession {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,482 @@
+//! Solving of linear layouts by the simplex method, without Z3.
+
+use super::{
+  context::LayoutContext,
+  linear::{LinExpr, Linearizer, Unlinear},
+  measure::Measure,
+  objective::Objective,
+  prop::{Prop, PropVariant},
+  solution::{Solution, TOLERANCE},
+};
+
+/// Coefficients and values smaller than this are treated as zero.
+const EPSILON: f64 = 1e-9;
+
+/// Pivots after which the solve is left to Z3.
+const MAX_PIVOTS: usize = 100_000;
+
+/// Solves the layout without Z3 if its constraints and objectives are linear and its soft
+/// constraints can all hold together.
+///
+/// Soft constraints take priority over objectives in Z3, so when all of them can hold, the
+/// optimum found here is the one Z3 would find. Returns `None` when that is not known, and the
+/// solver is needed.
+pub(crate) fn solve<'a>(
+  ctx: &'a LayoutContext,
+  measures: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+) -> Option<Solution> {
+  solve_with(ctx, measures, constraints, objectives, &[], None)
+}
+
+/// Solves the layout with `pins` held at their values, keeping the choices of `previous`, an
+/// earlier solution of it: selections and disjunctions take the branches they took there, and
+/// soft constraints that did not hold there are given up.
+///
+/// The result is optimal among the layouts that make those choices, not among all of them.
+/// Returns `None` when the layout is not linear or the pins rule out the choices.
+pub(crate) fn resolve<'a>(
+  ctx: &'a LayoutContext,
+  measures: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+  pins: &[(Measure<'a>, f64)],
+  previous: &Solution,
+) -> Option<Solution> {
+  let mut kept = Vec::with_capacity(constraints.len());
+  for &c in constraints {
+    if c.hard || previous.truth_of(c)? {
+      kept.push(c);
+    }
+  }
+  solve_with(ctx, measures, &kept, objectives, pins, Some(previous))
+}
+
+/// Solves the layout with all of `constraints` holding and `pins` at their values. Selections
+/// and disjunctions take the branches they took in `choices`, and without it must not be there.
+fn solve_with<'a>(
+  ctx: &'a LayoutContext,
+  measures: &[Measure<'a>],
+  constraints: &[Prop<'a>],
+  objectives: &[Objective<'a>],
+  pins: &[(Measure<'a>, f64)],
+  choices: Option<&Solution>,
+) -> Option<Solution> {
+  let mut lin = Linearizer::new(ctx);
+  let mut rows = vec![];
+  for &(m, value) in pins {
+    let mut row = linear(&mut lin, m, choices)?;
+    row.constant -= value;
+    rows.push(Relation::Eq(row));
+  }
+  for c in constraints {
+    relations(&mut lin, *c, false, choices, &mut rows)?;
+  }
+  let mut costs = Vec::with_capacity(objectives.len());
+  for o in objectives {
+    costs.push(match *o {
+      Objective::Minimize(m) => linear(&mut lin, m, choices)?,
+      Objective::Maximize(m) => linear(&mut lin, m, choices)?.scaled(-1.0),
+    });
+  }
+  for &m in measures {
+    linear(&mut lin, m, choices)?;
+  }
+  // The conditions of the selections taken have to keep their truth.
+  let mut i = 0;
+  while let Some(&(cond, holds)) = lin.conditions.get(i) {
+    relations(&mut lin, cond, !holds, choices, &mut rows)?;
+    i += 1;
+  }
+
+  let (values, unbounded) = optimize(lin.vars.len(), &rows, &costs).ok()?;
+  if !unbounded.is_empty() {
+    return None;
+  }
+  let solution = Solution::from_values(
+    ctx.id,
+    lin.vars.iter().map(|(&addr, &var)| (addr, values[var])),
+    lin.named,
+  );
+  if constraints
+    .iter()
+    .all(|c| solution.truth_of(*c) == Some(true))
+  {
+    Some(solution)
+  } else {
+    None
+  }
+}
+
+/// Why `optimize` found no solution.
+pub(super) enum Infeasible {
+  /// The relations cannot all hold.
+  Conflict,
+
+  /// The pivot limit was reached.
+  Stalled,
+}
+
+/// Values of `vars` variables that meet `relations` and minimize each of `costs` in turn, each
+/// without giving up on the ones before it.
+///
+/// Costs that are unbounded below are skipped, and their positions returned along with the
+/// values.
+pub(super) fn optimize(
+  vars: usize,
+  relations: &[Relation],
+  costs: &[LinExpr],
+) -> Result<(Vec<f64>, Vec<usize>), Infeasible> {
+  let mut tableau = Tableau::new(vars, relations);
+  tableau.make_feasible()?;
+  let mut unbounded = vec![];
+  for (i, cost) in costs.iter().enumerate() {
+    let mut column_costs = vec![0.0; tableau.columns];
+    for (&var, &coeff) in &cost.terms {
+      column_costs[2 * var] = coeff;
+      column_costs[2 * var + 1] = -coeff;
+    }
+    match tableau.minimize(&column_costs) {
+      Ok(true) => {}
+      Ok(false) => unbounded.push(i),
+      Err(e) => return Err(e),
+    }
+  }
+  let values = tableau.values();
+  let values = (0..vars)
+    .map(|var| values[2 * var] - values[2 * var + 1])
+    .collect::<Vec<_>>();
+  // Snapping moves values by up to a relative `EPSILON`, which coefficients that are not round
+  // can turn into a violation of the relations.
+  let snapped = values.iter().map(|&x| snap(x)).collect::<Vec<_>>();
+  if relations.iter().all(|x| x.holds(&snapped)) {
+    Ok((snapped, unbounded))
+  } else {
+    Ok((values, unbounded))
+  }
+}
+
+/// `expr == 0` or `expr <= 0`.
+#[derive(Clone)]
+pub(super) enum Relation {
+  Eq(LinExpr),
+  Le(LinExpr),
+}
+
+impl Relation {
+  /// Whether the relation holds for `values` of the variables, up to the tolerance of
+  /// `Solution`.
+  fn holds(&self, values: &[f64]) -> bool {
+    let eval = |x: &LinExpr| {
+      x.terms
+        .iter()
+        .map(|(&var, &coeff)| coeff * values[var])
+        .sum::<f64>()
+        + x.constant
+    };
+    match self {
+      Relation::Eq(x) => eval(x).abs() <= TOLERANCE,
+      Relation::Le(x) => eval(x) <= TOLERANCE,
+    }
+  }
+}
+
+/// Adds the linear relations that `prop`, or its negation, asserts to `rows`, or returns `None`
+/// if it asserts anything else.
+///
+/// Strict inequalities are left to Z3, whose optimum for them is not attained.
+fn relations<'a>(
+  lin: &mut Linearizer<'a>,
+  prop: Prop<'a>,
+  negated: bool,
+  choices: Option<&Solution>,
+  rows: &mut Vec<Relation>,
+) -> Option<()> {
+  use PropVariant as V;
+  match (*prop.variant, negated) {
+    (V::Const(x), _) if x != negated => {}
+    (V::Eq(l, r), false) => rows.push(Relation::Eq(difference(lin, l, r, choices)?)),
+    (V::Le(l, r), false) | (V::Gt(l, r), true) => {
+      rows.push(Relation::Le(difference(lin, l, r, choices)?))
+    }
+    (V::Ge(l, r), false) | (V::Lt(l, r), true) => {
+      rows.push(Relation::Le(difference(lin, r, l, choices)?))
+    }
+    (V::And(l, r), false) => {
+      relations(lin, l, false, choices, rows)?;
+      relations(lin, r, false, choices, rows)?;
+    }
+    (V::Or(l, r), false) | (V::And(l, r), true) => {
+      // Keep the side that made the disjunction hold in `choices`.
+      let side = if choices?.truth_of(l)? != negated {
+        l
+      } else {
+        r
+      };
+      relations(lin, side, negated, choices, rows)?;
+    }
+    (V::Not(x), _) => relations(lin, x, !negated, choices, rows)?,
+    _ => return None,
+  }
+  Some(())
+}
+
+fn difference<'a>(
+  lin: &mut Linearizer<'a>,
+  l: Measure<'a>,
+  r: Measure<'a>,
+  choices: Option<&Solution>,
+) -> Option<LinExpr> {
+  let mut x = linear(lin, l, choices)?;
+  x.add_scaled(&linear(lin, r, choices)?, -1.0);
+  Some(x)
+}
+
+/// `m` as a linear expression, with its selections taking the branches they took in `choices`.
+fn linear<'a>(
+  lin: &mut Linearizer<'a>,
+  m: Measure<'a>,
+  choices: Option<&Solution>,
+) -> Option<LinExpr> {
+  loop {
+    match lin.measure(m) {
+      Ok(x) => return Some(x),
+      Err(Unlinear::Undecided(cond)) => {
+        let holds = choices?.truth_of(cond)?;
+        lin.decisions.insert(cond.addr(), holds);
+      }
+      Err(_) => return None,
+    }
+  }
+}
+
+/// Rounds `x` to a nearby fraction with a small denominator, undoing the rounding errors of the
+/// floating-point pivots. Values without one are kept.
+fn snap(x: f64) -> f64 {
+  let (mut num, mut prev_num) = (1.0, 0.0);
+  let (mut den, mut prev_den) = (0.0, 1.0);
+  let mut rest = x;
+  for _ in 0..32 {
+    let a = rest.floor();
+    let next_num = a * num + prev_num;
+    let next_den = a * den + prev_den;
+    prev_num = num;
+    prev_den = den;
+    num = next_num;
+    den = next_den;
+    if den > 1e6 {
+      break;
+    }
+    if (num / den - x).abs() <= EPSILON * x.abs().max(1.0) {
+      return num / den;
+    }
+    let fraction = rest - a;
+    if fraction < EPSILON {
+      break;
+    }
+    rest = 1.0 / fraction;
+  }
+  x
+}
+
+/// A dense simplex tableau over non-negative columns.
+///
+/// Each variable `v` is split into columns `2v` and `2v + 1`, its positive and negative parts.
+/// Then follow a slack column for each inequality and an artificial column for each row, which
+/// is only used by rows that are not satisfied at zero.
+struct Tableau {
+  rows: Vec<Vec<f64>>,
+  rhs: Vec<f64>,
+  basis: Vec<usize>,
+  columns: usize,
+  artificial: usize,
+
+  /// Columns that may enter the basis. Columns are barred once they would worsen an optimized
+  /// objective, which keeps earlier objectives optimal while later ones are optimized.
+  allowed: Vec<bool>,
+}
+
+impl Tableau {
+  fn new(vars: usize, relations: &[Relation]) -> Self {
+    let slacks = relations
+      .iter()
+      .filter(|x| matches!(x, Relation::Le(_)))
+      .count();
+    let artificial = 2 * vars + slacks;
+    let columns = artificial + relations.len();
+    let mut tableau = Tableau {
+      rows: Vec::with_capacity(relations.len()),
+      rhs: Vec::with_capacity(relations.len()),
+      basis: Vec::with_capacity(relations.len()),
+      columns,
+      artificial,
+      allowed: vec![true; columns],
+    };
+    let mut slack = 2 * vars;
+    for (i, relation) in relations.iter().enumerate() {
+      let mut row = vec![0.0; columns];
+      let (expr, slack) = match relation {
+        Relation::Eq(x) => (x, None),
+        Relation::Le(x) => {
+          slack += 1;
+          (x, Some(slack - 1))
+        }
+      };
+      for (&var, &coeff) in &expr.terms {
+        row[2 * var] = coeff;
+        row[2 * var + 1] = -coeff;
+      }
+      let rhs = -expr.constant;
+      let basic = match slack {
+        // The slack of an inequality that holds at zero starts out basic.
+        Some(slack) if rhs >= 0.0 => {
+          row[slack] = 1.0;
+          slack
+        }
+        _ => {
+          if let Some(slack) = slack {
+            row[slack] = 1.0;
+          }
+          if rhs < 0.0 {
+            for x in &mut row {
+              *x = -*x;
+            }
+          }
+          row[artificial + i] = 1.0;
+          artificial + i
+        }
+      };
+      tableau.rows.push(row);
+      tableau.rhs.push(rhs.abs());
+      tableau.basis.push(basic);
+    }
+    tableau
+  }
+
+  /// Finds a basic feasible solution.
+  fn make_feasible(&mut self) -> Result<(), Infeasible> {
+    let mut costs = vec![0.0; self.columns];
+    for x in &mut costs[self.artificial..] {
+      *x = 1.0;
+    }
+    self.minimize(&costs)?;
+    let infeasibility = self
+      .basis
+      .iter()
+      .zip(&self.rhs)
+      .filter(|&(&j, _)| j >= self.artificial)
+      .map(|(_, &x)| x)
+      .sum::<f64>();
+    if infeasibility > EPSILON {
+      return Err(Infeasible::Conflict);
+    }
+
+    // Artificial columns left in the basis are zero. Swap them out where the row allows.
+    for x in &mut self.allowed[self.artificial..] {
+      *x = false;
+    }
+    for i in 0..self.rows.len() {
+      if self.basis[i] >= self.artificial {
+        if let Some(j) = (0..self.artificial).find(|&j| self.rows[i][j].abs() > EPSILON) {
+          self.pivot(i, j);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Minimizes `costs` over the allowed columns by Bland's rule. Returns whether a minimum
+  /// exists, leaving the tableau at a feasible basis otherwise.
+  fn minimize(&mut self, costs: &[f64]) -> Result<bool, Infeasible> {
+    let mut is_basic = vec![false; self.columns];
+    for &j in &self.basis {
+      is_basic[j] = true;
+    }
+    // Reduced costs, kept up to date as a row of the tableau.
+    let mut reduced = costs.to_vec();
+    for (i, &b) in self.basis.iter().enumerate() {
+      if costs[b] != 0.0 {
+        for (x, &a) in reduced.iter_mut().zip(&self.rows[i]) {
+          *x -= costs[b] * a;
+        }
+      }
+    }
+
+    for _ in 0..MAX_PIVOTS {
+      let candidates = (0..self.columns).filter(|&j| self.allowed[j] && !is_basic[j]);
+      let entering = match candidates.clone().find(|&j| reduced[j] < -EPSILON) {
+        Some(j) => j,
+        None => {
+          for j in candidates.collect::<Vec<_>>() {
+            if reduced[j] > EPSILON {
+              self.allowed[j] = false;
+            }
+          }
+          return Ok(true);
+        }
+      };
+      let mut leaving: Option<usize> = None;
+      for i in 0..self.rows.len() {
+        if self.rows[i][entering] <= EPSILON {
+          continue;
+        }
+        let ratio = self.rhs[i] / self.rows[i][entering];
+        leaving = match leaving {
+          Some(k) => {
+            let best = self.rhs[k] / self.rows[k][entering];
+            if ratio < best - EPSILON || (ratio <= best + EPSILON && self.basis[i] < self.basis[k])
+            {
+              Some(i)
+            } else {
+              Some(k)
+            }
+          }
+          None => Some(i),
+        };
+      }
+      let leaving = match leaving {
+        Some(i) => i,
+        None => return Ok(false),
+      };
+      is_basic[self.basis[leaving]] = false;
+      is_basic[entering] = true;
+      self.pivot(leaving, entering);
+      let factor = reduced[entering];
+      for (x, &a) in reduced.iter_mut().zip(&self.rows[leaving]) {
+        *x -= factor * a;
+      }
+    }
+    Err(Infeasible::Stalled)
+  }
+
+  fn pivot(&mut self, row: usize, column: usize) {
+    let factor = self.rows[row][column];
+    for x in &mut self.rows[row] {
+      *x /= factor;
+    }
+    self.rhs[row] /= factor;
+    let pivot_row = self.rows[row].clone();
+    let pivot_rhs = self.rhs[row];
+    for i in 0..self.rows.len() {
+      let factor = self.rows[i][column];
+      if i == row || factor == 0.0 {
+        continue;
+      }
+      for (x, &p) in self.rows[i].iter_mut().zip(&pivot_row) {
+        *x -= factor * p;
+      }
+      self.rhs[i] -= factor * pivot_rhs;
+    }
+    self.basis[row] = column;
+  }
+
+  /// Value of each column at the current basis.
+  fn values(&self) -> Vec<f64> {
+    let mut values = vec![0.0; self.columns];
+    for (i, &j) in self.basis.iter().enumerate() {
+      values[j] = self.rhs[i];
+    }
+    values
+  }
+}
@@ -0,0 +1,237 @@
+//! SMT-LIB2 export of layouts, and import of models solved elsewhere.
+//!
+//! Unbound measures are named `m0`, `m1`, ... and unbound propositions `p0`, `p1`, ... in the
+//! order they are first reached from the measures of the widgets, then the objectives, then the
+//! constraints. Named constants keep their names, with a `named_` prefix.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::{
+  context::LayoutContext,
+  measure::{named_symbol, Measure, MeasureVariant},
+  objective::Objective,
+  prop::{Prop, PropVariant},
+  solution::{free_leaves, named_consts, Solution},
+};
+
+/// Values of the variables of a layout by name, like from the `get-model` output of a solver
+/// run on `LayoutBuilder::to_smtlib2`. Names are taken without `|` quotes.
+#[derive(Clone, Debug, Default)]
+pub struct ModelValues {
+  /// Values of real variables, as a numerator and a denominator.
+  pub reals: HashMap<String, (i64, i64)>,
+  pub bools: HashMap<String, bool>,
+}
+
+/// Variables of a layout with their SMT-LIB2 names.
+pub(crate) struct Variables<'a> {
+  measures: Vec<Measure<'a>>,
+  props: Vec<Prop<'a>>,
+  named: Vec<&'a str>,
+
+  /// Names of the unbound measures and propositions, by node address.
+  names: HashMap<usize, String>,
+}
+
+impl<'a> Variables<'a> {
+  pub fn of(measures: Vec<Measure<'a>>, props: Vec<Prop<'a>>) -> Self {
+    let named = named_consts(measures.iter().copied(), props.iter().copied());
+    let (measures, props) = free_leaves(measures, props);
+    let mut names = HashMap::new();
+    for (i, m) in measures.iter().enumerate() {
+      names.insert(m.addr(), format!("m{}", i));
+    }
+    for (i, p) in props.iter().enumerate() {
+      names.insert(p.addr(), format!("p{}", i));
+    }
+    Variables {
+      measures,
+      props,
+      named,
+      names,
+    }
+  }
+
+  /// Name of the unbound measure or proposition at the node address `addr`.
+  pub fn name_of(&self, addr: usize) -> Option<&str> {
+    self.names.get(&addr).map(|x| x.as_str())
+  }
+
+  /// Reads the values of the variables from `model`. Variables missing from it are left out of
+  /// the solution, and named constants missing from it take their defined values.
+  pub fn solution(&self, ctx: &LayoutContext, model: &ModelValues) -> Solution {
+    self.solution_from(
+      ctx,
+      |name| {
+        model
+          .reals
+          .get(name)
+          .map(|&(num, den)| num as f64 / den as f64)
+      },
+      |name| model.bools.get(name).copied(),
+    )
+  }
+
+  /// Like `solution`, with the values of `real` and `bool` variables by name.
+  pub fn solution_from(
+    &self,
+    ctx: &LayoutContext,
+    real: impl Fn(&str) -> Option<f64>,
+    bool: impl Fn(&str) -> Option<bool>,
+  ) -> Solution {
+    let measures = self
+      .measures
+      .iter()
+      .filter_map(|m| Some((m.addr(), real(&self.names[&m.addr()])?)));
+    let named = self
+      .named
+      .iter()
+      .filter_map(|&name| {
+        let value = real(&named_symbol(name)).or_else(|| ctx.definition(name))?;
+        Some((name.to_string(), value))
+      })
+      .collect();
+    let mut solution = Solution::from_values(ctx.id, measures, named);
+    for &p in &self.props {
+      if let Some(holds) = bool(&self.names[&p.addr()]) {
+        solution.set_truth(p, holds);
+      }
+    }
+    solution
+  }
+
+  /// The values of the variables in `solution`, by name. Variables without one are left out.
+  pub fn values(&self, solution: &Solution) -> (HashMap<String, f64>, HashMap<String, bool>) {
+    let measures = self.measures.iter().filter_map(|&m| {
+      let value = solution.value_of(m)?;
+      Some((self.names[&m.addr()].clone(), value))
+    });
+    let named = self.named.iter().filter_map(|&name| {
+      let value = solution.named_value(name)?;
+      Some((named_symbol(name), value))
+    });
+    let props = self.props.iter().filter_map(|&p| {
+      let holds = solution.truth_of(p)?;
+      Some((self.names[&p.addr()].clone(), holds))
+    });
+    (measures.chain(named).collect(), props.collect())
+  }
+
+  /// The layout as an SMT-LIB2 script that asserts `constraints`, optimizes `objectives` in
+  /// order, and asks for a model.
+  pub fn export(
+    &self,
+    ctx: &LayoutContext,
+    constraints: &[Prop<'a>],
+    objectives: &[Objective<'a>],
+  ) -> String {
+    let mut out = String::new();
+    for m in &self.measures {
+      writeln!(out, "(declare-const {} Real)", self.names[&m.addr()]).unwrap();
+    }
+    for p in &self.props {
+      writeln!(out, "(declare-const {} Bool)", self.names[&p.addr()]).unwrap();
+    }
+    for &name in &self.named {
+      let symbol = quote(&named_symbol(name));
+      writeln!(out, "(declare-const {} Real)", symbol).unwrap();
+      if let Some(value) = ctx.definition(name) {
+        writeln!(out, "(assert (= {} {}))", symbol, decimal(value)).unwrap();
+      }
+    }
+    for &c in constraints {
+      if let Some(label) = c.label() {
+        writeln!(out, "; {}", label).unwrap();
+      }
+      if c.is_hard() {
+        writeln!(out, "(assert {})", self.prop(c)).unwrap();
+      } else {
+        writeln!(out, "(assert-soft {} :weight {})", self.prop(c), c.weight).unwrap();
+      }
+    }
+    for o in objectives {
+      match *o {
+        Objective::Minimize(m) => writeln!(out, "(minimize {})", self.measure(m)).unwrap(),
+        Objective::Maximize(m) => writeln!(out, "(maximize {})", self.measure(m)).unwrap(),
+      }
+    }
+    out.push_str("(check-sat)\n(get-model)\n");
+    out
+  }
+
+  fn measure(&self, m: Measure<'a>) -> String {
+    use MeasureVariant as V;
+    match *m.variant {
+      V::Unbound => self.names[&m.addr()].clone(),
+      V::Const(num, den) => rational(num, den),
+      V::Named(name) => quote(&named_symbol(name)),
+      V::Add(l, r) => format!("(+ {} {})", self.measure(l), self.measure(r)),
+      V::Sub(l, r) => format!("(- {} {})", self.measure(l), self.measure(r)),
+      V::Mul(l, r) => format!("(* {} {})", self.measure(l), self.measure(r)),
+      V::Div(l, r) => format!("(/ {} {})", self.measure(l), self.measure(r)),
+      V::Select(cond, l, r) => format!(
+        "(ite {} {} {})",
+        self.prop(cond),
+        self.measure(l),
+        self.measure(r)
+      ),
+    }
+  }
+
+  fn prop(&self, p: Prop<'a>) -> String {
+    use PropVariant as V;
+    let (op, l, r) = match *p.variant {
+      V::Unbound => return self.names[&p.addr()].clone(),
+      V::Const(x) => return x.to_string(),
+      V::Eq(l, r) => ("=", l, r),
+      V::Lt(l, r) => ("<", l, r),
+      V::Le(l, r) => ("<=", l, r),
+      V::Gt(l, r) => (">", l, r),
+      V::Ge(l, r) => (">=", l, r),
+      V::Or(l, r) => return format!("(or {} {})", self.prop(l), self.prop(r)),
+      V::And(l, r) => return format!("(and {} {})", self.prop(l), self.prop(r)),
+      V::Not(x) => return format!("(not {})", self.prop(x)),
+    };
+    format!("({} {} {})", op, self.measure(l), self.measure(r))
+  }
+}
+
+/// The real literal `num / den`.
+fn rational(num: i32, den: i32) -> String {
+  let magnitude = if den == 1 {
+    format!("{}.0", num.unsigned_abs())
+  } else {
+    format!("(/ {}.0 {}.0)", num.unsigned_abs(), den)
+  };
+  if num < 0 {
+    format!("(- {})", magnitude)
+  } else {
+    magnitude
+  }
+}
+
+/// The real literal closest to `value`.
+fn decimal(value: f64) -> String {
+  let mut magnitude = value.abs().to_string();
+  if !magnitude.contains('.') {
+    magnitude.push_str(".0");
+  }
+  if value < 0.0 {
+    format!("(- {})", magnitude)
+  } else {
+    magnitude
+  }
+}
+
+/// `symbol`, quoted if it is not a simple symbol.
+fn quote(symbol: &str) -> String {
+  let simple = symbol
+    .chars()
+    .all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c));
+  if simple {
+    symbol.to_string()
+  } else {
+    format!("|{}|", symbol)
+  }
+}
@@ -0,0 +1,59 @@
+//! A serializable form of `BuildReport`, for shipping layout results to another process or
+//! keeping them as test fixtures.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::builder::{BuildReport, SolveStats, WidgetId};
+
+/// A `BuildReport` with its expressions rendered by `Display`, in the display format of their
+/// context.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReportSnapshot {
+  pub satisfied_constraints: Vec<String>,
+  pub unsatisfied_constraints: Vec<String>,
+  pub objective_values: Vec<(String, f64)>,
+  pub stats: SolveStats,
+  pub unconstrained_measures: Vec<String>,
+  pub constant_contradictions: Vec<String>,
+  pub dropped_constraints: Vec<String>,
+
+  /// Messages of the painters that failed.
+  pub paint_errors: Vec<(WidgetId, String)>,
+
+  /// Solved values of the measures of each widget. See `BuildReport::metrics_of`.
+  pub metrics: BTreeMap<WidgetId, Vec<f64>>,
+}
+
+impl<'a> BuildReport<'a> {
+  /// The report without references to its layout context. Tags are left out.
+  pub fn snapshot(&self) -> ReportSnapshot {
+    fn render<T: ToString>(xs: &[T]) -> Vec<String> {
+      xs.iter().map(|x| x.to_string()).collect()
+    }
+    ReportSnapshot {
+      satisfied_constraints: render(&self.satisfied_constraints),
+      unsatisfied_constraints: render(&self.unsatisfied_constraints),
+      objective_values: self
+        .objective_values
+        .iter()
+        .map(|(o, value)| (o.to_string(), *value))
+        .collect(),
+      stats: self.stats.clone(),
+      unconstrained_measures: render(&self.unconstrained_measures),
+      constant_contradictions: render(&self.constant_contradictions),
+      dropped_constraints: render(&self.dropped_constraints),
+      paint_errors: self
+        .paint_errors
+        .iter()
+        .map(|(id, e)| (*id, e.to_string()))
+        .collect(),
+      metrics: self
+        .metrics()
+        .iter()
+        .map(|(&id, values)| (id, values.clone()))
+        .collect(),
+    }
+  }
+}
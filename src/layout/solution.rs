@@ -0,0 +1,568 @@
+use std::{
+  cmp::Ordering,
+  collections::{HashMap, HashSet},
+};
+
+use super::{
+  builder::{LayoutBuilder, WidgetId},
+  context::LayoutContext,
+  error::{LayoutError, PaintError},
+  measure::{Measure, MeasureVariant},
+  prop::{Prop, PropVariant},
+  render::RenderTarget,
+  tag::{self, Tag},
+  widget::RawWidget,
+};
+#[cfg(feature = "z3")]
+use super::{context::Z3BuildContext, session::SolverSession};
+
+/// Slack allowed when comparing solved values, which are rounded to `f64`.
+pub(crate) const TOLERANCE: f64 = 1e-9;
+
+/// Values the solver assigned to the variables of a layout.
+///
+/// Evaluates any expression over those variables after the solve, including ones that were
+/// never part of the layout, like the gap between two widgets.
+#[derive(Clone, Debug, Default)]
+pub struct Solution {
+  layout_ctx_id: Option<u64>,
+  measures: HashMap<usize, f64>,
+  props: HashMap<usize, bool>,
+  named: HashMap<String, f64>,
+
+  /// Measures to hold at a value in `LayoutBuilder::resolve`, by node address.
+  pins: Vec<(usize, f64)>,
+
+  /// Solved bounds of the widgets that take pointer events, as left, right, top and bottom
+  /// edges, topmost first.
+  hit_regions: Vec<(WidgetId, [f64; 4])>,
+}
+
+/// One end of the range of a measure, from `Solution::bounds_of`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bound {
+  /// The infimum or supremum, infinite when the measure is unbounded that way.
+  pub value: f64,
+
+  /// Whether a layout attains `value`, unlike the supremum of a measure that is less than 10.
+  /// Infinite ends are open.
+  pub closed: bool,
+}
+
+/// A widget to paint, with its position and id in the builder, the solved values of its
+/// measures and its tag.
+type Painting<'w, 'a> = (
+  usize,
+  WidgetId,
+  &'w mut (dyn RawWidget<'a> + 'a),
+  Vec<f64>,
+  Option<Tag>,
+);
+
+impl Solution {
+  /// Reads the values of all unbound measures and propositions that `measures` and `props`
+  /// depend on from `model`.
+  #[cfg(feature = "z3")]
+  pub(crate) fn from_model<'a, 'ctx>(
+    model: &z3::Model<'ctx>,
+    build_ctx: &mut Z3BuildContext<'ctx>,
+    measures: impl IntoIterator<Item = Measure<'a>>,
+    props: impl IntoIterator<Item = Prop<'a>>,
+  ) -> Result<Self, LayoutError> {
+    let walker = Walker::over(measures, props);
+    let mut solution = Solution::default();
+    for m in walker.unbound_measures {
+      solution.layout_ctx_id = Some(m.ctx.id);
+      if let Some(value) = model.eval(&m.build_z3(build_ctx)?) {
+        if let Some((num, den)) = value.as_real() {
+          solution.measures.insert(m.addr(), num as f64 / den as f64);
+        }
+      }
+    }
+    for m in walker.named {
+      solution.layout_ctx_id = Some(m.ctx.id);
+      if let Some(value) = model.eval(&m.build_z3(build_ctx)?) {
+        if let (Some((num, den)), MeasureVariant::Named(name)) = (value.as_real(), m.variant) {
+          solution
+            .named
+            .insert(name.to_string(), num as f64 / den as f64);
+        }
+      }
+    }
+    for p in walker.unbound_props {
+      solution.layout_ctx_id = Some(p.ctx.id);
+      if let Some(value) = model
+        .eval(&p.build_z3(build_ctx)?)
+        .and_then(|x| x.as_bool())
+      {
+        solution.props.insert(p.addr(), value);
+      }
+    }
+    Ok(solution)
+  }
+
+  /// A solution with the given values of unbound measures, keyed by node address, and of named
+  /// constants.
+  pub(crate) fn from_values(
+    layout_ctx_id: u64,
+    measures: impl IntoIterator<Item = (usize, f64)>,
+    named: HashMap<String, f64>,
+  ) -> Self {
+    Solution {
+      layout_ctx_id: Some(layout_ctx_id),
+      measures: measures.into_iter().collect(),
+      props: HashMap::new(),
+      named,
+      pins: vec![],
+      hit_regions: vec![],
+    }
+  }
+
+  /// A copy of the solution with the values of the unbound measures in `values`, by node
+  /// address, replaced.
+  pub(crate) fn with_values(&self, values: impl IntoIterator<Item = (usize, f64)>) -> Self {
+    let mut solution = self.clone();
+    solution.measures.extend(values);
+    solution
+  }
+
+  /// Adds the values of `that`, a solution of variables disjoint from these.
+  #[cfg(feature = "z3")]
+  pub(crate) fn merge(&mut self, that: Solution) {
+    self.layout_ctx_id = self.layout_ctx_id.or(that.layout_ctx_id);
+    self.measures.extend(that.measures);
+    self.props.extend(that.props);
+    self.named.extend(that.named);
+    self.hit_regions.extend(that.hit_regions);
+  }
+
+  /// Holds `measure` at `value` when the layout is solved again with `LayoutBuilder::resolve`,
+  /// replacing an earlier pin of it. The measure has to be part of the layout.
+  pub fn pin(&mut self, measure: Measure, value: f64) {
+    self.unpin(measure);
+    self.pins.push((measure.addr(), value));
+  }
+
+  /// Removes the pin of `measure`, if there is one.
+  pub fn unpin(&mut self, measure: Measure) {
+    self.pins.retain(|&(addr, _)| addr != measure.addr());
+  }
+
+  /// Infimum and supremum of `measure` in the layouts of `builder` that meet its hard
+  /// constraints, with the pins of the solution held, like how far a widget can grow while the
+  /// user keeps others where they put them. Soft constraints and objectives are ignored.
+  ///
+  /// An end that is only approached, like the supremum of a measure that is less than 10, is
+  /// open. So is an infinite end.
+  #[cfg(feature = "z3")]
+  pub fn bounds_of<'a>(
+    &self,
+    builder: &LayoutBuilder<'a>,
+    session: &mut SolverSession,
+    measure: Measure<'a>,
+  ) -> Result<(Bound, Bound), LayoutError> {
+    builder.bounds_of(session, measure, &self.pins)
+  }
+
+  pub(crate) fn pins(&self) -> &[(usize, f64)] {
+    &self.pins
+  }
+
+  pub(crate) fn set_pins(&mut self, pins: Vec<(usize, f64)>) {
+    self.pins = pins;
+  }
+
+  /// Value of the named constant `name` in the solution.
+  pub(crate) fn named_value(&self, name: &str) -> Option<f64> {
+    self.named.get(name).copied()
+  }
+
+  /// The widgets whose bounds contain the point (`x`, `y`), edges included, topmost first. Only
+  /// solutions of a `LayoutBuilder` know of its widgets.
+  pub fn hit_test(&self, x: f64, y: f64) -> Vec<WidgetId> {
+    self
+      .hit_regions
+      .iter()
+      .filter(|(_, [left, right, top, bottom])| {
+        *left <= x && x <= *right && *top <= y && y <= *bottom
+      })
+      .map(|&(id, _)| id)
+      .collect()
+  }
+
+  pub(crate) fn set_hit_regions(&mut self, regions: Vec<(WidgetId, [f64; 4])>) {
+    self.hit_regions = regions;
+  }
+
+  /// Sets the truth of the unbound proposition `prop`.
+  pub(crate) fn set_truth(&mut self, prop: Prop, holds: bool) {
+    self.props.insert(prop.addr(), holds);
+  }
+
+  /// Number of unbound measures and propositions with a solved value.
+  pub(crate) fn variable_count(&self) -> usize {
+    self.measures.len() + self.props.len()
+  }
+
+  /// Runs the painters of the widgets of `builder` with the solved values of their measures.
+  ///
+  /// Neither the solution nor the painters are used up, so the layout can be painted again, like
+  /// into a target of another scale, or through another builder whose widgets share the
+  /// measures of the solved layout. `LayoutBuilder::build` drops the painters once they ran.
+  ///
+  /// Widgets are painted by z-order, from the lowest. Painters of the same z-order that are
+  /// `Send` run on the rayon thread pool with the `parallel` feature.
+  ///
+  /// A failed painter does not stop the others. The error of the first widget whose painter
+  /// failed is returned after all are painted.
+  pub fn paint(&self, builder: &mut LayoutBuilder) -> Result<(), LayoutError> {
+    self.paint_into(builder, &mut ())
+  }
+
+  /// Like `paint`, with the painters drawing into `target`.
+  pub fn paint_into(
+    &self,
+    builder: &mut LayoutBuilder,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), LayoutError> {
+    match self.paint_widgets(builder, target)?.into_iter().next() {
+      Some((widget, _, source)) => Err(LayoutError::Painter { widget, source }),
+      None => Ok(()),
+    }
+  }
+
+  /// Like `paint`, but returns the errors of all failed painters with the position and id of
+  /// their widget, in the order the widgets were pushed.
+  pub(crate) fn paint_widgets(
+    &self,
+    builder: &mut LayoutBuilder,
+    target: &mut dyn RenderTarget,
+  ) -> Result<Vec<(usize, WidgetId, PaintError)>, LayoutError> {
+    // Every widget sees the solution before the first one is painted.
+    let mut painters = builder
+      .painters()
+      .into_iter()
+      .enumerate()
+      .map(|(i, (id, z, tag, w))| {
+        let values = self.values_of_widget(&*w)?;
+        tag::with_current(tag.clone(), || w.on_solved(&values));
+        Ok((z.value(self)?, (i, id, w, values, tag)))
+      })
+      .collect::<Result<Vec<_>, LayoutError>>()?;
+    painters.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let mut painters = painters.into_iter().peekable();
+    let mut errors = vec![];
+    while let Some((z, painting)) = painters.next() {
+      let mut layer = vec![painting];
+      while let Some((_, painting)) = painters.next_if(|(x, _)| *x == z) {
+        layer.push(painting);
+      }
+      errors.extend(self.paint_layer(layer, target)?);
+    }
+    errors.sort_by_key(|(i, _, _)| *i);
+    Ok(errors)
+  }
+
+  /// The solved values of the measures of `widget`.
+  pub(crate) fn values_of_widget(&self, widget: &dyn RawWidget) -> Result<Vec<f64>, LayoutError> {
+    widget
+      .measures()
+      .into_iter()
+      .map(|m| {
+        self
+          .value_of(m)
+          .ok_or_else(|| LayoutError::ModelExtraction {
+            measure: m.to_string(),
+          })
+      })
+      .collect()
+  }
+
+  /// Paints widgets of the same z-order, with their position and id in the builder, the solved
+  /// values of their measures and their tags, and returns the errors of the painters that
+  /// failed.
+  fn paint_layer(
+    &self,
+    layer: Vec<Painting>,
+    target: &mut dyn RenderTarget,
+  ) -> Result<Vec<(usize, WidgetId, PaintError)>, LayoutError> {
+    let mut jobs = vec![];
+    let mut errors = vec![];
+    for (i, id, w, values, tag) in layer {
+      if w.send_painter().is_none() {
+        if let Err(e) = tag::with_current(tag, || w.paint(&values, target)) {
+          errors.push((i, id, e));
+        }
+      } else if let Some(painter) = w.send_painter() {
+        jobs.push((i, id, painter, (values, tag)));
+      }
+    }
+
+    #[cfg(feature = "parallel")]
+    let results = {
+      use rayon::prelude::*;
+      jobs
+        .into_par_iter()
+        .map(|(i, id, painter, (values, tag))| (i, id, tag::with_current(tag, || painter(&values))))
+        .collect::<Vec<_>>()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = jobs
+      .into_iter()
+      .map(|(i, id, painter, (values, tag))| (i, id, tag::with_current(tag, || painter(&values))));
+    for (i, id, res) in results {
+      if let Err(e) = res {
+        errors.push((i, id, e));
+      }
+    }
+    Ok(errors)
+  }
+
+  /// The solved value of `measure` as a constant of `ctx`, which may be a new context, for
+  /// solving later stages of a layout against it. Rounded like `Measure::new_const`.
+  pub fn freeze_measure<'b>(
+    &self,
+    ctx: &'b LayoutContext,
+    measure: Measure,
+  ) -> Result<Measure<'b>, LayoutError> {
+    let value = self
+      .value_of(measure)
+      .ok_or_else(|| LayoutError::ModelExtraction {
+        measure: measure.to_string(),
+      })?;
+    Measure::new_const(ctx, value)
+  }
+
+  /// The solved values of named measures as constants of `ctx`, by name. See `freeze_measure`.
+  ///
+  /// Freezes a widget from its `ReflectMeasures::named_measures`, taken before it is pushed.
+  pub fn freeze<'a, 'b>(
+    &self,
+    ctx: &'b LayoutContext,
+    measures: impl IntoIterator<Item = (String, Measure<'a>)>,
+  ) -> Result<HashMap<String, Measure<'b>>, LayoutError> {
+    measures
+      .into_iter()
+      .map(|(name, m)| Ok((name, self.freeze_measure(ctx, m)?)))
+      .collect()
+  }
+
+  /// Value of `measure` in the solution, or `None` if it depends on a variable the solver did
+  /// not see or divides by zero.
+  pub fn value_of(&self, measure: Measure) -> Option<f64> {
+    use MeasureVariant as V;
+    if self.layout_ctx_id.is_some() && self.layout_ctx_id != Some(measure.ctx.id) {
+      return None;
+    }
+    Some(match *measure.variant {
+      V::Unbound => return self.measures.get(&measure.addr()).copied(),
+      V::Const(num, den) => num as f64 / den as f64,
+      V::Named(name) => return self.named.get(name).copied(),
+      V::Add(l, r) => self.value_of(l)? + self.value_of(r)?,
+      V::Sub(l, r) => self.value_of(l)? - self.value_of(r)?,
+      V::Mul(l, r) => self.value_of(l)? * self.value_of(r)?,
+      V::Div(l, r) => {
+        let r = self.value_of(r)?;
+        if r == 0.0 {
+          return None;
+        }
+        self.value_of(l)? / r
+      }
+      V::Select(cond, l, r) => {
+        if self.truth_of(cond)? {
+          self.value_of(l)?
+        } else {
+          self.value_of(r)?
+        }
+      }
+    })
+  }
+
+  /// Truth of `prop` in the solution, or `None` if it depends on a variable the solver did not
+  /// see.
+  pub fn truth_of(&self, prop: Prop) -> Option<bool> {
+    use PropVariant as V;
+    if self.layout_ctx_id.is_some() && self.layout_ctx_id != Some(prop.ctx.id) {
+      return None;
+    }
+    Some(match *prop.variant {
+      V::Unbound => return self.props.get(&prop.addr()).copied(),
+      V::Const(x) => x,
+      V::Eq(l, r) => (self.value_of(l)? - self.value_of(r)?).abs() <= TOLERANCE,
+      V::Lt(l, r) => self.value_of(l)? < self.value_of(r)? - TOLERANCE,
+      V::Le(l, r) => self.value_of(l)? <= self.value_of(r)? + TOLERANCE,
+      V::Gt(l, r) => self.value_of(l)? > self.value_of(r)? + TOLERANCE,
+      V::Ge(l, r) => self.value_of(l)? >= self.value_of(r)? - TOLERANCE,
+      V::Or(l, r) => self.truth_of(l)? || self.truth_of(r)?,
+      V::And(l, r) => self.truth_of(l)? && self.truth_of(r)?,
+      V::Not(x) => !self.truth_of(x)?,
+    })
+  }
+}
+
+/// Names of the named constants that `measures` and `props` depend on, each listed once.
+pub(crate) fn named_consts<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+) -> Vec<&'a str> {
+  let mut names = Walker::over(measures, props)
+    .named
+    .iter()
+    .filter_map(|m| match *m.variant {
+      MeasureVariant::Named(name) => Some(name),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  names.sort_unstable();
+  names.dedup();
+  names
+}
+
+/// Unbound measures that `measures` and `props` depend on, each listed once.
+pub(crate) fn free_measures<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+) -> Vec<Measure<'a>> {
+  Walker::over(measures, props).unbound_measures
+}
+
+/// Unbound measures and propositions that `measures` and `props` depend on, each listed once in
+/// the order they are reached.
+pub(crate) fn free_leaves<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+) -> (Vec<Measure<'a>>, Vec<Prop<'a>>) {
+  let walker = Walker::over(measures, props);
+  (walker.unbound_measures, walker.unbound_props)
+}
+
+/// Unbound measures and propositions that each group of measures and propositions depends on
+/// and no group before it does, each listed in the order they are reached.
+#[cfg(feature = "persist")]
+pub(crate) fn leaves_by_group<'a>(
+  groups: impl IntoIterator<Item = (Vec<Measure<'a>>, Vec<Prop<'a>>)>,
+) -> Vec<(Vec<Measure<'a>>, Vec<Prop<'a>>)> {
+  let mut walker = Walker::default();
+  groups
+    .into_iter()
+    .map(|(measures, props)| {
+      for m in measures {
+        walker.measure(m);
+      }
+      for p in props {
+        walker.prop(p);
+      }
+      (
+        std::mem::take(&mut walker.unbound_measures),
+        std::mem::take(&mut walker.unbound_props),
+      )
+    })
+    .collect()
+}
+
+/// Node addresses of the unbound measures and propositions that `measures` and `props` depend
+/// on, each listed once.
+#[cfg(feature = "z3")]
+pub(crate) fn free_variables<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+) -> Vec<usize> {
+  let walker = Walker::over(measures, props);
+  let measures = walker.unbound_measures.iter().map(|x| x.addr());
+  measures
+    .chain(walker.unbound_props.iter().map(|x| x.addr()))
+    .collect()
+}
+
+/// Measures that `measures` and `props` are or depend on whose node address is in `addrs`, by
+/// address.
+pub(crate) fn find_measures<'a>(
+  measures: impl IntoIterator<Item = Measure<'a>>,
+  props: impl IntoIterator<Item = Prop<'a>>,
+  addrs: impl IntoIterator<Item = usize>,
+) -> HashMap<usize, Measure<'a>> {
+  let mut walker = Walker {
+    wanted: addrs.into_iter().collect(),
+    ..Walker::default()
+  };
+  for m in measures {
+    walker.measure(m);
+  }
+  for p in props {
+    walker.prop(p);
+  }
+  walker.found
+}
+
+/// Collects the unbound leaves and named constants of expressions, visiting shared nodes once.
+#[derive(Default)]
+struct Walker<'a> {
+  visited: HashSet<usize>,
+  unbound_measures: Vec<Measure<'a>>,
+  unbound_props: Vec<Prop<'a>>,
+  named: Vec<Measure<'a>>,
+
+  /// Addresses of the measures to collect into `found`.
+  wanted: HashSet<usize>,
+  found: HashMap<usize, Measure<'a>>,
+}
+
+impl<'a> Walker<'a> {
+  fn over(
+    measures: impl IntoIterator<Item = Measure<'a>>,
+    props: impl IntoIterator<Item = Prop<'a>>,
+  ) -> Self {
+    let mut walker = Walker::default();
+    for m in measures {
+      walker.measure(m);
+    }
+    for p in props {
+      walker.prop(p);
+    }
+    walker
+  }
+
+  fn measure(&mut self, m: Measure<'a>) {
+    use MeasureVariant as V;
+    if !self.visited.insert(m.addr()) {
+      return;
+    }
+    if self.wanted.contains(&m.addr()) {
+      self.found.insert(m.addr(), m);
+    }
+    match *m.variant {
+      V::Unbound => self.unbound_measures.push(m),
+      V::Named(_) => self.named.push(m),
+      V::Const(..) => {}
+      V::Add(l, r) | V::Sub(l, r) | V::Mul(l, r) | V::Div(l, r) => {
+        self.measure(l);
+        self.measure(r);
+      }
+      V::Select(cond, l, r) => {
+        self.prop(cond);
+        self.measure(l);
+        self.measure(r);
+      }
+    }
+  }
+
+  fn prop(&mut self, p: Prop<'a>) {
+    use PropVariant as V;
+    if !self.visited.insert(p.addr()) {
+      return;
+    }
+    match *p.variant {
+      V::Unbound => self.unbound_props.push(p),
+      V::Const(_) => {}
+      V::Eq(l, r) | V::Lt(l, r) | V::Le(l, r) | V::Gt(l, r) | V::Ge(l, r) => {
+        self.measure(l);
+        self.measure(r);
+      }
+      V::Or(l, r) | V::And(l, r) => {
+        self.prop(l);
+        self.prop(r);
+      }
+      V::Not(x) => self.prop(x),
+    }
+  }
+}
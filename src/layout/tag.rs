@@ -0,0 +1,36 @@
+//! Application data attached to widgets, to map solved layouts back to domain objects.
+
+use std::{any::Any, cell::RefCell, sync::Arc};
+
+/// Data attached to a widget with `LayoutBuilder::set_tag`.
+pub type Tag = Arc<dyn Any + Send + Sync>;
+
+thread_local! {
+  /// Tag of the widget whose painter runs on this thread.
+  static CURRENT: RefCell<Option<Tag>> = RefCell::new(None);
+}
+
+/// The tag of the widget being painted, if it has one of type `T`. Only set while the painter
+/// or `RawWidget::on_solved` of a widget runs, including painters on the rayon thread pool.
+pub fn current_tag<T: Any + Send + Sync>() -> Option<Arc<T>> {
+  CURRENT
+    .with(|x| x.borrow().clone())
+    .and_then(|x| x.downcast().ok())
+}
+
+/// Runs `f` with `tag` as the current tag of this thread. The previous tag is restored even if
+/// `f` panics.
+pub(crate) fn with_current<R>(tag: Option<Tag>, f: impl FnOnce() -> R) -> R {
+  let _restore = Restore(CURRENT.with(|x| x.replace(tag)));
+  f()
+}
+
+/// Puts the tag it holds back as the current tag when dropped.
+struct Restore(Option<Tag>);
+
+impl Drop for Restore {
+  fn drop(&mut self) {
+    let previous = self.0.take();
+    CURRENT.with(|x| *x.borrow_mut() = previous);
+  }
+}
@@ -0,0 +1,85 @@
+//! Reusable bundles of widgets and constraints, parameterized by name, like the grids and
+//! spacing rules of a design system.
+
+use std::collections::HashMap;
+
+use super::{context::LayoutContext, error::LayoutError, measure::Measure, widget::Parts};
+
+/// Values bound to the parameters of a template. Parameters are constants like a gap or a
+/// number of columns, or measures of the layout the template is instantiated in.
+#[derive(Clone, Debug, Default)]
+pub struct Params<'a> {
+  values: HashMap<String, f64>,
+  measures: HashMap<String, Measure<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> Params<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with(mut self, name: &str, value: f64) -> Self {
+    self.values.insert(name.to_string(), value);
+    self
+  }
+
+  pub fn with_measure(mut self, name: &str, measure: Measure<'a>) -> Self {
+    self.measures.insert(name.to_string(), measure);
+    self
+  }
+
+  /// The constant bound to `name`.
+  pub fn value(&self, name: &str) -> Result<f64, LayoutError> {
+    self
+      .values
+      .get(name)
+      .copied()
+      .ok_or_else(|| LayoutError::Unbound {
+        name: name.to_string(),
+      })
+  }
+
+  /// The constant bound to `name` as a whole number, like a count of columns.
+  pub fn count(&self, name: &str) -> Result<usize, LayoutError> {
+    let value = self.value(name)?;
+    if value < 0.0 || value.fract() != 0.0 {
+      return Err(LayoutError::BadConst { value });
+    }
+    Ok(value as usize)
+  }
+
+  /// The measure bound to `name`, or the constant bound to it as a measure.
+  pub fn measure(&self, ctx: &'a LayoutContext, name: &str) -> Result<Measure<'a>, LayoutError> {
+    match self.measures.get(name) {
+      Some(&m) => Ok(m),
+      None => Measure::new_const(ctx, self.value(name)?),
+    }
+  }
+}
+
+/// A function from parameters to widgets and the constraints between them, instantiated with
+/// `LayoutBuilder::push_template`. Unlike a `CompositeWidget`, a template is not consumed, so
+/// it can be instantiated any number of times with different parameters.
+///
+/// Closures taking the context and the parameters are templates.
+pub trait ConstraintTemplate<'a> {
+  fn instantiate(
+    &self,
+    ctx: &'a LayoutContext,
+    params: &Params<'a>,
+  ) -> Result<Parts<'a>, LayoutError>;
+}
+
+impl<'a, F> ConstraintTemplate<'a> for F
+where
+  F: Fn(&'a LayoutContext, &Params<'a>) -> Result<Parts<'a>, LayoutError>,
+{
+  fn instantiate(
+    &self,
+    ctx: &'a LayoutContext,
+    params: &Params<'a>,
+  ) -> Result<Parts<'a>, LayoutError> {
+    self(ctx, params)
+  }
+}
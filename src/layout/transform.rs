@@ -0,0 +1,34 @@
+//! Transforms from the local coordinates of containers to absolute coordinates.
+
+/// A translation from local to absolute coordinates. Nested containers stack theirs, so a
+/// widget is painted with the translations of all its containers combined.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+  pub dx: f64,
+  pub dy: f64,
+}
+
+impl Transform {
+  pub const IDENTITY: Transform = Transform { dx: 0.0, dy: 0.0 };
+
+  pub fn translate(dx: f64, dy: f64) -> Self {
+    Transform { dx, dy }
+  }
+
+  /// The transform of `inner` followed by `self`, for a container nested in the container of
+  /// `self`.
+  pub fn then(self, inner: Transform) -> Self {
+    Transform {
+      dx: self.dx + inner.dx,
+      dy: self.dy + inner.dy,
+    }
+  }
+
+  pub fn x(&self, x: f64) -> f64 {
+    x + self.dx
+  }
+
+  pub fn y(&self, y: f64) -> f64 {
+    y + self.dy
+  }
+}
@@ -0,0 +1,125 @@
+//! Lengths in physical units, so layouts for print and for screens share the same code.
+//!
+//! Measures are in canonical units, device pixels of the target. A `Units` of the builder
+//! converts lengths to them with its DPI and scale when the measure is made.
+
+use super::{context::LayoutContext, error::LayoutError, measure::Measure};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unit {
+  /// CSS pixels, 1/96 of an inch.
+  Px,
+  /// Points, 1/72 of an inch.
+  Pt,
+  Mm,
+  In,
+  /// The font size of `Units::em`.
+  Em,
+}
+
+/// A length in some unit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Length {
+  pub value: f64,
+  pub unit: Unit,
+}
+
+#[allow(dead_code)]
+impl Length {
+  pub fn new(value: f64, unit: Unit) -> Self {
+    Self { value, unit }
+  }
+
+  pub fn px(value: f64) -> Self {
+    Self::new(value, Unit::Px)
+  }
+
+  pub fn pt(value: f64) -> Self {
+    Self::new(value, Unit::Pt)
+  }
+
+  pub fn mm(value: f64) -> Self {
+    Self::new(value, Unit::Mm)
+  }
+
+  pub fn inches(value: f64) -> Self {
+    Self::new(value, Unit::In)
+  }
+
+  pub fn em(value: f64) -> Self {
+    Self::new(value, Unit::Em)
+  }
+}
+
+/// How lengths map to canonical units. The default is a screen of 96 DPI at scale 1, where
+/// pixels are canonical units, with 16px text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Units {
+  /// Canonical units per inch, before `scale`. 72 lays out in points, like PDF.
+  pub dpi: f64,
+
+  /// Factor applied on top of `dpi`, like the device pixel ratio of a screen.
+  pub scale: f64,
+
+  /// The font size that `Unit::Em` refers to.
+  pub em: Length,
+}
+
+impl Default for Units {
+  fn default() -> Self {
+    Self {
+      dpi: 96.0,
+      scale: 1.0,
+      em: Length::px(16.0),
+    }
+  }
+}
+
+#[allow(dead_code)]
+impl Units {
+  /// Lengths for print, where a canonical unit is a point.
+  pub fn print() -> Self {
+    Self {
+      dpi: 72.0,
+      ..Self::default()
+    }
+  }
+
+  /// Canonical units in a `unit`.
+  pub fn per(&self, unit: Unit) -> f64 {
+    let inch = self.dpi * self.scale;
+    match unit {
+      Unit::Px => inch / 96.0,
+      Unit::Pt => inch / 72.0,
+      Unit::Mm => inch / 25.4,
+      Unit::In => inch,
+      Unit::Em => match self.em.unit {
+        // A font size in ems would never resolve.
+        Unit::Em => 16.0 * inch / 96.0,
+        unit => self.em.value * self.per(unit),
+      },
+    }
+  }
+
+  /// `length` in canonical units.
+  pub fn to_canonical(&self, length: Length) -> f64 {
+    length.value * self.per(length.unit)
+  }
+
+  /// A canonical value back in `unit`, like to report a solved measure in millimeters.
+  pub fn from_canonical(&self, value: f64, unit: Unit) -> Length {
+    Length::new(value / self.per(unit), unit)
+  }
+
+  /// A constant measure of `length` in canonical units.
+  pub fn measure<'a>(
+    &self,
+    ctx: &'a LayoutContext,
+    length: Length,
+  ) -> Result<Measure<'a>, LayoutError> {
+    Measure::new_const(ctx, self.to_canonical(length))
+  }
+}
@@ -1,9 +1,354 @@
-use anyhow::Result;
+use super::{
+  error::PaintError,
+  geometry::{Margins, Point, RectMeasures},
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+};
 
-use super::{measure::Measure, prop::Prop};
+/// A painter that can run on another thread, with the values of the measures of its widget.
+pub type SendPainter<'a> = Box<dyn FnMut(&[f64]) -> Result<(), PaintError> + Send + 'a>;
 
 pub trait RawWidget<'a> {
   fn measures(&self) -> Vec<Measure<'a>>;
   fn constraints(&self) -> Vec<Prop<'a>>;
-  fn paint(self: Box<Self>, measures: &[f64]) -> Result<()>;
+  /// Paints the widget with the solved values of `measures`. Widgets may be painted more than
+  /// once, like into targets of different scales.
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError>;
+
+  /// Like `paint`, for a widget laid out in the local coordinates of containers, which
+  /// `transform` takes to absolute coordinates. Widgets that do not override this are painted
+  /// with their local metrics.
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let _ = transform;
+    self.paint(measures, target)
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    vec![]
+  }
+
+  /// Called with the solved values of `measures` before any widget is painted, like to cache
+  /// geometry or register accessibility data. Children of containers get their local values.
+  fn on_solved(&mut self, measures: &[f64]) {
+    let _ = measures;
+  }
+
+  /// The painter of the widget, for widgets whose painter is `Send`. With the `parallel`
+  /// feature, these are run on the rayon thread pool. Others are painted with `paint` on the
+  /// calling thread.
+  fn send_painter(&mut self) -> Option<&mut SendPainter<'a>> {
+    None
+  }
+
+  /// The area of the widget that `Solution::hit_test` finds, if it takes pointer events.
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    None
+  }
+
+  /// The height the content of the widget needs at the solved `width` of its bounds, like of
+  /// text that wraps. See `LayoutBuilder::fit_intrinsic_sizes`.
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    let _ = width;
+    None
+  }
+
+  /// Constraints from the content of the widget at the tentative values of `measures`, like a
+  /// minimum width for a label at the height it was given. See
+  /// `LayoutBuilder::measure_and_arrange`.
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    let _ = measures;
+    vec![]
+  }
+
+  /// Named points that connectors, callouts and alignments attach to, like `out-east`. These are
+  /// the anchors of `bounds` by default.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    self.bounds().map(|x| x.anchors()).unwrap_or_default()
+  }
+}
+
+/// A widget whose painter takes typed metrics instead of the values of its measures. Every
+/// `Widget` is a `RawWidget`, so it can be pushed to a `LayoutBuilder`.
+pub trait Widget<'a> {
+  type Metrics;
+
+  fn measures(&self) -> Vec<Measure<'a>>;
+  fn constraints(&self) -> Vec<Prop<'a>>;
+
+  /// The metrics of the widget from the solved values of `measures`, in order.
+  fn decode(&self, values: &[f64]) -> Self::Metrics;
+
+  fn paint(
+    &mut self,
+    metrics: Self::Metrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError>;
+
+  /// `metrics` decoded in the local coordinates of containers, taken to absolute coordinates
+  /// by `transform`. Metrics without coordinates are kept as they are.
+  fn transform(metrics: Self::Metrics, transform: Transform) -> Self::Metrics {
+    let _ = transform;
+    metrics
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    vec![]
+  }
+
+  /// See `RawWidget::on_solved`.
+  fn on_solved(&mut self, metrics: &Self::Metrics) {
+    let _ = metrics;
+  }
+
+  /// See `RawWidget::bounds`.
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    None
+  }
+
+  /// See `RawWidget::intrinsic_height_for`.
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    let _ = width;
+    None
+  }
+
+  /// See `RawWidget::content_constraints`.
+  fn content_constraints(&self, metrics: &Self::Metrics) -> Vec<Prop<'a>> {
+    let _ = metrics;
+    vec![]
+  }
+
+  /// See `RawWidget::anchors`.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    Widget::bounds(self)
+      .map(|x| x.anchors())
+      .unwrap_or_default()
+  }
+}
+
+impl<'a, W: Widget<'a>> RawWidget<'a> for W {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    Widget::measures(self)
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    Widget::constraints(self)
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    let metrics = self.decode(measures);
+    Widget::paint(self, metrics, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let metrics = W::transform(self.decode(measures), transform);
+    Widget::paint(self, metrics, target)
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    Widget::objectives(self)
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    let metrics = self.decode(measures);
+    Widget::on_solved(self, &metrics)
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Widget::bounds(self)
+  }
+
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    Widget::intrinsic_height_for(self, width)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    Widget::content_constraints(self, &self.decode(measures))
+  }
+
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    Widget::anchors(self)
+  }
+}
+
+/// A widget that always has bounds, so generic helpers like `distribute_horizontally` work
+/// with any such widget, not only rectangles.
+pub trait BoundedWidget<'a>: RawWidget<'a> {
+  fn rect(&self) -> RectMeasures<'a>;
+
+  fn left(&self) -> Measure<'a> {
+    self.rect().left
+  }
+
+  fn right(&self) -> Measure<'a> {
+    self.rect().right
+  }
+
+  fn top(&self) -> Measure<'a> {
+    self.rect().top
+  }
+
+  fn bottom(&self) -> Measure<'a> {
+    self.rect().bottom
+  }
+
+  fn width(&self) -> Measure<'a> {
+    self.rect().width()
+  }
+
+  fn height(&self) -> Measure<'a> {
+    self.rect().height()
+  }
+
+  fn center(&self) -> Point<'a> {
+    self.rect().center()
+  }
+
+  /// Holds when the widget lies entirely inside `that`.
+  fn within(&self, that: &dyn BoundedWidget<'a>) -> Prop<'a> {
+    that.rect().contains(&self.rect())
+  }
+
+  /// Space that `distribute_horizontally` and the stacking helpers keep around the widget. None
+  /// by default, see `Margined`.
+  fn margins(&self) -> Margins<'a> {
+    Margins::zero(self.rect().left.ctx)
+  }
+
+  /// The rectangle with the margins of the widget around it.
+  fn margin_box(&self) -> RectMeasures<'a> {
+    self.margins().around(&self.rect())
+  }
+}
+
+/// The bounding box of `group`, or `None` if it is empty.
+pub fn group_bounds<'a>(group: &[&dyn BoundedWidget<'a>]) -> Option<RectMeasures<'a>> {
+  group.iter().map(|x| x.rect()).reduce(|a, b| a.union(&b))
+}
+
+/// The center of the bounding box of `group`, or `None` if it is empty.
+pub fn group_center<'a>(group: &[&dyn BoundedWidget<'a>]) -> Option<Point<'a>> {
+  group_bounds(group).map(|x| x.center())
+}
+
+/// Places `group` from left to right in order, with equal gaps between the margin boxes of
+/// neighbours.
+pub fn distribute_horizontally<'a>(group: &[&dyn BoundedWidget<'a>]) -> Vec<Prop<'a>> {
+  distribute(group, |x| {
+    let margin_box = x.margin_box();
+    (margin_box.left, margin_box.right)
+  })
+}
+
+/// Places `group` from top to bottom in order, with equal gaps between the margin boxes of
+/// neighbours.
+pub fn distribute_vertically<'a>(group: &[&dyn BoundedWidget<'a>]) -> Vec<Prop<'a>> {
+  distribute(group, |x| {
+    let margin_box = x.margin_box();
+    (margin_box.top, margin_box.bottom)
+  })
+}
+
+/// How the margins of neighbours that meet combine in `stack_vertically`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MarginCollapse {
+  /// Both margins are kept, and the space between the neighbours is their sum.
+  Separate,
+
+  /// Margins collapse as vertical margins of blocks do in CSS: the space is the largest
+  /// positive margin plus the most negative one.
+  Collapse,
+}
+
+/// Places `group` in a row from left to right, each widget right after the margin of the
+/// previous one, so that neighbours are apart by the sum of their margins.
+pub fn stack_horizontally<'a>(group: &[&dyn BoundedWidget<'a>]) -> Vec<Prop<'a>> {
+  group
+    .windows(2)
+    .map(|x| {
+      let (prev, next) = (x[0], x[1]);
+      let space = prev.margins().right + next.margins().left;
+      next.left().prop_eq(prev.right() + space).hard()
+    })
+    .collect()
+}
+
+/// Places `group` in a column from top to bottom, each widget right after the margin of the
+/// previous one, combining the margins by `collapse`.
+pub fn stack_vertically<'a>(
+  group: &[&dyn BoundedWidget<'a>],
+  collapse: MarginCollapse,
+) -> Vec<Prop<'a>> {
+  group
+    .windows(2)
+    .map(|x| {
+      let (prev, next) = (x[0], x[1]);
+      let (above, below) = (prev.margins().bottom, next.margins().top);
+      let space = match collapse {
+        MarginCollapse::Separate => above + below,
+        MarginCollapse::Collapse => {
+          let zero = Measure::zero(above.ctx);
+          above.max(below).max(zero) + above.min(below).min(zero)
+        }
+      };
+      next.top().prop_eq(prev.bottom() + space).hard()
+    })
+    .collect()
+}
+
+fn distribute<'a>(
+  group: &[&dyn BoundedWidget<'a>],
+  span: impl Fn(&dyn BoundedWidget<'a>) -> (Measure<'a>, Measure<'a>),
+) -> Vec<Prop<'a>> {
+  let gaps = group
+    .windows(2)
+    .map(|x| span(x[1]).0 - span(x[0]).1)
+    .collect::<Vec<_>>();
+  let zero = match gaps.first() {
+    Some(x) => Measure::zero(x.ctx),
+    None => return vec![],
+  };
+  let mut constraints = vec![gaps[0].prop_ge(zero).hard()];
+  for x in gaps.windows(2) {
+    constraints.push(x[0].prop_eq(x[1]).hard());
+  }
+  constraints
+}
+
+/// A reusable group of widgets, like a labeled box, pushed with
+/// `LayoutBuilder::push_composite` as its parts and the constraints between them.
+pub trait CompositeWidget<'a> {
+  fn expand(self) -> Parts<'a>;
+}
+
+/// What a `CompositeWidget` expands into.
+#[derive(Default)]
+pub struct Parts<'a> {
+  pub widgets: Vec<Box<dyn RawWidget<'a> + 'a>>,
+
+  /// Constraints that place the widgets relative to each other. These are pushed as they are,
+  /// so internal constraints that must hold should be `hard`.
+  pub constraints: Vec<Prop<'a>>,
+}
+
+impl<'a> Parts<'a> {
+  pub fn push_widget<W: RawWidget<'a> + 'a>(&mut self, widget: W) {
+    self.widgets.push(Box::new(widget));
+  }
+
+  pub fn push_constraint(&mut self, prop: Prop<'a>) {
+    self.constraints.push(prop);
+  }
 }
@@ -1,2 +1,12 @@
+// Lets derived impls refer to this crate as `liquid_layout` from inside it too.
+extern crate self as liquid_layout;
+
+pub mod editor;
+pub mod fallback;
+#[cfg(feature = "z3")]
+pub mod generate;
+pub mod imposition;
 pub mod layout;
+#[cfg(feature = "tokens")]
+pub mod tokens;
 pub mod widgets;
@@ -0,0 +1,98 @@
+//! Design tokens, such as spacing scales, radii and breakpoints, as named constants.
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::layout::{context::LayoutContext, measure::Measure};
+
+#[derive(Error, Debug)]
+pub enum TokenError {
+  #[error("invalid token file: {0}")]
+  Parse(#[from] serde_json::Error),
+  #[error("token file must be a JSON object")]
+  NotAnObject,
+}
+
+/// Dimension tokens loaded from a design-token JSON file.
+///
+/// Groups nest as objects and token names join the path with dots, so `{"spacing": {"md": 8}}`
+/// defines `spacing.md`. A token is either a number or an object with a `$value`, as in the W3C
+/// design tokens format. Values are numbers or strings in `px`. Other tokens, like colors, are
+/// left out.
+#[derive(Clone, Debug, Default)]
+pub struct Tokens {
+  values: Vec<(String, f64)>,
+}
+
+#[allow(dead_code)]
+impl Tokens {
+  pub fn from_json(json: &str) -> Result<Self, TokenError> {
+    let root = match serde_json::from_str(json)? {
+      Value::Object(x) => x,
+      _ => return Err(TokenError::NotAnObject),
+    };
+    let mut tokens = Tokens::default();
+    tokens.collect("", &root);
+    Ok(tokens)
+  }
+
+  fn collect(&mut self, prefix: &str, group: &Map<String, Value>) {
+    for (key, value) in group {
+      if key.starts_with('$') {
+        continue;
+      }
+      let name = if prefix.is_empty() {
+        key.clone()
+      } else {
+        format!("{}.{}", prefix, key)
+      };
+      match value {
+        Value::Object(x) => match x.get("$value") {
+          Some(value) => {
+            if let Some(value) = dimension(value) {
+              self.values.push((name, value));
+            }
+          }
+          None => self.collect(&name, x),
+        },
+        _ => {
+          if let Some(value) = dimension(value) {
+            self.values.push((name, value));
+          }
+        }
+      }
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<f64> {
+    self
+      .values
+      .iter()
+      .find(|(x, _)| x == name)
+      .map(|&(_, value)| value)
+  }
+
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.values.iter().map(|(name, _)| name.as_str())
+  }
+
+  /// Defines every token as a named constant of `ctx`, replacing earlier values.
+  pub fn define_all(&self, ctx: &LayoutContext) {
+    for (name, value) in &self.values {
+      ctx.define(name, *value);
+    }
+  }
+
+  /// The named constant of the token `name`, or `None` if there is no such token.
+  pub fn measure<'a>(&self, ctx: &'a LayoutContext, name: &str) -> Option<Measure<'a>> {
+    self.get(name).map(|_| Measure::named_const(ctx, name))
+  }
+}
+
+fn dimension(value: &Value) -> Option<f64> {
+  match value {
+    Value::Number(x) => x.as_f64(),
+    Value::String(x) => x.trim().strip_suffix("px")?.trim().parse().ok(),
+    _ => None,
+  }
+}
@@ -0,0 +1,196 @@
+use crate::layout::{
+  angle::Angle,
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+pub type ArcPainter<'a> = Box<dyn Painter<ArcMetrics> + 'a>;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArcMetrics {
+  pub cx: f64,
+  pub cy: f64,
+  pub radius: f64,
+
+  /// Angles in radians, like those of `Angle`.
+  pub start: f64,
+  pub end: f64,
+}
+
+impl ArcMetrics {
+  /// The arc moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    ArcMetrics {
+      cx: transform.x(self.cx),
+      cy: transform.y(self.cy),
+      ..self
+    }
+  }
+}
+
+/// A circular arc running clockwise on screen from `start` to `end`, like a curved connector or
+/// the track of a gauge. `end` is kept within a full turn after `start`.
+///
+/// Endpoints are linear as long as the angles or the radius are constants. See `Angle::sin`.
+pub struct Arc<'a> {
+  pub center: Point<'a>,
+  pub radius: Measure<'a>,
+  pub start: Angle<'a>,
+  pub end: Angle<'a>,
+
+  pub painter: ArcPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Arc<'a> {
+  pub fn new(
+    center: Point<'a>,
+    radius: Measure<'a>,
+    start: Angle<'a>,
+    end: Angle<'a>,
+    painter: ArcPainter<'a>,
+  ) -> Self {
+    Self {
+      center,
+      radius,
+      start,
+      end,
+      painter,
+    }
+  }
+
+  /// An arc of unbound center and radius between the constant angles, in degrees.
+  pub fn unbound(
+    ctx: &'a LayoutContext,
+    start_degrees: f64,
+    end_degrees: f64,
+    painter: ArcPainter<'a>,
+  ) -> Self {
+    let center = Point {
+      x: Measure::new_unbound(ctx),
+      y: Measure::new_unbound(ctx),
+    };
+    Self::new(
+      center,
+      Measure::new_unbound(ctx),
+      Angle::degrees(ctx, start_degrees),
+      Angle::degrees(ctx, end_degrees),
+      painter,
+    )
+  }
+
+  /// The angle from `start` to `end`.
+  pub fn sweep(&self) -> Angle<'a> {
+    Angle::from_radians(self.end.radians - self.start.radians)
+  }
+
+  pub fn start_point(&self) -> Point<'a> {
+    Point::on_circle(self.center, self.radius, self.start)
+  }
+
+  pub fn end_point(&self) -> Point<'a> {
+    Point::on_circle(self.center, self.radius, self.end)
+  }
+
+  /// The point halfway along the arc.
+  pub fn midpoint(&self) -> Point<'a> {
+    let middle = Angle::from_radians((self.start.radians + self.end.radians) / 2.0);
+    Point::on_circle(self.center, self.radius, middle)
+  }
+
+  /// Holds when the arc passes through the constant angle of `degrees`.
+  pub fn covers(&self, degrees: f64) -> Prop<'a> {
+    let ctx = self.radius.ctx;
+    let turn = Angle::degrees(ctx, 360.0).radians;
+    let zero = Measure::zero(ctx);
+    // The angle from `start`, wrapped into a single turn for starts in `[-2π, 4π)`.
+    let offset = Angle::degrees(ctx, degrees).radians - self.start.radians;
+    let offset = offset.prop_lt(zero).select(offset + turn, offset);
+    let offset = offset.prop_lt(zero).select(offset + turn, offset);
+    let offset = offset.prop_ge(turn).select(offset - turn, offset);
+    offset.prop_le(self.sweep().radians)
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Arc<'a> {
+  /// The box around the endpoints, grown to the extremes of the circle that the arc passes.
+  fn rect(&self) -> RectMeasures<'a> {
+    let (start, end) = (self.start_point(), self.end_point());
+    let (cx, cy, r) = (self.center.x, self.center.y, self.radius);
+    let ends = RectMeasures::around(&[start, end]).unwrap();
+    RectMeasures {
+      left: self.covers(180.0).select(cx - r, ends.left),
+      right: self.covers(0.0).select(cx + r, ends.right),
+      top: self.covers(270.0).select(cy - r, ends.top),
+      bottom: self.covers(90.0).select(cy + r, ends.bottom),
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Arc<'a> {
+  type Metrics = ArcMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![
+      self.center.x,
+      self.center.y,
+      self.radius,
+      self.start.radians,
+      self.end.radians,
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let ctx = self.radius.ctx;
+    let zero = Measure::zero(ctx);
+    let sweep = self.sweep().radians;
+    vec![
+      self.radius.prop_ge(zero).hard(),
+      sweep.prop_ge(zero).hard(),
+      sweep.prop_le(Angle::degrees(ctx, 360.0).radians).hard(),
+    ]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  /// The endpoints `start` and `end`, the `middle` of the arc and the `center` of its circle.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    vec![
+      ("start", self.start_point()),
+      ("end", self.end_point()),
+      ("middle", self.midpoint()),
+      ("center", self.center),
+    ]
+  }
+
+  fn decode(&self, values: &[f64]) -> ArcMetrics {
+    ArcMetrics {
+      cx: values[0],
+      cy: values[1],
+      radius: values[2],
+      start: values[3],
+      end: values[4],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: ArcMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: ArcMetrics, transform: Transform) -> ArcMetrics {
+    metrics.transformed(transform)
+  }
+}
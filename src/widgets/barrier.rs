@@ -0,0 +1,58 @@
+use crate::layout::{geometry::Point, measure::Measure, prop::Prop};
+
+use super::{Guideline, GuidelineAxis, RectangleError, RectangleMeasures};
+
+/// The edge of a group that a `Barrier` follows.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BarrierSide {
+  Left,
+  Right,
+  Top,
+  Bottom,
+}
+
+/// A guideline at the outermost edge of a group of rectangles on one side, like the barriers of
+/// Android's ConstraintLayout. A barrier on the right of a column of labels follows the widest
+/// label, whichever it ends up being.
+#[derive(Copy, Clone, Debug)]
+pub struct Barrier<'a> {
+  pub side: BarrierSide,
+  pub position: Measure<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Barrier<'a> {
+  pub fn new(side: BarrierSide, group: &[&RectangleMeasures<'a>]) -> Result<Self, RectangleError> {
+    let position = match side {
+      BarrierSide::Left => RectangleMeasures::group_leftmost(group)?,
+      BarrierSide::Right => RectangleMeasures::group_rightmost(group)?,
+      BarrierSide::Top => RectangleMeasures::group_topmost(group)?,
+      BarrierSide::Bottom => RectangleMeasures::group_bottommost(group)?,
+    };
+    Ok(Self { side, position })
+  }
+
+  /// The barrier as a guideline, to be pushed as a widget or compared against.
+  pub fn guideline(&self) -> Guideline<'a> {
+    let axis = match self.side {
+      BarrierSide::Left | BarrierSide::Right => GuidelineAxis::Vertical,
+      BarrierSide::Top | BarrierSide::Bottom => GuidelineAxis::Horizontal,
+    };
+    Guideline::new(axis, self.position)
+  }
+
+  /// Holds when `point` lies on the barrier.
+  pub fn aligns(&self, point: Point<'a>) -> Prop<'a> {
+    self.guideline().aligns(point)
+  }
+
+  /// Holds when `point` lies on the barrier or outside the group, like to the right of a barrier
+  /// on the right.
+  pub fn clears(&self, point: Point<'a>) -> Prop<'a> {
+    match self.side {
+      BarrierSide::Left | BarrierSide::Top => self.guideline().before(point),
+      BarrierSide::Right | BarrierSide::Bottom => self.guideline().after(point),
+    }
+  }
+}
@@ -0,0 +1,244 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget},
+};
+
+use super::{Rectangle, RectangleMetrics};
+
+pub type BreadcrumbsPainter<'a> = Box<dyn Painter<BreadcrumbsMetrics> + 'a>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BreadcrumbsMetrics {
+  pub frame: RectangleMetrics,
+
+  /// Indices of the crumbs shown in the trail.
+  pub visible: Vec<usize>,
+
+  /// Indices of the crumbs collapsed into the ellipsis.
+  pub collapsed: Vec<usize>,
+}
+
+pub struct Breadcrumb<'a> {
+  pub rect: Rectangle<'a>,
+  pub visible: Prop<'a>,
+}
+
+/// A trail of crumbs separated by `separator` units, collapsing middle crumbs into an ellipsis
+/// when space is tight.
+///
+/// The first and the last crumbs are always visible. Collapsed crumbs form a contiguous run
+/// right after the first crumb, and as few crumbs as possible are collapsed.
+#[derive(ReflectMeasures)]
+pub struct Breadcrumbs<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub separator: f64,
+  #[reflect(nested)]
+  pub ellipsis: Rectangle<'a>,
+  crumbs: Vec<Breadcrumb<'a>>,
+
+  pub painter: BreadcrumbsPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Breadcrumbs<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    ellipsis: Rectangle<'a>,
+    separator: f64,
+    painter: BreadcrumbsPainter<'a>,
+  ) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      separator,
+      ellipsis,
+      crumbs: vec![],
+      painter,
+    }
+  }
+
+  /// Appends a crumb. Its rectangle should have a fixed width.
+  pub fn push_crumb(&mut self, rect: Rectangle<'a>) {
+    let visible = Prop::new_unbound(rect.left.ctx);
+    self.crumbs.push(Breadcrumb { rect, visible });
+  }
+
+  pub fn crumbs(&self) -> &[Breadcrumb<'a>] {
+    &self.crumbs
+  }
+
+  fn is_middle(&self, index: usize) -> bool {
+    index != 0 && index + 1 != self.crumbs.len()
+  }
+
+  fn crumb_visible(&self, index: usize) -> Prop<'a> {
+    if self.is_middle(index) {
+      self.crumbs[index].visible
+    } else {
+      Prop::new_const(self.left.ctx, true)
+    }
+  }
+
+  /// Holds when at least one crumb is collapsed into the ellipsis.
+  pub fn collapsed(&self) -> Prop<'a> {
+    // Collapsed crumbs start right after the first one.
+    if self.crumbs.len() > 2 {
+      !self.crumbs[1].visible
+    } else {
+      Prop::new_const(self.left.ctx, false)
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Breadcrumbs<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> RawWidget<'a> for Breadcrumbs<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let ctx = self.left.ctx;
+    let mut measures = vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+    ];
+    measures.extend(RawWidget::measures(&self.ellipsis));
+    for (i, crumb) in self.crumbs.iter().enumerate() {
+      measures.push(
+        self
+          .crumb_visible(i)
+          .select(Measure::new_const(ctx, 1.0).unwrap(), Measure::zero(ctx)),
+      );
+      measures.extend(RawWidget::measures(&crumb.rect));
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let ctx = self.left.ctx;
+    let zero = Measure::zero(ctx);
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right),
+      (self.top + self.height).prop_eq(self.bottom),
+    ];
+    constraints.extend(self.ellipsis.constraints());
+
+    let collapsed = self.collapsed();
+    let mut cursor = self.left;
+    for (i, crumb) in self.crumbs.iter().enumerate() {
+      constraints.extend(crumb.rect.constraints());
+      constraints.push(crumb.rect.top.prop_eq(self.top).hard());
+      constraints.push(crumb.rect.left.prop_eq(cursor).hard());
+
+      if self.is_middle(i) {
+        // Once a middle crumb is shown, every crumb after it is shown as well.
+        if self.is_middle(i + 1) {
+          constraints.push((!crumb.visible | self.crumbs[i + 1].visible).hard());
+        }
+        constraints.push(crumb.visible.with_weight(1));
+      }
+
+      cursor = cursor
+        + self
+          .crumb_visible(i)
+          .select(crumb.rect.width + self.separator, zero);
+
+      if i == 0 {
+        constraints.push(self.ellipsis.left.prop_eq(cursor).hard());
+        constraints.push(self.ellipsis.top.prop_eq(self.top).hard());
+        cursor = cursor + collapsed.select(self.ellipsis.width + self.separator, zero);
+      }
+    }
+
+    constraints.push((cursor - self.separator).prop_le(self.right).hard());
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame = RectangleMetrics {
+      left: measures[0],
+      right: measures[1],
+      top: measures[2],
+      bottom: measures[3],
+      width: measures[4],
+      height: measures[5],
+    }
+    .transformed(transform);
+
+    let ellipsis_len = RawWidget::measures(&self.ellipsis).len();
+    let ellipsis_measures = &measures[6..6 + ellipsis_len];
+    let mut offset = 6 + ellipsis_len;
+
+    let mut visible = vec![];
+    let mut collapsed = vec![];
+    for (i, crumb) in self.crumbs.iter_mut().enumerate() {
+      let len = RawWidget::measures(&crumb.rect).len();
+      if measures[offset] != 0.0 {
+        visible.push(i);
+        RawWidget::paint_at(
+          &mut crumb.rect,
+          &measures[offset + 1..offset + 1 + len],
+          transform,
+          target,
+        )?;
+      } else {
+        collapsed.push(i);
+      }
+      offset += 1 + len;
+    }
+
+    if !collapsed.is_empty() {
+      RawWidget::paint_at(&mut self.ellipsis, ellipsis_measures, transform, target)?;
+    }
+
+    self.painter.paint(
+      BreadcrumbsMetrics {
+        frame,
+        visible,
+        collapsed,
+      },
+      target,
+    )
+  }
+}
@@ -0,0 +1,228 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::{LayoutError, PaintError},
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  unit::{Length, Units},
+  widget::{BoundedWidget, Widget},
+};
+
+use super::RectangleMetrics;
+
+pub type CanvasPainter<'a> = Box<dyn Painter<CanvasMetrics> + 'a>;
+
+/// Standard sizes of a `Canvas`. Paper sizes are in portrait.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PageSize {
+  A3,
+  A4,
+  A5,
+  Letter,
+  Legal,
+  /// A 1280×720 screen.
+  Hd,
+  /// A 1920×1080 screen.
+  FullHd,
+  Custom {
+    width: Length,
+    height: Length,
+  },
+}
+
+#[allow(dead_code)]
+impl PageSize {
+  /// Width and height of the page.
+  pub fn dimensions(&self) -> (Length, Length) {
+    match *self {
+      PageSize::A3 => (Length::mm(297.0), Length::mm(420.0)),
+      PageSize::A4 => (Length::mm(210.0), Length::mm(297.0)),
+      PageSize::A5 => (Length::mm(148.0), Length::mm(210.0)),
+      PageSize::Letter => (Length::inches(8.5), Length::inches(11.0)),
+      PageSize::Legal => (Length::inches(8.5), Length::inches(14.0)),
+      PageSize::Hd => (Length::px(1280.0), Length::px(720.0)),
+      PageSize::FullHd => (Length::px(1920.0), Length::px(1080.0)),
+      PageSize::Custom { width, height } => (width, height),
+    }
+  }
+
+  /// The page turned sideways.
+  pub fn landscape(&self) -> PageSize {
+    let (width, height) = self.dimensions();
+    PageSize::Custom {
+      width: height,
+      height: width,
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CanvasMetrics {
+  pub frame: RectangleMetrics,
+
+  /// The page without its margins, where content is safe from trimming or screen edges.
+  pub safe_area: RectangleMetrics,
+}
+
+/// The page or screen a layout is drawn on, with its top left corner at the origin. Push it
+/// with `LayoutBuilder::push_root` to keep every other widget on the page.
+///
+/// The size is fixed at construction. Margins are constant by default, and can be replaced with
+/// unbound measures to let the solver pick them.
+pub struct Canvas<'a> {
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub margin_left: Measure<'a>,
+  pub margin_right: Measure<'a>,
+  pub margin_top: Measure<'a>,
+  pub margin_bottom: Measure<'a>,
+
+  units: Units,
+  pub painter: CanvasPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Canvas<'a> {
+  /// A canvas of `size` converted with `units`, like the ones of `LayoutBuilder::units`,
+  /// without margins.
+  pub fn new(
+    ctx: &'a LayoutContext,
+    units: &Units,
+    size: PageSize,
+    painter: CanvasPainter<'a>,
+  ) -> Result<Self, LayoutError> {
+    let (width, height) = size.dimensions();
+    let zero = Measure::zero(ctx);
+    Ok(Self {
+      width: units.measure(ctx, width)?,
+      height: units.measure(ctx, height)?,
+      margin_left: zero,
+      margin_right: zero,
+      margin_top: zero,
+      margin_bottom: zero,
+      units: *units,
+      painter,
+    })
+  }
+
+  /// The canvas with `margin` on every side.
+  pub fn with_margin(self, margin: Length) -> Result<Self, LayoutError> {
+    self.with_margins(margin, margin, margin, margin)
+  }
+
+  /// The canvas with margins in the order of CSS: top, right, bottom and left.
+  pub fn with_margins(
+    mut self,
+    top: Length,
+    right: Length,
+    bottom: Length,
+    left: Length,
+  ) -> Result<Self, LayoutError> {
+    let ctx = self.width.ctx;
+    self.margin_top = self.units.measure(ctx, top)?;
+    self.margin_right = self.units.measure(ctx, right)?;
+    self.margin_bottom = self.units.measure(ctx, bottom)?;
+    self.margin_left = self.units.measure(ctx, left)?;
+    Ok(self)
+  }
+
+  /// The page without its margins.
+  pub fn safe_area(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.margin_left,
+      right: self.width - self.margin_right,
+      top: self.margin_top,
+      bottom: self.height - self.margin_bottom,
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Canvas<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    let zero = Measure::zero(self.width.ctx);
+    RectMeasures {
+      left: zero,
+      right: self.width,
+      top: zero,
+      bottom: self.height,
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Canvas<'a> {
+  type Metrics = CanvasMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![
+      self.width,
+      self.height,
+      self.margin_left,
+      self.margin_right,
+      self.margin_top,
+      self.margin_bottom,
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.width.ctx);
+    vec![
+      self.margin_left.prop_ge(zero).hard(),
+      self.margin_right.prop_ge(zero).hard(),
+      self.margin_top.prop_ge(zero).hard(),
+      self.margin_bottom.prop_ge(zero).hard(),
+      (self.margin_left + self.margin_right)
+        .prop_le(self.width)
+        .hard(),
+      (self.margin_top + self.margin_bottom)
+        .prop_le(self.height)
+        .hard(),
+    ]
+  }
+
+  fn decode(&self, values: &[f64]) -> CanvasMetrics {
+    let (width, height) = (values[0], values[1]);
+    let (left, right, top, bottom) = (values[2], values[3], values[4], values[5]);
+    CanvasMetrics {
+      frame: RectangleMetrics {
+        left: 0.0,
+        right: width,
+        top: 0.0,
+        bottom: height,
+        width,
+        height,
+      },
+      safe_area: RectangleMetrics {
+        left,
+        right: width - right,
+        top,
+        bottom: height - bottom,
+        width: width - left - right,
+        height: height - top - bottom,
+      },
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: CanvasMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: CanvasMetrics, transform: Transform) -> CanvasMetrics {
+    CanvasMetrics {
+      frame: metrics.frame.transformed(transform),
+      safe_area: metrics.safe_area.transformed(transform),
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+}
@@ -0,0 +1,208 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, CompositeWidget, Parts, RawWidget},
+};
+
+/// A widget that the solver may hide, like a subtitle that is dropped when it does not fit.
+///
+/// While hidden, the constraints of the widget are lifted, its bounds collapse to zero size and
+/// it is neither painted nor notified by `RawWidget::on_solved`.
+pub struct Collapsible<'a, W> {
+  pub widget: W,
+
+  /// Holds when the widget is shown. The solver decides it, unless it is constrained.
+  pub visible: Prop<'a>,
+
+  /// Weight of the preference for showing the widget.
+  priority: Option<u32>,
+  constraints: Vec<Prop<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a, W: RawWidget<'a>> Collapsible<'a, W> {
+  /// Wraps `widget`, preferring to show it with the weight `priority`.
+  pub fn new(ctx: &'a LayoutContext, widget: W, priority: u32) -> Self {
+    Self {
+      priority: Some(priority.max(1)),
+      ..Self::with_visibility(widget, Prop::new_unbound(ctx))
+    }
+  }
+
+  /// Wraps `widget`, showing it exactly where `visible` holds.
+  pub fn with_visibility(widget: W, visible: Prop<'a>) -> Self {
+    Self {
+      widget,
+      visible,
+      priority: None,
+      constraints: vec![],
+    }
+  }
+
+  /// Adds a constraint that only applies while the widget is shown, like its intrinsic size.
+  pub fn push_constraint(&mut self, prop: Prop<'a>) {
+    self.constraints.push(prop);
+  }
+
+  /// `gap` while the widget is shown and zero while it is hidden, for spacing it from its
+  /// neighbours.
+  pub fn gap(&self, gap: f64) -> Measure<'a> {
+    let ctx = self.visible.ctx;
+    self
+      .visible
+      .select(Measure::new_const(ctx, gap).unwrap(), Measure::zero(ctx))
+  }
+}
+
+impl<'a, W: BoundedWidget<'a>> BoundedWidget<'a> for Collapsible<'a, W> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.widget.rect()
+  }
+}
+
+impl<'a, W: RawWidget<'a>> RawWidget<'a> for Collapsible<'a, W> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let ctx = self.visible.ctx;
+    let mut measures = vec![self
+      .visible
+      .select(Measure::new_const(ctx, 1.0).unwrap(), Measure::zero(ctx))];
+    measures.extend(self.widget.measures());
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints: Vec<_> = self
+      .widget
+      .constraints()
+      .into_iter()
+      .chain(self.constraints.iter().copied())
+      .map(|x| x.when(self.visible))
+      .collect();
+    if let Some(bounds) = self.widget.bounds() {
+      let collapsed = bounds.right.prop_eq(bounds.left) & bounds.bottom.prop_eq(bounds.top);
+      constraints.push(collapsed.when(!self.visible).hard());
+    }
+    if let Some(priority) = self.priority {
+      constraints.push(self.visible.with_weight(priority));
+    }
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.widget.objectives()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    if measures[0] != 0.0 {
+      self.widget.on_solved(&measures[1..]);
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    self.widget.bounds()
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    if measures[0] == 0.0 {
+      return vec![];
+    }
+    self
+      .widget
+      .content_constraints(&measures[1..])
+      .into_iter()
+      .map(|x| x.when(self.visible))
+      .collect()
+  }
+
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    self.widget.anchors()
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    if measures[0] == 0.0 {
+      return Ok(());
+    }
+    self.widget.paint_at(&measures[1..], transform, target)
+  }
+}
+
+/// Widgets that are each shown only if they fit, like buttons of a toolbar that spill into a
+/// menu. Each has a reward for being shown, and the solver shows the set of widgets with the
+/// largest total reward that satisfies the hard constraints.
+///
+/// Constraints placing a widget should be restricted to layouts where it is shown with
+/// `Prop::when`, or the spacing around it should vanish with it, like with `Collapsible::gap`.
+pub struct OptionalWidgets<'a> {
+  ctx: &'a LayoutContext,
+  widgets: Vec<Box<dyn RawWidget<'a> + 'a>>,
+  included: Vec<Prop<'a>>,
+  constraints: Vec<Prop<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> OptionalWidgets<'a> {
+  pub fn new(ctx: &'a LayoutContext) -> Self {
+    Self {
+      ctx,
+      widgets: vec![],
+      included: vec![],
+      constraints: vec![],
+    }
+  }
+
+  /// Adds an optional widget, rewarded with `reward` for being shown. Returns the proposition
+  /// that holds when it is shown.
+  pub fn push<W: RawWidget<'a> + 'a>(&mut self, widget: W, reward: u32) -> Prop<'a> {
+    self.push_collapsible(Collapsible::new(self.ctx, widget, reward))
+  }
+
+  /// Adds a widget wrapped by the caller, like one with constraints that only apply while it is
+  /// shown.
+  pub fn push_collapsible<W: RawWidget<'a> + 'a>(
+    &mut self,
+    widget: Collapsible<'a, W>,
+  ) -> Prop<'a> {
+    let included = widget.visible;
+    self.widgets.push(Box::new(widget));
+    self.included.push(included);
+    included
+  }
+
+  pub fn push_constraint(&mut self, prop: Prop<'a>) {
+    self.constraints.push(prop);
+  }
+
+  /// Propositions that hold when the widgets are shown, in insertion order.
+  pub fn included(&self) -> &[Prop<'a>] {
+    &self.included
+  }
+
+  /// Number of widgets shown.
+  pub fn included_count(&self) -> Measure<'a> {
+    Prop::count(self.ctx, &self.included)
+  }
+}
+
+impl<'a> CompositeWidget<'a> for OptionalWidgets<'a> {
+  fn expand(self) -> Parts<'a> {
+    Parts {
+      widgets: self.widgets,
+      constraints: self.constraints,
+    }
+  }
+}
@@ -0,0 +1,140 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget, Widget},
+};
+
+use super::{Rectangle, RectanglePainter};
+
+/// A widget whose children are laid out in its local coordinates, with the origin at the top
+/// left corner of its frame. Sub-layouts can be built against the origin without knowing where
+/// the container ends up, and containers nest.
+///
+/// Children are painted after the frame, with their metrics in absolute coordinates.
+pub struct Container<'a> {
+  /// Where the container is, in the coordinates of its parent.
+  pub frame: Rectangle<'a>,
+
+  children: Vec<Box<dyn RawWidget<'a> + 'a>>,
+  constraints: Vec<Prop<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> Container<'a> {
+  pub fn new(ctx: &'a LayoutContext, painter: RectanglePainter<'a>) -> Self {
+    Self {
+      frame: Rectangle::unbound(ctx, painter),
+      children: vec![],
+      constraints: vec![],
+    }
+  }
+
+  /// Adds a child, whose measures are in the local coordinates of the container. Children with
+  /// `RawWidget::bounds` are kept inside the frame.
+  pub fn push_child<W: RawWidget<'a> + 'a>(&mut self, widget: W) {
+    self.children.push(Box::new(widget));
+  }
+
+  /// Adds a constraint between the children, in local coordinates.
+  pub fn push_constraint(&mut self, prop: Prop<'a>) {
+    self.constraints.push(prop);
+  }
+
+  /// The frame of the container in its local coordinates.
+  pub fn local_bounds(&self) -> RectMeasures<'a> {
+    let zero = Measure::zero(self.frame.width.ctx);
+    RectMeasures {
+      left: zero,
+      right: self.frame.width,
+      top: zero,
+      bottom: self.frame.height,
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Container<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.frame.rect()
+  }
+}
+
+impl<'a> RawWidget<'a> for Container<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let mut measures = RawWidget::measures(&self.frame);
+    for child in &self.children {
+      measures.extend(child.measures());
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let local = self.local_bounds();
+    let mut constraints = RawWidget::constraints(&self.frame);
+    for child in &self.children {
+      constraints.extend(child.constraints());
+      if let Some(bounds) = child.bounds() {
+        constraints.push(local.contains(&bounds).hard());
+      }
+    }
+    constraints.extend(self.constraints.iter().copied());
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.children.iter().flat_map(|x| x.objectives()).collect()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    for child in &mut self.children {
+      let len = child.measures().len();
+      child.on_solved(&measures[offset..offset + len]);
+      offset += len;
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Widget::bounds(&self.frame)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    let mut constraints = vec![];
+    for child in &self.children {
+      let len = child.measures().len();
+      constraints.extend(child.content_constraints(&measures[offset..offset + len]));
+      offset += len;
+    }
+    constraints
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame_len = RawWidget::measures(&self.frame).len();
+    let frame = Widget::decode(&self.frame, &measures[..frame_len]);
+    RawWidget::paint_at(&mut self.frame, &measures[..frame_len], transform, target)?;
+
+    let local = transform.then(Transform::translate(frame.left, frame.top));
+    let mut offset = frame_len;
+    for child in &mut self.children {
+      let len = child.measures().len();
+      child.paint_at(&measures[offset..offset + len], local, target)?;
+      offset += len;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,185 @@
+use crate::layout::{
+  angle::Angle,
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+pub type EllipsePainter<'a> = Box<dyn Painter<EllipseMetrics> + 'a>;
+
+/// cos 45° as a ratio, rounded down so that points derived from it stay inside the ellipse.
+const DIAGONAL: (i32, i32) = (7071, 10000);
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EllipseMetrics {
+  pub cx: f64,
+  pub cy: f64,
+  pub rx: f64,
+  pub ry: f64,
+}
+
+impl EllipseMetrics {
+  /// The ellipse moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    EllipseMetrics {
+      cx: transform.x(self.cx),
+      cy: transform.y(self.cy),
+      ..self
+    }
+  }
+}
+
+/// An axis-aligned ellipse, like a node of a diagram. It is laid out by its bounding box, so
+/// it lines up with rectangles, and its anchors lie on its outline.
+pub struct Ellipse<'a> {
+  pub center: Point<'a>,
+  pub rx: Measure<'a>,
+  pub ry: Measure<'a>,
+
+  pub painter: EllipsePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Ellipse<'a> {
+  pub fn new(
+    center: Point<'a>,
+    rx: Measure<'a>,
+    ry: Measure<'a>,
+    painter: EllipsePainter<'a>,
+  ) -> Self {
+    Self {
+      center,
+      rx,
+      ry,
+      painter,
+    }
+  }
+
+  pub fn unbound(ctx: &'a LayoutContext, painter: EllipsePainter<'a>) -> Self {
+    let center = Point {
+      x: Measure::new_unbound(ctx),
+      y: Measure::new_unbound(ctx),
+    };
+    Self::new(
+      center,
+      Measure::new_unbound(ctx),
+      Measure::new_unbound(ctx),
+      painter,
+    )
+  }
+
+  /// An ellipse whose radii are the same measure.
+  pub fn circle(ctx: &'a LayoutContext, painter: EllipsePainter<'a>) -> Self {
+    let radius = Measure::new_unbound(ctx);
+    Self {
+      rx: radius,
+      ry: radius,
+      ..Self::unbound(ctx, painter)
+    }
+  }
+
+  /// The largest rectangle inside the ellipse, up to rounding. Content of the ellipse, like its
+  /// label, fits if it fits in this.
+  pub fn inner_rect(&self) -> RectMeasures<'a> {
+    let diagonal = Measure::new_ratio(self.rx.ctx, DIAGONAL.0, DIAGONAL.1).unwrap();
+    let (dx, dy) = (self.rx * diagonal, self.ry * diagonal);
+    RectMeasures {
+      left: self.center.x - dx,
+      right: self.center.x + dx,
+      top: self.center.y - dy,
+      bottom: self.center.y + dy,
+    }
+  }
+
+  /// Holds when `that` lies inside the ellipse. Conservative: `that` is kept within
+  /// `inner_rect`, so that the constraint stays linear.
+  pub fn contains(&self, that: &RectMeasures<'a>) -> Prop<'a> {
+    self.inner_rect().contains(that)
+  }
+
+  /// The point of the outline at `angle`, like `Point::on_circle`. Linear in the radii as long
+  /// as `angle` is a constant.
+  pub fn point_at(&self, angle: Angle<'a>) -> Point<'a> {
+    Point {
+      x: self.center.x + self.rx * angle.cos(),
+      y: self.center.y + self.ry * angle.sin(),
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Ellipse<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.center.x - self.rx,
+      right: self.center.x + self.rx,
+      top: self.center.y - self.ry,
+      bottom: self.center.y + self.ry,
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Ellipse<'a> {
+  type Metrics = EllipseMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.center.x, self.center.y, self.rx, self.ry]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.rx.ctx);
+    let rect = self.rect();
+    vec![
+      self.rx.prop_ge(zero).hard(),
+      self.ry.prop_ge(zero).hard(),
+      rect.top.prop_ge(zero),
+      rect.left.prop_ge(zero),
+    ]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  /// The anchors of the bounding box, with the corners moved onto the outline.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    let inner = self.inner_rect();
+    self
+      .rect()
+      .anchors()
+      .into_iter()
+      .map(|(name, point)| match name {
+        "top-left" | "top-right" | "bottom-left" | "bottom-right" => {
+          (name, inner.anchor(name).unwrap_or(point))
+        }
+        _ => (name, point),
+      })
+      .collect()
+  }
+
+  fn decode(&self, values: &[f64]) -> EllipseMetrics {
+    EllipseMetrics {
+      cx: values[0],
+      cy: values[1],
+      rx: values[2],
+      ry: values[3],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: EllipseMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: EllipseMetrics, transform: Transform) -> EllipseMetrics {
+    metrics.transformed(transform)
+  }
+}
@@ -0,0 +1,223 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget},
+};
+
+use super::{Rectangle, RectangleMetrics};
+
+pub type FormPainter<'a> = Box<dyn Painter<FormMetrics> + 'a>;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormMetrics {
+  pub frame: RectangleMetrics,
+  pub label_width: f64,
+  pub stacked: bool,
+}
+
+#[derive(ReflectMeasures)]
+pub struct FormRow<'a> {
+  #[reflect(nested)]
+  pub label: Rectangle<'a>,
+  #[reflect(nested)]
+  pub field: Rectangle<'a>,
+}
+
+/// Rows of label/field pairs with a shared label column.
+///
+/// Labels sit to the left of their fields while the form is at least `stack_below` wide, and
+/// above them otherwise. A label wider than the label column gets a line of its own, with its
+/// field below it in the field column, so that the two never overlap.
+#[derive(ReflectMeasures)]
+pub struct Form<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  /// Width of the label column: the widest label, bounded by `max_label_width`.
+  pub label_width: Measure<'a>,
+  pub stacked: Prop<'a>,
+
+  pub column_gap: f64,
+  pub row_gap: f64,
+  max_label_width: f64,
+  rows: Vec<FormRow<'a>>,
+
+  pub painter: FormPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Form<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    max_label_width: f64,
+    stack_below: f64,
+    painter: FormPainter<'a>,
+  ) -> Self {
+    let width = Measure::new_unbound(ctx);
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width,
+      height: Measure::new_unbound(ctx),
+      label_width: Measure::new_unbound(ctx),
+      stacked: width.prop_lt(Measure::new_const(ctx, stack_below).unwrap()),
+      column_gap: 8.0,
+      row_gap: 8.0,
+      max_label_width,
+      rows: vec![],
+      painter,
+    }
+  }
+
+  /// Appends a row. The field's width is solved so that it stretches to the form's right edge.
+  pub fn push_row(&mut self, label: Rectangle<'a>, field: Rectangle<'a>) {
+    self.rows.push(FormRow { label, field });
+  }
+
+  pub fn rows(&self) -> &[FormRow<'a>] {
+    &self.rows
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Form<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> RawWidget<'a> for Form<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let ctx = self.left.ctx;
+    let mut measures = vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+      self.label_width,
+      self
+        .stacked
+        .select(Measure::new_const(ctx, 1.0).unwrap(), Measure::zero(ctx)),
+    ];
+    for row in &self.rows {
+      measures.extend(RawWidget::measures(&row.label));
+      measures.extend(RawWidget::measures(&row.field));
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let ctx = self.left.ctx;
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+      self.width.prop_ge(Measure::zero(ctx)).hard(),
+    ];
+
+    let widest_label = self
+      .rows
+      .iter()
+      .map(|x| x.label.width)
+      .reduce(|a, b| a.max(b))
+      .unwrap_or_else(|| Measure::zero(ctx));
+    constraints.push(
+      self
+        .label_width
+        .prop_eq(widest_label.min(Measure::new_const(ctx, self.max_label_width).unwrap())),
+    );
+
+    let mut row_top = self.top;
+    for row in &self.rows {
+      constraints.extend(row.label.constraints());
+      constraints.extend(row.field.constraints());
+
+      let wrapped = self.stacked | row.label.width.prop_gt(self.label_width);
+      constraints.push(row.label.left.prop_eq(self.left));
+      constraints.push(row.label.top.prop_eq(row_top));
+      constraints.push(row.field.right.prop_eq(self.right));
+      constraints.push(
+        row.field.left.prop_eq(
+          self
+            .stacked
+            .select(self.left, self.left + self.label_width + self.column_gap),
+        ),
+      );
+      constraints.push(
+        row
+          .field
+          .top
+          .prop_eq(wrapped.select(row.label.bottom, row_top)),
+      );
+
+      let row_bottom = wrapped.select(row.field.bottom, row.label.bottom.max(row.field.bottom));
+      row_top = row_bottom + self.row_gap;
+    }
+
+    let content_bottom = if self.rows.is_empty() {
+      self.top
+    } else {
+      row_top - self.row_gap
+    };
+    constraints.push(self.bottom.prop_eq(content_bottom));
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let metrics = FormMetrics {
+      frame: RectangleMetrics {
+        left: measures[0],
+        right: measures[1],
+        top: measures[2],
+        bottom: measures[3],
+        width: measures[4],
+        height: measures[5],
+      }
+      .transformed(transform),
+      label_width: measures[6],
+      stacked: measures[7] != 0.0,
+    };
+    self.painter.paint(metrics, target)?;
+
+    let mut offset = 8;
+    for row in &mut self.rows {
+      for part in [&mut row.label, &mut row.field] {
+        let len = RawWidget::measures(&*part).len();
+        RawWidget::paint_at(part, &measures[offset..offset + len], transform, target)?;
+        offset += len;
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,135 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget, Widget},
+};
+
+use super::{Rectangle, RectangleMeasures, RectanglePainter};
+
+/// A set of widgets that can be constrained as one, through the bounding box of its members.
+/// Unlike a `Container`, members share the coordinates of the group, and the box follows them
+/// instead of holding them in.
+///
+/// The box is painted before the members, like a background.
+pub struct Group<'a> {
+  /// The union of the bounds of the members. It is unbound while no member has bounds.
+  pub frame: Rectangle<'a>,
+
+  members: Vec<Box<dyn RawWidget<'a> + 'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> Group<'a> {
+  /// A group whose box paints nothing.
+  pub fn new(ctx: &'a LayoutContext) -> Self {
+    Self::with_painter(ctx, Box::new(|_| Ok(())))
+  }
+
+  /// A group whose box is painted by `painter`, like a border around the members.
+  pub fn with_painter(ctx: &'a LayoutContext, painter: RectanglePainter<'a>) -> Self {
+    Self {
+      frame: Rectangle::unbound(ctx, painter),
+      members: vec![],
+    }
+  }
+
+  /// Adds a member. Members with `RawWidget::bounds` extend the box.
+  pub fn push<W: RawWidget<'a> + 'a>(&mut self, widget: W) {
+    self.members.push(Box::new(widget));
+  }
+
+  pub fn measures(&self) -> RectangleMeasures<'a> {
+    self.frame.measures()
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Group<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.frame.rect()
+  }
+}
+
+impl<'a> RawWidget<'a> for Group<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let mut measures = RawWidget::measures(&self.frame);
+    for member in &self.members {
+      measures.extend(member.measures());
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = RawWidget::constraints(&self.frame);
+    let bounds = self
+      .members
+      .iter()
+      .filter_map(|x| x.bounds())
+      .reduce(|a, b| a.union(&b));
+    if let Some(bounds) = bounds {
+      let frame = self.frame.rect();
+      constraints.push(frame.left.prop_eq(bounds.left).hard());
+      constraints.push(frame.right.prop_eq(bounds.right).hard());
+      constraints.push(frame.top.prop_eq(bounds.top).hard());
+      constraints.push(frame.bottom.prop_eq(bounds.bottom).hard());
+    }
+    for member in &self.members {
+      constraints.extend(member.constraints());
+    }
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.members.iter().flat_map(|x| x.objectives()).collect()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    for member in &mut self.members {
+      let len = member.measures().len();
+      member.on_solved(&measures[offset..offset + len]);
+      offset += len;
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Widget::bounds(&self.frame)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    let mut constraints = vec![];
+    for member in &self.members {
+      let len = member.measures().len();
+      constraints.extend(member.content_constraints(&measures[offset..offset + len]));
+      offset += len;
+    }
+    constraints
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame_len = RawWidget::measures(&self.frame).len();
+    RawWidget::paint_at(&mut self.frame, &measures[..frame_len], transform, target)?;
+    let mut offset = frame_len;
+    for member in &mut self.members {
+      let len = member.measures().len();
+      member.paint_at(&measures[offset..offset + len], transform, target)?;
+      offset += len;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,132 @@
+use crate::layout::{
+  error::{LayoutError, PaintError},
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::Widget,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GuidelineAxis {
+  /// A line of constant x.
+  Vertical,
+  /// A line of constant y.
+  Horizontal,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GuidelineMetrics {
+  pub axis: GuidelineAxis,
+  pub position: f64,
+}
+
+/// An invisible line that widgets align to, like the guidelines of Android's ConstraintLayout.
+/// It paints nothing, and needs to be pushed only to show up in reports and snapshots.
+#[derive(Copy, Clone, Debug)]
+pub struct Guideline<'a> {
+  pub axis: GuidelineAxis,
+
+  /// The x of a vertical guideline, or the y of a horizontal one.
+  pub position: Measure<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Guideline<'a> {
+  pub fn new(axis: GuidelineAxis, position: Measure<'a>) -> Self {
+    Self { axis, position }
+  }
+
+  /// A guideline `offset` past `origin`, like 16 units from the left of a card.
+  pub fn at(axis: GuidelineAxis, origin: Measure<'a>, offset: f64) -> Self {
+    Self::new(axis, origin + offset)
+  }
+
+  /// A guideline `percent` of the way from `start` to `end`. The percentage is exact to two
+  /// decimals.
+  pub fn percent(
+    axis: GuidelineAxis,
+    start: Measure<'a>,
+    end: Measure<'a>,
+    percent: f64,
+  ) -> Result<Self, LayoutError> {
+    let fraction = (percent * 100.0).round();
+    if !fraction.is_finite() || fraction.abs() > i32::MAX as f64 {
+      return Err(LayoutError::BadConst { value: percent });
+    }
+    let fraction = Measure::new_ratio(start.ctx, fraction as i32, 10000)?;
+    Ok(Self::new(axis, start + (end - start) * fraction))
+  }
+
+  /// A guideline `percent` of the way across `parent`, from its left or its top.
+  pub fn percent_of(
+    axis: GuidelineAxis,
+    parent: &RectMeasures<'a>,
+    percent: f64,
+  ) -> Result<Self, LayoutError> {
+    match axis {
+      GuidelineAxis::Vertical => Self::percent(axis, parent.left, parent.right, percent),
+      GuidelineAxis::Horizontal => Self::percent(axis, parent.top, parent.bottom, percent),
+    }
+  }
+
+  /// The coordinate of `point` across the guideline.
+  fn across(&self, point: Point<'a>) -> Measure<'a> {
+    match self.axis {
+      GuidelineAxis::Vertical => point.x,
+      GuidelineAxis::Horizontal => point.y,
+    }
+  }
+
+  /// Holds when `point` lies on the guideline.
+  pub fn aligns(&self, point: Point<'a>) -> Prop<'a> {
+    self.across(point).prop_eq(self.position)
+  }
+
+  /// Holds when `point` lies on the guideline or before it, left or above.
+  pub fn before(&self, point: Point<'a>) -> Prop<'a> {
+    self.across(point).prop_le(self.position)
+  }
+
+  /// Holds when `point` lies on the guideline or after it, right or below.
+  pub fn after(&self, point: Point<'a>) -> Prop<'a> {
+    self.across(point).prop_ge(self.position)
+  }
+}
+
+impl<'a> Widget<'a> for Guideline<'a> {
+  type Metrics = GuidelineMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.position]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![]
+  }
+
+  fn decode(&self, values: &[f64]) -> GuidelineMetrics {
+    GuidelineMetrics {
+      axis: self.axis,
+      position: values[0],
+    }
+  }
+
+  fn paint(&mut self, _: GuidelineMetrics, _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    Ok(())
+  }
+
+  fn transform(metrics: GuidelineMetrics, transform: Transform) -> GuidelineMetrics {
+    let position = match metrics.axis {
+      GuidelineAxis::Vertical => transform.x(metrics.position),
+      GuidelineAxis::Horizontal => transform.y(metrics.position),
+    };
+    GuidelineMetrics {
+      position,
+      ..metrics
+    }
+  }
+}
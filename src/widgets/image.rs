@@ -0,0 +1,248 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+use super::RectangleMetrics;
+
+pub type ImagePainter<'a> = Box<dyn Painter<ImageMetrics> + 'a>;
+
+/// How an `Image` fills its frame, like the CSS `object-fit`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fit {
+  /// Scaled to fit inside the frame, centered, keeping its aspect ratio.
+  Contain,
+
+  /// Scaled to fill the frame, centered, keeping its aspect ratio. What sticks out is cropped.
+  Cover,
+
+  /// Scaled to the frame, ignoring its aspect ratio.
+  Stretch,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageMetrics {
+  pub frame: RectangleMetrics,
+  pub fit: Fit,
+
+  /// Where the whole image is drawn. It extends past the frame with `Fit::Cover`.
+  pub placement: RectangleMetrics,
+
+  /// The part of the image inside the frame, in pixels of the image.
+  pub crop: RectangleMetrics,
+}
+
+impl ImageMetrics {
+  /// The image moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    ImageMetrics {
+      frame: self.frame.transformed(transform),
+      placement: self.placement.transformed(transform),
+      ..self
+    }
+  }
+}
+
+/// A raster image of known natural size in a frame, fit to it by `fit`.
+#[derive(ReflectMeasures)]
+pub struct Image<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  /// Size of the image in its own pixels.
+  pub natural_width: f64,
+  pub natural_height: f64,
+  pub fit: Fit,
+
+  pub painter: ImagePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Image<'a> {
+  /// An image of the natural size in a frame that is unbound.
+  pub fn new(
+    ctx: &'a LayoutContext,
+    natural_width: f64,
+    natural_height: f64,
+    fit: Fit,
+    painter: ImagePainter<'a>,
+  ) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      natural_width,
+      natural_height,
+      fit,
+      painter,
+    }
+  }
+
+  /// An image of the size of the file at `path`, read from its header.
+  #[cfg(feature = "images")]
+  pub fn open(
+    ctx: &'a LayoutContext,
+    path: impl AsRef<std::path::Path>,
+    fit: Fit,
+    painter: ImagePainter<'a>,
+  ) -> Result<Self, image::ImageError> {
+    let (width, height) = image::image_dimensions(path)?;
+    Ok(Self::new(ctx, width as f64, height as f64, fit, painter))
+  }
+
+  /// Holds when the frame is the natural size of the image.
+  pub fn natural_size(&self) -> Prop<'a> {
+    let ctx = self.width.ctx;
+    self
+      .width
+      .prop_eq(Measure::new_const(ctx, self.natural_width).unwrap())
+      & self
+        .height
+        .prop_eq(Measure::new_const(ctx, self.natural_height).unwrap())
+  }
+
+  /// Holds when the frame has the aspect ratio of the image, so that every fit looks the same.
+  pub fn keeps_aspect_ratio(&self) -> Prop<'a> {
+    (self.width * self.natural_height).prop_eq(self.height * self.natural_width)
+  }
+
+  /// Where the whole image is drawn, as in `ImageMetrics::placement`.
+  pub fn placement(&self) -> RectMeasures<'a> {
+    let frame = self.rect();
+    let (scale_x, scale_y) = (
+      self.width / self.natural_width,
+      self.height / self.natural_height,
+    );
+    let scale = match self.fit {
+      Fit::Contain => scale_x.min(scale_y),
+      Fit::Cover => scale_x.max(scale_y),
+      Fit::Stretch => return frame,
+    };
+    let (width, height) = (scale * self.natural_width, scale * self.natural_height);
+    let left = self.left + (self.width - width) / 2.0;
+    let top = self.top + (self.height - height) / 2.0;
+    RectMeasures {
+      left,
+      right: left + width,
+      top,
+      bottom: top + height,
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Image<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Image<'a> {
+  type Metrics = ImageMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let placement = self.placement();
+    vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+      placement.left,
+      placement.right,
+      placement.top,
+      placement.bottom,
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.width.ctx);
+    vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+      self.width.prop_ge(zero).hard(),
+      self.height.prop_ge(zero).hard(),
+      self.top.prop_ge(zero),
+      self.left.prop_ge(zero),
+    ]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn decode(&self, values: &[f64]) -> ImageMetrics {
+    let rectangle = |left: f64, right: f64, top: f64, bottom: f64| RectangleMetrics {
+      left,
+      right,
+      top,
+      bottom,
+      width: right - left,
+      height: bottom - top,
+    };
+    let frame = rectangle(values[0], values[1], values[2], values[3]);
+    let placement = rectangle(values[6], values[7], values[8], values[9]);
+
+    // The frame in pixels of the image, clipped to the image.
+    let (scale_x, scale_y) = (
+      self.natural_width / placement.width,
+      self.natural_height / placement.height,
+    );
+    let to_image_x = |x: f64| {
+      ((x - placement.left) * scale_x)
+        .max(0.0)
+        .min(self.natural_width)
+    };
+    let to_image_y = |y: f64| {
+      ((y - placement.top) * scale_y)
+        .max(0.0)
+        .min(self.natural_height)
+    };
+    let crop = rectangle(
+      to_image_x(frame.left),
+      to_image_x(frame.right),
+      to_image_y(frame.top),
+      to_image_y(frame.bottom),
+    );
+
+    ImageMetrics {
+      frame,
+      fit: self.fit,
+      placement,
+      crop,
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: ImageMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: ImageMetrics, transform: Transform) -> ImageMetrics {
+    metrics.transformed(transform)
+  }
+}
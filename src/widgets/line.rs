@@ -0,0 +1,168 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+pub type LinePainter<'a> = Box<dyn Painter<LineMetrics> + 'a>;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineMetrics {
+  pub x1: f64,
+  pub y1: f64,
+  pub x2: f64,
+  pub y2: f64,
+}
+
+impl LineMetrics {
+  /// The line moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    LineMetrics {
+      x1: transform.x(self.x1),
+      y1: transform.y(self.y1),
+      x2: transform.x(self.x2),
+      y2: transform.y(self.y2),
+    }
+  }
+
+  pub fn length(&self) -> f64 {
+    (self.x2 - self.x1).hypot(self.y2 - self.y1)
+  }
+}
+
+/// A line segment between two points, like a separator, a leader line or a ruler.
+pub struct Line<'a> {
+  pub from: Point<'a>,
+  pub to: Point<'a>,
+
+  pub painter: LinePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Line<'a> {
+  pub fn new(from: Point<'a>, to: Point<'a>, painter: LinePainter<'a>) -> Self {
+    Self { from, to, painter }
+  }
+
+  pub fn unbound(ctx: &'a LayoutContext, painter: LinePainter<'a>) -> Self {
+    let point = || Point {
+      x: Measure::new_unbound(ctx),
+      y: Measure::new_unbound(ctx),
+    };
+    Self::new(point(), point(), painter)
+  }
+
+  /// Horizontal extent from `from` to `to`, negative when the line runs leftwards.
+  pub fn dx(&self) -> Measure<'a> {
+    self.to.x - self.from.x
+  }
+
+  /// Vertical extent from `from` to `to`, negative when the line runs upwards.
+  pub fn dy(&self) -> Measure<'a> {
+    self.to.y - self.from.y
+  }
+
+  /// The length of the line, exact for horizontal and vertical lines. Diagonal lines measure
+  /// the sum of their extents, which keeps the measure linear. See `length_squared`.
+  pub fn length(&self) -> Measure<'a> {
+    self.dx().abs() + self.dy().abs()
+  }
+
+  /// The exact squared length, which is not linear unless the extents are constant.
+  pub fn length_squared(&self) -> Measure<'a> {
+    self.dx() * self.dx() + self.dy() * self.dy()
+  }
+
+  /// `dy / dx`, which is not linear unless `dx` is a nonzero constant. See `with_slope`.
+  pub fn slope(&self) -> Measure<'a> {
+    self.dy() / self.dx()
+  }
+
+  /// Holds when the line rises by `slope` per unit to the right, in screen coordinates.
+  pub fn with_slope(&self, slope: f64) -> Prop<'a> {
+    self.dy().prop_eq(self.dx() * slope)
+  }
+
+  pub fn horizontal(&self) -> Prop<'a> {
+    self.from.y.prop_eq(self.to.y)
+  }
+
+  pub fn vertical(&self) -> Prop<'a> {
+    self.from.x.prop_eq(self.to.x)
+  }
+
+  pub fn midpoint(&self) -> Point<'a> {
+    self.point_at(0.5)
+  }
+
+  /// The point a fraction `t` of the way from `from` to `to`.
+  pub fn point_at(&self, t: f64) -> Point<'a> {
+    Point {
+      x: self.from.x + self.dx() * t,
+      y: self.from.y + self.dy() * t,
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Line<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.from.x.min(self.to.x),
+      right: self.from.x.max(self.to.x),
+      top: self.from.y.min(self.to.y),
+      bottom: self.from.y.max(self.to.y),
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Line<'a> {
+  type Metrics = LineMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.from.x, self.from.y, self.to.x, self.to.y]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  /// The endpoints `from` and `to`, and the `center` of the line.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    vec![
+      ("from", self.from),
+      ("to", self.to),
+      ("center", self.midpoint()),
+    ]
+  }
+
+  fn decode(&self, values: &[f64]) -> LineMetrics {
+    LineMetrics {
+      x1: values[0],
+      y1: values[1],
+      x2: values[2],
+      y2: values[3],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: LineMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: LineMetrics, transform: Transform) -> LineMetrics {
+    metrics.transformed(transform)
+  }
+}
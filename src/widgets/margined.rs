@@ -0,0 +1,90 @@
+use crate::layout::{
+  error::PaintError,
+  geometry::{Margins, Point, RectMeasures},
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget, SendPainter},
+};
+
+/// A widget with margins, which `distribute_horizontally` and the stacking helpers keep clear
+/// of its neighbours. The widget is otherwise unchanged: its bounds do not include the margins.
+pub struct Margined<'a, W> {
+  pub widget: W,
+  pub margins: Margins<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a, W: BoundedWidget<'a>> Margined<'a, W> {
+  pub fn new(widget: W, margins: Margins<'a>) -> Self {
+    Self { widget, margins }
+  }
+
+  /// Wraps `widget` with `margin` on every side.
+  pub fn uniform(widget: W, margin: Measure<'a>) -> Self {
+    Self::new(widget, Margins::uniform(margin))
+  }
+}
+
+impl<'a, W: BoundedWidget<'a>> BoundedWidget<'a> for Margined<'a, W> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.widget.rect()
+  }
+
+  fn margins(&self) -> Margins<'a> {
+    self.margins
+  }
+}
+
+impl<'a, W: RawWidget<'a>> RawWidget<'a> for Margined<'a, W> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self.widget.measures()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    self.widget.constraints()
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.widget.paint(measures, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.widget.paint_at(measures, transform, target)
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.widget.objectives()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    self.widget.on_solved(measures)
+  }
+
+  fn send_painter(&mut self) -> Option<&mut SendPainter<'a>> {
+    self.widget.send_painter()
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    self.widget.bounds()
+  }
+
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    self.widget.intrinsic_height_for(width)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    self.widget.content_constraints(measures)
+  }
+
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    self.widget.anchors()
+  }
+}
@@ -1,6 +1,56 @@
+mod arc;
+mod barrier;
+mod breadcrumbs;
+mod canvas;
+mod collapsible;
+mod container;
+mod ellipse;
+mod form;
+mod group;
+mod guideline;
+mod image;
+mod line;
+mod margined;
+mod padded;
+mod polygon;
+mod polyline;
 mod rectangle;
+mod rotated_rect;
+mod rounded_rectangle;
+mod row;
+mod scatter_labels;
+mod swimlane;
+mod tab_strip;
+mod text;
+mod toolbar;
+mod triangle;
 
 #[cfg(test)]
 mod test;
 
+pub use self::image::*;
+pub use arc::*;
+pub use barrier::*;
+pub use breadcrumbs::*;
+pub use canvas::*;
+pub use collapsible::*;
+pub use container::*;
+pub use ellipse::*;
+pub use form::*;
+pub use group::*;
+pub use guideline::*;
+pub use line::*;
+pub use margined::*;
+pub use padded::*;
+pub use polygon::*;
+pub use polyline::*;
 pub use rectangle::*;
+pub use rotated_rect::*;
+pub use rounded_rectangle::*;
+pub use row::*;
+pub use scatter_labels::*;
+pub use swimlane::*;
+pub use tab_strip::*;
+pub use text::*;
+pub use toolbar::*;
+pub use triangle::*;
@@ -0,0 +1,165 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget},
+};
+
+use super::RectangleMeasures;
+
+/// A widget with padding around it, like the box model of CSS. Constraints on the `Padded`
+/// target the outer box, and the widget is inset from it by the padding.
+///
+/// Padding is constant by default, and can be replaced with unbound measures to let the solver
+/// pick it. It is never negative.
+pub struct Padded<'a, W> {
+  pub widget: W,
+
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub padding_left: Measure<'a>,
+  pub padding_right: Measure<'a>,
+  pub padding_top: Measure<'a>,
+  pub padding_bottom: Measure<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a, W: BoundedWidget<'a>> Padded<'a, W> {
+  /// Wraps `widget` with `padding` on every side.
+  pub fn new(ctx: &'a LayoutContext, widget: W, padding: f64) -> Self {
+    let padding = Measure::new_const(ctx, padding).unwrap();
+    Self {
+      widget,
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      padding_left: padding,
+      padding_right: padding,
+      padding_top: padding,
+      padding_bottom: padding,
+    }
+  }
+
+  /// The widget with padding in the order of CSS: top, right, bottom and left.
+  pub fn with_padding(
+    self,
+    top: Measure<'a>,
+    right: Measure<'a>,
+    bottom: Measure<'a>,
+    left: Measure<'a>,
+  ) -> Self {
+    Self {
+      padding_top: top,
+      padding_right: right,
+      padding_bottom: bottom,
+      padding_left: left,
+      ..self
+    }
+  }
+
+  /// The outer box.
+  pub fn measures(&self) -> RectangleMeasures<'a> {
+    RectangleMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+      width: self.width,
+      height: self.height,
+    }
+  }
+
+  fn paddings(&self) -> [Measure<'a>; 4] {
+    [
+      self.padding_top,
+      self.padding_right,
+      self.padding_bottom,
+      self.padding_left,
+    ]
+  }
+}
+
+impl<'a, W: BoundedWidget<'a>> BoundedWidget<'a> for Padded<'a, W> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.measures().rect()
+  }
+}
+
+impl<'a, W: BoundedWidget<'a>> RawWidget<'a> for Padded<'a, W> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let mut measures = vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+    ];
+    measures.extend(self.paddings().iter().copied());
+    measures.extend(self.widget.measures());
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.width.ctx);
+    let inner = self.widget.rect();
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+      inner.left.prop_eq(self.left + self.padding_left).hard(),
+      inner.right.prop_eq(self.right - self.padding_right).hard(),
+      inner.top.prop_eq(self.top + self.padding_top).hard(),
+      inner
+        .bottom
+        .prop_eq(self.bottom - self.padding_bottom)
+        .hard(),
+    ];
+    for padding in self.paddings().iter() {
+      constraints.push(padding.prop_ge(zero).hard());
+    }
+    constraints.extend(self.widget.constraints());
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self.widget.objectives()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    self.widget.on_solved(&measures[10..]);
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    self.widget.content_constraints(&measures[10..])
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.widget.paint_at(&measures[10..], transform, target)
+  }
+}
@@ -0,0 +1,201 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::Widget,
+};
+
+pub type PolygonPainter<'a> = Box<dyn Painter<PolygonMetrics> + 'a>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolygonMetrics {
+  /// The `(x, y)` of each vertex, in order.
+  pub vertices: Vec<(f64, f64)>,
+}
+
+impl PolygonMetrics {
+  /// The polygon moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    PolygonMetrics {
+      vertices: self
+        .vertices
+        .into_iter()
+        .map(|(x, y)| (transform.x(x), transform.y(y)))
+        .collect(),
+    }
+  }
+
+  /// Like `Polygon::signed_area`.
+  pub fn signed_area(&self) -> f64 {
+    let n = self.vertices.len();
+    (0..n)
+      .map(|i| {
+        let ((x1, y1), (x2, y2)) = (self.vertices[i], self.vertices[(i + 1) % n]);
+        x1 * y2 - x2 * y1
+      })
+      .sum::<f64>()
+      / 2.0
+  }
+}
+
+/// A closed shape through a fixed number of solved vertices, like an arrow callout or a banner.
+pub struct Polygon<'a> {
+  ctx: &'a LayoutContext,
+  pub vertices: Vec<Point<'a>>,
+
+  /// Whether the polygon is constrained to be convex. See `is_convex`.
+  pub convex: bool,
+
+  pub painter: PolygonPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Polygon<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    vertices: Vec<Point<'a>>,
+    painter: PolygonPainter<'a>,
+  ) -> Self {
+    Self {
+      ctx,
+      vertices,
+      convex: false,
+      painter,
+    }
+  }
+
+  /// A polygon of `count` unbound vertices.
+  pub fn unbound(ctx: &'a LayoutContext, count: usize, painter: PolygonPainter<'a>) -> Self {
+    let vertices = (0..count)
+      .map(|_| Point {
+        x: Measure::new_unbound(ctx),
+        y: Measure::new_unbound(ctx),
+      })
+      .collect();
+    Self::new(ctx, vertices, painter)
+  }
+
+  /// The polygon constrained to be convex.
+  pub fn with_convexity(mut self) -> Self {
+    self.convex = true;
+    self
+  }
+
+  pub fn bounding_box(&self) -> Option<RectMeasures<'a>> {
+    RectMeasures::around(&self.vertices)
+  }
+
+  /// The mean of the vertices. It is the centroid of the area for triangles and for shapes
+  /// symmetric about it, and stays linear unlike the centroid in general.
+  pub fn centroid(&self) -> Option<Point<'a>> {
+    let zero = Measure::zero(self.ctx);
+    let n = self.vertices.len() as f64;
+    if self.vertices.is_empty() {
+      return None;
+    }
+    let (x, y) = self
+      .vertices
+      .iter()
+      .fold((zero, zero), |(x, y), vertex| (x + vertex.x, y + vertex.y));
+    Some(Point { x: x / n, y: y / n })
+  }
+
+  /// The shoelace area, positive when the vertices run clockwise on screen. Not linear unless
+  /// the vertices are constants.
+  pub fn signed_area(&self) -> Measure<'a> {
+    let n = self.vertices.len();
+    (0..n)
+      .map(|i| {
+        let (a, b) = (self.vertices[i], self.vertices[(i + 1) % n]);
+        a.x * b.y - b.x * a.y
+      })
+      .fold(Measure::zero(self.ctx), |total, term| total + term)
+      / 2.0
+  }
+
+  /// Holds when every corner turns the same way, with straight corners allowed. Like
+  /// `signed_area`, this is not linear unless the vertices are constants.
+  pub fn is_convex(&self) -> Prop<'a> {
+    let zero = Measure::zero(self.ctx);
+    let n = self.vertices.len();
+    let turns: Vec<Measure<'a>> = (0..n)
+      .map(|i| {
+        let (a, b, c) = (
+          self.vertices[i],
+          self.vertices[(i + 1) % n],
+          self.vertices[(i + 2) % n],
+        );
+        (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x)
+      })
+      .collect();
+    let all = |f: &dyn Fn(Measure<'a>) -> Prop<'a>| {
+      turns
+        .iter()
+        .fold(Prop::new_const(self.ctx, true), |all, &turn| all & f(turn))
+    };
+    all(&|turn| turn.prop_ge(zero)) | all(&|turn| turn.prop_le(zero))
+  }
+}
+
+impl<'a> Widget<'a> for Polygon<'a> {
+  type Metrics = PolygonMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self
+      .vertices
+      .iter()
+      .flat_map(|vertex| vec![vertex.x, vertex.y])
+      .collect()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    if self.convex {
+      vec![self.is_convex().hard()]
+    } else {
+      vec![]
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    self.bounding_box()
+  }
+
+  /// The anchors of the bounding box, with its center moved to the centroid.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    let (rect, centroid) = match (self.bounding_box(), self.centroid()) {
+      (Some(rect), Some(centroid)) => (rect, centroid),
+      _ => return vec![],
+    };
+    rect
+      .anchors()
+      .into_iter()
+      .map(|(name, point)| match name {
+        "center" => (name, centroid),
+        _ => (name, point),
+      })
+      .collect()
+  }
+
+  fn decode(&self, values: &[f64]) -> PolygonMetrics {
+    PolygonMetrics {
+      vertices: values.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: PolygonMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: PolygonMetrics, transform: Transform) -> PolygonMetrics {
+    metrics.transformed(transform)
+  }
+}
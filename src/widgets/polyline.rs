@@ -0,0 +1,196 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::Widget,
+};
+
+use super::LineMetrics;
+
+pub type PolylinePainter<'a> = Box<dyn Painter<PolylineMetrics> + 'a>;
+
+/// How a segment of a `Polyline` may run.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SegmentKind {
+  Free,
+  Horizontal,
+  Vertical,
+
+  /// Horizontal or vertical, as the solver picks.
+  Orthogonal,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolylineMetrics {
+  /// The `(x, y)` of each vertex, in order.
+  pub vertices: Vec<(f64, f64)>,
+}
+
+impl PolylineMetrics {
+  /// The polyline moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    PolylineMetrics {
+      vertices: self
+        .vertices
+        .into_iter()
+        .map(|(x, y)| (transform.x(x), transform.y(y)))
+        .collect(),
+    }
+  }
+
+  pub fn segments(&self) -> Vec<LineMetrics> {
+    self
+      .vertices
+      .windows(2)
+      .map(|pair| LineMetrics {
+        x1: pair[0].0,
+        y1: pair[0].1,
+        x2: pair[1].0,
+        y2: pair[1].1,
+      })
+      .collect()
+  }
+
+  pub fn length(&self) -> f64 {
+    self.segments().iter().map(|segment| segment.length()).sum()
+  }
+}
+
+/// A path through a fixed number of solved vertices, like a connector routed between boxes.
+/// Every segment is `SegmentKind::Free` until set otherwise.
+pub struct Polyline<'a> {
+  ctx: &'a LayoutContext,
+  pub vertices: Vec<Point<'a>>,
+  segments: Vec<SegmentKind>,
+
+  pub painter: PolylinePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Polyline<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    vertices: Vec<Point<'a>>,
+    painter: PolylinePainter<'a>,
+  ) -> Self {
+    let segments = vec![SegmentKind::Free; vertices.len().saturating_sub(1)];
+    Self {
+      ctx,
+      vertices,
+      segments,
+      painter,
+    }
+  }
+
+  /// A polyline of `count` unbound vertices.
+  pub fn unbound(ctx: &'a LayoutContext, count: usize, painter: PolylinePainter<'a>) -> Self {
+    let vertices = (0..count)
+      .map(|_| Point {
+        x: Measure::new_unbound(ctx),
+        y: Measure::new_unbound(ctx),
+      })
+      .collect();
+    Self::new(ctx, vertices, painter)
+  }
+
+  /// Sets how the segment from vertex `index` to the next one may run.
+  pub fn set_segment(&mut self, index: usize, kind: SegmentKind) {
+    self.segments[index] = kind;
+  }
+
+  /// The polyline with every segment `SegmentKind::Orthogonal`, like an elbow connector.
+  pub fn orthogonal(mut self) -> Self {
+    for kind in &mut self.segments {
+      *kind = SegmentKind::Orthogonal;
+    }
+    self
+  }
+
+  pub fn segments(&self) -> &[SegmentKind] {
+    &self.segments
+  }
+
+  pub fn first(&self) -> Option<Point<'a>> {
+    self.vertices.first().copied()
+  }
+
+  pub fn last(&self) -> Option<Point<'a>> {
+    self.vertices.last().copied()
+  }
+
+  /// Total length, exact when every segment is horizontal or vertical. Like `Line::length`,
+  /// diagonal segments measure the sum of their extents.
+  pub fn length(&self) -> Measure<'a> {
+    self
+      .vertices
+      .windows(2)
+      .map(|pair| (pair[1].x - pair[0].x).abs() + (pair[1].y - pair[0].y).abs())
+      .fold(Measure::zero(self.ctx), |total, length| total + length)
+  }
+}
+
+impl<'a> Widget<'a> for Polyline<'a> {
+  type Metrics = PolylineMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self
+      .vertices
+      .iter()
+      .flat_map(|vertex| vec![vertex.x, vertex.y])
+      .collect()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    self
+      .vertices
+      .windows(2)
+      .zip(self.segments.iter())
+      .filter_map(|(pair, kind)| {
+        let horizontal = pair[0].y.prop_eq(pair[1].y);
+        let vertical = pair[0].x.prop_eq(pair[1].x);
+        match kind {
+          SegmentKind::Free => None,
+          SegmentKind::Horizontal => Some(horizontal.hard()),
+          SegmentKind::Vertical => Some(vertical.hard()),
+          SegmentKind::Orthogonal => Some((horizontal | vertical).hard()),
+        }
+      })
+      .collect()
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    RectMeasures::around(&self.vertices)
+  }
+
+  /// The endpoints `from` and `to`, like those of a `Line`.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    match (self.first(), self.last()) {
+      (Some(from), Some(to)) => vec![("from", from), ("to", to)],
+      _ => vec![],
+    }
+  }
+
+  fn decode(&self, values: &[f64]) -> PolylineMetrics {
+    PolylineMetrics {
+      vertices: values.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: PolylineMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: PolylineMetrics, transform: Transform) -> PolylineMetrics {
+    metrics.transformed(transform)
+  }
+}
@@ -1,5 +1,15 @@
-use crate::layout::{context::LayoutContext, measure::Measure, prop::Prop, widget::RawWidget};
-use anyhow::Result;
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{RectMeasures, Size},
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -8,14 +18,11 @@ pub enum RectangleError {
   EmptyGroup,
 }
 
-#[derive(Copy, Clone)]
-pub struct Point<'a> {
-  pub x: Measure<'a>,
-  pub y: Measure<'a>,
-}
+pub use crate::layout::geometry::Point;
 
-pub type RectanglePainter<'a> = Box<dyn FnOnce(RectangleMetrics) -> Result<()> + 'a>;
+pub type RectanglePainter<'a> = Box<dyn Painter<RectangleMetrics> + 'a>;
 
+#[derive(ReflectMeasures)]
 pub struct Rectangle<'a> {
   pub left: Measure<'a>,
   pub right: Measure<'a>,
@@ -24,11 +31,10 @@ pub struct Rectangle<'a> {
   pub width: Measure<'a>,
   pub height: Measure<'a>,
 
-  // `drop` is NOT called on this!
   pub painter: RectanglePainter<'a>,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, ReflectMeasures)]
 pub struct RectangleMeasures<'a> {
   pub left: Measure<'a>,
   pub right: Measure<'a>,
@@ -38,7 +44,32 @@ pub struct RectangleMeasures<'a> {
   pub height: Measure<'a>,
 }
 
+/// Containment in a parent that tolerates the rectangle sticking out by a bounded amount.
+#[derive(Copy, Clone, Debug)]
+pub struct TolerantContainment<'a> {
+  /// How far the rectangle sticks out of the parent on its worst side, zero when contained.
+  pub overflow: Measure<'a>,
+  pub max_overflow: Measure<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> TolerantContainment<'a> {
+  /// Overflow beyond `max_overflow` is ruled out, and full containment is preferred.
+  pub fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![
+      self.overflow.prop_le(self.max_overflow).hard(),
+      self.overflow.prop_eq(Measure::zero(self.overflow.ctx)),
+    ]
+  }
+
+  /// Minimizes the overflow when full containment is out of reach.
+  pub fn objective(&self) -> Objective<'a> {
+    Objective::Minimize(self.overflow)
+  }
+}
+
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RectangleMetrics {
   pub left: f64,
   pub right: f64,
@@ -48,11 +79,24 @@ pub struct RectangleMetrics {
   pub height: f64,
 }
 
+impl RectangleMetrics {
+  /// The rectangle moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    RectangleMetrics {
+      left: transform.x(self.left),
+      right: transform.x(self.right),
+      top: transform.y(self.top),
+      bottom: transform.y(self.bottom),
+      ..self
+    }
+  }
+}
+
 #[allow(dead_code)]
 impl<'a> RectangleMeasures<'a> {
-  pub fn group_center(group: &[&RectangleMeasures<'a>]) -> Result<Point<'a>> {
+  pub fn group_center(group: &[&RectangleMeasures<'a>]) -> Result<Point<'a>, RectangleError> {
     if group.len() == 0 {
-      Err(RectangleError::EmptyGroup.into())
+      Err(RectangleError::EmptyGroup)
     } else {
       let p = group
         .iter()
@@ -69,9 +113,9 @@ impl<'a> RectangleMeasures<'a> {
     }
   }
 
-  pub fn group_leftmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>> {
+  pub fn group_leftmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>, RectangleError> {
     if group.len() == 0 {
-      Err(RectangleError::EmptyGroup.into())
+      Err(RectangleError::EmptyGroup)
     } else {
       let p = group
         .iter()
@@ -82,9 +126,9 @@ impl<'a> RectangleMeasures<'a> {
     }
   }
 
-  pub fn group_rightmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>> {
+  pub fn group_rightmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>, RectangleError> {
     if group.len() == 0 {
-      Err(RectangleError::EmptyGroup.into())
+      Err(RectangleError::EmptyGroup)
     } else {
       let p = group
         .iter()
@@ -95,18 +139,18 @@ impl<'a> RectangleMeasures<'a> {
     }
   }
 
-  pub fn group_topmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>> {
+  pub fn group_topmost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>, RectangleError> {
     if group.len() == 0 {
-      Err(RectangleError::EmptyGroup.into())
+      Err(RectangleError::EmptyGroup)
     } else {
       let p = group.iter().map(|x| x.top).reduce(|a, b| a.min(b)).unwrap();
       Ok(p)
     }
   }
 
-  pub fn group_bottommost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>> {
+  pub fn group_bottommost(group: &[&RectangleMeasures<'a>]) -> Result<Measure<'a>, RectangleError> {
     if group.len() == 0 {
-      Err(RectangleError::EmptyGroup.into())
+      Err(RectangleError::EmptyGroup)
     } else {
       let p = group
         .iter()
@@ -124,7 +168,42 @@ impl<'a> RectangleMeasures<'a> {
       & self.bottom_to(that.top, 0.0)
   }
 
-  pub fn center(&self) -> Result<Point<'a>> {
+  /// Like `within`, but lets the rectangle stick out of `parent` by up to `max_overflow` on
+  /// every side. The overflow actually used can be read from the solution.
+  pub fn within_tolerant(
+    &self,
+    parent: &RectangleMeasures<'a>,
+    max_overflow: f64,
+  ) -> TolerantContainment<'a> {
+    let ctx = self.left.ctx;
+    let overflow = Measure::zero(ctx)
+      .max(parent.left - self.left)
+      .max(self.right - parent.right)
+      .max(parent.top - self.top)
+      .max(self.bottom - parent.bottom);
+    TolerantContainment {
+      overflow,
+      max_overflow: Measure::new_const(ctx, max_overflow).unwrap(),
+    }
+  }
+
+  pub fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+
+  pub fn size(&self) -> Size<'a> {
+    Size {
+      width: self.width,
+      height: self.height,
+    }
+  }
+
+  pub fn center(&self) -> Result<Point<'a>, RectangleError> {
     Self::group_center(&[self])
   }
 
@@ -217,7 +296,15 @@ impl<'a> Rectangle<'a> {
   }
 }
 
-impl<'a> RawWidget<'a> for Rectangle<'a> {
+impl<'a> BoundedWidget<'a> for Rectangle<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.measures().rect()
+  }
+}
+
+impl<'a> Widget<'a> for Rectangle<'a> {
+  type Metrics = RectangleMetrics;
+
   fn measures(&self) -> Vec<Measure<'a>> {
     vec![
       self.left,
@@ -231,29 +318,50 @@ impl<'a> RawWidget<'a> for Rectangle<'a> {
 
   fn constraints(&self) -> Vec<Prop<'a>> {
     vec![
-      (self.left + self.width).prop_eq(self.right),
-      (self.top + self.height).prop_eq(self.bottom),
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
       self.top.prop_ge(Measure::zero(self.top.ctx)),
       self.left.prop_ge(Measure::zero(self.left.ctx)),
       self
         .width
-        .prop_ge(Measure::new_const(self.width.ctx, 0.0).unwrap()),
+        .prop_ge(Measure::new_const(self.width.ctx, 0.0).unwrap())
+        .hard(),
       self
         .height
-        .prop_ge(Measure::new_const(self.height.ctx, 0.0).unwrap()),
+        .prop_ge(Measure::new_const(self.height.ctx, 0.0).unwrap())
+        .hard(),
     ]
   }
 
-  fn paint(self: Box<Self>, measures: &[f64]) -> Result<()> {
-    let metrics = RectangleMetrics {
-      left: measures[0],
-      right: measures[1],
-      top: measures[2],
-      bottom: measures[3],
-      width: measures[4],
-      height: measures[5],
-    };
-
-    (self.painter)(metrics)
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    })
+  }
+
+  fn decode(&self, values: &[f64]) -> RectangleMetrics {
+    RectangleMetrics {
+      left: values[0],
+      right: values[1],
+      top: values[2],
+      bottom: values[3],
+      width: values[4],
+      height: values[5],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: RectangleMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: RectangleMetrics, transform: Transform) -> RectangleMetrics {
+    metrics.transformed(transform)
   }
 }
@@ -0,0 +1,204 @@
+use crate::layout::{
+  angle::Angle,
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+use super::RectangleMetrics;
+
+pub type RotatedRectPainter<'a> = Box<dyn Painter<RotatedRectMetrics> + 'a>;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotatedRectMetrics {
+  pub cx: f64,
+  pub cy: f64,
+  pub width: f64,
+  pub height: f64,
+
+  /// Clockwise on screen, in radians.
+  pub angle: f64,
+
+  /// The axis-aligned bounding box.
+  pub bounds: RectangleMetrics,
+}
+
+impl RotatedRectMetrics {
+  /// The rectangle moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    RotatedRectMetrics {
+      cx: transform.x(self.cx),
+      cy: transform.y(self.cy),
+      bounds: self.bounds.transformed(transform),
+      ..self
+    }
+  }
+}
+
+/// A rectangle of `width` and `height` turned clockwise by `angle` around its center, like a
+/// rotated label. It is laid out by its axis-aligned bounding box, kept tight by constraints,
+/// so that overlap and containment work as for other widgets.
+///
+/// The constraints are linear as long as `angle` is a constant.
+#[derive(ReflectMeasures)]
+pub struct RotatedRect<'a> {
+  pub cx: Measure<'a>,
+  pub cy: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+  pub angle: Angle<'a>,
+
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+
+  pub painter: RotatedRectPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> RotatedRect<'a> {
+  pub fn new(ctx: &'a LayoutContext, angle: Angle<'a>, painter: RotatedRectPainter<'a>) -> Self {
+    Self {
+      cx: Measure::new_unbound(ctx),
+      cy: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      angle,
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      painter,
+    }
+  }
+
+  /// A rectangle of constant size turned by a constant angle, like a label of known extent.
+  pub fn with_size(
+    ctx: &'a LayoutContext,
+    width: f64,
+    height: f64,
+    degrees: f64,
+    painter: RotatedRectPainter<'a>,
+  ) -> Self {
+    Self {
+      width: Measure::new_const(ctx, width).unwrap(),
+      height: Measure::new_const(ctx, height).unwrap(),
+      ..Self::new(ctx, Angle::degrees(ctx, degrees), painter)
+    }
+  }
+
+  pub fn center(&self) -> Point<'a> {
+    Point {
+      x: self.cx,
+      y: self.cy,
+    }
+  }
+
+  /// The corners of the rectangle before rotation, clockwise from the top left, where they end
+  /// up after it.
+  pub fn corners(&self) -> [Point<'a>; 4] {
+    let (cos, sin) = (self.angle.cos(), self.angle.sin());
+    let (w, h) = (self.width / 2.0, self.height / 2.0);
+    let corner = |dx: Measure<'a>, dy: Measure<'a>| Point {
+      x: self.cx + dx * cos - dy * sin,
+      y: self.cy + dx * sin + dy * cos,
+    };
+    let zero = Measure::zero(self.cx.ctx);
+    [
+      corner(zero - w, zero - h),
+      corner(w, zero - h),
+      corner(w, h),
+      corner(zero - w, h),
+    ]
+  }
+}
+
+impl<'a> BoundedWidget<'a> for RotatedRect<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> Widget<'a> for RotatedRect<'a> {
+  type Metrics = RotatedRectMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![
+      self.cx,
+      self.cy,
+      self.width,
+      self.height,
+      self.angle.radians,
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.cx.ctx);
+    let (cos, sin) = (self.angle.cos().abs(), self.angle.sin().abs());
+    // Half the extents of the bounding box.
+    let half_width = (self.width * cos + self.height * sin) / 2.0;
+    let half_height = (self.width * sin + self.height * cos) / 2.0;
+    vec![
+      self.width.prop_ge(zero).hard(),
+      self.height.prop_ge(zero).hard(),
+      self.left.prop_eq(self.cx - half_width).hard(),
+      self.right.prop_eq(self.cx + half_width).hard(),
+      self.top.prop_eq(self.cy - half_height).hard(),
+      self.bottom.prop_eq(self.cy + half_height).hard(),
+      self.top.prop_ge(zero),
+      self.left.prop_ge(zero),
+    ]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn decode(&self, values: &[f64]) -> RotatedRectMetrics {
+    let (left, right, top, bottom) = (values[5], values[6], values[7], values[8]);
+    RotatedRectMetrics {
+      cx: values[0],
+      cy: values[1],
+      width: values[2],
+      height: values[3],
+      angle: values[4],
+      bounds: RectangleMetrics {
+        left,
+        right,
+        top,
+        bottom,
+        width: right - left,
+        height: bottom - top,
+      },
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: RotatedRectMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: RotatedRectMetrics, transform: Transform) -> RotatedRectMetrics {
+    metrics.transformed(transform)
+  }
+}
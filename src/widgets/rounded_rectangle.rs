@@ -0,0 +1,194 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+use super::{RectangleMeasures, RectangleMetrics};
+
+pub type RoundedRectanglePainter<'a> = Box<dyn Painter<RoundedRectangleMetrics> + 'a>;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundedRectangleMetrics {
+  pub frame: RectangleMetrics,
+
+  pub top_left: f64,
+  pub top_right: f64,
+  pub bottom_right: f64,
+  pub bottom_left: f64,
+}
+
+impl RoundedRectangleMetrics {
+  /// The rectangle moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    RoundedRectangleMetrics {
+      frame: self.frame.transformed(transform),
+      ..self
+    }
+  }
+}
+
+/// A rectangle with rounded corners. Each corner radius is a measure, kept between zero and
+/// half the width and the height, so that radii can follow the solved size.
+#[derive(ReflectMeasures)]
+pub struct RoundedRectangle<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub top_left: Measure<'a>,
+  pub top_right: Measure<'a>,
+  pub bottom_right: Measure<'a>,
+  pub bottom_left: Measure<'a>,
+
+  pub painter: RoundedRectanglePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> RoundedRectangle<'a> {
+  /// A rectangle whose edges and corner radii are all unbound.
+  pub fn unbound(ctx: &'a LayoutContext, painter: RoundedRectanglePainter<'a>) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      top_left: Measure::new_unbound(ctx),
+      top_right: Measure::new_unbound(ctx),
+      bottom_right: Measure::new_unbound(ctx),
+      bottom_left: Measure::new_unbound(ctx),
+      painter,
+    }
+  }
+
+  /// The rectangle with `radius` on every corner.
+  pub fn with_radius(self, radius: Measure<'a>) -> Self {
+    Self {
+      top_left: radius,
+      top_right: radius,
+      bottom_right: radius,
+      bottom_left: radius,
+      ..self
+    }
+  }
+
+  pub fn measures(&self) -> RectangleMeasures<'a> {
+    RectangleMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+      width: self.width,
+      height: self.height,
+    }
+  }
+
+  pub fn radii(&self) -> [Measure<'a>; 4] {
+    [
+      self.top_left,
+      self.top_right,
+      self.bottom_right,
+      self.bottom_left,
+    ]
+  }
+
+  /// Holds when every corner is as round as it can be, which makes a pill or a circle.
+  pub fn fully_rounded(&self) -> Prop<'a> {
+    let max = self.width.min(self.height) / 2.0;
+    let ctx = self.width.ctx;
+    self
+      .radii()
+      .iter()
+      .fold(Prop::new_const(ctx, true), |all, radius| {
+        all & radius.prop_eq(max)
+      })
+  }
+}
+
+impl<'a> BoundedWidget<'a> for RoundedRectangle<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.measures().rect()
+  }
+}
+
+impl<'a> Widget<'a> for RoundedRectangle<'a> {
+  type Metrics = RoundedRectangleMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+      self.top_left,
+      self.top_right,
+      self.bottom_right,
+      self.bottom_left,
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let zero = Measure::zero(self.width.ctx);
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+      self.top.prop_ge(zero),
+      self.left.prop_ge(zero),
+      self.width.prop_ge(zero).hard(),
+      self.height.prop_ge(zero).hard(),
+    ];
+    for radius in self.radii().iter() {
+      constraints.push(radius.prop_ge(zero).hard());
+      constraints.push((*radius * 2.0).prop_le(self.width).hard());
+      constraints.push((*radius * 2.0).prop_le(self.height).hard());
+    }
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn decode(&self, values: &[f64]) -> RoundedRectangleMetrics {
+    RoundedRectangleMetrics {
+      frame: RectangleMetrics {
+        left: values[0],
+        right: values[1],
+        top: values[2],
+        bottom: values[3],
+        width: values[4],
+        height: values[5],
+      },
+      top_left: values[6],
+      top_right: values[7],
+      bottom_right: values[8],
+      bottom_left: values[9],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: RoundedRectangleMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: RoundedRectangleMetrics, transform: Transform) -> RoundedRectangleMetrics {
+    metrics.transformed(transform)
+  }
+}
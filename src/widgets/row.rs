@@ -0,0 +1,228 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  objective::Objective,
+  prop::Prop,
+  render::RenderTarget,
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget, Widget},
+};
+
+use super::{Rectangle, RectanglePainter};
+
+/// How a child of a `Row` is sized, like the `flex` shorthand of CSS.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Flex {
+  /// Width of the child before free space is shared out.
+  pub basis: f64,
+
+  /// Share of the space left in the row that the child takes.
+  pub grow: f64,
+
+  /// Share of the overflow of the row that the child gives up, in proportion to its basis.
+  pub shrink: f64,
+}
+
+#[allow(dead_code)]
+impl Flex {
+  pub fn new(basis: f64, grow: f64, shrink: f64) -> Self {
+    Self {
+      basis,
+      grow,
+      shrink,
+    }
+  }
+
+  /// A child that stays at `basis` however much space there is.
+  pub fn fixed(basis: f64) -> Self {
+    Self::new(basis, 0.0, 0.0)
+  }
+}
+
+struct FlexItem<'a> {
+  widget: Box<dyn BoundedWidget<'a> + 'a>,
+  flex: Flex,
+}
+
+/// Children laid out from left to right along the top of a frame, sized like the items of a CSS
+/// flexbox row: each starts at its basis, then the space left in the row is shared out by
+/// `Flex::grow`, or the overflow is taken back by `Flex::shrink`.
+///
+/// The width of the frame can be constrained, or left to the solver, which prefers the sum of the
+/// bases. Children never shrink below zero, and are placed by their margin boxes, so that their
+/// `BoundedWidget::margins` are kept clear. They share the coordinates of the row and are painted
+/// after the frame.
+pub struct Row<'a> {
+  pub frame: Rectangle<'a>,
+
+  /// Space between neighbours.
+  pub gap: f64,
+
+  items: Vec<FlexItem<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> Row<'a> {
+  pub fn new(ctx: &'a LayoutContext, painter: RectanglePainter<'a>) -> Self {
+    Self {
+      frame: Rectangle::unbound(ctx, painter),
+      gap: 0.0,
+      items: vec![],
+    }
+  }
+
+  /// The row with `gap` between neighbours.
+  pub fn with_gap(self, gap: f64) -> Self {
+    Self { gap, ..self }
+  }
+
+  /// Adds a child at the end of the row.
+  pub fn push<W: BoundedWidget<'a> + 'a>(&mut self, widget: W, flex: Flex) {
+    self.items.push(FlexItem {
+      widget: Box::new(widget),
+      flex,
+    });
+  }
+
+  /// Width of the frame left over by the bases, the gaps and the horizontal margins of the
+  /// children, negative when the children overflow it.
+  pub fn free_space(&self) -> Measure<'a> {
+    let gaps = self.gap * self.items.len().saturating_sub(1) as f64;
+    let bases: f64 = self.items.iter().map(|x| x.flex.basis).sum();
+    self
+      .items
+      .iter()
+      .map(|x| x.widget.margins())
+      .fold(self.frame.width - (bases + gaps), |free, margins| {
+        free - margins.left - margins.right
+      })
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Row<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    self.frame.rect()
+  }
+}
+
+impl<'a> RawWidget<'a> for Row<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let mut measures = RawWidget::measures(&self.frame);
+    for item in &self.items {
+      measures.extend(item.widget.measures());
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let frame = self.frame.rect();
+    let zero = Measure::zero(self.frame.width.ctx);
+    let free = self.free_space();
+    let growing = free.prop_ge(zero);
+    let total_grow: f64 = self.items.iter().map(|x| x.flex.grow).sum();
+    let total_shrink: f64 = self
+      .items
+      .iter()
+      .map(|x| x.flex.shrink * x.flex.basis)
+      .sum();
+
+    let mut constraints = RawWidget::constraints(&self.frame);
+    constraints.push(free.prop_eq(zero));
+    let mut left = frame.left;
+    for item in &self.items {
+      let rect = item.widget.rect();
+      let Flex {
+        basis,
+        grow,
+        shrink,
+      } = item.flex;
+      // The share of the free space is written as a product, so that it is exact for shares
+      // like a third.
+      let extra = rect.width() - basis;
+      let grown = if total_grow > 0.0 {
+        (extra * total_grow).prop_eq(free * grow)
+      } else {
+        extra.prop_eq(zero)
+      };
+      // A child that would shrink past zero stays empty and the row overflows, like the minimum
+      // width of a flex item.
+      let shrunk = if total_shrink > 0.0 {
+        let width = free * (shrink * basis) + basis * total_shrink;
+        (rect.width() * total_shrink).prop_eq(width.max(zero))
+      } else {
+        extra.prop_eq(zero)
+      };
+      let outer = item.widget.margin_box();
+      constraints.push(grown.when(growing).hard());
+      constraints.push(shrunk.when(!growing).hard());
+      constraints.push(rect.width().prop_ge(zero).hard());
+      constraints.push(outer.left.prop_eq(left).hard());
+      constraints.push(outer.top.prop_eq(frame.top).hard());
+      constraints.push(outer.bottom.prop_le(frame.bottom).hard());
+      left = outer.right + self.gap;
+      constraints.extend(item.widget.constraints());
+    }
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    self
+      .items
+      .iter()
+      .flat_map(|x| x.widget.objectives())
+      .collect()
+  }
+
+  fn on_solved(&mut self, measures: &[f64]) {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    for item in &mut self.items {
+      let len = item.widget.measures().len();
+      item.widget.on_solved(&measures[offset..offset + len]);
+      offset += len;
+    }
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Widget::bounds(&self.frame)
+  }
+
+  fn content_constraints(&self, measures: &[f64]) -> Vec<Prop<'a>> {
+    let mut offset = RawWidget::measures(&self.frame).len();
+    let mut constraints = vec![];
+    for item in &self.items {
+      let len = item.widget.measures().len();
+      constraints.extend(
+        item
+          .widget
+          .content_constraints(&measures[offset..offset + len]),
+      );
+      offset += len;
+    }
+    constraints
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame_len = RawWidget::measures(&self.frame).len();
+    RawWidget::paint_at(&mut self.frame, &measures[..frame_len], transform, target)?;
+    let mut offset = frame_len;
+    for item in &mut self.items {
+      let len = item.widget.measures().len();
+      item
+        .widget
+        .paint_at(&measures[offset..offset + len], transform, target)?;
+      offset += len;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,226 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::Widget,
+};
+
+use super::RectangleMetrics;
+
+pub type ScatterLabelsPainter<'a> = Box<dyn Painter<ScatterLabelsMetrics> + 'a>;
+
+/// Scale of label rewards relative to the preference between candidate positions.
+const LABEL_REWARD_SCALE: u32 = 10;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScatterLabelsMetrics {
+  /// Placement of each label, `None` for hidden labels.
+  pub labels: Vec<Option<RectangleMetrics>>,
+}
+
+pub struct ScatterLabel<'a> {
+  pub anchor: Point<'a>,
+  pub width: f64,
+  pub height: f64,
+  pub reward: u32,
+  pub rect: RectMeasures<'a>,
+  pub visible: Prop<'a>,
+
+  /// Positions above right, above left, below right and below left of the anchor, in order of
+  /// preference.
+  candidates: Vec<Prop<'a>>,
+}
+
+/// Labels of a scatter plot, placed around their anchors without overlapping each other.
+///
+/// The solver chooses which labels to show, maximizing the total reward of the shown labels,
+/// and where to put each of them.
+pub struct ScatterLabels<'a> {
+  pub ctx: &'a LayoutContext,
+  labels: Vec<ScatterLabel<'a>>,
+
+  /// Distance between an anchor and the corner of its label.
+  pub offset: f64,
+
+  /// Area the shown labels have to stay in, usually the plot area.
+  pub bounds: Option<RectMeasures<'a>>,
+
+  /// Upper bound on the number of shown labels.
+  pub max_visible: Option<usize>,
+
+  pub painter: ScatterLabelsPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> ScatterLabels<'a> {
+  pub fn new(ctx: &'a LayoutContext, offset: f64, painter: ScatterLabelsPainter<'a>) -> Self {
+    Self {
+      ctx,
+      labels: vec![],
+      offset,
+      bounds: None,
+      max_visible: None,
+      painter,
+    }
+  }
+
+  /// Adds a label of the given size for the point at `anchor`, and returns whether it is shown.
+  pub fn push_label(
+    &mut self,
+    anchor: Point<'a>,
+    width: f64,
+    height: f64,
+    reward: u32,
+  ) -> Prop<'a> {
+    let ctx = self.ctx;
+    let origin = Point {
+      x: Measure::new_unbound(ctx),
+      y: Measure::new_unbound(ctx),
+    };
+    let rect = RectMeasures {
+      left: origin.x,
+      right: origin.x + width,
+      top: origin.y,
+      bottom: origin.y + height,
+    };
+
+    let d = self.offset;
+    let right = anchor.x + d;
+    let left = anchor.x - (d + width);
+    let above = anchor.y - (d + height);
+    let below = anchor.y + d;
+    let candidates = [(right, above), (left, above), (right, below), (left, below)]
+      .iter()
+      .map(|&(x, y)| origin.x.prop_eq(x) & origin.y.prop_eq(y))
+      .collect();
+
+    let visible = Prop::new_unbound(ctx);
+    self.labels.push(ScatterLabel {
+      anchor,
+      width,
+      height,
+      reward,
+      rect,
+      visible,
+      candidates,
+    });
+    visible
+  }
+
+  pub fn labels(&self) -> &[ScatterLabel<'a>] {
+    &self.labels
+  }
+
+  /// Whether the labels of `a` and `b` may overlap at all. Only decided for constant anchors.
+  fn may_overlap(&self, a: &ScatterLabel<'a>, b: &ScatterLabel<'a>) -> bool {
+    let reach =
+      |a: Measure<'a>, b: Measure<'a>, extent: f64| match (a.const_value(), b.const_value()) {
+        (Some(a), Some(b)) => (a - b).abs() < extent,
+        _ => true,
+      };
+    reach(
+      a.anchor.x,
+      b.anchor.x,
+      2.0 * self.offset + a.width + b.width,
+    ) && reach(
+      a.anchor.y,
+      b.anchor.y,
+      2.0 * self.offset + a.height + b.height,
+    )
+  }
+}
+
+impl<'a> Widget<'a> for ScatterLabels<'a> {
+  type Metrics = ScatterLabelsMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let one = Measure::new_const(self.ctx, 1.0).unwrap();
+    let zero = Measure::zero(self.ctx);
+    self
+      .labels
+      .iter()
+      .flat_map(|x| vec![x.visible.select(one, zero), x.rect.left, x.rect.top])
+      .collect()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = vec![];
+    for (i, label) in self.labels.iter().enumerate() {
+      let placed = label
+        .candidates
+        .iter()
+        .copied()
+        .reduce(|a, b| a | b)
+        .unwrap();
+      constraints.push((!label.visible | placed).hard());
+      if let Some(bounds) = &self.bounds {
+        constraints.push((!label.visible | bounds.contains(&label.rect)).hard());
+      }
+      constraints.push(
+        label
+          .visible
+          .with_weight(label.reward.max(1) * LABEL_REWARD_SCALE),
+      );
+      for (j, &candidate) in label.candidates.iter().enumerate().take(3) {
+        constraints.push(candidate.with_weight(3 - j as u32));
+      }
+
+      for other in &self.labels[i + 1..] {
+        if self.may_overlap(label, other) {
+          constraints
+            .push((!label.visible | !other.visible | label.rect.disjoint(&other.rect)).hard());
+        }
+      }
+    }
+    if let Some(max_visible) = self.max_visible {
+      let visible = self.labels.iter().map(|x| x.visible).collect::<Vec<_>>();
+      constraints.push(Prop::at_most(self.ctx, &visible, max_visible).hard());
+    }
+    constraints
+  }
+
+  fn decode(&self, values: &[f64]) -> ScatterLabelsMetrics {
+    let labels = self
+      .labels
+      .iter()
+      .zip(values.chunks(3))
+      .map(|(label, m)| {
+        if m[0] == 0.0 {
+          return None;
+        }
+        Some(RectangleMetrics {
+          left: m[1],
+          right: m[1] + label.width,
+          top: m[2],
+          bottom: m[2] + label.height,
+          width: label.width,
+          height: label.height,
+        })
+      })
+      .collect();
+    ScatterLabelsMetrics { labels }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: ScatterLabelsMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: ScatterLabelsMetrics, transform: Transform) -> ScatterLabelsMetrics {
+    ScatterLabelsMetrics {
+      labels: metrics
+        .labels
+        .into_iter()
+        .map(|x| x.map(|x| x.transformed(transform)))
+        .collect(),
+    }
+  }
+}
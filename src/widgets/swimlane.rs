@@ -0,0 +1,122 @@
+use crate::layout::{
+  context::LayoutContext, error::PaintError, measure::Measure, objective::Objective, prop::Prop,
+  reflect::ReflectMeasures, render::RenderTarget, transform::Transform, widget::RawWidget,
+};
+
+use super::{Point, Rectangle, RectangleMeasures, RectanglePainter};
+
+/// Lanes stacked as horizontal bands, each growing to fit the elements placed in it.
+#[derive(ReflectMeasures)]
+pub struct Swimlane<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+
+  /// Space kept between a lane's border and the elements placed in it.
+  pub padding: f64,
+
+  pub lanes: Vec<Rectangle<'a>>,
+  connectors: Vec<(Point<'a>, Point<'a>)>,
+}
+
+#[allow(dead_code)]
+impl<'a> Swimlane<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    lane_painters: Vec<RectanglePainter<'a>>,
+    padding: f64,
+  ) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      padding,
+      lanes: lane_painters
+        .into_iter()
+        .map(|painter| Rectangle::unbound(ctx, painter))
+        .collect(),
+      connectors: vec![],
+    }
+  }
+
+  pub fn lane(&self, index: usize) -> RectangleMeasures<'a> {
+    self.lanes[index].measures()
+  }
+
+  /// Constrains `element` to lie inside the lane at `index`, keeping `padding` from its borders.
+  pub fn place(&self, index: usize, element: &RectangleMeasures<'a>) -> Prop<'a> {
+    let lane = self.lane(index);
+    element.left.prop_ge(lane.left + self.padding)
+      & element.right.prop_le(lane.right - self.padding)
+      & element.top.prop_ge(lane.top + self.padding)
+      & element.bottom.prop_le(lane.bottom - self.padding)
+  }
+
+  /// Registers a connector between two points, whose Manhattan length is minimized.
+  pub fn connect(&mut self, from: Point<'a>, to: Point<'a>) {
+    self.connectors.push((from, to));
+  }
+}
+
+impl<'a> RawWidget<'a> for Swimlane<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    self
+      .lanes
+      .iter()
+      .flat_map(|x| RawWidget::measures(x).into_iter())
+      .collect()
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = self
+      .lanes
+      .iter()
+      .flat_map(|x| x.constraints().into_iter())
+      .collect::<Vec<_>>();
+
+    let mut last_bottom = self.top;
+    for lane in &self.lanes {
+      constraints.push(lane.left.prop_eq(self.left).hard());
+      constraints.push(lane.right.prop_eq(self.right).hard());
+      constraints.push(lane.top.prop_eq(last_bottom).hard());
+      last_bottom = lane.bottom;
+    }
+    constraints.push(self.bottom.prop_eq(last_bottom).hard());
+    constraints
+  }
+
+  fn objectives(&self) -> Vec<Objective<'a>> {
+    // Lanes shrink to their contents first, then connectors are kept short.
+    let mut objectives = vec![Objective::Minimize(self.bottom - self.top)];
+    if let Some(total) = self
+      .connectors
+      .iter()
+      .map(|(from, to)| (from.x - to.x).abs() + (from.y - to.y).abs())
+      .reduce(|a, b| a + b)
+    {
+      objectives.push(Objective::Minimize(total));
+    }
+    objectives
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let mut offset = 0;
+    for lane in &mut self.lanes {
+      let len = RawWidget::measures(&*lane).len();
+      RawWidget::paint_at(lane, &measures[offset..offset + len], transform, target)?;
+      offset += len;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,287 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget},
+};
+
+use super::{Rectangle, RectangleMetrics};
+
+pub type TabStripPainter<'a> = Box<dyn Painter<TabStripMetrics> + 'a>;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TabStripMode {
+  /// Every tab shares the strip equally.
+  EqualWidth,
+
+  /// Every tab takes its content width.
+  HugContent,
+
+  /// Tabs shrink from their content width towards their minimum width.
+  CompressToMin,
+
+  /// Tabs take their minimum width and the strip scrolls.
+  Scroll,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TabStripMetrics {
+  pub frame: RectangleMetrics,
+  pub mode: TabStripMode,
+  pub tabs: Vec<RectangleMetrics>,
+
+  /// Total width of the tabs. Exceeds the frame width only in `Scroll` mode.
+  pub scroll_width: f64,
+}
+
+pub struct Tab<'a> {
+  pub rect: Rectangle<'a>,
+  pub content_width: f64,
+  pub min_width: f64,
+}
+
+/// A horizontal strip of tabs.
+///
+/// The widest mode that fits is chosen, in the order equal width, hug content, compress to
+/// min. When even the minimum widths do not fit, the strip scrolls if `scrollable` is set and
+/// is unsatisfiable otherwise.
+#[derive(ReflectMeasures)]
+pub struct TabStrip<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub gap: f64,
+  pub scrollable: bool,
+  tabs: Vec<Tab<'a>>,
+
+  pub painter: TabStripPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> TabStrip<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    gap: f64,
+    scrollable: bool,
+    painter: TabStripPainter<'a>,
+  ) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      gap,
+      scrollable,
+      tabs: vec![],
+      painter,
+    }
+  }
+
+  /// Appends a tab. The strip decides the horizontal placement and the height of `rect`.
+  pub fn push_tab(&mut self, rect: Rectangle<'a>, content_width: f64, min_width: f64) {
+    self.tabs.push(Tab {
+      rect,
+      content_width,
+      min_width: min_width.min(content_width),
+    });
+  }
+
+  pub fn tabs(&self) -> &[Tab<'a>] {
+    &self.tabs
+  }
+
+  /// Width left for the tabs once the gaps are taken out.
+  fn available(&self) -> Measure<'a> {
+    self.width - self.gap * self.tabs.len().saturating_sub(1) as f64
+  }
+
+  fn total_content_width(&self) -> f64 {
+    self.tabs.iter().map(|x| x.content_width).sum()
+  }
+
+  fn total_min_width(&self) -> f64 {
+    self.tabs.iter().map(|x| x.min_width).sum()
+  }
+
+  pub fn equal_width_fits(&self) -> Prop<'a> {
+    let widest = self
+      .tabs
+      .iter()
+      .map(|x| x.content_width)
+      .fold(0.0, f64::max);
+    let ctx = self.width.ctx;
+    Measure::new_const(ctx, widest * self.tabs.len() as f64)
+      .unwrap()
+      .prop_le(self.available())
+  }
+
+  pub fn hug_content_fits(&self) -> Prop<'a> {
+    let ctx = self.width.ctx;
+    Measure::new_const(ctx, self.total_content_width())
+      .unwrap()
+      .prop_le(self.available())
+  }
+
+  pub fn compress_to_min_fits(&self) -> Prop<'a> {
+    let ctx = self.width.ctx;
+    Measure::new_const(ctx, self.total_min_width())
+      .unwrap()
+      .prop_le(self.available())
+  }
+
+  fn tab_width(&self, tab: &Tab<'a>) -> Measure<'a> {
+    let ctx = self.width.ctx;
+    let available = self.available();
+    let content = Measure::new_const(ctx, tab.content_width).unwrap();
+    let min = Measure::new_const(ctx, tab.min_width).unwrap();
+
+    // Compressed tabs give up the same fraction of their slack.
+    let slack = self.total_content_width() - self.total_min_width();
+    let compressed = if slack > 0.0 {
+      min
+        + (available - self.total_min_width())
+          * Measure::new_const(ctx, tab.content_width - tab.min_width).unwrap()
+          / slack
+    } else {
+      min
+    };
+
+    self.equal_width_fits().select(
+      available / self.tabs.len() as f64,
+      self
+        .hug_content_fits()
+        .select(content, self.compress_to_min_fits().select(compressed, min)),
+    )
+  }
+}
+
+impl<'a> BoundedWidget<'a> for TabStrip<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> RawWidget<'a> for TabStrip<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let ctx = self.left.ctx;
+    let mode = self.equal_width_fits().select(
+      Measure::zero(ctx),
+      self.hug_content_fits().select(
+        Measure::new_const(ctx, 1.0).unwrap(),
+        self.compress_to_min_fits().select(
+          Measure::new_const(ctx, 2.0).unwrap(),
+          Measure::new_const(ctx, 3.0).unwrap(),
+        ),
+      ),
+    );
+    let mut measures = vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+      mode,
+    ];
+    for tab in &self.tabs {
+      measures.extend(RawWidget::measures(&tab.rect));
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+    ];
+    if !self.scrollable && !self.tabs.is_empty() {
+      constraints.push(self.compress_to_min_fits().hard());
+    }
+
+    let mut cursor = self.left;
+    for tab in &self.tabs {
+      constraints.extend(tab.rect.constraints());
+      constraints.push(tab.rect.left.prop_eq(cursor).hard());
+      constraints.push(tab.rect.top.prop_eq(self.top).hard());
+      constraints.push(tab.rect.bottom.prop_eq(self.bottom).hard());
+      constraints.push(tab.rect.width.prop_eq(self.tab_width(tab)).hard());
+      cursor = tab.rect.right + self.gap;
+    }
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame = RectangleMetrics {
+      left: measures[0],
+      right: measures[1],
+      top: measures[2],
+      bottom: measures[3],
+      width: measures[4],
+      height: measures[5],
+    }
+    .transformed(transform);
+    let mode = match measures[6] as u32 {
+      0 => TabStripMode::EqualWidth,
+      1 => TabStripMode::HugContent,
+      2 => TabStripMode::CompressToMin,
+      _ => TabStripMode::Scroll,
+    };
+
+    let mut offset = 7;
+    let mut tabs = vec![];
+    for tab in &mut self.tabs {
+      let len = RawWidget::measures(&tab.rect).len();
+      let tab_measures = &measures[offset..offset + len];
+      tabs.push(
+        crate::layout::widget::Widget::decode(&tab.rect, tab_measures).transformed(transform),
+      );
+      RawWidget::paint_at(&mut tab.rect, tab_measures, transform, target)?;
+      offset += len;
+    }
+
+    let scroll_width = match (tabs.first(), tabs.last()) {
+      (Some(first), Some(last)) => last.right - first.left,
+      _ => 0.0,
+    };
+    self.painter.paint(
+      TabStripMetrics {
+        frame,
+        mode,
+        tabs,
+        scroll_width,
+      },
+      target,
+    )
+  }
+}
@@ -1,7 +1,46 @@
 use rand::Rng;
 
-use super::Rectangle;
-use crate::layout::{builder::LayoutBuilder, context::LayoutContext, measure::Measure};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use super::{
+  Arc as ArcWidget, ArcMetrics, Barrier, BarrierSide, Breadcrumbs, BreadcrumbsMetrics, Canvas,
+  CanvasMetrics, Collapsible, Container, Direction, Ellipse, EllipseMetrics, Fit, Flex, Group,
+  Guideline, GuidelineAxis, Image, ImageMetrics, Line, LineMetrics, LinePainter, Margined,
+  OptionalWidgets, Padded, PageSize, Polygon, PolygonMetrics, Polyline, PolylineMetrics, Rectangle,
+  RectangleMeasures, RectangleMetrics, RotatedRect, RoundedRectangle, RoundedRectangleMetrics, Row,
+  SegmentKind, Swimlane, TabStrip, TabStripMetrics, TabStripMode, Text, TextMetrics, Toolbar,
+  ToolbarMetrics, Triangle, TriangleMetrics, Typeface,
+};
+#[cfg(feature = "z3")]
+use crate::layout::{
+  builder::{Distance, Diversity, SolverLogic},
+  session::SolverSession,
+};
+use crate::layout::{
+  builder::{LayoutBuilder, SolvePath, SolverConfig},
+  cache::MemoryStore,
+  cancellation::CancellationToken,
+  context::LayoutContext,
+  error::{LayoutError, PaintError},
+  format::DisplayFormat,
+  geometry::{Margins, Point},
+  import::Sublayout,
+  measure::Measure,
+  objective::Objective,
+  owned::{MeasureId, OwnedLayout, OwnedObjective},
+  progress::SolvePhase,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{with_target, RenderTarget},
+  smtlib::ModelValues,
+  tag::current_tag,
+  template::Params,
+  unit::{Length, Unit, Units},
+  widget::{
+    distribute_horizontally, group_center, stack_horizontally, stack_vertically, BoundedWidget,
+    CompositeWidget, MarginCollapse, Parts, RawWidget, SendPainter, Widget,
+  },
+};
 
 #[test]
 fn test_rectangle_success() {
@@ -21,6 +60,9 @@ fn test_rectangle_success() {
 
   let report = builder.build().unwrap();
   assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(report.stats.variables, 4);
+  assert_eq!(report.stats.constraints, 6);
+  debug_assert_eq!(ctx.stats().live_painters, 0);
 }
 
 #[test]
@@ -33,9 +75,152 @@ fn test_rectangle_fail() {
   rect.bottom = Measure::new_const(&ctx, 1.0).unwrap();
   builder.push_widget(rect);
 
+  let err = builder.build().unwrap_err();
+  match err {
+    LayoutError::Unsat { core } => assert_eq!(core.len(), 1),
+    _ => panic!("unexpected error: {}", err),
+  }
+  debug_assert_eq!(ctx.stats().live_painters, 1);
+  drop(builder);
+  debug_assert_eq!(ctx.stats().live_painters, 0);
+}
+
+#[test]
+fn test_paint_errors() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(0));
+  let mut ids = vec![];
+  for i in 0..3 {
+    let painted = painted.clone();
+    ids.push(builder.push_widget(Rectangle::with_width_and_height(
+      &ctx,
+      5.0,
+      10.0,
+      Box::new(move |_| {
+        if i == 1 {
+          return Err("no font".into());
+        }
+        *painted.borrow_mut() += 1;
+        Ok(())
+      }),
+    )));
+  }
+
+  let report = builder.build().unwrap();
+  assert_eq!(*painted.borrow(), 2);
+  assert_eq!(report.paint_errors.len(), 1);
+  assert_eq!(report.paint_errors[0].0, ids[1]);
+  assert_eq!(report.paint_errors[0].1.to_string(), "no font");
+}
+
+#[test]
+fn test_metrics_of() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(false));
+  let flag = painted.clone();
+  let mut rect = Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(move |_| {
+      *flag.borrow_mut() = true;
+      Ok(())
+    }),
+  );
+  rect.top = Measure::new_const(&ctx, 1.0).unwrap();
+  rect.left = Measure::new_const(&ctx, 2.0).unwrap();
+  let id = builder.push_widget(rect);
+
+  let report = builder.dry_run().unwrap();
+  assert_eq!(
+    report.metrics_of(id),
+    Some(&[2.0, 7.0, 1.0, 11.0, 5.0, 10.0][..])
+  );
+  assert!(!*painted.borrow());
+
+  builder.remove_widget(id);
+  assert_eq!(builder.dry_run().unwrap().metrics_of(id), None);
+}
+
+#[test]
+fn test_hit_test() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let mut square = |offset: f64| {
+    let mut rect = Rectangle::with_width_and_height(&ctx, 10.0, 10.0, Box::new(|_| Ok(())));
+    rect.top = Measure::new_const(&ctx, offset).unwrap();
+    rect.left = Measure::new_const(&ctx, offset).unwrap();
+    builder.push_widget(rect)
+  };
+  let below = square(0.0);
+  let above = square(5.0);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.hit_test(7.0, 7.0), vec![above, below]);
+  assert_eq!(solution.hit_test(1.0, 1.0), vec![below]);
+  assert!(solution.hit_test(20.0, 20.0).is_empty());
+
+  builder.set_z_order(below, 1);
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.hit_test(7.0, 7.0), vec![below, above]);
+}
+
+#[test]
+fn test_z_index() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+  let mut push = |name: &'static str| {
+    let painted = painted.clone();
+    builder.push_widget(Rectangle::with_width_and_height(
+      &ctx,
+      5.0,
+      5.0,
+      Box::new(move |_| {
+        painted.borrow_mut().push(name);
+        Ok(())
+      }),
+    ))
+  };
+  let first = push("first");
+  let second = push("second");
+
+  // The solver stacks the first rectangle over the second one.
+  let (z_first, z_second) = (Measure::new_unbound(&ctx), Measure::new_unbound(&ctx));
+  builder.push_hard_constraint(z_second.prop_eq(Measure::new_const(&ctx, 3.0).unwrap()));
+  builder.push_hard_constraint(z_first.prop_eq(z_second + 1.0));
+  assert!(builder.set_z_index(first, z_first));
+  assert!(builder.set_z_index(second, z_second));
+
   let report = builder.build().unwrap();
-  assert!(!report.unsatisfied_constraints.is_empty());
-  println!("test_rectangle_fail: {:?}", report);
+  assert_eq!(report.solution.value_of(z_first), Some(4.0));
+  assert_eq!(*painted.borrow(), vec!["second", "first"]);
+}
+
+#[test]
+fn test_render_target() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  for (width, height) in [(5.0, 10.0), (3.0, 4.0)] {
+    builder.push_widget(Rectangle::with_width_and_height(
+      &ctx,
+      width,
+      height,
+      Box::new(with_target(
+        |metrics: RectangleMetrics, target: &mut dyn RenderTarget| {
+          let canvas = target.downcast_mut::<Vec<(f64, f64)>>().unwrap();
+          canvas.push((metrics.width, metrics.height));
+          Ok(())
+        },
+      )),
+    ));
+  }
+
+  let mut canvas: Vec<(f64, f64)> = vec![];
+  builder.build_into(&mut canvas).unwrap();
+  assert_eq!(canvas, vec![(5.0, 10.0), (3.0, 4.0)]);
 }
 
 #[test]
@@ -89,3 +274,3862 @@ fn test_nesting_rectangles() {
   let report = builder.build().unwrap();
   assert!(report.unsatisfied_constraints.is_empty());
 }
+
+#[test]
+fn test_swimlane_fits_contents() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let heights = Rc::new(RefCell::new(vec![]));
+
+  let painters = (0..2)
+    .map(|_| {
+      let heights = heights.clone();
+      Box::new(move |metrics: super::RectangleMetrics| {
+        heights.borrow_mut().push(metrics.height);
+        Ok(())
+      }) as super::RectanglePainter
+    })
+    .collect();
+  let mut lanes = Swimlane::new(&ctx, painters, 5.0);
+  builder.push_constraint(lanes.top.prop_eq(Measure::zero(&ctx)));
+
+  let a = Rectangle::with_width_and_height(&ctx, 20.0, 30.0, Box::new(|_| Ok(())));
+  let b = Rectangle::with_width_and_height(&ctx, 20.0, 10.0, Box::new(|_| Ok(())));
+  builder.push_constraint(lanes.place(0, &a.measures()));
+  builder.push_constraint(lanes.place(1, &b.measures()));
+  lanes.connect(
+    a.measures().center().unwrap(),
+    b.measures().center().unwrap(),
+  );
+  builder.push_widget(a);
+  builder.push_widget(b);
+  builder.push_widget(lanes);
+
+  let report = builder.build().unwrap();
+  assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(*heights.borrow(), vec![40.0, 20.0]);
+}
+
+#[test]
+fn test_toolbar_overflow_by_priority() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let partition = Rc::new(RefCell::new(None));
+
+  let button = Rectangle::with_width_and_height(&ctx, 10.0, 10.0, Box::new(|_| Ok(())));
+  let partition_out = partition.clone();
+  let mut toolbar = Toolbar::new(
+    &ctx,
+    button,
+    0.0,
+    Box::new(move |metrics: ToolbarMetrics| {
+      *partition_out.borrow_mut() = Some((metrics.visible, metrics.overflowed));
+      Ok(())
+    }),
+  );
+  builder.push_constraint(toolbar.left.prop_eq(Measure::zero(&ctx)));
+  builder.push_constraint(
+    toolbar
+      .width
+      .prop_eq(Measure::new_const(&ctx, 100.0).unwrap()),
+  );
+  for priority in &[1, 3, 2] {
+    let item = Rectangle::with_width_and_height(&ctx, 40.0, 10.0, Box::new(|_| Ok(())));
+    toolbar.push_item(item, *priority);
+  }
+  builder.push_widget(toolbar);
+
+  builder.build().unwrap();
+  assert_eq!(partition.borrow().clone(), Some((vec![1, 2], vec![0])));
+}
+
+#[test]
+fn test_tab_strip_compresses_before_scrolling() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let layout = Rc::new(RefCell::new(None));
+
+  let layout_out = layout.clone();
+  let mut strip = TabStrip::new(
+    &ctx,
+    0.0,
+    true,
+    Box::new(move |metrics: TabStripMetrics| {
+      let widths = metrics.tabs.iter().map(|x| x.width).collect::<Vec<_>>();
+      *layout_out.borrow_mut() = Some((metrics.mode, widths));
+      Ok(())
+    }),
+  );
+  builder.push_constraint(strip.left.prop_eq(Measure::zero(&ctx)));
+  builder.push_constraint(strip.top.prop_eq(Measure::zero(&ctx)));
+  builder.push_constraint(strip.width.prop_eq(Measure::new_const(&ctx, 80.0).unwrap()));
+  builder.push_constraint(
+    strip
+      .height
+      .prop_eq(Measure::new_const(&ctx, 10.0).unwrap()),
+  );
+  for _ in 0..2 {
+    strip.push_tab(Rectangle::unbound(&ctx, Box::new(|_| Ok(()))), 60.0, 20.0);
+  }
+  builder.push_widget(strip);
+
+  builder.build().unwrap();
+  assert_eq!(
+    layout.borrow().clone(),
+    Some((TabStripMode::CompressToMin, vec![40.0, 40.0]))
+  );
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solver_session_reuse() {
+  let mut session = SolverSession::new();
+  let ctx = LayoutContext::new();
+
+  for width in &[5.0, 10.0] {
+    let mut builder = LayoutBuilder::new(&ctx);
+    let rect = Rectangle::with_width_and_height(&ctx, *width, 10.0, Box::new(|_| Ok(())));
+    builder.push_widget(rect);
+    let report = builder.build_with(&mut session).unwrap();
+    assert!(report.unsatisfied_constraints.is_empty());
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solver_session_stored() {
+  // The session owns its context, so it can live in app state and outlive any one layout.
+  struct App {
+    session: SolverSession,
+  }
+
+  let mut app = App {
+    session: SolverSession::new(),
+  };
+  for width in &[5.0, 10.0] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let rect = Rectangle::with_width_and_height(&ctx, *width, 10.0, Box::new(|_| Ok(())));
+    let measure = rect.width;
+    builder.push_widget(rect);
+    let report = builder.check_with(&mut app.session).unwrap();
+    assert_eq!(report.solution.value_of(measure), Some(*width));
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_checkpoint_rollback() {
+  let mut session = SolverSession::new();
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let width = rect.width;
+  builder.push_widget(rect);
+  builder.check_with(&mut session).unwrap();
+
+  builder.checkpoint();
+  builder.push_hard_constraint(width.prop_eq(Measure::new_const(&ctx, 6.0).unwrap()));
+  let err = builder.check_with(&mut session).unwrap_err();
+  assert!(matches!(err, LayoutError::Unsat { .. }));
+
+  builder.rollback();
+  let report = builder.build_with(&mut session).unwrap();
+  assert!(report.unsatisfied_constraints.is_empty());
+}
+
+#[test]
+fn test_reflect_nested_measures() {
+  let ctx = LayoutContext::new();
+  let button = Rectangle::with_width_and_height(&ctx, 10.0, 10.0, Box::new(|_| Ok(())));
+  let toolbar = Toolbar::new(&ctx, button, 0.0, Box::new(|_| Ok(())));
+
+  let names = toolbar
+    .named_measures()
+    .into_iter()
+    .map(|(name, _)| name)
+    .collect::<Vec<_>>();
+  assert_eq!(
+    names[..6],
+    ["left", "right", "top", "bottom", "width", "height"]
+  );
+  assert!(names.contains(&"overflow_button.width".to_string()));
+}
+
+#[test]
+fn test_labelled_constraint_violation() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let width = rect.width;
+  builder.push_widget(rect);
+  builder.push_constraint(
+    width
+      .prop_ge(Measure::new_const(&ctx, 20.0).unwrap())
+      .with_label("sidebar.min_width"),
+  );
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.violations(), ["sidebar.min_width violated"]);
+}
+
+#[test]
+fn test_display_format() {
+  let ctx = LayoutContext::new();
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let mut format = DisplayFormat::default().with_precision(2);
+  format.name_reflected("sidebar", &rect);
+  ctx.set_display_format(format);
+
+  let third = Measure::new_ratio(&ctx, 1, 3).unwrap();
+  assert_eq!((rect.left + third).to_string(), "(sidebar.left + 0.33)");
+
+  ctx.set_display_format(DisplayFormat::default().rational());
+  assert_eq!(third.to_string(), "1/3");
+}
+
+#[test]
+fn test_drain_painters() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(false));
+
+  let painted_ = painted.clone();
+  let rect = Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(move |_| {
+      *painted_.borrow_mut() = true;
+      Ok(())
+    }),
+  );
+  let width = rect.width;
+  builder.push_widget(rect);
+  builder.push_constraint(width.prop_gt(Measure::new_const(&ctx, 6.0).unwrap()));
+  assert_eq!(ctx.stats().live_painters, 1);
+
+  assert_eq!(builder.drain_painters(), 1);
+  assert_eq!(ctx.stats().live_painters, 0);
+  let report = builder.build().unwrap();
+  assert_eq!(report.unsatisfied_constraints.len(), 1);
+  assert!(!*painted.borrow());
+}
+
+#[test]
+fn test_solution_query() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let a = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let b = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let (a_right, b_left) = (a.right, b.left);
+  builder.push_hard_constraint(a.left.prop_eq(Measure::zero(&ctx)));
+  builder.push_hard_constraint(b.left.prop_eq(a.right + 3.0));
+  builder.push_widget(a);
+  builder.push_widget(b);
+
+  let solution = builder.build().unwrap().solution;
+  assert_eq!(solution.value_of(b_left - a_right), Some(3.0));
+  assert_eq!(solution.truth_of(b_left.prop_gt(a_right)), Some(true));
+  assert_eq!(solution.value_of(Measure::new_unbound(&ctx)), None);
+}
+
+#[test]
+fn test_solve_then_paint() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+
+  let painted_ = painted.clone();
+  let rect = Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(move |metrics: RectangleMetrics| {
+      painted_.borrow_mut().push(metrics.width);
+      Ok(())
+    }),
+  );
+  let width = rect.width;
+  builder.push_widget(rect);
+
+  let solution = builder.solve().unwrap();
+  assert!(painted.borrow().is_empty());
+  assert_eq!(solution.value_of(width), Some(5.0));
+  solution.paint(&mut builder).unwrap();
+  assert_eq!(*painted.borrow(), [5.0]);
+}
+
+#[test]
+fn test_repaint() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let resource = Rc::new(());
+
+  let held = resource.clone();
+  builder.push_widget(Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(with_target(
+      move |metrics: RectangleMetrics, target: &mut dyn RenderTarget| {
+        let _ = &held;
+        let (scale, canvas) = target.downcast_mut::<(f64, Vec<f64>)>().unwrap();
+        canvas.push(metrics.width * *scale);
+        Ok(())
+      },
+    )),
+  ));
+
+  // The same solution is painted at two scales.
+  let solution = builder.solve().unwrap();
+  let (mut normal, mut retina) = ((1.0, vec![]), (2.0, vec![]));
+  solution.paint_into(&mut builder, &mut normal).unwrap();
+  solution.paint_into(&mut builder, &mut retina).unwrap();
+  assert_eq!((&normal.1, &retina.1), (&vec![5.0], &vec![10.0]));
+
+  // Painters are dropped with what they captured once built.
+  assert_eq!(Rc::strong_count(&resource), 2);
+  builder.build_into(&mut normal).unwrap();
+  assert_eq!(Rc::strong_count(&resource), 1);
+}
+
+#[test]
+fn test_rebuild() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+
+  let painted_ = painted.clone();
+  let rect = Rectangle::unbound(
+    &ctx,
+    Box::new(move |metrics: RectangleMetrics| {
+      painted_.borrow_mut().push(metrics.width);
+      Ok(())
+    }),
+  );
+  let width = rect.width;
+  builder.push_widget(rect);
+  builder.push_hard_constraint(width.prop_eq(Measure::new_const(&ctx, 5.0).unwrap()));
+
+  builder.checkpoint();
+  builder.push_constraint(width.prop_ge(Measure::new_const(&ctx, 8.0).unwrap()));
+  let report = builder.build().unwrap();
+  assert_eq!(report.unsatisfied_constraints.len(), 1);
+  assert_eq!(*painted.borrow(), [5.0]);
+
+  builder.rollback();
+  let report = builder.build().unwrap();
+  assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(report.solution.value_of(width), Some(5.0));
+  assert_eq!(*painted.borrow(), [5.0]);
+}
+
+#[test]
+fn test_handles() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let width = rect.width;
+  builder.push_widget(rect);
+  let min_width =
+    builder.push_hard_constraint(width.prop_ge(Measure::new_const(&ctx, 5.0).unwrap()));
+  builder.push_objective(Objective::Minimize(width));
+
+  builder.checkpoint();
+  let extra = Rectangle::with_width_and_height(&ctx, 1.0, 1.0, Box::new(|_| Ok(())));
+  let extra_width = extra.width;
+  let extra = builder.push_widget(extra);
+  builder.push_hard_constraint(width.prop_ge(extra_width));
+  assert!(builder.replace_constraint(
+    min_width,
+    width.prop_ge(Measure::new_const(&ctx, 8.0).unwrap()).hard()
+  ));
+  assert_eq!(ctx.stats().live_painters, 2);
+  assert!(builder.remove_widget(extra));
+  assert!(!builder.remove_widget(extra));
+  assert_eq!(ctx.stats().live_painters, 1);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(width), Some(8.0));
+
+  builder.rollback();
+  assert!(builder.remove_constraint(min_width));
+  assert!(!builder.replace_constraint(min_width, Prop::new_const(&ctx, true)));
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(width), Some(0.0));
+}
+
+#[test]
+fn test_constraint_groups() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let width = rect.width;
+  builder.push_widget(rect);
+  builder
+    .group("compact")
+    .push_hard_constraint(width.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder
+    .group("expanded")
+    .push_hard_constraint(width.prop_eq(Measure::new_const(&ctx, 20.0).unwrap()));
+
+  builder.group("expanded").disable();
+  assert_eq!(builder.solve().unwrap().value_of(width), Some(10.0));
+
+  builder.group("compact").disable();
+  builder.group("expanded").enable();
+  assert!(!builder.group("compact").is_enabled());
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(width), Some(20.0));
+  assert_eq!(report.stats.constraints, 7);
+}
+
+#[test]
+fn test_pin_and_resolve() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (left, right, width) = (rect.left, rect.right, rect.width);
+  let zero = Measure::new_const(&ctx, 0.0).unwrap();
+  builder.push_hard_constraint(rect.left.prop_eq(zero));
+  builder.push_hard_constraint(rect.top.prop_eq(zero));
+  builder.push_hard_constraint(rect.bottom.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder.push_constraint(rect.width.prop_eq(Measure::new_const(&ctx, 100.0).unwrap()));
+  builder.push_widget(rect);
+
+  let mut solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(width), Some(100.0));
+
+  // The pin breaks the preferred width, which only a full solve gives up.
+  solution.pin(right, 150.0);
+  let mut solution = builder.resolve(&solution).unwrap();
+  assert_eq!(solution.value_of(width), Some(150.0));
+
+  solution.pin(right, 160.0);
+  let mut solution = builder.resolve(&solution).unwrap();
+  assert_eq!(solution.value_of(left), Some(0.0));
+  assert_eq!(solution.value_of(width), Some(160.0));
+
+  solution.unpin(right);
+  solution.pin(Measure::new_unbound(&ctx), 1.0);
+  assert!(matches!(
+    builder.resolve(&solution),
+    Err(LayoutError::UnknownPin)
+  ));
+}
+
+#[test]
+fn test_freeze() {
+  let frame_ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&frame_ctx);
+  let frame = Rectangle::with_width_and_height(&frame_ctx, 200.0, 100.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(
+    frame
+      .left
+      .prop_eq(Measure::new_const(&frame_ctx, 10.0).unwrap()),
+  );
+  builder.push_hard_constraint(
+    frame
+      .top
+      .prop_eq(Measure::new_const(&frame_ctx, 0.0).unwrap()),
+  );
+  let frame_measures = frame.named_measures();
+  builder.push_widget(frame);
+  let solution = builder.solve().unwrap();
+
+  let card_ctx = LayoutContext::new();
+  let fixed = solution.freeze(&card_ctx, frame_measures).unwrap();
+  assert_eq!(fixed["right"].const_value(), Some(210.0));
+
+  let mut builder = LayoutBuilder::new(&card_ctx);
+  let margin = Measure::new_const(&card_ctx, 5.0).unwrap();
+  let card = Rectangle::unbound(&card_ctx, Box::new(|_| Ok(())));
+  let width = card.width;
+  builder.push_hard_constraint(card.left.prop_ge(fixed["left"] + margin));
+  builder.push_hard_constraint(card.right.prop_le(fixed["right"] - margin));
+  builder.push_objective(Objective::Maximize(width));
+  builder.push_widget(card);
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(width), Some(190.0));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_build_async() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let width = rect.width;
+  builder.push_widget(rect);
+  let report = builder.build_async().await.unwrap();
+  assert_eq!(report.solution.value_of(width), Some(5.0));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "current_thread")]
+async fn test_build_async_current_thread() {
+  let ctx = LayoutContext::new();
+  let c = |x| Measure::new_const(&ctx, x).unwrap();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (width, wide) = (rect.width, Prop::new_unbound(&ctx));
+  builder.push_hard_constraint(width.prop_eq(wide.select(c(8.0), c(4.0))));
+  builder.push_hard_constraint(rect.height.prop_eq(c(2.0)));
+  builder.push_objective(Objective::Maximize(width));
+  builder.push_widget(rect);
+  let report = builder.build_async().await.unwrap();
+  assert_eq!(report.solution.value_of(width), Some(8.0));
+  assert_eq!(report.solution.truth_of(wide), Some(true));
+  assert!(report.paint_errors.is_empty());
+}
+
+#[test]
+fn test_fallback() {
+  use crate::fallback::{fallback, EMAIL_WIDTH};
+
+  let ctx = LayoutContext::new();
+  let c = |x| Measure::new_const(&ctx, x).unwrap();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let page = Measure::new_unbound(&ctx);
+  let sidebar = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let main = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (sidebar_width, main_width) = (sidebar.width, main.width);
+  builder.push_hard_constraint(sidebar.left.prop_eq(c(0.0)));
+  builder.push_hard_constraint(main.left.prop_eq(sidebar.right));
+  builder.push_hard_constraint(main.right.prop_eq(page));
+  builder.push_hard_constraint(sidebar.width.prop_ge(c(150.0)));
+  // A quarter of wide pages, which a table cannot decide on.
+  let wide = page.prop_ge(c(800.0));
+  builder.push_hard_constraint(sidebar.width.prop_eq(page * 0.25).when(wide));
+  builder.push_objective(Objective::Minimize(sidebar.width));
+  builder.push_widget(sidebar);
+  builder.push_widget(main);
+
+  builder.checkpoint();
+  builder.push_hard_constraint(page.prop_eq(c(1200.0)));
+  let flexible = builder.solve().unwrap();
+  builder.rollback();
+  assert_eq!(flexible.value_of(sidebar_width), Some(300.0));
+  assert_eq!(flexible.value_of(main_width), Some(900.0));
+
+  let variant = fallback(&builder, &flexible, page, EMAIL_WIDTH).unwrap();
+  assert_eq!(variant.dropped.len(), 1);
+  assert_eq!(variant.flexible.value_of(main_width), Some(900.0));
+  assert_eq!(variant.fixed.value_of(page), Some(600.0));
+  assert_eq!(variant.fixed.value_of(sidebar_width), Some(150.0));
+  assert_eq!(variant.fixed.value_of(main_width), Some(450.0));
+}
+
+#[test]
+fn test_layout_cache() {
+  let store = Arc::new(MemoryStore::new(8));
+  let build = |wide: f64| {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx).with_cache(store.clone());
+    let painted = Rc::new(RefCell::new(None));
+
+    let painted_ = painted.clone();
+    let rect = Rectangle::unbound(
+      &ctx,
+      Box::new(move |metrics: RectangleMetrics| {
+        *painted_.borrow_mut() = Some(metrics.width);
+        Ok(())
+      }),
+    );
+    let wide = rect.width.prop_ge(Measure::new_const(&ctx, wide).unwrap());
+    builder.push_hard_constraint(rect.left.prop_eq(Measure::new_const(&ctx, 0.0).unwrap()));
+    builder.push_constraint(wide | rect.width.prop_eq(Measure::new_const(&ctx, 5.0).unwrap()));
+    builder.push_objective(Objective::Minimize(rect.right));
+    builder.push_widget(rect);
+    let path = builder.build().unwrap().stats.path;
+    let width = painted.borrow().unwrap();
+    (path, width)
+  };
+
+  let (path, width) = build(10.0);
+  assert_ne!(path, SolvePath::Cached);
+  assert_eq!(build(10.0), (SolvePath::Cached, width));
+  assert_eq!(store.len(), 1);
+  assert_ne!(build(20.0).0, SolvePath::Cached);
+  assert_eq!(store.len(), 2);
+}
+
+#[test]
+fn test_layout_cache_collision() {
+  use crate::layout::cache::{CachedLayout, LayoutStore};
+  use std::sync::Mutex;
+
+  /// A store with one entry, which every key finds.
+  struct OneSlot(Mutex<Option<CachedLayout>>);
+
+  impl LayoutStore for OneSlot {
+    fn get(&self, _: u64) -> Option<CachedLayout> {
+      self.0.lock().unwrap().clone()
+    }
+
+    fn insert(&self, _: u64, layout: CachedLayout) {
+      *self.0.lock().unwrap() = Some(layout);
+    }
+  }
+
+  let store = Arc::new(OneSlot(Mutex::new(None)));
+  let build = |width: f64| {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx).with_cache(store.clone());
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    let measure = rect.width;
+    builder.push_hard_constraint(rect.width.prop_eq(Measure::new_const(&ctx, width).unwrap()));
+    builder.push_widget(rect);
+    let report = builder.build().unwrap();
+    (report.stats.path, report.solution.value_of(measure))
+  };
+
+  assert_ne!(build(10.0).0, SolvePath::Cached);
+  assert_eq!(build(10.0), (SolvePath::Cached, Some(10.0)));
+  let (path, width) = build(20.0);
+  assert_ne!(path, SolvePath::Cached);
+  assert_eq!(width, Some(20.0));
+}
+
+/// A square whose painter is `Send`.
+struct Tile<'a> {
+  size: Measure<'a>,
+  painter: SendPainter<'a>,
+}
+
+impl<'a> RawWidget<'a> for Tile<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.size]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![self
+      .size
+      .prop_eq(Measure::new_const(self.size.ctx, 4.0).unwrap())
+      .hard()]
+  }
+
+  fn paint(&mut self, measures: &[f64], _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    (self.painter)(measures)
+  }
+
+  fn send_painter(&mut self) -> Option<&mut SendPainter<'a>> {
+    Some(&mut self.painter)
+  }
+}
+
+/// A line of text with typed metrics.
+struct Caption<'a> {
+  left: Measure<'a>,
+  width: Measure<'a>,
+  painted: Rc<RefCell<Option<(f64, f64)>>>,
+}
+
+impl<'a> Widget<'a> for Caption<'a> {
+  type Metrics = (f64, f64);
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.left, self.width]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![
+      self
+        .left
+        .prop_eq(Measure::new_const(self.left.ctx, 2.0).unwrap())
+        .hard(),
+      self.width.prop_eq(self.left * 3.0).hard(),
+    ]
+  }
+
+  fn decode(&self, values: &[f64]) -> (f64, f64) {
+    (values[0], values[1])
+  }
+
+  fn paint(&mut self, metrics: (f64, f64), _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    *self.painted.borrow_mut() = Some(metrics);
+    Ok(())
+  }
+}
+
+#[test]
+fn test_typed_widget() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(None));
+  builder.push_widget(Caption {
+    left: Measure::new_unbound(&ctx),
+    width: Measure::new_unbound(&ctx),
+    painted: painted.clone(),
+  });
+  builder.build().unwrap();
+  assert_eq!(*painted.borrow(), Some((2.0, 6.0)));
+}
+
+/// A fixed width that logs when it is solved and painted.
+struct Traced<'a> {
+  name: &'static str,
+  width: Measure<'a>,
+  log: Rc<RefCell<Vec<String>>>,
+}
+
+impl<'a> Widget<'a> for Traced<'a> {
+  type Metrics = f64;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.width]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![self
+      .width
+      .prop_eq(Measure::new_const(self.width.ctx, 7.0).unwrap())
+      .hard()]
+  }
+
+  fn decode(&self, values: &[f64]) -> f64 {
+    values[0]
+  }
+
+  fn on_solved(&mut self, width: &f64) {
+    let entry = format!("solved {} {}", self.name, width);
+    self.log.borrow_mut().push(entry);
+  }
+
+  fn paint(&mut self, _: f64, _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    let entry = format!("painted {}", self.name);
+    self.log.borrow_mut().push(entry);
+    Ok(())
+  }
+}
+
+#[test]
+fn test_tags() {
+  #[derive(Debug, PartialEq)]
+  struct Node(u32);
+
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+  let mut push = |width: f64| {
+    let painted = painted.clone();
+    builder.push_widget(Rectangle::with_width_and_height(
+      &ctx,
+      width,
+      5.0,
+      Box::new(move |_| {
+        painted
+          .borrow_mut()
+          .push(current_tag::<Node>().map(|x| x.0));
+        Ok(())
+      }),
+    ))
+  };
+  let (first, second, untagged) = (push(1.0), push(2.0), push(3.0));
+  assert!(builder.set_tag(first, Node(7)));
+  assert!(builder.set_tag(second, "second"));
+  assert_eq!(builder.tag_of::<Node>(first), Some(&Node(7)));
+
+  let report = builder.build().unwrap();
+  assert_eq!(*painted.borrow(), [Some(7), None, None]);
+  assert_eq!(report.tag_of::<&str>(second), Some(&"second"));
+  assert_eq!(report.tag_of::<Node>(untagged), None);
+  let nodes = report.tagged::<Node>().collect::<Vec<_>>();
+  assert_eq!(nodes, [(first, &Node(7))]);
+  assert_eq!(current_tag::<Node>(), None);
+}
+
+#[test]
+fn test_tag_restored_on_panic() {
+  use crate::layout::tag::with_current;
+
+  #[derive(Debug, PartialEq)]
+  struct Node(u32);
+
+  with_current(Some(Arc::new(Node(1))), || {
+    let res = std::panic::catch_unwind(|| {
+      with_current(Some(Arc::new(Node(2))), || {
+        assert_eq!(current_tag::<Node>().map(|x| x.0), Some(2));
+        panic!("painter failed");
+      })
+    });
+    assert!(res.is_err());
+    assert_eq!(current_tag::<Node>().map(|x| x.0), Some(1));
+  });
+  assert_eq!(current_tag::<Node>(), None);
+}
+
+/// Handles kept past the layout that made them.
+struct Sidebar {
+  layout: OwnedLayout,
+  width: MeasureId,
+  content: MeasureId,
+}
+
+#[test]
+fn test_owned_layout() {
+  let sidebar = std::thread::spawn(|| {
+    let mut layout = OwnedLayout::new();
+    let (width, total) = (layout.new_unbound(), layout.named_const("total"));
+    let content = layout.sub(total, width);
+    let min = layout.new_const(20.0).unwrap();
+    let fits = layout.prop_ge(width, min);
+    layout.push_hard_constraint(fits);
+    let wide = layout.prop_ge(content, width);
+    layout.push_hard_constraint(wide);
+    layout.push_objective(OwnedObjective::Maximize(width));
+    layout.define("total", 100.0);
+    Sidebar {
+      layout,
+      width,
+      content,
+    }
+  })
+  .join()
+  .unwrap();
+
+  let solution = sidebar.layout.solve().unwrap();
+  assert_eq!(solution.value_of(sidebar.width), Some(50.0));
+  assert_eq!(solution.value_of(sidebar.content), Some(50.0));
+}
+
+#[test]
+fn test_context_reset() {
+  let mut ctx = LayoutContext::new();
+  let x = Measure::new_unbound(&ctx);
+  let mut builder = LayoutBuilder::new(&ctx);
+  builder.push_constraint(x.prop_eq(Measure::new_const(&ctx, 100.5).unwrap()).hard());
+  assert_eq!(ctx.node_counts().measures, 2);
+  assert_eq!(ctx.node_counts().props, 1);
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(x), Some(100.5));
+  drop(builder);
+
+  ctx.reset();
+  assert_eq!(ctx.node_counts(), Default::default());
+  let bytes = ctx.allocated_bytes();
+  let y = Measure::new_unbound(&ctx);
+  assert_eq!(ctx.allocated_bytes(), bytes);
+  assert_eq!(solution.value_of(y), None);
+
+  let mut layout = OwnedLayout::new();
+  let width = layout.new_const(20.0).unwrap();
+  for _ in 0..3 {
+    assert_eq!(
+      layout.solve_in(&mut ctx).unwrap().value_of(width),
+      Some(20.0)
+    );
+  }
+  assert_eq!(ctx.node_counts().measures, 1);
+}
+
+#[test]
+fn test_shared_context() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<LayoutContext>();
+  assert_send_sync::<Measure<'static>>();
+  assert_send_sync::<Prop<'static>>();
+
+  let ctx = LayoutContext::new();
+  let width = Measure::new_unbound(&ctx);
+  let constraints = std::thread::scope(|s| {
+    let lower = s.spawn(|| {
+      width
+        .prop_ge(Measure::new_const(&ctx, 10.0).unwrap())
+        .hard()
+    });
+    let upper = s.spawn(|| {
+      width
+        .prop_le(Measure::new_const(&ctx, 30.0).unwrap())
+        .hard()
+    });
+    [lower.join().unwrap(), upper.join().unwrap()]
+  });
+
+  let mut builder = LayoutBuilder::new(&ctx);
+  for prop in constraints {
+    builder.push_constraint(prop);
+  }
+  builder.push_objective(Objective::Maximize(width));
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(width), Some(30.0));
+  assert_eq!(
+    format!("{}", width + Measure::zero(&ctx)),
+    format!("{}", width)
+  );
+}
+
+#[test]
+fn test_on_solved() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let log = Rc::new(RefCell::new(vec![]));
+  for name in ["a", "b"] {
+    builder.push_widget(Traced {
+      name,
+      width: Measure::new_unbound(&ctx),
+      log: log.clone(),
+    });
+  }
+
+  builder.build().unwrap();
+  assert_eq!(
+    *log.borrow(),
+    ["solved a 7", "solved b 7", "painted a", "painted b"]
+  );
+}
+
+/// A box with a label on top of it.
+struct LabeledBox<'a> {
+  label: Rectangle<'a>,
+  body: Rectangle<'a>,
+}
+
+impl<'a> CompositeWidget<'a> for LabeledBox<'a> {
+  fn expand(self) -> Parts<'a> {
+    let mut parts = Parts::default();
+    parts.push_constraint(self.label.left.prop_eq(self.body.left).hard());
+    parts.push_constraint(self.body.top.prop_eq(self.label.bottom + 1.0).hard());
+    parts.push_widget(self.label);
+    parts.push_widget(self.body);
+    parts
+  }
+}
+
+#[test]
+fn test_composite_widget() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let labeled = LabeledBox {
+    label: Rectangle::with_width_and_height(&ctx, 8.0, 2.0, Box::new(|_| Ok(()))),
+    body: Rectangle::with_width_and_height(&ctx, 20.0, 10.0, Box::new(|_| Ok(()))),
+  };
+  let (label, body) = (labeled.label.left, labeled.body.top);
+  builder.push_hard_constraint(label.prop_eq(Measure::new_const(&ctx, 3.0).unwrap()));
+  builder.push_hard_constraint(labeled.label.top.prop_eq(Measure::zero(&ctx)));
+
+  let ids = builder.push_composite(labeled);
+  assert_eq!(ids.len(), 2);
+  let report = builder.build().unwrap();
+  assert_eq!(report.metrics_of(ids[1]).map(|x| x[0]), Some(3.0));
+  assert_eq!(report.solution.value_of(body), Some(3.0));
+}
+
+#[test]
+fn test_nested_containers() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(None));
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+
+  // A rectangle at (2, 3) in a container at (1, 1) in a container at (10, 20).
+  let painted_ = painted.clone();
+  let rect = Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    5.0,
+    Box::new(move |metrics: RectangleMetrics| {
+      *painted_.borrow_mut() = Some((metrics.left, metrics.top));
+      Ok(())
+    }),
+  );
+  let mut inner = Container::new(&ctx, Box::new(|_| Ok(())));
+  inner.push_constraint(rect.left.prop_eq(at(2.0)).hard());
+  inner.push_constraint(rect.top.prop_eq(at(3.0)).hard());
+  let (local_left, inner_width) = (rect.left, inner.frame.width);
+  inner.push_child(rect);
+
+  let mut outer = Container::new(&ctx, Box::new(|_| Ok(())));
+  outer.push_constraint(inner.frame.left.prop_eq(at(1.0)).hard());
+  outer.push_constraint(inner.frame.top.prop_eq(at(1.0)).hard());
+  outer.push_child(inner);
+  builder.push_hard_constraint(outer.frame.left.prop_eq(at(10.0)));
+  builder.push_hard_constraint(outer.frame.top.prop_eq(at(20.0)));
+  builder.push_objective(Objective::Minimize(outer.frame.width));
+  builder.push_widget(outer);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(local_left), Some(2.0));
+  assert_eq!(report.solution.value_of(inner_width), Some(7.0));
+  assert_eq!(*painted.borrow(), Some((13.0, 24.0)));
+}
+
+#[test]
+fn test_collapsible() {
+  for (space, shown) in [(100.0, true), (50.0, false)] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let painted = Rc::new(RefCell::new(vec![]));
+    let rect = |name: &'static str| {
+      let painted = painted.clone();
+      Rectangle::unbound(
+        &ctx,
+        Box::new(move |_: RectangleMetrics| {
+          painted.borrow_mut().push(name);
+          Ok(())
+        }),
+      )
+    };
+    let title = rect("title");
+    let mut subtitle = Collapsible::new(&ctx, rect("subtitle"), 5);
+    subtitle.push_constraint(
+      subtitle
+        .widget
+        .height
+        .prop_eq(Measure::new_const(&ctx, 30.0).unwrap())
+        .hard(),
+    );
+
+    let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+    builder.push_hard_constraint(title.top.prop_eq(at(0.0)));
+    builder.push_hard_constraint(title.height.prop_eq(at(30.0)));
+    builder.push_hard_constraint(
+      subtitle
+        .widget
+        .top
+        .prop_eq(title.bottom + subtitle.gap(10.0)),
+    );
+    builder.push_hard_constraint(subtitle.widget.bottom.prop_le(at(space)));
+    let (visible, bottom) = (subtitle.visible, subtitle.widget.bottom);
+    builder.push_widget(title);
+    builder.push_widget(subtitle);
+
+    let report = builder.build().unwrap();
+    assert_eq!(report.solution.truth_of(visible), Some(shown));
+    if shown {
+      assert_eq!(report.solution.value_of(bottom), Some(70.0));
+      assert_eq!(*painted.borrow(), vec!["title", "subtitle"]);
+    } else {
+      assert_eq!(report.solution.value_of(bottom), Some(30.0));
+      assert_eq!(*painted.borrow(), vec!["title"]);
+    }
+  }
+}
+
+#[test]
+fn test_optional_widgets() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+  let mut buttons = OptionalWidgets::new(&ctx);
+
+  // Only two buttons fit, and the two with the highest rewards are shown.
+  let mut used = Measure::zero(&ctx);
+  for (i, &reward) in [1, 2, 3, 1].iter().enumerate() {
+    let painted = painted.clone();
+    let button = Rectangle::with_width_and_height(
+      &ctx,
+      40.0,
+      20.0,
+      Box::new(move |_: RectangleMetrics| {
+        painted.borrow_mut().push(i);
+        Ok(())
+      }),
+    );
+    let width = button.width;
+    let included = buttons.push(button, reward);
+    used = used + included.select(width, Measure::zero(&ctx));
+  }
+  buttons.push_constraint(
+    used
+      .prop_le(Measure::new_const(&ctx, 100.0).unwrap())
+      .hard(),
+  );
+  let count = buttons.included_count();
+  builder.push_composite(buttons);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(count), Some(2.0));
+  assert_eq!(*painted.borrow(), vec![1, 2]);
+}
+
+/// `columns` rectangles of equal width between `left` and `right`, `gap` apart.
+fn columns<'a>(ctx: &'a LayoutContext, params: &Params<'a>) -> Result<Parts<'a>, LayoutError> {
+  let (left, right) = (params.measure(ctx, "left")?, params.measure(ctx, "right")?);
+  let gap = params.value("gap")?;
+  let n = params.count("columns")?;
+  let width = Measure::new_unbound(ctx);
+  let mut parts = Parts::default();
+  let mut cursor = left;
+  for _ in 0..n {
+    let column = Rectangle::unbound(ctx, Box::new(|_| Ok(())));
+    parts.push_constraint(column.left.prop_eq(cursor).hard());
+    parts.push_constraint(column.width.prop_eq(width).hard());
+    cursor = column.right + gap;
+    parts.push_widget(column);
+  }
+  parts.push_constraint((cursor - gap).prop_eq(right).hard());
+  Ok(parts)
+}
+
+#[test]
+fn test_constraint_template() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let wide = Params::new()
+    .with("columns", 3.0)
+    .with("gap", 10.0)
+    .with("left", 0.0)
+    .with_measure("right", at(320.0));
+  let narrow = wide.clone().with("columns", 2.0).with("gap", 20.0);
+  let wide = builder.push_template(&columns, &wide).unwrap();
+  let narrow = builder.push_template(&columns, &narrow).unwrap();
+  assert!(matches!(
+    builder.push_template(&columns, &Params::new()),
+    Err(LayoutError::Unbound { .. })
+  ));
+
+  let report = builder.dry_run().unwrap();
+  let width = |id| report.metrics_of(id).unwrap()[4];
+  assert_eq!(width(wide[2]), 100.0);
+  assert_eq!(width(narrow[1]), 150.0);
+  assert_eq!(report.metrics_of(narrow[1]).unwrap()[1], 320.0);
+}
+
+#[test]
+fn test_anchors() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let node = || Rectangle::with_width_and_height(&ctx, 40.0, 20.0, Box::new(|_| Ok(())));
+  let (a, b) = (node(), node());
+  builder.push_hard_constraint(a.left.prop_eq(at(0.0)));
+  builder.push_hard_constraint(a.top.prop_eq(at(0.0)));
+  let (a, b) = (builder.push_widget(a), builder.push_widget(b));
+
+  // b is connected 30 to the right of a, centered on it.
+  let (from, to) = (
+    builder.anchor(a, "out-east").unwrap(),
+    builder.anchor(b, "out-west").unwrap(),
+  );
+  builder.push_hard_constraint((from.x + 30.0).prop_eq(to.x));
+  builder.push_hard_constraint(from.y.prop_eq(to.y));
+  let badge = builder.anchor(b, "top-right").unwrap();
+  assert!(builder.set_anchor(b, "badge", badge));
+  assert!(builder.anchor(b, "nowhere").is_none());
+
+  let report = builder.build().unwrap();
+  let badge = builder.anchor(b, "badge").unwrap();
+  assert_eq!(report.solution.value_of(badge.x), Some(110.0));
+  assert_eq!(report.solution.value_of(badge.y), Some(0.0));
+  let center = builder.anchor(b, "center").unwrap();
+  assert_eq!(report.solution.value_of(center.y), Some(10.0));
+}
+
+#[test]
+fn test_bounded_widgets() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let item = || Rectangle::with_width_and_height(&ctx, 20.0, 20.0, Box::new(|_| Ok(())));
+  let (a, b) = (item(), item());
+  let mut panel = Container::new(&ctx, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(panel.frame.left.prop_eq(at(50.0)));
+  builder.push_hard_constraint(panel.frame.top.prop_eq(at(0.0)));
+  builder.push_hard_constraint(panel.height().prop_eq(at(20.0)));
+  panel.push_child(item());
+
+  // The panel sits between `a` and `b`, evenly spaced.
+  for x in distribute_horizontally(&[&a, &panel, &b]) {
+    builder.push_constraint(x);
+  }
+  for x in [&a, &b] {
+    builder.push_hard_constraint(x.top.prop_eq(at(0.0)));
+  }
+  builder.push_hard_constraint(a.left.prop_eq(at(0.0)));
+  builder.push_hard_constraint(b.right().prop_eq(at(150.0)));
+  let center = group_center(&[&a, &panel, &b]).unwrap();
+  let (panel_width, a_in_panel) = (panel.width(), a.within(&panel));
+  builder.push_widget(a);
+  builder.push_widget(b);
+  builder.push_widget(panel);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(panel_width), Some(50.0));
+  assert_eq!(solution.value_of(center.x), Some(75.0));
+  assert_eq!(solution.truth_of(a_in_panel), Some(false));
+}
+
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+#[test]
+fn test_report_snapshot() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let rect = Rectangle::with_width_and_height(&ctx, 20.0, 10.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(rect.left.prop_eq(Measure::new_const(&ctx, 5.0).unwrap()));
+  builder.push_constraint(
+    rect
+      .top
+      .prop_eq(Measure::new_const(&ctx, 1.0).unwrap())
+      .with_label("top"),
+  );
+  let id = builder.push_widget(rect);
+
+  let report = builder.dry_run().unwrap();
+  let json = serde_json::to_string(&report.snapshot()).unwrap();
+  let snapshot: crate::layout::snapshot::ReportSnapshot = serde_json::from_str(&json).unwrap();
+  assert!(snapshot.satisfied_constraints.contains(&"top".to_string()));
+  assert_eq!(snapshot.metrics[&id], report.metrics_of(id).unwrap());
+
+  let metrics = Widget::decode(
+    &Rectangle::unbound(&ctx, Box::new(|_| Ok(()))),
+    &snapshot.metrics[&id],
+  );
+  let json = serde_json::to_string(&metrics).unwrap();
+  let metrics: RectangleMetrics = serde_json::from_str(&json).unwrap();
+  assert_eq!((metrics.left, metrics.top, metrics.right), (5.0, 1.0, 25.0));
+}
+
+/// A card built in its own context: a header of height 10 over a body of height up to 100.
+fn card<'b>(
+  ctx: &'b LayoutContext,
+  painted: &Rc<RefCell<Vec<(f64, f64)>>>,
+) -> (LayoutBuilder<'b>, Point<'b>) {
+  let mut builder = LayoutBuilder::new(ctx);
+  let at = |x: f64| Measure::new_const(ctx, x).unwrap();
+  let rect = |width: f64| {
+    let painted = painted.clone();
+    let mut rect = Rectangle::unbound(
+      ctx,
+      Box::new(move |m: RectangleMetrics| {
+        painted.borrow_mut().push((m.left, m.top));
+        Ok(())
+      }),
+    );
+    rect.width = at(width);
+    rect
+  };
+  let (header, body) = (rect(40.0), rect(40.0));
+  builder.push_hard_constraint(header.left.prop_eq(at(0.0)));
+  builder.push_hard_constraint(header.top.prop_eq(at(0.0)));
+  builder.push_hard_constraint(header.height.prop_eq(at(10.0)));
+  builder.push_hard_constraint(body.left.prop_eq(at(0.0)));
+  builder.push_hard_constraint(body.top.prop_eq(header.bottom));
+  builder.push_hard_constraint(body.height.prop_le(at(100.0)));
+  builder.push_objective(Objective::Maximize(body.height));
+  let corner = Point {
+    x: body.left,
+    y: body.bottom,
+  };
+  builder.push_widget(header);
+  builder.push_widget(body);
+  (builder, corner)
+}
+
+#[test]
+fn test_sublayout() {
+  let component_ctx = LayoutContext::new();
+  let ctx = LayoutContext::new();
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+
+  // Solved with the page, which cuts the body short.
+  let painted = Rc::new(RefCell::new(vec![]));
+  let (component, corner) = card(&component_ctx, &painted);
+  let mut builder = LayoutBuilder::new(&ctx);
+  let mut sub = Sublayout::new(&ctx, component);
+  let corner = sub.point(corner);
+  builder.push_hard_constraint(sub.origin.x.prop_eq(at(100.0)));
+  builder.push_hard_constraint(sub.origin.y.prop_eq(at(50.0)));
+  builder.push_hard_constraint(corner.y.prop_le(at(130.0)));
+  builder.push_widget(sub);
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(corner.y), Some(130.0));
+  assert_eq!(*painted.borrow(), vec![(100.0, 50.0), (100.0, 60.0)]);
+
+  // Solved on its own, and only placed by the page.
+  let (component, corner) = card(&component_ctx, &painted);
+  let solution = component.solve().unwrap();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let mut sub = Sublayout::solved(&ctx, component, &solution);
+  let corner = sub.point(corner);
+  builder.push_hard_constraint(sub.origin.x.prop_eq(at(0.0)));
+  builder.push_hard_constraint(sub.origin.y.prop_eq(at(0.0)));
+  builder.push_objective(Objective::Minimize(corner.y));
+  builder.push_widget(sub);
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(corner.y), Some(110.0));
+}
+
+/// Text of `chars` characters 10 wide, wrapped in lines 10 high.
+struct Paragraph<'a> {
+  rect: Rectangle<'a>,
+  chars: f64,
+}
+
+impl<'a> RawWidget<'a> for Paragraph<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    RawWidget::measures(&self.rect)
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    RawWidget::constraints(&self.rect)
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    RawWidget::paint(&mut self.rect, measures, target)
+  }
+
+  fn bounds(&self) -> Option<crate::layout::geometry::RectMeasures<'a>> {
+    RawWidget::bounds(&self.rect)
+  }
+
+  fn intrinsic_height_for(&self, width: f64) -> Option<f64> {
+    let per_line = (width / 10.0).floor().max(1.0);
+    Some((self.chars / per_line).ceil() * 10.0)
+  }
+}
+
+#[test]
+fn test_intrinsic_sizes() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (width, height) = (rect.width, rect.height);
+
+  // The paragraph narrows as it grows taller, like next to a floating figure.
+  builder.push_hard_constraint(width.prop_eq(at(150.0) - height));
+  builder.push_hard_constraint(height.prop_le(at(100.0)));
+  builder.push_widget(Paragraph { rect, chars: 30.0 });
+
+  assert!(!builder.fit_intrinsic_sizes(1).unwrap());
+  assert!(builder.fit_intrinsic_sizes(8).unwrap());
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(height), Some(30.0));
+  assert_eq!(solution.value_of(width), Some(120.0));
+}
+
+/// A label of `chars` characters 10 wide, on one line if it is less than 20 high and on two
+/// lines otherwise.
+struct Label<'a> {
+  rect: Rectangle<'a>,
+  chars: f64,
+}
+
+impl<'a> Widget<'a> for Label<'a> {
+  type Metrics = RectangleMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    Widget::measures(&self.rect)
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    Widget::constraints(&self.rect)
+  }
+
+  fn decode(&self, values: &[f64]) -> RectangleMetrics {
+    Widget::decode(&self.rect, values)
+  }
+
+  fn paint(
+    &mut self,
+    metrics: RectangleMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    Widget::paint(&mut self.rect, metrics, target)
+  }
+
+  fn content_constraints(&self, metrics: &RectangleMetrics) -> Vec<Prop<'a>> {
+    let lines = if metrics.height < 20.0 { 1.0 } else { 2.0 };
+    let width = Measure::new_const(self.rect.width.ctx, (self.chars / lines).ceil() * 10.0);
+    vec![self.rect.width.prop_ge(width.unwrap()).hard()]
+  }
+}
+
+#[test]
+fn test_measure_and_arrange() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Arc::new(std::sync::Mutex::new(vec![]));
+
+  let mut labels = vec![];
+  for &(chars, height) in &[(5.0, 10.0), (7.0, 20.0)] {
+    let painted = painted.clone();
+    let rect = Rectangle::unbound(
+      &ctx,
+      Box::new(move |m: RectangleMetrics| {
+        painted.lock().unwrap().push(m.width);
+        Ok(())
+      }),
+    );
+    builder.push_hard_constraint(rect.height.prop_eq(at(height)));
+    builder.push_objective(Objective::Minimize(rect.width));
+    labels.push(rect.width);
+    builder.push_widget(Label { rect, chars });
+  }
+
+  // The measure pass knows nothing of the text.
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(labels[0]), Some(0.0));
+
+  let solution = builder.measure_and_arrange().unwrap();
+  assert_eq!(solution.value_of(labels[0]), Some(50.0));
+  assert_eq!(solution.value_of(labels[1]), Some(40.0));
+
+  // Content constraints are replaced, not stacked, and kept for the build.
+  builder.measure_and_arrange().unwrap();
+  builder.build().unwrap();
+  assert_eq!(*painted.lock().unwrap(), vec![50.0, 40.0]);
+}
+
+#[test]
+fn test_units() {
+  let ctx = LayoutContext::new();
+  let units = Units {
+    em: Length::pt(12.0),
+    ..Units::print()
+  };
+  let mut builder = LayoutBuilder::new(&ctx).with_units(units);
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (width, height) = (rect.width, rect.height);
+  builder.push_hard_constraint(width.prop_eq(builder.length(Length::inches(2.0)).unwrap()));
+  builder.push_hard_constraint(height.prop_eq(builder.length(Length::em(1.5)).unwrap()));
+  builder.push_widget(rect);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(width), Some(144.0));
+  assert_eq!(solution.value_of(height), Some(18.0));
+  let mm = units.from_canonical(solution.value_of(width).unwrap(), Unit::Mm);
+  assert!((mm.value - 50.8).abs() < 1e-9);
+
+  // A screen at twice the pixel density.
+  let retina = Units {
+    scale: 2.0,
+    ..Units::default()
+  };
+  assert_eq!(retina.to_canonical(Length::px(10.0)), 20.0);
+  assert_eq!(retina.to_canonical(Length::em(1.0)), 32.0);
+  assert_eq!(retina.to_canonical(Length::pt(72.0)), 192.0);
+}
+
+#[test]
+fn test_snap() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let a = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let b = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  for r in &[&a, &b] {
+    builder.push_hard_constraint(r.top.prop_eq(at(0.0)) & r.bottom.prop_eq(at(20.0)));
+  }
+  builder.push_hard_constraint(a.left.prop_eq(at(0.0)));
+  builder.push_hard_constraint(a.right.prop_le(at(100.0) / at(3.0)));
+  builder.push_hard_constraint(b.left.prop_eq(a.right));
+  builder.push_hard_constraint(b.right.prop_eq(at(100.0)));
+  builder.push_objective(Objective::Maximize(a.right));
+  let (a_right, b_left) = (a.right, b.left);
+  builder.push_widget(a);
+  let b = builder.push_widget(b);
+
+  let solution = builder.solve().unwrap();
+  assert!((solution.value_of(b_left).unwrap() - 100.0 / 3.0).abs() < 1e-9);
+  let snapped = builder.snap(&solution, 1.0).unwrap();
+  assert_eq!(snapped.value_of(a_right), Some(33.0));
+  assert_eq!(snapped.value_of(b_left), Some(33.0));
+  assert_eq!(snapped.hit_test(33.5, 10.0), vec![b]);
+
+  // Rounding down would break the lower bound, so the edge stays where it was solved.
+  builder.push_hard_constraint(a_right.prop_ge(at(33.3)));
+  let solution = builder.solve().unwrap();
+  let snapped = builder.snap(&solution, 1.0).unwrap();
+  assert_eq!(snapped.value_of(a_right), solution.value_of(b_left));
+  assert!(builder.snap(&solution, 0.0).is_err());
+}
+
+#[test]
+fn test_canvas() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_units(Units::print());
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Arc::new(std::sync::Mutex::new(None));
+
+  // Widgets pushed before the root are kept on the page too.
+  let a = Rectangle::with_width_and_height(&ctx, 100.0, 50.0, Box::new(|_| Ok(())));
+  builder.push_objective(Objective::Maximize(a.left));
+  let a_left = a.left;
+  builder.push_widget(a);
+
+  let canvas = Canvas::new(
+    &ctx,
+    builder.units(),
+    PageSize::Letter,
+    Box::new({
+      let painted = painted.clone();
+      move |m: CanvasMetrics| {
+        *painted.lock().unwrap() = Some(m);
+        Ok(())
+      }
+    }),
+  )
+  .unwrap()
+  .with_margin(Length::inches(1.0))
+  .unwrap();
+  let safe_area = canvas.safe_area();
+  let canvas = builder.push_root(canvas);
+
+  let b = Rectangle::with_width_and_height(&ctx, 100.0, 50.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(safe_area.contains(&b.rect()));
+  builder.push_objective(Objective::Maximize(b.top));
+  let b_bottom = b.bottom;
+  builder.push_widget(b);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(a_left), Some(512.0));
+  assert_eq!(solution.value_of(b_bottom), Some(720.0));
+
+  builder.build().unwrap();
+  let metrics = painted.lock().unwrap().unwrap();
+  assert_eq!((metrics.frame.width, metrics.frame.height), (612.0, 792.0));
+  assert_eq!(metrics.safe_area.left, 72.0);
+  assert_eq!(metrics.safe_area.bottom, 720.0);
+
+  // Without the root, widgets may leave the page.
+  builder.remove_widget(canvas);
+  builder.push_hard_constraint(a_left.prop_ge(at(600.0)));
+  assert!(builder.solve().is_ok());
+}
+
+#[test]
+fn test_ellipse() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Arc::new(std::sync::Mutex::new(None));
+
+  let node = Ellipse::unbound(
+    &ctx,
+    Box::new({
+      let painted = painted.clone();
+      move |m: EllipseMetrics| {
+        *painted.lock().unwrap() = Some(m);
+        Ok(())
+      }
+    }),
+  );
+  let label = Rectangle::with_width_and_height(&ctx, 70.71, 35.35, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(node.contains(&label.rect()));
+  builder.push_hard_constraint(node.left().prop_eq(at(10.0)) & node.top().prop_eq(at(20.0)));
+  builder.push_objective(Objective::Minimize(node.width() + node.height()));
+
+  // A rectangle beside the ellipse lines up with its bounding box.
+  let side = Rectangle::with_width_and_height(&ctx, 10.0, 10.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(side.left.prop_eq(node.right()));
+  builder.push_hard_constraint(side.top.prop_eq(node.center.y));
+  let east = node.rect().anchor("out-east").unwrap();
+  let corner = Widget::anchors(&node)
+    .into_iter()
+    .find(|x| x.0 == "top-left")
+    .unwrap()
+    .1;
+  let side_left = side.left;
+  builder.push_widget(label);
+  builder.push_widget(side);
+  builder.push_widget(node);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(side_left), solution.value_of(east.x));
+  let corner = (
+    solution.value_of(corner.x).unwrap(),
+    solution.value_of(corner.y).unwrap(),
+  );
+
+  builder.build().unwrap();
+  let m = painted.lock().unwrap().unwrap();
+  assert!((m.rx - 50.0).abs() < 0.01 && (m.ry - 25.0).abs() < 0.01);
+  assert!((m.cx - m.rx - 10.0).abs() < 1e-6 && (m.cy - m.ry - 20.0).abs() < 1e-6);
+  let (dx, dy) = ((corner.0 - m.cx) / m.rx, (corner.1 - m.cy) / m.ry);
+  assert!(dx * dx + dy * dy <= 1.0);
+}
+
+#[test]
+fn test_line() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Arc::new(std::sync::Mutex::new(vec![]));
+  let painter = || -> LinePainter {
+    let painted = painted.clone();
+    Box::new(move |m: LineMetrics| {
+      painted.lock().unwrap().push(m);
+      Ok(())
+    })
+  };
+
+  // A separator under a header, and a leader line at 45° from its midpoint.
+  let header = Rectangle::with_width_and_height(&ctx, 200.0, 40.0, Box::new(|_| Ok(())));
+  let separator = Line::unbound(&ctx, painter());
+  builder.push_hard_constraint(header.left.prop_eq(at(10.0)) & header.top.prop_eq(at(10.0)));
+  builder.push_hard_constraint(separator.horizontal());
+  builder.push_hard_constraint(separator.from.x.prop_eq(header.left));
+  builder.push_hard_constraint(separator.from.y.prop_eq(header.bottom + 5.0));
+  builder.push_hard_constraint(separator.length().prop_eq(header.width));
+  builder.push_hard_constraint(separator.dx().prop_ge(at(0.0)));
+
+  let end = Point {
+    x: Measure::new_unbound(&ctx),
+    y: Measure::new_unbound(&ctx),
+  };
+  let leader = Line::new(separator.midpoint(), end, painter());
+  builder.push_hard_constraint(leader.with_slope(1.0));
+  builder.push_hard_constraint(leader.dx().prop_eq(at(30.0)));
+  let end = Widget::anchors(&leader)[1].1;
+  builder.push_widget(header);
+  builder.push_widget(separator);
+  builder.push_widget(leader);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(end.x), Some(140.0));
+  assert_eq!(solution.value_of(end.y), Some(85.0));
+
+  builder.build().unwrap();
+  let painted = painted.lock().unwrap();
+  assert_eq!(painted.len(), 2);
+  assert_eq!(
+    (painted[0].x1, painted[0].y1, painted[0].x2),
+    (10.0, 55.0, 210.0)
+  );
+  assert_eq!(painted[0].length(), 200.0);
+  assert!((painted[1].length() - 30.0 * 2f64.sqrt()).abs() < 1e-9);
+}
+
+#[test]
+fn test_polyline() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(None));
+  let painted_2 = painted.clone();
+
+  // An elbow connector from the right of one box to the left of another.
+  let source = Rectangle::with_width_and_height(&ctx, 40.0, 20.0, Box::new(|_| Ok(())));
+  let target = Rectangle::with_width_and_height(&ctx, 40.0, 20.0, Box::new(|_| Ok(())));
+  let connector = Polyline::unbound(
+    &ctx,
+    3,
+    Box::new(move |m: PolylineMetrics| {
+      *painted_2.borrow_mut() = Some(m);
+      Ok(())
+    }),
+  )
+  .orthogonal();
+  assert_eq!(connector.segments(), &[SegmentKind::Orthogonal; 2]);
+  builder.push_hard_constraint(source.left.prop_eq(at(10.0)) & source.top.prop_eq(at(10.0)));
+  builder.push_hard_constraint(target.left.prop_eq(at(110.0)) & target.top.prop_eq(at(60.0)));
+  let (from, to) = (connector.first().unwrap(), connector.last().unwrap());
+  builder.push_hard_constraint(from.x.prop_eq(source.right) & from.y.prop_eq(at(20.0)));
+  builder.push_hard_constraint(to.x.prop_eq(target.left) & to.y.prop_eq(at(70.0)));
+  let length = connector.length();
+  let elbow = connector.vertices[1];
+  builder.push_widget(source);
+  builder.push_widget(target);
+  builder.push_widget(connector);
+
+  let solution = builder.solve().unwrap();
+  let elbow = (
+    solution.value_of(elbow.x).unwrap(),
+    solution.value_of(elbow.y).unwrap(),
+  );
+  assert!(elbow == (110.0, 20.0) || elbow == (50.0, 70.0));
+  assert_eq!(solution.value_of(length), Some(110.0));
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  let painted = painted.as_ref().unwrap();
+  assert_eq!(painted.vertices.len(), 3);
+  assert_eq!(painted.segments().len(), 2);
+  assert_eq!(painted.length(), 110.0);
+}
+
+#[test]
+fn test_polygon() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(None));
+  let painted_2 = painted.clone();
+
+  // An arrow callout pointing right, placed by its origin.
+  let origin = Point {
+    x: Measure::new_unbound(&ctx),
+    y: Measure::new_unbound(&ctx),
+  };
+  let offsets = [
+    (0.0, 0.0),
+    (60.0, 0.0),
+    (60.0, -10.0),
+    (90.0, 15.0),
+    (60.0, 40.0),
+    (60.0, 30.0),
+    (0.0, 30.0),
+  ];
+  let vertices = offsets
+    .iter()
+    .map(|&(x, y)| Point {
+      x: origin.x + x,
+      y: origin.y + y,
+    })
+    .collect();
+  let arrow = Polygon::new(
+    &ctx,
+    vertices,
+    Box::new(move |m: PolygonMetrics| {
+      *painted_2.borrow_mut() = Some(m);
+      Ok(())
+    }),
+  );
+  let triangle = Polygon::new(
+    &ctx,
+    vec![
+      Point {
+        x: at(0.0),
+        y: at(0.0),
+      },
+      Point {
+        x: at(10.0),
+        y: at(0.0),
+      },
+      Point {
+        x: at(0.0),
+        y: at(10.0),
+      },
+    ],
+    Box::new(|_| Ok(())),
+  );
+  builder.push_hard_constraint(origin.x.prop_eq(at(20.0)) & origin.y.prop_eq(at(30.0)));
+  let (bounds, centroid) = (arrow.bounding_box().unwrap(), arrow.centroid().unwrap());
+  let (area, convex) = (arrow.signed_area(), arrow.is_convex());
+  let center = Widget::anchors(&arrow)
+    .into_iter()
+    .find(|(name, _)| *name == "center")
+    .unwrap()
+    .1;
+  builder.push_widget(arrow);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(bounds.left), Some(20.0));
+  assert_eq!(solution.value_of(bounds.right), Some(110.0));
+  assert_eq!(solution.value_of(bounds.top), Some(20.0));
+  assert_eq!(solution.value_of(bounds.bottom), Some(70.0));
+  assert!((solution.value_of(centroid.x).unwrap() - (20.0 + 330.0 / 7.0)).abs() < 1e-9);
+  assert_eq!(solution.value_of(center.y), solution.value_of(centroid.y));
+  assert_eq!(solution.value_of(area), Some(2550.0));
+  assert_eq!(solution.truth_of(convex), Some(false));
+  assert_eq!(solution.truth_of(triangle.is_convex()), Some(true));
+  assert_eq!(solution.value_of(triangle.signed_area()), Some(50.0));
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  assert_eq!(painted.as_ref().unwrap().signed_area(), 2550.0);
+}
+
+#[test]
+fn test_triangle() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(None));
+  let painted_2 = painted.clone();
+
+  // A disclosure indicator, pointing down from a 12 unit wide base.
+  let indicator = Triangle::unbound(
+    &ctx,
+    Box::new(move |m: TriangleMetrics| {
+      *painted_2.borrow_mut() = Some(m);
+      Ok(())
+    }),
+  );
+  builder.push_hard_constraint(indicator.pointing_equilateral(Direction::Down));
+  builder.push_hard_constraint(indicator.b.x.prop_eq(at(10.0)) & indicator.b.y.prop_eq(at(5.0)));
+  builder.push_hard_constraint(indicator.c.x.prop_eq(at(22.0)));
+  let (a, centroid) = (indicator.a, indicator.centroid());
+  let (isosceles, clockwise) = (indicator.is_isosceles(), indicator.is_clockwise());
+  let bounds = BoundedWidget::rect(&indicator);
+  builder.push_widget(indicator);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(a.x), Some(16.0));
+  assert!((solution.value_of(a.y).unwrap() - (5.0 + 12.0 * 0.866)).abs() < 1e-9);
+  assert_eq!(solution.value_of(centroid.x), Some(16.0));
+  assert_eq!(solution.value_of(bounds.left), Some(10.0));
+  assert_eq!(solution.value_of(bounds.top), Some(5.0));
+  assert_eq!(solution.truth_of(isosceles), Some(true));
+  assert_eq!(solution.truth_of(clockwise), Some(true));
+
+  builder.build().unwrap();
+  let painted = painted.borrow().unwrap();
+  assert_eq!((painted.x2, painted.x3), (10.0, 22.0));
+}
+
+#[test]
+fn test_arc() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(None));
+  let painted_2 = painted.clone();
+
+  // The track of a gauge, over the top half of its circle.
+  let gauge = ArcWidget::unbound(
+    &ctx,
+    180.0,
+    360.0,
+    Box::new(move |m: ArcMetrics| {
+      *painted_2.borrow_mut() = Some(m);
+      Ok(())
+    }),
+  );
+  builder.push_hard_constraint(gauge.center.x.prop_eq(at(100.0)));
+  builder.push_hard_constraint(gauge.center.y.prop_eq(at(100.0)));
+  builder.push_hard_constraint(gauge.radius.prop_eq(at(50.0)));
+  let bounds = BoundedWidget::rect(&gauge);
+  let (start, middle) = (gauge.start_point(), gauge.midpoint());
+  let (left, bottom) = (gauge.covers(180.0), gauge.covers(90.0));
+  builder.push_widget(gauge);
+
+  let solution = builder.solve().unwrap();
+  let value = |m| (solution.value_of(m).unwrap() * 1000.0).round() / 1000.0;
+  assert_eq!((value(start.x), value(start.y)), (50.0, 100.0));
+  assert_eq!((value(middle.x), value(middle.y)), (100.0, 50.0));
+  assert_eq!(solution.truth_of(left), Some(true));
+  assert_eq!(solution.truth_of(bottom), Some(false));
+  assert_eq!(value(bounds.left), 50.0);
+  assert_eq!(value(bounds.right), 150.0);
+  assert_eq!(value(bounds.top), 50.0);
+  assert_eq!(value(bounds.bottom), 100.0);
+
+  builder.build().unwrap();
+  let painted = painted.borrow().unwrap();
+  assert_eq!((painted.cx, painted.radius), (100.0, 50.0));
+}
+
+#[test]
+fn test_rounded_rectangle() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(vec![]));
+  let painter = || -> super::RoundedRectanglePainter {
+    let painted = painted.clone();
+    Box::new(move |m: RoundedRectangleMetrics| {
+      painted.borrow_mut().push(m);
+      Ok(())
+    })
+  };
+
+  // A pill button, and a narrow card whose corners round as far as its width allows.
+  let button = RoundedRectangle::unbound(&ctx, painter());
+  let button = button.with_radius(Measure::new_unbound(&ctx));
+  builder.push_hard_constraint(button.width.prop_eq(at(120.0)) & button.height.prop_eq(at(40.0)));
+  builder.push_hard_constraint(button.fully_rounded());
+  let radius = Measure::new_unbound(&ctx);
+  let card = RoundedRectangle::unbound(&ctx, painter()).with_radius(radius);
+  builder.push_hard_constraint(card.width.prop_eq(at(30.0)) & card.height.prop_eq(at(200.0)));
+  builder.push_objective(Objective::Maximize(radius));
+  let named = card.named_measures();
+  assert!(named.iter().any(|(name, _)| name == "top_left"));
+  builder.push_widget(button);
+  builder.push_widget(card);
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  assert_eq!(painted.len(), 2);
+  assert_eq!(painted[0].top_left, 20.0);
+  assert_eq!(painted[0].bottom_right, 20.0);
+  assert_eq!(painted[1].frame.width, 30.0);
+  assert_eq!(painted[1].top_right, 15.0);
+}
+
+#[test]
+fn test_rotated_rect() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+
+  // An axis label turned upright against the left edge, and a tag at 45° beside it.
+  let axis = RotatedRect::with_size(&ctx, 100.0, 20.0, 90.0, Box::new(|_| Ok(())));
+  let tag = RotatedRect::with_size(&ctx, 100.0, 20.0, 45.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(axis.left.prop_eq(at(0.0)) & axis.top.prop_eq(at(0.0)));
+  builder.push_hard_constraint(tag.left.prop_eq(axis.right) & tag.top.prop_eq(at(0.0)));
+  let (axis_center, axis_corner) = (axis.center(), axis.corners()[0]);
+  let (tag_bounds, tag_corner) = (BoundedWidget::rect(&tag), tag.corners()[1]);
+  builder.push_widget(axis);
+  builder.push_widget(tag);
+
+  let solution = builder.solve().unwrap();
+  let value = |m| (solution.value_of(m).unwrap() * 1000.0).round() / 1000.0;
+  assert_eq!(value(axis_center.x), 10.0);
+  assert_eq!(value(axis_center.y), 50.0);
+  assert_eq!(value(axis_corner.x), 20.0);
+  assert_eq!(value(axis_corner.y), 0.0);
+  let side = 120.0 * std::f64::consts::FRAC_1_SQRT_2;
+  assert!((value(tag_bounds.width()) - side).abs() < 1e-3);
+  assert!((value(tag_bounds.height()) - side).abs() < 1e-3);
+  // The top right corner of the tag turns down to the right edge of its box.
+  assert_eq!(value(tag_corner.x), value(tag_bounds.right));
+}
+
+/// Fixed advances, with `AV` and `VA` kerned.
+struct TestFace;
+
+impl Typeface for TestFace {
+  fn advance(&self, _c: char, size: f64) -> f64 {
+    size * 0.6
+  }
+
+  fn kerning(&self, left: char, right: char, size: f64) -> f64 {
+    match (left, right) {
+      ('A', 'V') | ('V', 'A') => -size * 0.1,
+      _ => 0.0,
+    }
+  }
+
+  fn ascent(&self, size: f64) -> f64 {
+    size * 0.8
+  }
+
+  fn descent(&self, size: f64) -> f64 {
+    size * 0.2
+  }
+
+  fn cap_height(&self, size: f64) -> f64 {
+    size * 0.7
+  }
+}
+
+#[test]
+fn test_text() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(None));
+  let painted_2 = painted.clone();
+
+  // An icon beside a heading, their tops aligned with the capital letters.
+  let heading = Text::new(
+    &ctx,
+    &TestFace,
+    "AVA",
+    20.0,
+    Box::new(move |m: TextMetrics| {
+      *painted_2.borrow_mut() = Some(m);
+      Ok(())
+    }),
+  )
+  .unwrap();
+  let icon = Rectangle::with_width_and_height(&ctx, 14.0, 14.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(heading.left.prop_eq(at(30.0)) & heading.top.prop_eq(at(10.0)));
+  builder.push_hard_constraint(icon.right.prop_eq(heading.left - 4.0));
+  builder.push_hard_constraint(icon.top.prop_eq(heading.cap_top()));
+  let baseline = Widget::anchors(&heading)
+    .into_iter()
+    .find(|(name, _)| *name == "baseline-right")
+    .unwrap()
+    .1;
+  let icon_top = icon.top;
+  builder.push_widget(icon);
+  builder.push_widget(heading);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(icon_top), Some(12.0));
+  assert_eq!(solution.value_of(baseline.x), Some(62.0));
+  assert_eq!(solution.value_of(baseline.y), Some(26.0));
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  let painted = painted.as_ref().unwrap();
+  assert_eq!(painted.text, "AVA");
+  assert_eq!((painted.frame.width, painted.frame.height), (32.0, 20.0));
+  assert_eq!(painted.baseline, 26.0);
+}
+
+#[test]
+fn test_text_wrapping() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(vec![]));
+  let painter = || -> super::TextPainter {
+    let painted = painted.clone();
+    Box::new(move |m: TextMetrics| {
+      painted.borrow_mut().push(m);
+      Ok(())
+    })
+  };
+
+  // Words 12, 24, 12 and 36 wide with 6 wide spaces, on 10 high lines. They take one line from
+  // 102 wide, two from 54, three from 42 and four from 36.
+  let text = "aa bbbb cc dddddd";
+  let column = Text::wrapping(&ctx, &TestFace, text, 10.0, painter()).unwrap();
+  let narrow = Text::wrapping(&ctx, &TestFace, text, 10.0, painter()).unwrap();
+  builder.push_hard_constraint(column.width.prop_le(at(60.0)));
+  builder.push_objective(Objective::Minimize(column.height));
+  builder.push_hard_constraint(narrow.width.prop_eq(at(40.0)));
+  let (lines, narrow_lines) = (column.line_count, narrow.line_count);
+  builder.push_widget(column);
+  builder.push_widget(narrow);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(lines), Some(2.0));
+  assert_eq!(solution.value_of(narrow_lines), Some(4.0));
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  assert_eq!(painted[0].frame.height, 20.0);
+  assert_eq!(painted[0].lines.len(), 2);
+  assert!(painted[0].frame.width >= 54.0);
+  assert_eq!(painted[1].lines, vec!["aa", "bbbb", "cc", "dddddd"]);
+  assert_eq!(painted[1].frame.height, 40.0);
+}
+
+#[test]
+fn test_text_wrapping_long_paragraph() {
+  // Words of one to eight letters, 6 wide each, set in 300 wide lines of at most 50 letters.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+  let seen = painted.clone();
+  let painter: super::TextPainter = Box::new(move |m: TextMetrics| {
+    seen.borrow_mut().push(m);
+    Ok(())
+  });
+  let text = (0..600)
+    .map(|i| "abcdefgh"[..1 + i * 7 % 8].to_string())
+    .collect::<Vec<_>>()
+    .join(" ");
+  let paragraph = Text::wrapping(&ctx, &TestFace, &text, 10.0, painter).unwrap();
+  builder.push_hard_constraint(
+    paragraph
+      .width
+      .prop_eq(Measure::new_const(&ctx, 300.0).unwrap()),
+  );
+  let lines = paragraph.line_count;
+  builder.push_widget(paragraph);
+
+  let report = builder.build().unwrap();
+  let painted = painted.borrow();
+  assert!(painted[0].lines.iter().all(|x| x.len() <= 50));
+  assert_eq!(painted[0].lines.join(" "), text);
+  assert_eq!(
+    report.solution.value_of(lines),
+    Some(painted[0].lines.len() as f64)
+  );
+}
+
+#[test]
+fn test_image() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(vec![]));
+  let painter = || -> super::ImagePainter {
+    let painted = painted.clone();
+    Box::new(move |m: ImageMetrics| {
+      painted.borrow_mut().push(m);
+      Ok(())
+    })
+  };
+
+  // A 200×100 photo in 100×100 frames, and at its natural size.
+  let mut frames = vec![];
+  for fit in [Fit::Contain, Fit::Cover, Fit::Stretch].iter() {
+    let image = Image::new(&ctx, 200.0, 100.0, *fit, painter());
+    builder.push_hard_constraint(image.width.prop_eq(at(100.0)) & image.height.prop_eq(at(100.0)));
+    frames.push(image);
+  }
+  let natural = Image::new(&ctx, 200.0, 100.0, Fit::Contain, painter());
+  builder.push_hard_constraint(natural.keeps_aspect_ratio());
+  builder.push_hard_constraint(natural.width.prop_eq(at(300.0)));
+  let natural_height = natural.height;
+  for image in frames {
+    builder.push_hard_constraint(image.left.prop_eq(at(0.0)) & image.top.prop_eq(at(0.0)));
+    builder.push_widget(image);
+  }
+  builder.push_widget(natural);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(natural_height), Some(150.0));
+
+  builder.build().unwrap();
+  let painted = painted.borrow();
+  let (contain, cover, stretch) = (painted[0], painted[1], painted[2]);
+  assert_eq!(
+    (
+      contain.placement.top,
+      contain.placement.width,
+      contain.placement.height
+    ),
+    (25.0, 100.0, 50.0)
+  );
+  assert_eq!((contain.crop.width, contain.crop.height), (200.0, 100.0));
+  assert_eq!(
+    (cover.placement.left, cover.placement.width),
+    (-50.0, 200.0)
+  );
+  assert_eq!((cover.crop.left, cover.crop.right), (50.0, 150.0));
+  assert_eq!((cover.crop.top, cover.crop.bottom), (0.0, 100.0));
+  assert_eq!(stretch.placement.width, 100.0);
+  assert_eq!((stretch.crop.width, stretch.crop.height), (200.0, 100.0));
+  assert_eq!(painted[3].crop.width, 200.0);
+}
+
+#[test]
+fn test_guideline() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+
+  // Labels end and fields start a quarter across a card, below a header band of fixed height.
+  let card = Rectangle::with_width_and_height(&ctx, 300.0, 200.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(card.left.prop_eq(at(10.0)) & card.top.prop_eq(at(10.0)));
+  let rect = BoundedWidget::rect(&card);
+  let split = Guideline::percent_of(GuidelineAxis::Vertical, &rect, 25.0).unwrap();
+  let header = Guideline::at(GuidelineAxis::Horizontal, card.top, 40.0);
+  let label = Rectangle::with_width_and_height(&ctx, 60.0, 20.0, Box::new(|_| Ok(())));
+  let field = Rectangle::with_width_and_height(&ctx, 120.0, 20.0, Box::new(|_| Ok(())));
+  let (label_rect, field_rect) = (BoundedWidget::rect(&label), BoundedWidget::rect(&field));
+  builder.push_hard_constraint(split.aligns(label_rect.anchor("center-right").unwrap()));
+  builder.push_hard_constraint(split.aligns(field_rect.anchor("center-left").unwrap()));
+  builder.push_hard_constraint(header.aligns(label_rect.anchor("top-left").unwrap()));
+  builder.push_hard_constraint(label.top.prop_eq(field.top));
+  builder.push_hard_constraint(split.after(Point {
+    x: field.right,
+    y: field.top,
+  }));
+  assert!(Guideline::percent(GuidelineAxis::Vertical, card.left, card.right, f64::NAN).is_err());
+  builder.push_widget(card);
+  builder.push_widget(label);
+  builder.push_widget(field);
+  builder.push_widget(split);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(split.position), Some(85.0));
+  assert_eq!(solution.value_of(label_rect.left), Some(25.0));
+  assert_eq!(solution.value_of(field_rect.top), Some(50.0));
+  let report = builder.build().unwrap();
+  assert!(report.paint_errors.is_empty());
+}
+
+#[test]
+fn test_barrier() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+
+  // Descriptions start after the widest of a column of labels, wherever it is.
+  let mut labels = vec![];
+  let mut descriptions = vec![];
+  for (i, width) in [40.0, 90.0, 60.0].iter().enumerate() {
+    let label = Rectangle::with_width_and_height(&ctx, *width, 20.0, Box::new(|_| Ok(())));
+    let description = Rectangle::with_width_and_height(&ctx, 100.0, 20.0, Box::new(|_| Ok(())));
+    builder.push_hard_constraint(label.left.prop_eq(at(0.0)));
+    builder.push_hard_constraint(label.top.prop_eq(at(i as f64 * 30.0)));
+    builder.push_hard_constraint(description.top.prop_eq(label.top));
+    labels.push(label.measures());
+    descriptions.push(description.measures());
+    builder.push_widget(label);
+    builder.push_widget(description);
+  }
+  let group = labels.iter().collect::<Vec<_>>();
+  let barrier = Barrier::new(BarrierSide::Right, &group).unwrap();
+  for description in &descriptions {
+    builder.push_hard_constraint(barrier.clears(description.rect().anchor("center-left").unwrap()));
+    builder.push_objective(Objective::Minimize(description.left));
+  }
+  let top = Barrier::new(BarrierSide::Top, &group).unwrap();
+  builder.push_hard_constraint(top.aligns(descriptions[0].rect().anchor("top-left").unwrap()));
+  assert!(Barrier::new(BarrierSide::Left, &[]).is_err());
+  builder.push_widget(barrier.guideline());
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(barrier.position), Some(90.0));
+  for description in &descriptions {
+    assert_eq!(solution.value_of(description.left), Some(90.0));
+  }
+  assert_eq!(solution.value_of(top.position), Some(0.0));
+  builder.build().unwrap();
+}
+
+#[test]
+fn test_group() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(vec![]));
+  let rect = |width: f64, height: f64, name: &'static str| {
+    let painted = painted.clone();
+    Rectangle::with_width_and_height(
+      &ctx,
+      width,
+      height,
+      Box::new(move |m: RectangleMetrics| {
+        painted
+          .borrow_mut()
+          .push((name, m.left, m.top, m.width, m.height));
+        Ok(())
+      }),
+    )
+  };
+
+  // A bordered pair of rectangles, and a sidebar next to the pair.
+  let painted_ = painted.clone();
+  let mut group = Group::with_painter(
+    &ctx,
+    Box::new(move |m: RectangleMetrics| {
+      painted_
+        .borrow_mut()
+        .push(("border", m.left, m.top, m.width, m.height));
+      Ok(())
+    }),
+  );
+  let (a, b) = (rect(40.0, 20.0, "a"), rect(30.0, 30.0, "b"));
+  builder.push_hard_constraint(a.left.prop_eq(at(10.0)) & a.top.prop_eq(at(10.0)));
+  builder.push_hard_constraint(b.left.prop_eq(at(60.0)) & b.top.prop_eq(at(50.0)));
+  group.push(a);
+  group.push(b);
+  let sidebar = rect(20.0, 20.0, "sidebar");
+  builder.push_hard_constraint(sidebar.measures().right_to(group.right(), 10.0));
+  builder.push_hard_constraint(sidebar.top.prop_eq(group.top()));
+  let frame = group.measures();
+  builder.push_widget(group);
+  builder.push_widget(sidebar);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(frame.width), Some(80.0));
+  assert_eq!(solution.value_of(frame.height), Some(70.0));
+
+  builder.build().unwrap();
+  assert_eq!(
+    *painted.borrow(),
+    vec![
+      ("border", 10.0, 10.0, 80.0, 70.0),
+      ("a", 10.0, 10.0, 40.0, 20.0),
+      ("b", 60.0, 50.0, 30.0, 30.0),
+      ("sidebar", 100.0, 10.0, 20.0, 20.0),
+    ]
+  );
+}
+
+#[test]
+fn test_padded() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let painted = Rc::new(RefCell::new(vec![]));
+  let rect = |width: f64, height: f64| {
+    let painted = painted.clone();
+    Rectangle::with_width_and_height(
+      &ctx,
+      width,
+      height,
+      Box::new(move |m: RectangleMetrics| {
+        painted.borrow_mut().push((m.left, m.top));
+        Ok(())
+      }),
+    )
+  };
+
+  // A button with constant padding, and a badge centered in a slot by padding that is solved.
+  let button = Padded::new(&ctx, rect(60.0, 20.0), 10.0);
+  builder.push_hard_constraint(button.left.prop_eq(at(0.0)) & button.top.prop_eq(at(0.0)));
+  let side = Measure::new_unbound(&ctx);
+  let zero = Measure::zero(&ctx);
+  let badge = Padded::new(&ctx, rect(60.0, 20.0), 0.0).with_padding(zero, side, zero, side);
+  builder.push_hard_constraint(badge.measures().right_to(button.right, 0.0));
+  builder.push_hard_constraint(badge.top.prop_eq(at(0.0)) & badge.width.prop_eq(at(100.0)));
+  let (button_size, badge_height) = ((button.width, button.height), badge.height);
+  builder.push_widget(button);
+  builder.push_widget(badge);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(button_size.0), Some(80.0));
+  assert_eq!(solution.value_of(button_size.1), Some(40.0));
+  assert_eq!(solution.value_of(side), Some(20.0));
+  assert_eq!(solution.value_of(badge_height), Some(20.0));
+
+  builder.build().unwrap();
+  assert_eq!(*painted.borrow(), vec![(10.0, 10.0), (100.0, 0.0)]);
+}
+
+#[test]
+fn test_margins() {
+  for (collapse, tops) in [
+    (MarginCollapse::Separate, [0.0, 50.0, 80.0]),
+    (MarginCollapse::Collapse, [0.0, 40.0, 70.0]),
+  ] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+    let paragraph = |top: f64, bottom: f64| {
+      let rect = Rectangle::with_width_and_height(&ctx, 100.0, 20.0, Box::new(|_| Ok(())));
+      Margined::new(rect, Margins::new(at(top), at(0.0), at(bottom), at(0.0)))
+    };
+
+    // Paragraphs in a column, where the second pulls the third closer by a negative margin.
+    let paragraphs = [
+      paragraph(0.0, 10.0),
+      paragraph(20.0, -5.0),
+      paragraph(15.0, 0.0),
+    ];
+    let group = paragraphs
+      .iter()
+      .map(|x| x as &dyn BoundedWidget)
+      .collect::<Vec<_>>();
+    for x in stack_vertically(&group, collapse) {
+      builder.push_constraint(x);
+    }
+    builder.push_hard_constraint(paragraphs[0].widget.top.prop_eq(at(0.0)));
+    for x in &paragraphs {
+      builder.push_hard_constraint(x.widget.left.prop_eq(at(0.0)));
+    }
+
+    // Buttons in a row, apart by the sum of their margins.
+    let button = |margin: f64| {
+      let rect = Rectangle::with_width_and_height(&ctx, 30.0, 20.0, Box::new(|_| Ok(())));
+      Margined::uniform(rect, at(margin))
+    };
+    let (ok, cancel) = (button(8.0), button(4.0));
+    for x in stack_horizontally(&[&ok, &cancel]) {
+      builder.push_constraint(x);
+    }
+    builder.push_hard_constraint(ok.widget.left.prop_eq(at(0.0)) & ok.widget.top.prop_eq(at(0.0)));
+    builder.push_hard_constraint(cancel.widget.top.prop_eq(at(0.0)));
+
+    let tops_ = paragraphs.iter().map(|x| x.widget.top).collect::<Vec<_>>();
+    let cancel_left = cancel.widget.left;
+    for x in paragraphs {
+      builder.push_widget(x);
+    }
+    builder.push_widget(ok);
+    builder.push_widget(cancel);
+
+    let solution = builder.solve().unwrap();
+    let solved = tops_
+      .iter()
+      .map(|x| solution.value_of(*x).unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(solved, tops);
+    assert_eq!(solution.value_of(cancel_left), Some(42.0));
+  }
+}
+
+#[test]
+fn test_row() {
+  // A fixed icon, a title that takes twice the space left of a spacer, and a button. At 300 the
+  // row has 90 to share out, and at 130 it overflows by 80, which the title and the button give
+  // up in proportion to their bases. Unconstrained, it is as wide as the bases.
+  for (width, widths) in [
+    (300.0, vec![20.0, 160.0, 30.0, 60.0]),
+    (130.0, vec![20.0, 50.0, 0.0, 30.0]),
+    (f64::NAN, vec![20.0, 100.0, 0.0, 60.0]),
+  ] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+    let painted = Rc::new(RefCell::new(vec![]));
+    let item = || {
+      let painted = painted.clone();
+      Rectangle::unbound(
+        &ctx,
+        Box::new(move |m: RectangleMetrics| {
+          painted.borrow_mut().push((m.left, m.width));
+          Ok(())
+        }),
+      )
+    };
+    let items = vec![item(), item(), item(), item()];
+    for x in &items {
+      builder.push_hard_constraint(x.height.prop_eq(at(20.0)));
+    }
+
+    let mut row = Row::new(&ctx, Box::new(|_| Ok(()))).with_gap(10.0);
+    builder.push_hard_constraint(row.frame.left.prop_eq(at(5.0)) & row.frame.top.prop_eq(at(0.0)));
+    if !width.is_nan() {
+      builder.push_hard_constraint(row.frame.width.prop_eq(at(width)));
+    }
+    let flexes = [
+      Flex::fixed(20.0),
+      Flex::new(100.0, 2.0, 1.0),
+      Flex::new(0.0, 1.0, 0.0),
+      Flex::new(60.0, 0.0, 1.0),
+    ];
+    for (item, flex) in items.into_iter().zip(flexes.iter()) {
+      row.push(item, *flex);
+    }
+    let frame = row.frame.measures();
+    builder.push_widget(row);
+
+    let solution = builder.solve().unwrap();
+    assert_eq!(solution.value_of(frame.height), Some(20.0));
+    builder.build().unwrap();
+    let painted = painted.borrow();
+    assert_eq!(painted.iter().map(|x| x.1).collect::<Vec<_>>(), widths);
+    assert_eq!(painted[0].0, 5.0);
+    assert_eq!(
+      painted[3].0 + painted[3].1,
+      5.0 + widths.iter().sum::<f64>() + 30.0
+    );
+  }
+}
+
+#[test]
+fn test_row_margins() {
+  // The margins of the icon push the title right, and take 10 of the 60 of the row. The row
+  // overflows by 90, which would take the badge below zero, so it stays empty.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let icon = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let title = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let badge = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (icon_, title_, badge_) = (icon.measures(), title.measures(), badge.measures());
+  for x in [&icon_, &title_, &badge_] {
+    builder.push_hard_constraint(x.height.prop_eq(at(20.0)));
+  }
+
+  let mut row = Row::new(&ctx, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(row.frame.left.prop_eq(at(0.0)) & row.frame.top.prop_eq(at(0.0)));
+  builder.push_hard_constraint(row.frame.width.prop_eq(at(60.0)));
+  let margins = Margins::new(at(0.0), at(5.0), at(0.0), at(5.0));
+  row.push(Margined::new(icon, margins), Flex::fixed(20.0));
+  row.push(title, Flex::new(100.0, 0.0, 1.0));
+  row.push(badge, Flex::new(20.0, 0.0, 5.0));
+  builder.push_widget(row);
+
+  let solution = builder.solve().unwrap();
+  let solved = |x: &RectangleMeasures| {
+    (
+      solution.value_of(x.left).unwrap(),
+      solution.value_of(x.width).unwrap(),
+    )
+  };
+  assert_eq!(solved(&icon_), (5.0, 20.0));
+  assert_eq!(solved(&title_), (30.0, 55.0));
+  assert_eq!(solved(&badge_), (85.0, 0.0));
+}
+
+#[test]
+fn test_prop_count() {
+  // Each flag would rather hold, but only two of them may.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let flags = (0..3).map(|_| Prop::new_unbound(&ctx)).collect::<Vec<_>>();
+  for &flag in &flags {
+    builder.push_constraint(flag);
+  }
+  builder.push_hard_constraint(Prop::at_most(&ctx, &flags, 2));
+  let count = Prop::count(&ctx, &flags);
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(count), Some(2.0));
+  let holding = flags
+    .iter()
+    .filter(|&&x| solution.truth_of(x) == Some(true))
+    .count();
+  assert_eq!(holding, 2);
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_scatter_labels_dense() {
+  use super::{ScatterLabels, ScatterLabelsMetrics};
+  use crate::layout::geometry::RectMeasures;
+
+  // Three labels of one point, with room on its right only: each of the two corners there fits
+  // one of them, so the one of least reward is dropped, and only one is shown once capped.
+  for (max_visible, shown) in [(None, 2), (Some(1), 1)] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+    let painted = Rc::new(RefCell::new(vec![]));
+    let seen = painted.clone();
+    let mut labels = ScatterLabels::new(
+      &ctx,
+      2.0,
+      Box::new(move |m: ScatterLabelsMetrics| {
+        *seen.borrow_mut() = m.labels;
+        Ok(())
+      }),
+    );
+    labels.max_visible = max_visible;
+    labels.bounds = Some(RectMeasures {
+      left: at(100.0),
+      right: at(200.0),
+      top: at(0.0),
+      bottom: at(200.0),
+    });
+    let anchor = Point {
+      x: at(100.0),
+      y: at(100.0),
+    };
+    for &reward in &[5, 1, 5] {
+      labels.push_label(anchor, 40.0, 10.0, reward);
+    }
+    builder.push_widget(labels);
+    builder.build().unwrap();
+
+    let painted = painted.borrow();
+    let visible = painted.iter().flatten().collect::<Vec<_>>();
+    assert_eq!(visible.len(), shown);
+    assert!(painted[1].is_none());
+    for (i, a) in visible.iter().enumerate() {
+      for b in &visible[i + 1..] {
+        let apart =
+          a.right <= b.left || b.right <= a.left || a.bottom <= b.top || b.bottom <= a.top;
+        assert!(apart, "{:?} overlaps {:?}", a, b);
+      }
+    }
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_form_long_label() {
+  use super::Form;
+
+  // The column is as wide as the widest label up to 100, so the label of 150 takes a line of its
+  // own, and its field goes below it.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let mut form = Form::new(&ctx, 100.0, 200.0, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(form.left.prop_eq(at(0.0)) & form.top.prop_eq(at(0.0)));
+  builder.push_hard_constraint(form.width.prop_eq(at(400.0)));
+  let mut fields = vec![];
+  for &width in &[60.0, 80.0, 150.0] {
+    let label = Rectangle::with_width_and_height(&ctx, width, 20.0, Box::new(|_| Ok(())));
+    let field = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    builder.push_hard_constraint(field.height.prop_eq(at(30.0)));
+    fields.push(field.measures());
+    form.push_row(label, field);
+  }
+  let (label_width, height) = (form.label_width, form.height);
+  builder.push_widget(form);
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(label_width), Some(100.0));
+  let solved = fields
+    .iter()
+    .map(|x| {
+      (
+        solution.value_of(x.left).unwrap(),
+        solution.value_of(x.top).unwrap(),
+      )
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(solved, [(108.0, 0.0), (108.0, 38.0), (108.0, 96.0)]);
+  assert_eq!(solution.value_of(height), Some(126.0));
+}
+
+#[test]
+fn test_sticky_stays_over_previous() {
+  use crate::layout::{
+    geometry::{RectMeasures, Size},
+    placement::sticky,
+  };
+
+  // The window shrank to 400 and cut into the palette, which was at 350. However much the
+  // layout would rather have it at 0, it keeps overlapping where it was, as close as it gets.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let size = Size {
+    width: at(100.0),
+    height: at(50.0),
+  };
+  let rect = |x, y| RectMeasures::from_origin_and_size(Point { x, y }, size);
+  let palette = rect(Measure::new_unbound(&ctx), Measure::new_unbound(&ctx));
+  let screen = RectMeasures {
+    left: at(0.0),
+    right: at(400.0),
+    top: at(0.0),
+    bottom: at(300.0),
+  };
+  let sticky = sticky(palette, screen, Some(rect(at(350.0), at(20.0))));
+  for c in sticky.constraints() {
+    builder.push_constraint(c);
+  }
+  builder.push_objective(sticky.objective().unwrap());
+  builder.push_constraint(palette.left.prop_eq(at(0.0)).with_weight(5000));
+
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(palette.left), Some(300.0));
+  assert_eq!(solution.value_of(palette.top), Some(20.0));
+}
+
+#[test]
+fn test_pip_nearest_candidate() {
+  use crate::layout::{
+    geometry::{RectMeasures, Size},
+    placement::pip,
+  };
+
+  // The container is not constant, so the distance to the hint is minimized instead of
+  // weighing the candidates.
+  let ctx = LayoutContext::new();
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let container = RectMeasures {
+    left: Measure::new_unbound(&ctx),
+    right: Measure::new_unbound(&ctx),
+    top: Measure::new_unbound(&ctx),
+    bottom: Measure::new_unbound(&ctx),
+  };
+  let size = Size {
+    width: at(100.0),
+    height: at(50.0),
+  };
+  let solve = |hint: (f64, f64), obstacle: Option<RectMeasures>| {
+    let mut builder = LayoutBuilder::new(&ctx);
+    for (m, value) in [
+      (container.left, 0.0),
+      (container.right, 400.0),
+      (container.top, 0.0),
+      (container.bottom, 300.0),
+    ] {
+      builder.push_hard_constraint(m.prop_eq(at(value)));
+    }
+    let mut pip = pip(
+      container,
+      size,
+      Point {
+        x: at(hint.0),
+        y: at(hint.1),
+      },
+    );
+    if let Some(obstacle) = obstacle {
+      pip.avoid(obstacle);
+    }
+    for c in pip.constraints() {
+      builder.push_constraint(c);
+    }
+    builder.push_objective(pip.objective().unwrap());
+    let solution = builder.solve().unwrap();
+    (
+      solution.value_of(pip.rect.left).unwrap(),
+      solution.value_of(pip.rect.top).unwrap(),
+    )
+  };
+
+  assert_eq!(solve((60.0, 280.0), None), (0.0, 250.0));
+  assert_eq!(solve((350.0, 20.0), None), (300.0, 0.0));
+
+  // With the top right corner taken, the middle of the right edge is closer than the middle of
+  // the top edge.
+  let obstacle = RectMeasures {
+    left: at(300.0),
+    right: at(400.0),
+    top: at(0.0),
+    bottom: at(50.0),
+  };
+  assert_eq!(solve((350.0, 20.0), Some(obstacle)), (300.0, 125.0));
+}
+
+#[test]
+fn test_simplex_keeps_unround_values() {
+  // The optimum of 100000.00005 is close enough to 100000 for snapping to round it there, past
+  // the bound it sits on.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let bound = Measure::new_ratio(&ctx, 2_000_000_001, 20_000).unwrap();
+  let x = Measure::new_unbound(&ctx);
+  builder.push_hard_constraint(x.prop_ge(bound));
+  builder.push_objective(Objective::Minimize(x));
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Simplex);
+  assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(report.solution.value_of(x), report.solution.value_of(bound));
+}
+
+#[test]
+fn test_z_order() {
+  let painted = Arc::new(std::sync::Mutex::new(vec![]));
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let tile = |name: &'static str| {
+    let painted = painted.clone();
+    Tile {
+      size: Measure::new_unbound(&ctx),
+      painter: Box::new(move |measures: &[f64]| {
+        assert_eq!(measures, [4.0]);
+        painted.lock().unwrap().push(name);
+        Ok(())
+      }),
+    }
+  };
+  let top = builder.push_widget(tile("top"));
+  builder.push_widget(tile("middle"));
+  let seen = painted.clone();
+  let bottom = builder.push_widget(Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(move |_| {
+      seen.lock().unwrap().push("bottom");
+      Ok(())
+    }),
+  ));
+  assert!(builder.set_z_order(top, 1));
+  assert!(builder.set_z_order(bottom, -1));
+  builder.build().unwrap();
+  assert_eq!(*painted.lock().unwrap(), vec!["bottom", "middle", "top"]);
+  assert_eq!(ctx.stats().live_painters, 0);
+
+  assert!(builder.remove_widget(top));
+  assert!(!builder.set_z_order(top, 2));
+}
+
+#[test]
+fn test_progress() {
+  let phases = Arc::new(std::sync::Mutex::new(vec![]));
+  let ctx = LayoutContext::new();
+  let seen = phases.clone();
+  let mut builder = LayoutBuilder::new(&ctx).on_progress(move |phase, _| {
+    let mut seen = seen.lock().unwrap();
+    if seen.last() != Some(&phase) {
+      seen.push(phase);
+    }
+  });
+  builder.push_widget(Rectangle::with_width_and_height(
+    &ctx,
+    5.0,
+    10.0,
+    Box::new(|_| Ok(())),
+  ));
+  builder.build().unwrap();
+  assert_eq!(
+    *phases.lock().unwrap(),
+    vec![
+      SolvePhase::Lowering,
+      SolvePhase::Checking,
+      SolvePhase::Extracting,
+      SolvePhase::Painting
+    ]
+  );
+}
+
+#[test]
+fn test_cancel_in_flight() {
+  // Cancelled once the solve is under way, so the painter never runs.
+  let ctx = LayoutContext::new();
+  let token = CancellationToken::new();
+  let canceller = token.clone();
+  let mut builder = LayoutBuilder::new(&ctx)
+    .with_cancellation(token)
+    .on_progress(move |phase, _| {
+      if phase == SolvePhase::Checking {
+        canceller.cancel();
+      }
+    });
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| panic!("painted a cancelled layout")));
+  builder.push_constraint(rect.width.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder.push_constraint(rect.width.prop_eq(Measure::new_const(&ctx, 20.0).unwrap()));
+  builder.push_widget(rect);
+  match builder.build() {
+    Err(LayoutError::Cancelled) => {}
+    res => panic!("unexpected result: {:?}", res.map(|_| ())),
+  }
+}
+
+#[test]
+fn test_objective_priority() {
+  // Pulling the width down and pushing it up conflict, so the objective of higher priority
+  // decides it, whichever is declared first.
+  for (min_priority, max_priority, width) in [(1, 0, 10.0), (0, 1, 50.0)] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    let w = rect.width;
+    builder.push_hard_constraint(w.prop_ge(Measure::new_const(&ctx, 10.0).unwrap()));
+    builder.push_hard_constraint(w.prop_le(Measure::new_const(&ctx, 50.0).unwrap()));
+    builder.push_widget(rect);
+    builder.add_objective(Objective::Maximize(w), max_priority);
+    builder.add_objective(Objective::Minimize(w), min_priority);
+    let report = builder.build().unwrap();
+    assert_eq!(report.solution.value_of(w), Some(width));
+  }
+}
+
+#[test]
+fn test_paint_from_model() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let painted = Rc::new(RefCell::new(vec![]));
+
+  let painted_ = painted.clone();
+  let rect = Rectangle::unbound(
+    &ctx,
+    Box::new(move |metrics: RectangleMetrics| {
+      painted_.borrow_mut().push((metrics.left, metrics.width));
+      Ok(())
+    }),
+  );
+  builder.push_hard_constraint(rect.width.prop_ge(Measure::new_const(&ctx, 2.5).unwrap()));
+  builder.push_widget(rect);
+
+  let script = builder.to_smtlib2();
+  assert!(script.contains("(declare-const m5 Real)"));
+  assert!(script.contains("(assert (= (+ m0 m4) m1))"));
+  assert!(script.contains("(assert (>= m4 (/ 5.0 2.0)))"));
+
+  let mut model = ModelValues::default();
+  for (i, &value) in [1, 6, 0, 3, 5, 3].iter().enumerate() {
+    model.reals.insert(format!("m{}", i), (value, 1));
+  }
+  let solution = builder.paint_from_model(&model).unwrap();
+  assert_eq!(*painted.borrow(), [(1.0, 5.0)]);
+  assert_eq!(solution.variable_count(), 6);
+}
+
+#[test]
+fn test_within_tolerant() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let parent = Rectangle::with_width_and_height(&ctx, 15.0, 10.0, Box::new(|_| Ok(())));
+  let child = Rectangle::with_width_and_height(&ctx, 18.0, 10.0, Box::new(|_| Ok(())));
+  let containment = child.measures().within_tolerant(&parent.measures(), 4.0);
+  builder.push_hard_constraint(parent.left.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder.push_hard_constraint(parent.top.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  for c in containment.constraints() {
+    builder.push_constraint(c);
+  }
+  builder.push_objective(containment.objective());
+  builder.push_widget(parent);
+  builder.push_widget(child);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.solution.value_of(containment.overflow), Some(1.5));
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_named_const() {
+  let mut session = SolverSession::new();
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let a = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let b = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let gap = b.left - a.right;
+  builder.push_hard_constraint(gap.prop_eq(Measure::named_const(&ctx, "gutter")));
+  builder.push_widget(a);
+  builder.push_widget(b);
+
+  let err = builder.check_with(&mut session).unwrap_err();
+  assert!(matches!(err, LayoutError::Undefined { .. }));
+
+  ctx.define("gutter", 8.0);
+  let report = builder.check_with(&mut session).unwrap();
+  assert_eq!(report.solution.value_of(gap), Some(8.0));
+
+  ctx.define("gutter", 12.0);
+  let report = builder.check_with(&mut session).unwrap();
+  assert_eq!(report.solution.value_of(gap), Some(12.0));
+}
+
+#[test]
+fn test_deterministic_build() {
+  let solve = || {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+      deterministic: true,
+      ..SolverConfig::default()
+    });
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    let (left, width) = (rect.left, rect.width);
+    builder.push_hard_constraint(width.prop_ge(Measure::new_const(&ctx, 3.0).unwrap()));
+    builder.push_widget(rect);
+    let solution = builder.solve().unwrap();
+    (solution.value_of(left), solution.value_of(width))
+  };
+  assert_eq!(solve(), (Some(0.0), Some(3.0)));
+  assert_eq!(solve(), solve());
+}
+
+#[cfg(feature = "persist")]
+#[test]
+fn test_save_and_load() {
+  use crate::layout::{persist::PersistError, solution::Solution};
+
+  fn layout(ctx: &LayoutContext, min_width: f64) -> (LayoutBuilder<'_>, Measure<'_>) {
+    let mut builder = LayoutBuilder::new(ctx);
+    let rect = Rectangle::unbound(ctx, Box::new(|_| Ok(())));
+    let width = rect.width;
+    builder.push_widget(rect);
+    builder.push_hard_constraint(width.prop_eq(Measure::named_const(ctx, "card.width")));
+    builder.push_hard_constraint(width.prop_ge(Measure::new_const(ctx, min_width).unwrap()));
+    ctx.define("card.width", 120.0);
+    (builder, width)
+  }
+  let path = std::env::temp_dir().join(format!("liquid-layout-{}.json", std::process::id()));
+
+  let ctx = LayoutContext::new();
+  let (builder, _) = layout(&ctx, 100.0);
+  builder.solve().unwrap().save(&path, &builder).unwrap();
+
+  let ctx = LayoutContext::new();
+  let (mut builder, width) = layout(&ctx, 100.0);
+  let solution = Solution::load(&path, &builder).unwrap();
+  assert_eq!(solution.value_of(width), Some(120.0));
+  assert_eq!(
+    solution.value_of(Measure::named_const(&ctx, "card.width")),
+    Some(120.0)
+  );
+
+  // Changes that keep the number of widgets and constraints are told apart too.
+  ctx.define("card.width", 110.0);
+  let err = Solution::load(&path, &builder).unwrap_err();
+  assert!(matches!(err, PersistError::Mismatch));
+  let other = LayoutContext::new();
+  let (narrower, _) = layout(&other, 90.0);
+  let err = Solution::load(&path, &narrower).unwrap_err();
+  assert!(matches!(err, PersistError::Mismatch));
+
+  ctx.define("card.width", 120.0);
+  assert!(Solution::load(&path, &builder).is_ok());
+  builder.push_widget(Rectangle::unbound(&ctx, Box::new(|_| Ok(()))));
+  let err = Solution::load(&path, &builder).unwrap_err();
+  assert!(matches!(err, PersistError::Mismatch));
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "tokens")]
+#[test]
+fn test_design_tokens() {
+  use crate::tokens::Tokens;
+
+  let tokens = Tokens::from_json(
+    r##"{
+      "spacing": { "sm": 4, "md": { "$value": "8px", "$type": "dimension" } },
+      "color": { "primary": { "$value": "#0af", "$type": "color" } }
+    }"##,
+  )
+  .unwrap();
+  assert_eq!(
+    tokens.names().collect::<Vec<_>>(),
+    ["spacing.md", "spacing.sm"]
+  );
+
+  let ctx = LayoutContext::new();
+  tokens.define_all(&ctx);
+  assert_eq!(ctx.definition("spacing.md"), Some(8.0));
+  assert!(tokens.measure(&ctx, "spacing.lg").is_none());
+}
+
+#[test]
+fn test_unconstrained_measures() {
+  struct Probe<'a>(Measure<'a>, Rc<RefCell<Option<f64>>>);
+
+  impl<'a> RawWidget<'a> for Probe<'a> {
+    fn measures(&self) -> Vec<Measure<'a>> {
+      vec![self.0]
+    }
+
+    fn constraints(&self) -> Vec<Prop<'a>> {
+      vec![]
+    }
+
+    fn paint(&mut self, measures: &[f64], _: &mut dyn RenderTarget) -> Result<(), PaintError> {
+      *self.1.borrow_mut() = Some(measures[0]);
+      Ok(())
+    }
+  }
+
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    zero_unconstrained: true,
+    ..SolverConfig::default()
+  });
+  let value = Rc::new(RefCell::new(None));
+  let free = Measure::new_unbound(&ctx);
+  builder.push_widget(Probe(free, value.clone()));
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.unconstrained_measures.len(), 1);
+  assert_eq!(*value.borrow(), Some(0.0));
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_sample_layouts() {
+  let mut session = SolverSession::new();
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let left = rect.left;
+  builder.push_widget(rect);
+
+  let solutions = crate::generate::sample(
+    &mut builder,
+    &mut session,
+    0.0..100.0,
+    4,
+    &mut rand::thread_rng(),
+  )
+  .unwrap();
+  let lefts = solutions
+    .iter()
+    .map(|x| x.value_of(left).unwrap())
+    .collect::<Vec<_>>();
+  assert!(lefts.iter().all(|&x| (0.0..=100.0).contains(&x)));
+  assert!(lefts.iter().any(|&x| x != lefts[0]));
+}
+
+#[test]
+fn test_constant_contradictions() {
+  let ctx = LayoutContext::new();
+  let five = Measure::new_const(&ctx, 5.0).unwrap();
+  let three = Measure::new_const(&ctx, 3.0).unwrap();
+
+  let mut builder = LayoutBuilder::new(&ctx);
+  builder.push_constraint(five.prop_lt(three));
+  let report = builder.build().unwrap();
+  assert_eq!(report.constant_contradictions.len(), 1);
+
+  let mut builder = LayoutBuilder::new(&ctx);
+  builder.push_hard_constraint(five.prop_lt(three));
+  match builder.build().unwrap_err() {
+    LayoutError::Unsat { core } => assert_eq!(core, ["5 < 3"]),
+    err => panic!("unexpected error: {}", err),
+  }
+}
+
+#[test]
+fn test_relax_on_unsat() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    relax_on_unsat: true,
+    ..SolverConfig::default()
+  });
+
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let left = rect.left;
+  builder.push_widget(rect);
+  let weak = left
+    .prop_eq(Measure::new_const(&ctx, 1.0).unwrap())
+    .with_weight(1);
+  builder.push_hard_constraint(weak);
+  builder.push_hard_constraint(
+    left
+      .prop_eq(Measure::new_const(&ctx, 2.0).unwrap())
+      .with_weight(5),
+  );
+  // Left out of the layout, so not relaxed either, although its tier comes first.
+  let mut group = builder.group("compact");
+  group.push_hard_constraint(
+    left
+      .prop_eq(Measure::new_const(&ctx, 3.0).unwrap())
+      .with_weight(0),
+  );
+  group.disable();
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.dropped_constraints.len(), 1);
+  assert_eq!(report.dropped_constraints[0].addr(), weak.addr());
+  assert_eq!(report.solution.value_of(left), Some(2.0));
+
+  // A hard constraint that is constant and false is relaxed too, instead of failing the build.
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    relax_on_unsat: true,
+    ..SolverConfig::default()
+  });
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let left = rect.left;
+  builder.push_widget(rect);
+  builder.push_hard_constraint(left.prop_eq(Measure::new_const(&ctx, 2.0).unwrap()));
+  let mistake = Measure::new_const(&ctx, 5.0)
+    .unwrap()
+    .prop_lt(Measure::new_const(&ctx, 3.0).unwrap())
+    .with_weight(1);
+  builder.push_hard_constraint(mistake);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.dropped_constraints.len(), 1);
+  assert_eq!(report.dropped_constraints[0].addr(), mistake.addr());
+  assert_eq!(report.constant_contradictions.len(), 1);
+  assert_eq!(report.solution.value_of(left), Some(2.0));
+}
+
+#[test]
+fn test_relax_widget_constraints() {
+  // The row keeps its fixed item at 20 wide, which the item itself contradicts. Relaxing the
+  // constraint pushed to the builder does not help, so the widgets give in, and the constraint
+  // holds again.
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    relax_on_unsat: true,
+    ..SolverConfig::default()
+  });
+
+  let item = Rectangle::with_width_and_height(&ctx, 30.0, 10.0, Box::new(|_| Ok(())));
+  let mut row = Row::new(&ctx, Box::new(|_| Ok(())));
+  let left = row.frame.left;
+  builder.push_hard_constraint(
+    left
+      .prop_eq(Measure::new_const(&ctx, 0.0).unwrap())
+      .with_weight(1),
+  );
+  row.push(item, Flex::fixed(20.0));
+  builder.push_widget(row);
+
+  let report = builder.build().unwrap();
+  assert!(!report.dropped_constraints.is_empty());
+  assert!(report
+    .dropped_constraints
+    .iter()
+    .all(|&x| report.solution.truth_of(x) == Some(false)));
+  assert_eq!(report.solution.value_of(left), Some(0.0));
+}
+
+#[test]
+fn test_elimination_path() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::with_width_and_height(&ctx, 5.0, 10.0, Box::new(|_| Ok(())));
+  let right = rect.right;
+  builder.push_hard_constraint(rect.left.prop_eq(Measure::new_const(&ctx, 2.0).unwrap()));
+  builder.push_hard_constraint(rect.top.prop_eq(Measure::new_const(&ctx, 3.0).unwrap()));
+  builder.push_widget(rect);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Elimination);
+  assert_eq!(report.solution.value_of(right), Some(7.0));
+  assert!(report.unsatisfied_constraints.is_empty());
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_bounds_of() {
+  use crate::layout::solution::{Bound, Solution};
+
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let width = Measure::new_unbound(&ctx);
+  let height = Measure::new_unbound(&ctx);
+  let left = Measure::new_unbound(&ctx);
+  builder.push_hard_constraint(width.prop_ge(Measure::new_const(&ctx, 40.0).unwrap()));
+  builder.push_hard_constraint(width.prop_le(Measure::new_const(&ctx, 312.0).unwrap()));
+  builder.push_hard_constraint(height.prop_gt(Measure::zero(&ctx)));
+  builder.push_hard_constraint(height.prop_lt(width));
+  builder.push_hard_constraint(left.prop_ge(Measure::zero(&ctx)));
+  builder.push_constraint(width.prop_eq(Measure::new_const(&ctx, 100.0).unwrap()));
+
+  let closed = |value| Bound {
+    value,
+    closed: true,
+  };
+  let open = |value| Bound {
+    value,
+    closed: false,
+  };
+  let mut session = SolverSession::new();
+  let mut solution = Solution::default();
+  assert_eq!(
+    solution.bounds_of(&builder, &mut session, width).unwrap(),
+    (closed(40.0), closed(312.0))
+  );
+  assert_eq!(
+    solution.bounds_of(&builder, &mut session, height).unwrap(),
+    (open(0.0), open(312.0))
+  );
+  assert_eq!(
+    solution.bounds_of(&builder, &mut session, left).unwrap(),
+    (closed(0.0), open(f64::INFINITY))
+  );
+
+  solution.pin(width, 100.0);
+  assert_eq!(
+    solution.bounds_of(&builder, &mut session, height).unwrap(),
+    (open(0.0), open(100.0))
+  );
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solve_n() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let width = Measure::new_unbound(&ctx);
+  builder.push_hard_constraint(width.prop_ge(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder.push_hard_constraint(width.prop_le(Measure::new_const(&ctx, 30.0).unwrap()));
+
+  let mut session = SolverSession::new();
+  let diversity = Diversity {
+    measures: vec![width],
+    distance: Distance::Chebyshev,
+    min_distance: 10.0,
+  };
+  let solutions = builder.solve_n(&mut session, 5, &diversity).unwrap();
+  assert!((2..=3).contains(&solutions.len()));
+  for (i, a) in solutions.iter().enumerate() {
+    for b in &solutions[i + 1..] {
+      let (a, b) = (a.value_of(width).unwrap(), b.value_of(width).unwrap());
+      assert!((a - b).abs() >= 10.0 - 1e-9);
+    }
+  }
+  assert!(builder.check_with(&mut session).is_ok());
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solver_logic() {
+  let ctx = LayoutContext::new();
+  for logic in [None, Some(SolverLogic::Nra)].iter().copied() {
+    let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+      logic,
+      ..SolverConfig::default()
+    });
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    builder.push_hard_constraint(
+      (rect.width * rect.height)
+        .prop_eq(Measure::new_const(&ctx, 50.0).unwrap())
+        .with_label("area"),
+    );
+    builder.push_hard_constraint(
+      rect
+        .width
+        .prop_eq(Measure::new_const(&ctx, 5.0).unwrap())
+        .with_label("width"),
+    );
+    builder.push_hard_constraint(
+      rect
+        .height
+        .prop_eq(Measure::new_const(&ctx, 20.0).unwrap())
+        .with_label("height"),
+    );
+    builder.push_widget(rect);
+
+    match builder.build().unwrap_err() {
+      LayoutError::Unsat { mut core } => {
+        core.sort();
+        assert_eq!(core, ["area", "height", "width"]);
+      }
+      err => panic!("unexpected error: {}", err),
+    }
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solver_timeout() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    rlimit: Some(1),
+    ..SolverConfig::default()
+  });
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(
+    (rect.width * rect.height).prop_eq(Measure::new_const(&ctx, 50.0).unwrap()),
+  );
+  builder.push_hard_constraint(
+    (rect.width * rect.width).prop_eq(Measure::new_const(&ctx, 2.0).unwrap()),
+  );
+  builder.push_widget(rect);
+
+  match builder.build().unwrap_err() {
+    LayoutError::Timeout => {}
+    err => panic!("unexpected error: {}", err),
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_solver_logic_path() {
+  let ctx = LayoutContext::new();
+  let build = |objective: bool| {
+    let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+      logic: Some(SolverLogic::Lra),
+      ..SolverConfig::default()
+    });
+    let rect = Rectangle::with_width_and_height(&ctx, 5.0, 5.0, Box::new(|_| Ok(())));
+    let left = rect.left;
+    // A disjunction, which the simplex method leaves to Z3.
+    let at_start = left.prop_eq(Measure::new_const(&ctx, 0.0).unwrap());
+    let at_end = left.prop_eq(Measure::new_const(&ctx, 10.0).unwrap());
+    builder.push_hard_constraint(at_start | at_end);
+    builder.push_hard_constraint(left.prop_gt(Measure::new_const(&ctx, 5.0).unwrap()));
+    if objective {
+      builder.push_objective(Objective::Minimize(left));
+    }
+    builder.push_widget(rect);
+    let report = builder.build().unwrap();
+    (report.stats.path, report.solution.value_of(left))
+  };
+
+  assert_eq!(build(false), (SolvePath::Logic, Some(10.0)));
+  assert_eq!(build(true), (SolvePath::Solver, Some(10.0)));
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_decompose() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    decompose: true,
+    ..SolverConfig::default()
+  });
+
+  let mut rights = vec![];
+  for i in 0..3 {
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    builder.push_hard_constraint(
+      rect
+        .left
+        .prop_ge(Measure::new_const(&ctx, i as f64).unwrap()),
+    );
+    builder.push_hard_constraint(
+      rect.width.prop_eq(Measure::new_const(&ctx, 10.0).unwrap())
+        | rect.width.prop_eq(Measure::new_const(&ctx, 20.0).unwrap()),
+    );
+    builder.push_objective(Objective::Minimize(rect.right));
+    rights.push(rect.right);
+    builder.push_widget(rect);
+  }
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Decomposed);
+  for (i, &right) in rights.iter().enumerate() {
+    assert_eq!(report.solution.value_of(right), Some(i as f64 + 10.0));
+  }
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_decompose_unsat() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx).with_solver_config(SolverConfig {
+    decompose: true,
+    ..SolverConfig::default()
+  });
+
+  let a = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let b = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(a.width.prop_eq(Measure::new_const(&ctx, 10.0).unwrap()));
+  builder.push_hard_constraint(b.width.prop_ge(Measure::new_const(&ctx, 30.0).unwrap()));
+  builder.push_hard_constraint(b.width.prop_le(Measure::new_const(&ctx, 20.0).unwrap()));
+  builder.push_widget(a);
+  builder.push_widget(b);
+
+  match builder.build() {
+    Err(LayoutError::Unsat { core }) => assert_eq!(core.len(), 2),
+    Err(err) => panic!("unexpected error: {}", err),
+    Ok(_) => panic!("conflicting widths were solved"),
+  }
+}
+
+#[test]
+fn test_simplex_path() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let a = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let b = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (a_right, b_left, b_width) = (a.right, b.left, b.width);
+  let zero = Measure::zero(&ctx);
+  builder.push_hard_constraint(a.left.prop_eq(zero));
+  builder.push_hard_constraint(a.top.prop_eq(zero));
+  builder.push_hard_constraint(b.top.prop_eq(zero));
+  builder.push_hard_constraint(a.height.prop_eq(b.height));
+  builder.push_hard_constraint(a.height.prop_ge(Measure::new_const(&ctx, 20.0).unwrap()));
+  builder.push_hard_constraint(a.width.prop_ge(Measure::new_const(&ctx, 50.0).unwrap()));
+  builder.push_hard_constraint(b.left.prop_ge(a.right + 8.0));
+  builder.push_hard_constraint(b.right.prop_le(Measure::new_const(&ctx, 300.0).unwrap()));
+  builder.push_constraint(b.width.prop_ge(Measure::new_const(&ctx, 100.0).unwrap()));
+  builder.push_objective(Objective::Minimize(a.height));
+  builder.push_objective(Objective::Maximize(b.width));
+  builder.push_widget(a);
+  builder.push_widget(b);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Simplex);
+  assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(report.solution.value_of(a_right), Some(50.0));
+  assert_eq!(report.solution.value_of(b_left), Some(58.0));
+  assert_eq!(report.solution.value_of(b_width), Some(242.0));
+}
+
+#[test]
+#[cfg(not(feature = "z3"))]
+fn test_native_backend() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let (right, width) = (rect.right, rect.width);
+  builder.push_hard_constraint(rect.left.prop_ge(Measure::new_const(&ctx, 5.0).unwrap()));
+  builder.push_hard_constraint(
+    rect.width.prop_eq(Measure::new_const(&ctx, 10.0).unwrap())
+      | rect.width.prop_eq(Measure::new_const(&ctx, 20.0).unwrap()),
+  );
+  builder.push_constraint(rect.width.prop_gt(Measure::new_const(&ctx, 15.0).unwrap()));
+  builder.push_objective(Objective::Minimize(rect.right));
+  builder.push_widget(rect);
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Native);
+  assert!(report.unsatisfied_constraints.is_empty());
+  assert_eq!(report.solution.value_of(width), Some(20.0));
+  assert_eq!(report.solution.value_of(right), Some(25.0));
+}
+
+#[test]
+#[cfg(not(feature = "z3"))]
+fn test_native_soft_conflicts() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let mut widths = vec![];
+  for _ in 0..16 {
+    let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+    widths.push(rect.width);
+    builder.push_constraint(
+      rect
+        .width
+        .prop_eq(Measure::new_const(&ctx, 10.0).unwrap())
+        .with_weight(2),
+    );
+    builder.push_constraint(
+      rect
+        .width
+        .prop_eq(Measure::new_const(&ctx, 20.0).unwrap())
+        .with_weight(1),
+    );
+    builder.push_widget(rect);
+  }
+
+  let report = builder.build().unwrap();
+  assert_eq!(report.stats.path, SolvePath::Native);
+  assert_eq!(report.unsatisfied_constraints.len(), 16);
+  for width in widths {
+    assert_eq!(report.solution.value_of(width), Some(10.0));
+  }
+}
+
+#[test]
+#[cfg(not(feature = "z3"))]
+fn test_native_unsupported() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  builder.push_hard_constraint(
+    (rect.width * rect.height).prop_eq(Measure::new_const(&ctx, 200.0).unwrap()),
+  );
+  builder.push_widget(rect);
+
+  match builder.build().unwrap_err() {
+    LayoutError::Unsupported { .. } => {}
+    err => panic!("unexpected error: {}", err),
+  }
+}
+
+#[test]
+fn test_nonlinearities() {
+  let ctx = LayoutContext::new();
+  let mut builder = LayoutBuilder::new(&ctx);
+
+  let rect = Rectangle::unbound(&ctx, Box::new(|_| Ok(())));
+  let area = rect.width * rect.height;
+  let wide = Prop::new_unbound(&ctx);
+  let scale = wide.select(
+    Measure::new_const(&ctx, 2.0).unwrap(),
+    Measure::new_const(&ctx, 1.0).unwrap(),
+  );
+  builder.push_hard_constraint(
+    area
+      .prop_eq(Measure::new_const(&ctx, 200.0).unwrap())
+      .with_label("area"),
+  );
+  builder.push_hard_constraint(area.prop_le(Measure::new_const(&ctx, 400.0).unwrap()));
+  builder.push_hard_constraint(
+    (rect.width * scale)
+      .prop_le(Measure::new_const(&ctx, 40.0).unwrap())
+      .with_label("scaled width"),
+  );
+  builder.push_hard_constraint(
+    (rect.width * Measure::new_const(&ctx, 2.0).unwrap())
+      .prop_ge(Measure::new_const(&ctx, 10.0).unwrap()),
+  );
+  builder.push_widget(rect);
+
+  let found = builder.nonlinearities();
+  assert_eq!(found.len(), 2);
+  assert_eq!(found[0].users.len(), 2);
+  assert_eq!(found[0].users[0], "area");
+  assert!(found[0]
+    .suggestion
+    .as_ref()
+    .unwrap()
+    .contains("named constant"));
+  assert_eq!(found[1].users, vec!["scaled width"]);
+  assert!(found[1].suggestion.as_ref().unwrap().contains("select"));
+}
+
+/// Lays out a trail of five crumbs 20 wide, with separators of 5 and an ellipsis 10 wide, in a
+/// container `width` wide. Returns the metrics of the trail, the left of each painted crumb and
+/// the left of the ellipsis if it was painted.
+fn breadcrumb_trail(width: f64) -> (BreadcrumbsMetrics, Vec<(usize, f64)>, Option<f64>) {
+  let ctx = LayoutContext::new();
+  let c = |x| Measure::new_const(&ctx, x).unwrap();
+  let mut builder = LayoutBuilder::new(&ctx);
+  let trail = Rc::new(RefCell::new(None));
+  let painted = Rc::new(RefCell::new(vec![]));
+  let ellipsis = Rc::new(RefCell::new(None));
+
+  let ellipsis_out = ellipsis.clone();
+  let trail_out = trail.clone();
+  let mut breadcrumbs = Breadcrumbs::new(
+    &ctx,
+    Rectangle::with_width_and_height(
+      &ctx,
+      10.0,
+      10.0,
+      Box::new(move |metrics: RectangleMetrics| {
+        *ellipsis_out.borrow_mut() = Some(metrics.left);
+        Ok(())
+      }),
+    ),
+    5.0,
+    Box::new(move |metrics: BreadcrumbsMetrics| {
+      *trail_out.borrow_mut() = Some(metrics);
+      Ok(())
+    }),
+  );
+  for i in 0..5 {
+    let painted_out = painted.clone();
+    breadcrumbs.push_crumb(Rectangle::with_width_and_height(
+      &ctx,
+      20.0,
+      10.0,
+      Box::new(move |metrics: RectangleMetrics| {
+        painted_out.borrow_mut().push((i, metrics.left));
+        Ok(())
+      }),
+    ));
+  }
+  builder.push_hard_constraint(breadcrumbs.left.prop_eq(c(0.0)));
+  builder.push_hard_constraint(breadcrumbs.top.prop_eq(c(0.0)));
+  builder.push_hard_constraint(breadcrumbs.width.prop_eq(c(width)));
+  builder.push_hard_constraint(breadcrumbs.height.prop_eq(c(10.0)));
+  builder.push_widget(breadcrumbs);
+  builder.build().unwrap();
+
+  let trail = trail.borrow_mut().take().unwrap();
+  let painted = painted.borrow().clone();
+  let ellipsis = *ellipsis.borrow();
+  (trail, painted, ellipsis)
+}
+
+#[test]
+fn test_breadcrumbs_wide() {
+  let (trail, painted, ellipsis) = breadcrumb_trail(120.0);
+  assert_eq!(trail.visible, vec![0, 1, 2, 3, 4]);
+  assert!(trail.collapsed.is_empty());
+  assert_eq!(
+    painted,
+    vec![(0, 0.0), (1, 25.0), (2, 50.0), (3, 75.0), (4, 100.0)]
+  );
+  assert_eq!(ellipsis, None);
+}
+
+#[test]
+fn test_breadcrumbs_narrow() {
+  // Without the ellipsis, two middle crumbs would fit in 100.
+  let (trail, painted, ellipsis) = breadcrumb_trail(100.0);
+  assert_eq!(trail.visible, vec![0, 3, 4]);
+  assert_eq!(trail.collapsed, vec![1, 2]);
+  assert_eq!(painted, vec![(0, 0.0), (3, 40.0), (4, 65.0)]);
+  assert_eq!(ellipsis, Some(25.0));
+  assert_eq!(trail.frame.width, 100.0);
+}
+
+#[test]
+fn test_imposition_booklet() {
+  use crate::imposition::{Imposition, PageOrder, SheetSide, SheetSpec};
+
+  let sheet = SheetSpec {
+    width: 220.0,
+    height: 150.0,
+    columns: 2,
+    rows: 1,
+    gutter: 0.0,
+    crop_margin: 10.0,
+  };
+  let mut imposition = Imposition::new(sheet, PageOrder::Booklet);
+  for _ in 0..8 {
+    imposition.push_page(100.0, 130.0);
+  }
+  let sheets = imposition.solve().unwrap();
+  let order = sheets
+    .iter()
+    .map(|x| (x.sheet, x.side, x.pages.iter().map(|p| p.page).collect()))
+    .collect::<Vec<(usize, SheetSide, Vec<usize>)>>();
+  assert_eq!(
+    order,
+    vec![
+      (0, SheetSide::Front, vec![7, 0]),
+      (0, SheetSide::Back, vec![1, 6]),
+      (1, SheetSide::Front, vec![5, 2]),
+      (1, SheetSide::Back, vec![3, 4]),
+    ]
+  );
+  // Facing pages meet at the spine.
+  let front = &sheets[0].pages;
+  assert_eq!((front[0].rect.left, front[0].rect.right), (10.0, 110.0));
+  assert_eq!((front[1].rect.left, front[1].rect.right), (110.0, 210.0));
+}
+
+#[test]
+fn test_imposition_sequential() {
+  use crate::imposition::{Imposition, ImpositionError, PageOrder, SheetSpec};
+
+  let sheet = SheetSpec {
+    width: 100.0,
+    height: 60.0,
+    columns: 2,
+    rows: 1,
+    gutter: 10.0,
+    crop_margin: 5.0,
+  };
+  let mut imposition = Imposition::new(sheet, PageOrder::Sequential);
+  for _ in 0..3 {
+    imposition.push_page(40.0, 50.0);
+  }
+  let sheets = imposition.solve().unwrap();
+  let lefts = sheets
+    .iter()
+    .map(|x| {
+      x.pages
+        .iter()
+        .map(|p| (p.page, p.rect.left, p.rect.top))
+        .collect()
+    })
+    .collect::<Vec<Vec<_>>>();
+  assert_eq!(
+    lefts,
+    vec![vec![(0, 5.0, 5.0), (1, 55.0, 5.0)], vec![(2, 5.0, 5.0)]]
+  );
+
+  let empty = Imposition::new(
+    SheetSpec {
+      columns: 0,
+      ..sheet
+    },
+    PageOrder::Sequential,
+  );
+  assert!(matches!(empty.solve(), Err(ImpositionError::EmptyGrid)));
+}
+
+#[test]
+fn test_editor_snap() {
+  use crate::editor::{snap, GuideAnchor, GuideAxis};
+
+  let rect = |left: f64, top: f64, width: f64, height: f64| RectangleMetrics {
+    left,
+    right: left + width,
+    top,
+    bottom: top + height,
+    width,
+    height,
+  };
+  let targets = [rect(100.0, 100.0, 100.0, 50.0)];
+
+  // The left edge is 3 from the right edge of the target, and the center is 2 above its center.
+  let snapped = snap(rect(203.0, 113.0, 40.0, 20.0), &targets, 5.0).unwrap();
+  assert_eq!((snapped.rect.left, snapped.rect.top), (200.0, 115.0));
+  assert_eq!((snapped.rect.width, snapped.rect.height), (40.0, 20.0));
+  let guides = snapped
+    .guides
+    .iter()
+    .map(|x| {
+      (
+        x.axis,
+        x.position,
+        x.target,
+        x.dragged_anchor,
+        x.target_anchor,
+      )
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(
+    guides,
+    vec![
+      (
+        GuideAxis::Vertical,
+        200.0,
+        0,
+        GuideAnchor::Start,
+        GuideAnchor::End
+      ),
+      (
+        GuideAxis::Horizontal,
+        125.0,
+        0,
+        GuideAnchor::Center,
+        GuideAnchor::Center
+      ),
+    ]
+  );
+
+  let unsnapped = snap(rect(300.0, 300.0, 40.0, 20.0), &targets, 5.0).unwrap();
+  assert_eq!((unsnapped.rect.left, unsnapped.rect.top), (300.0, 300.0));
+  assert!(unsnapped.guides.is_empty());
+}
+
+#[test]
+fn test_angle_constant() {
+  use crate::layout::angle::Angle;
+
+  let ctx = LayoutContext::new();
+  let close = |m: Measure, expected: f64| (m.const_value().unwrap() - expected).abs() < 1e-6;
+  assert!(close(Angle::degrees(&ctx, 30.0).sin(), 0.5));
+  assert!(close(Angle::degrees(&ctx, 60.0).cos(), 0.5));
+  assert!(close(
+    Angle::radians(&ctx, -std::f64::consts::PI).cos(),
+    -1.0
+  ));
+
+  // Constant degrees are converted up front, so trigonometry stays exact.
+  let angle = Angle::from_degrees(Measure::new_const(&ctx, 90.0).unwrap());
+  assert!(close(angle.radians, std::f64::consts::FRAC_PI_2));
+  assert!(close(angle.sin(), 1.0));
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_angle_piecewise() {
+  use crate::layout::angle::Angle;
+
+  // Chords over 32 pieces stray from the curve by at most (2π / 32)² / 8.
+  for &x in &[1.0, -1.0, 7.0] {
+    let ctx = LayoutContext::new();
+    let mut builder = LayoutBuilder::new(&ctx);
+    let radians = Measure::new_unbound(&ctx);
+    let (sin, cos) = (Measure::new_unbound(&ctx), Measure::new_unbound(&ctx));
+    let angle = Angle::from_radians(radians);
+    builder.push_hard_constraint(radians.prop_eq(Measure::new_const(&ctx, x).unwrap()));
+    builder.push_hard_constraint(sin.prop_eq(angle.sin()));
+    builder.push_hard_constraint(cos.prop_eq(angle.cos()));
+    let solution = builder.solve().unwrap();
+    assert!((solution.value_of(sin).unwrap() - f64::sin(x)).abs() < 0.005);
+    assert!((solution.value_of(cos).unwrap() - f64::cos(x)).abs() < 0.005);
+  }
+}
+
+#[test]
+fn test_point_on_circle() {
+  use crate::layout::angle::Angle;
+
+  let ctx = LayoutContext::new();
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let center = Point {
+    x: at(100.0),
+    y: at(50.0),
+  };
+  let points = Angle::evenly_spaced(&ctx, 4, 90.0)
+    .into_iter()
+    .map(|angle| {
+      let p = Point::on_circle(center, at(10.0), angle);
+      (p.x.const_value().unwrap(), p.y.const_value().unwrap())
+    })
+    .collect::<Vec<_>>();
+  let expected = [(100.0, 60.0), (90.0, 50.0), (100.0, 40.0), (110.0, 50.0)];
+  for (p, e) in points.iter().zip(&expected) {
+    assert!(
+      (p.0 - e.0).abs() < 1e-4 && (p.1 - e.1).abs() < 1e-4,
+      "{:?}",
+      p
+    );
+  }
+}
+
+#[test]
+fn test_rect_measures() {
+  use crate::layout::{geometry::RectMeasures, solution::Solution};
+
+  let ctx = LayoutContext::new();
+  let at = |x: f64| Measure::new_const(&ctx, x).unwrap();
+  let rect = |left, top, right, bottom| RectMeasures {
+    left: at(left),
+    top: at(top),
+    right: at(right),
+    bottom: at(bottom),
+  };
+  let unbound = || RectMeasures {
+    left: Measure::new_unbound(&ctx),
+    top: Measure::new_unbound(&ctx),
+    right: Measure::new_unbound(&ctx),
+    bottom: Measure::new_unbound(&ctx),
+  };
+  let edges = |solution: &Solution, r: &RectMeasures| {
+    [r.left, r.top, r.right, r.bottom].map(|x| solution.value_of(x).unwrap())
+  };
+
+  // Touching edges do not intersect.
+  assert_eq!(
+    rect(0.0, 0.0, 10.0, 10.0)
+      .intersects(&rect(10.0, 0.0, 20.0, 10.0))
+      .const_value(),
+    Some(false)
+  );
+  assert_eq!(
+    rect(0.0, 0.0, 10.0, 10.0)
+      .intersects(&rect(9.0, 9.0, 20.0, 20.0))
+      .const_value(),
+    Some(true)
+  );
+
+  // Pushed as far to the bottom right as the deflated frame allows.
+  let mut builder = LayoutBuilder::new(&ctx);
+  let inner = unbound();
+  builder.push_hard_constraint(inner.width().prop_eq(at(20.0)));
+  builder.push_hard_constraint(inner.height().prop_eq(at(10.0)));
+  builder.push_hard_constraint(
+    rect(0.0, 0.0, 100.0, 50.0)
+      .deflate(at(5.0))
+      .contains(&inner),
+  );
+  builder.push_objective(Objective::Maximize(inner.left + inner.top));
+  let solution = builder.solve().unwrap();
+  assert_eq!(edges(&solution, &inner), [75.0, 35.0, 95.0, 45.0]);
+
+  // Slid left until it touches the obstacle, which disjointness allows.
+  let mut builder = LayoutBuilder::new(&ctx);
+  let sliding = unbound();
+  builder.push_hard_constraint(sliding.width().prop_eq(at(10.0)));
+  builder.push_hard_constraint(sliding.top.prop_eq(at(0.0)) & sliding.bottom.prop_eq(at(10.0)));
+  builder.push_hard_constraint(sliding.left.prop_ge(at(0.0)));
+  builder.push_hard_constraint(sliding.disjoint(&rect(0.0, 0.0, 15.0, 10.0).inflate(at(1.0))));
+  builder.push_objective(Objective::Minimize(sliding.left));
+  let solution = builder.solve().unwrap();
+  assert_eq!(solution.value_of(sliding.left), Some(16.0));
+
+  let mut builder = LayoutBuilder::new(&ctx);
+  let (a, b, bounds) = (unbound(), unbound(), unbound());
+  for (r, (left, top, right, bottom)) in [(a, (0.0, 0.0, 10.0, 10.0)), (b, (5.0, -5.0, 30.0, 8.0))]
+  {
+    builder.push_hard_constraint(r.left.prop_eq(at(left)) & r.top.prop_eq(at(top)));
+    builder.push_hard_constraint(r.right.prop_eq(at(right)) & r.bottom.prop_eq(at(bottom)));
+  }
+  let union = a.union(&b);
+  builder.push_hard_constraint(
+    bounds.left.prop_eq(union.left)
+      & bounds.top.prop_eq(union.top)
+      & bounds.right.prop_eq(union.right)
+      & bounds.bottom.prop_eq(union.bottom),
+  );
+  let solution = builder.solve().unwrap();
+  assert_eq!(edges(&solution, &bounds), [0.0, -5.0, 30.0, 10.0]);
+}
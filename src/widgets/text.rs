@@ -0,0 +1,419 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::{LayoutError, PaintError},
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+use super::RectangleMetrics;
+
+pub type TextPainter<'a> = Box<dyn Painter<TextMetrics> + 'a>;
+
+/// Slack for a line to fit the solved width, which may undershoot a threshold by rounding.
+const FIT_TOLERANCE: f64 = 1e-6;
+
+/// The metrics of a font that text is measured with, at a font size in canonical units.
+///
+/// Implemented for `fontdue::Font` with the `fonts` feature.
+pub trait Typeface {
+  /// How far the pen moves after `c`.
+  fn advance(&self, c: char, size: f64) -> f64;
+
+  /// Adjustment of the advance between `left` and `right`, usually negative.
+  fn kerning(&self, _left: char, _right: char, _size: f64) -> f64 {
+    0.0
+  }
+
+  /// Height above the baseline.
+  fn ascent(&self, size: f64) -> f64;
+
+  /// Depth below the baseline, positive.
+  fn descent(&self, size: f64) -> f64;
+
+  fn line_gap(&self, _size: f64) -> f64 {
+    0.0
+  }
+
+  /// Height of capital letters above the baseline.
+  fn cap_height(&self, size: f64) -> f64;
+
+  /// Width of `text` set on one line.
+  fn width(&self, text: &str, size: f64) -> f64 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+      if let Some(previous) = previous {
+        width += self.kerning(previous, c, size);
+      }
+      width += self.advance(c, size);
+      previous = Some(c);
+    }
+    width
+  }
+}
+
+#[cfg(feature = "fonts")]
+impl Typeface for fontdue::Font {
+  fn advance(&self, c: char, size: f64) -> f64 {
+    self.metrics(c, size as f32).advance_width as f64
+  }
+
+  fn kerning(&self, left: char, right: char, size: f64) -> f64 {
+    self
+      .horizontal_kern(left, right, size as f32)
+      .unwrap_or(0.0) as f64
+  }
+
+  fn ascent(&self, size: f64) -> f64 {
+    self
+      .horizontal_line_metrics(size as f32)
+      .map(|x| x.ascent as f64)
+      .unwrap_or(size)
+  }
+
+  fn descent(&self, size: f64) -> f64 {
+    self
+      .horizontal_line_metrics(size as f32)
+      .map(|x| -x.descent as f64)
+      .unwrap_or(0.0)
+  }
+
+  fn line_gap(&self, size: f64) -> f64 {
+    self
+      .horizontal_line_metrics(size as f32)
+      .map(|x| x.line_gap as f64)
+      .unwrap_or(0.0)
+  }
+
+  /// The top of the outline of `H`.
+  fn cap_height(&self, size: f64) -> f64 {
+    let bounds = self.metrics('H', size as f32).bounds;
+    (bounds.ymin + bounds.height) as f64
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextMetrics {
+  pub frame: RectangleMetrics,
+  pub baseline: f64,
+  pub text: String,
+  pub size: f64,
+
+  /// The text as set, one entry per line.
+  pub lines: Vec<String>,
+}
+
+impl TextMetrics {
+  /// The text moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    TextMetrics {
+      frame: self.frame.transformed(transform),
+      baseline: transform.y(self.baseline),
+      ..self
+    }
+  }
+}
+
+/// The words of a wrapping `Text`, measured once so lines can be set at any width.
+struct Wrap {
+  words: Vec<(String, f64)>,
+  space: f64,
+}
+
+impl Wrap {
+  fn new(typeface: &dyn Typeface, text: &str, size: f64) -> Self {
+    Self {
+      words: text
+        .split_whitespace()
+        .map(|word| (word.to_string(), typeface.width(word, size)))
+        .collect(),
+      space: typeface.advance(' ', size),
+    }
+  }
+
+  /// Ranges of words on each line when set greedily in `width`. Words wider than `width` get a
+  /// line of their own.
+  fn lines(&self, width: f64) -> Vec<(usize, usize)> {
+    let mut lines = vec![];
+    let (mut start, mut line_width) = (0, 0.0);
+    for (i, (_, word)) in self.words.iter().enumerate() {
+      if i == start {
+        line_width = *word;
+      } else if line_width + self.space + word <= width + FIT_TOLERANCE {
+        line_width += self.space + word;
+      } else {
+        lines.push((start, i));
+        start = i;
+        line_width = *word;
+      }
+    }
+    if start < self.words.len() {
+      lines.push((start, self.words.len()));
+    }
+    lines
+  }
+
+  /// The narrowest width for each line count, widest first. The last entry is the width of
+  /// the longest word, below which words would overflow.
+  ///
+  /// Lines only get fewer as the width grows, so the width where each count starts is found by
+  /// bisection from the start of the count before. The text is set the same at the bisected
+  /// width as at its widest line, which is where the count starts, up to `FIT_TOLERANCE`.
+  fn thresholds(&self) -> Vec<(f64, usize)> {
+    let total = self.width_of((0, self.words.len()));
+    let mut thresholds = vec![];
+    let mut width = self.longest();
+    while !self.words.is_empty() {
+      let lines = self.lines(width);
+      let widest = lines.iter().map(|&x| self.width_of(x)).fold(0.0, f64::max);
+      thresholds.push((widest, lines.len()));
+      if lines.len() == 1 {
+        break;
+      }
+      let (mut fits, mut fewer) = (width, total);
+      while fewer - fits > FIT_TOLERANCE {
+        let mid = (fits + fewer) / 2.0;
+        if self.lines(mid).len() < lines.len() {
+          fewer = mid;
+        } else {
+          fits = mid;
+        }
+      }
+      width = fewer;
+    }
+    thresholds.reverse();
+    thresholds
+  }
+
+  /// Width of the words of `range` set on one line.
+  fn width_of(&self, (start, end): (usize, usize)) -> f64 {
+    let words = &self.words[start..end];
+    let spaces = words.len().saturating_sub(1) as f64 * self.space;
+    words.iter().map(|x| x.1).sum::<f64>() + spaces
+  }
+
+  fn longest(&self) -> f64 {
+    self.words.iter().map(|x| x.1).fold(0.0, f64::max)
+  }
+
+  fn text_of(&self, (start, end): (usize, usize)) -> String {
+    let words: Vec<&str> = self.words[start..end]
+      .iter()
+      .map(|x| x.0.as_str())
+      .collect();
+    words.join(" ")
+  }
+}
+
+/// A constant at least `value`, exact unlike `Measure::new_const`, so that text set at the
+/// solved width never needs more lines than the solver assumed.
+fn ceil_const(ctx: &LayoutContext, value: f64) -> Measure<'_> {
+  Measure::new_ratio(ctx, (value * 100.0).ceil() as i32, 100).unwrap()
+}
+
+/// A line of text, sized by the metrics of its typeface. Its box spans the ascent, the descent
+/// and the line gap, with the baseline and the cap height available for alignment.
+///
+/// Text made with `Text::wrapping` breaks between words instead, and its height follows the
+/// number of lines it takes at the solved width.
+#[derive(ReflectMeasures)]
+pub struct Text<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  /// Distance from `top` to the baseline.
+  pub ascent: Measure<'a>,
+  /// Distance from the top of capital letters to the baseline.
+  pub cap_height: Measure<'a>,
+
+  /// Number of lines at the solved width.
+  pub line_count: Measure<'a>,
+
+  pub text: String,
+  pub size: f64,
+  wrap: Option<Wrap>,
+
+  pub painter: TextPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Text<'a> {
+  /// `text` measured with `typeface` at `size`, at an unbound position.
+  pub fn new(
+    ctx: &'a LayoutContext,
+    typeface: &dyn Typeface,
+    text: impl Into<String>,
+    size: f64,
+    painter: TextPainter<'a>,
+  ) -> Result<Self, LayoutError> {
+    let text = text.into();
+    let ascent = typeface.ascent(size);
+    let height = ascent + typeface.descent(size) + typeface.line_gap(size);
+    Ok(Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_const(ctx, typeface.width(&text, size))?,
+      height: Measure::new_const(ctx, height)?,
+      ascent: Measure::new_const(ctx, ascent)?,
+      cap_height: Measure::new_const(ctx, typeface.cap_height(size))?,
+      line_count: Measure::new_const(ctx, 1.0)?,
+      text,
+      size,
+      wrap: None,
+      painter,
+    })
+  }
+
+  /// `text` broken into lines between words, with a width left to the solver. The height is
+  /// tied to the width through the widths at which the line count changes, so that the text
+  /// fits in a column of any solved width. The width stays at least that of the longest word.
+  pub fn wrapping(
+    ctx: &'a LayoutContext,
+    typeface: &dyn Typeface,
+    text: impl Into<String>,
+    size: f64,
+    painter: TextPainter<'a>,
+  ) -> Result<Self, LayoutError> {
+    let text = text.into();
+    let wrap = Wrap::new(typeface, &text, size);
+    let line_height = typeface.ascent(size) + typeface.descent(size) + typeface.line_gap(size);
+    let width = Measure::new_unbound(ctx);
+
+    let thresholds = wrap.thresholds();
+    let mut line_count = Measure::new_const(ctx, 1.0)?;
+    if let Some(&(_, most)) = thresholds.last() {
+      line_count = Measure::new_const(ctx, most as f64)?;
+      for &(threshold, count) in thresholds.iter().rev().skip(1) {
+        line_count = width
+          .prop_ge(ceil_const(ctx, threshold))
+          .select(Measure::new_const(ctx, count as f64)?, line_count);
+      }
+    }
+
+    Ok(Self {
+      width,
+      height: line_count * Measure::new_const(ctx, line_height)?,
+      line_count,
+      wrap: Some(wrap),
+      ..Self::new(ctx, typeface, text, size, painter)?
+    })
+  }
+
+  pub fn baseline(&self) -> Measure<'a> {
+    self.top + self.ascent
+  }
+
+  /// The top of capital letters, to align with the top of an icon or a box.
+  pub fn cap_top(&self) -> Measure<'a> {
+    self.baseline() - self.cap_height
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Text<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> Widget<'a> for Text<'a> {
+  type Metrics = TextMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+      self.baseline(),
+    ]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let ctx = self.left.ctx;
+    let zero = Measure::zero(ctx);
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right).hard(),
+      (self.top + self.height).prop_eq(self.bottom).hard(),
+      self.top.prop_ge(zero),
+      self.left.prop_ge(zero),
+    ];
+    if let Some(wrap) = &self.wrap {
+      constraints.push(self.width.prop_ge(ceil_const(ctx, wrap.longest())).hard());
+    }
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  /// The anchors of the box, and `baseline-left`, `baseline-right`, `cap-left` and `cap-right`
+  /// at the ends of the baseline and the cap height.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    let (baseline, cap_top) = (self.baseline(), self.cap_top());
+    let point = |x, y| Point { x, y };
+    let mut anchors = self.rect().anchors();
+    anchors.extend(vec![
+      ("baseline-left", point(self.left, baseline)),
+      ("baseline-right", point(self.right, baseline)),
+      ("cap-left", point(self.left, cap_top)),
+      ("cap-right", point(self.right, cap_top)),
+    ]);
+    anchors
+  }
+
+  fn decode(&self, values: &[f64]) -> TextMetrics {
+    TextMetrics {
+      frame: RectangleMetrics {
+        left: values[0],
+        right: values[1],
+        top: values[2],
+        bottom: values[3],
+        width: values[4],
+        height: values[5],
+      },
+      baseline: values[6],
+      text: self.text.clone(),
+      size: self.size,
+      lines: match &self.wrap {
+        Some(wrap) => wrap
+          .lines(values[4])
+          .into_iter()
+          .map(|x| wrap.text_of(x))
+          .collect(),
+        None => vec![self.text.clone()],
+      },
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: TextMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: TextMetrics, transform: Transform) -> TextMetrics {
+    metrics.transformed(transform)
+  }
+}
@@ -0,0 +1,230 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::RectMeasures,
+  measure::Measure,
+  prop::Prop,
+  reflect::ReflectMeasures,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, RawWidget},
+};
+
+use super::{Rectangle, RectangleMetrics};
+
+pub type ToolbarPainter<'a> = Box<dyn Painter<ToolbarMetrics> + 'a>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToolbarMetrics {
+  pub frame: RectangleMetrics,
+
+  /// Indices of the items shown in the toolbar, in insertion order.
+  pub visible: Vec<usize>,
+
+  /// Indices of the items moved into the overflow menu, in insertion order.
+  pub overflowed: Vec<usize>,
+}
+
+pub struct ToolbarItem<'a> {
+  pub rect: Rectangle<'a>,
+  pub priority: u32,
+  pub visible: Prop<'a>,
+}
+
+/// A horizontal row of items that spill into an overflow menu when the toolbar is too narrow.
+///
+/// The solver decides which items stay visible, preferring items with higher priority. The
+/// overflow button is only shown while at least one item overflows.
+#[derive(ReflectMeasures)]
+pub struct Toolbar<'a> {
+  pub left: Measure<'a>,
+  pub right: Measure<'a>,
+  pub top: Measure<'a>,
+  pub bottom: Measure<'a>,
+  pub width: Measure<'a>,
+  pub height: Measure<'a>,
+
+  pub gap: f64,
+  #[reflect(nested)]
+  pub overflow_button: Rectangle<'a>,
+  items: Vec<ToolbarItem<'a>>,
+
+  pub painter: ToolbarPainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Toolbar<'a> {
+  pub fn new(
+    ctx: &'a LayoutContext,
+    overflow_button: Rectangle<'a>,
+    gap: f64,
+    painter: ToolbarPainter<'a>,
+  ) -> Self {
+    Self {
+      left: Measure::new_unbound(ctx),
+      right: Measure::new_unbound(ctx),
+      top: Measure::new_unbound(ctx),
+      bottom: Measure::new_unbound(ctx),
+      width: Measure::new_unbound(ctx),
+      height: Measure::new_unbound(ctx),
+      gap,
+      overflow_button,
+      items: vec![],
+      painter,
+    }
+  }
+
+  /// Appends an item. Its rectangle should have a fixed width.
+  pub fn push_item(&mut self, rect: Rectangle<'a>, priority: u32) -> Prop<'a> {
+    let visible = Prop::new_unbound(rect.left.ctx);
+    self.items.push(ToolbarItem {
+      rect,
+      priority,
+      visible,
+    });
+    visible
+  }
+
+  pub fn items(&self) -> &[ToolbarItem<'a>] {
+    &self.items
+  }
+
+  /// Holds when at least one item is moved into the overflow menu.
+  pub fn overflowing(&self) -> Prop<'a> {
+    match self.items.iter().map(|x| x.visible).reduce(|a, b| a & b) {
+      Some(all_visible) => !all_visible,
+      None => Prop::new_const(self.width.ctx, false),
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Toolbar<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures {
+      left: self.left,
+      right: self.right,
+      top: self.top,
+      bottom: self.bottom,
+    }
+  }
+}
+
+impl<'a> RawWidget<'a> for Toolbar<'a> {
+  fn measures(&self) -> Vec<Measure<'a>> {
+    let ctx = self.left.ctx;
+    let mut measures = vec![
+      self.left,
+      self.right,
+      self.top,
+      self.bottom,
+      self.width,
+      self.height,
+    ];
+    measures.extend(RawWidget::measures(&self.overflow_button));
+    for item in &self.items {
+      measures.push(
+        item
+          .visible
+          .select(Measure::new_const(ctx, 1.0).unwrap(), Measure::zero(ctx)),
+      );
+      measures.extend(RawWidget::measures(&item.rect));
+    }
+    measures
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    let ctx = self.left.ctx;
+    let zero = Measure::zero(ctx);
+    let mut constraints = vec![
+      (self.left + self.width).prop_eq(self.right),
+      (self.top + self.height).prop_eq(self.bottom),
+    ];
+    constraints.extend(self.overflow_button.constraints());
+
+    let mut cursor = self.left;
+    for item in &self.items {
+      constraints.extend(item.rect.constraints());
+      constraints.push(item.rect.left.prop_eq(cursor).hard());
+      constraints.push(item.rect.top.prop_eq(self.top).hard());
+      constraints.push(item.visible.with_weight(item.priority.max(1)));
+      cursor = cursor + item.visible.select(item.rect.width + self.gap, zero);
+    }
+
+    let overflowing = self.overflowing();
+    constraints.push(self.overflow_button.left.prop_eq(cursor).hard());
+    constraints.push(self.overflow_button.top.prop_eq(self.top).hard());
+    constraints.push(
+      overflowing
+        .select(cursor + self.overflow_button.width, cursor - self.gap)
+        .prop_le(self.right)
+        .hard(),
+    );
+    constraints
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  fn paint(&mut self, measures: &[f64], target: &mut dyn RenderTarget) -> Result<(), PaintError> {
+    self.paint_at(measures, Transform::IDENTITY, target)
+  }
+
+  fn paint_at(
+    &mut self,
+    measures: &[f64],
+    transform: Transform,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    let frame = RectangleMetrics {
+      left: measures[0],
+      right: measures[1],
+      top: measures[2],
+      bottom: measures[3],
+      width: measures[4],
+      height: measures[5],
+    }
+    .transformed(transform);
+
+    let button_len = RawWidget::measures(&self.overflow_button).len();
+    let button_measures = &measures[6..6 + button_len];
+    let mut offset = 6 + button_len;
+
+    let mut visible = vec![];
+    let mut overflowed = vec![];
+    for (i, item) in self.items.iter_mut().enumerate() {
+      let len = RawWidget::measures(&item.rect).len();
+      if measures[offset] != 0.0 {
+        visible.push(i);
+        RawWidget::paint_at(
+          &mut item.rect,
+          &measures[offset + 1..offset + 1 + len],
+          transform,
+          target,
+        )?;
+      } else {
+        overflowed.push(i);
+      }
+      offset += 1 + len;
+    }
+
+    if !overflowed.is_empty() {
+      RawWidget::paint_at(
+        &mut self.overflow_button,
+        button_measures,
+        transform,
+        target,
+      )?;
+    }
+
+    self.painter.paint(
+      ToolbarMetrics {
+        frame,
+        visible,
+        overflowed,
+      },
+      target,
+    )
+  }
+}
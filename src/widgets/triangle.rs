@@ -0,0 +1,206 @@
+use crate::layout::{
+  context::LayoutContext,
+  error::PaintError,
+  geometry::{Point, RectMeasures},
+  measure::Measure,
+  prop::Prop,
+  render::{Painter, RenderTarget},
+  transform::Transform,
+  widget::{BoundedWidget, Widget},
+};
+
+pub type TrianglePainter<'a> = Box<dyn Painter<TriangleMetrics> + 'a>;
+
+/// √3/2, the height of an equilateral triangle per unit of base, rounded down.
+const EQUILATERAL_HEIGHT: (i32, i32) = (8660, 10000);
+
+/// Where the apex of a `Triangle` points, on screen.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  Right,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriangleMetrics {
+  pub x1: f64,
+  pub y1: f64,
+  pub x2: f64,
+  pub y2: f64,
+  pub x3: f64,
+  pub y3: f64,
+}
+
+impl TriangleMetrics {
+  /// The triangle moved by `transform`.
+  pub fn transformed(self, transform: Transform) -> Self {
+    TriangleMetrics {
+      x1: transform.x(self.x1),
+      y1: transform.y(self.y1),
+      x2: transform.x(self.x2),
+      y2: transform.y(self.y2),
+      x3: transform.x(self.x3),
+      y3: transform.y(self.y3),
+    }
+  }
+}
+
+/// A triangle, like an arrowhead or a disclosure indicator. `a` is the apex for the helpers that
+/// tell one vertex apart, and `b` and `c` span the base.
+pub struct Triangle<'a> {
+  pub a: Point<'a>,
+  pub b: Point<'a>,
+  pub c: Point<'a>,
+
+  pub painter: TrianglePainter<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a> Triangle<'a> {
+  pub fn new(a: Point<'a>, b: Point<'a>, c: Point<'a>, painter: TrianglePainter<'a>) -> Self {
+    Self { a, b, c, painter }
+  }
+
+  pub fn unbound(ctx: &'a LayoutContext, painter: TrianglePainter<'a>) -> Self {
+    let point = || Point {
+      x: Measure::new_unbound(ctx),
+      y: Measure::new_unbound(ctx),
+    };
+    Self::new(point(), point(), point(), painter)
+  }
+
+  /// Squared lengths of the sides `ab`, `bc` and `ca`. Exact, but not linear unless the
+  /// vertices are constants.
+  pub fn sides_squared(&self) -> [Measure<'a>; 3] {
+    let squared = |p: Point<'a>, q: Point<'a>| {
+      let (dx, dy) = (q.x - p.x, q.y - p.y);
+      dx * dx + dy * dy
+    };
+    [
+      squared(self.a, self.b),
+      squared(self.b, self.c),
+      squared(self.c, self.a),
+    ]
+  }
+
+  /// Holds when `ab` and `ac` are as long. Not linear, see `pointing` for a linear form.
+  pub fn is_isosceles(&self) -> Prop<'a> {
+    let [ab, _, ca] = self.sides_squared();
+    ab.prop_eq(ca)
+  }
+
+  /// Holds when every side is as long. Not linear, see `pointing_equilateral` for a linear
+  /// form.
+  pub fn is_equilateral(&self) -> Prop<'a> {
+    let [ab, bc, ca] = self.sides_squared();
+    ab.prop_eq(bc) & bc.prop_eq(ca)
+  }
+
+  /// Holds when the vertices run clockwise on screen. Not linear unless the vertices are
+  /// constants.
+  pub fn is_clockwise(&self) -> Prop<'a> {
+    let (a, b, c) = (self.a, self.b, self.c);
+    let turn = (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x);
+    turn.prop_gt(Measure::zero(a.x.ctx))
+  }
+
+  /// The base, from `b` to `c`, and the height of the apex over it, along the axes of
+  /// `direction`.
+  fn base_and_height(&self, direction: Direction) -> (Measure<'a>, Measure<'a>) {
+    let (a, b, c) = (self.a, self.b, self.c);
+    match direction {
+      Direction::Up => (c.x - b.x, b.y - a.y),
+      Direction::Down => (c.x - b.x, a.y - b.y),
+      Direction::Left => (c.y - b.y, b.x - a.x),
+      Direction::Right => (c.y - b.y, a.x - b.x),
+    }
+  }
+
+  /// Holds when the apex `a` points towards `direction` from an isosceles base `bc`
+  /// perpendicular to it, with `b` at the top or left. Linear.
+  pub fn pointing(&self, direction: Direction) -> Prop<'a> {
+    let (a, b, c) = (self.a, self.b, self.c);
+    let (base, height) = self.base_and_height(direction);
+    let zero = Measure::zero(a.x.ctx);
+    let aligned = match direction {
+      Direction::Up | Direction::Down => b.y.prop_eq(c.y) & (a.x * 2.0).prop_eq(b.x + c.x),
+      Direction::Left | Direction::Right => b.x.prop_eq(c.x) & (a.y * 2.0).prop_eq(b.y + c.y),
+    };
+    aligned & base.prop_ge(zero) & height.prop_ge(zero)
+  }
+
+  /// Like `pointing`, with the height of an equilateral triangle up to rounding.
+  pub fn pointing_equilateral(&self, direction: Direction) -> Prop<'a> {
+    let ratio =
+      Measure::new_ratio(self.a.x.ctx, EQUILATERAL_HEIGHT.0, EQUILATERAL_HEIGHT.1).unwrap();
+    let (base, height) = self.base_and_height(direction);
+    self.pointing(direction) & height.prop_eq(base * ratio)
+  }
+
+  /// The centroid of the area, where the medians meet.
+  pub fn centroid(&self) -> Point<'a> {
+    Point {
+      x: (self.a.x + self.b.x + self.c.x) / 3.0,
+      y: (self.a.y + self.b.y + self.c.y) / 3.0,
+    }
+  }
+}
+
+impl<'a> BoundedWidget<'a> for Triangle<'a> {
+  fn rect(&self) -> RectMeasures<'a> {
+    RectMeasures::around(&[self.a, self.b, self.c]).unwrap()
+  }
+}
+
+impl<'a> Widget<'a> for Triangle<'a> {
+  type Metrics = TriangleMetrics;
+
+  fn measures(&self) -> Vec<Measure<'a>> {
+    vec![self.a.x, self.a.y, self.b.x, self.b.y, self.c.x, self.c.y]
+  }
+
+  fn constraints(&self) -> Vec<Prop<'a>> {
+    vec![]
+  }
+
+  fn bounds(&self) -> Option<RectMeasures<'a>> {
+    Some(self.rect())
+  }
+
+  /// The vertices `a`, `b` and `c`, and the `center` at the centroid.
+  fn anchors(&self) -> Vec<(&'static str, Point<'a>)> {
+    vec![
+      ("a", self.a),
+      ("b", self.b),
+      ("c", self.c),
+      ("center", self.centroid()),
+    ]
+  }
+
+  fn decode(&self, values: &[f64]) -> TriangleMetrics {
+    TriangleMetrics {
+      x1: values[0],
+      y1: values[1],
+      x2: values[2],
+      y2: values[3],
+      x3: values[4],
+      y3: values[5],
+    }
+  }
+
+  fn paint(
+    &mut self,
+    metrics: TriangleMetrics,
+    target: &mut dyn RenderTarget,
+  ) -> Result<(), PaintError> {
+    self.painter.paint(metrics, target)
+  }
+
+  fn transform(metrics: TriangleMetrics, transform: Transform) -> TriangleMetrics {
+    metrics.transformed(transform)
+  }
+}